@@ -0,0 +1,124 @@
+//! Library-level benchmarks: each proving phase, verification and Poseidon
+//! (alone and from many threads at once), without actix in the way.
+//!
+//! ```bash
+//! cargo bench --bench prover
+//! ```
+
+use ark_bn254::{Bn254, Fr};
+use ark_circom::circom::{R1CSFile, R1CS};
+use ark_circom::CircomConfig;
+use ark_ff::UniformRand;
+use ark_groth16::{prepare_verifying_key, Proof};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rand::{thread_rng, Rng};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use zk_auth_api::{
+    calculate_witness, commitment_for, default_zkey_path, derive_child_secret, derive_user_hash, prove_from_witness,
+    read_proving_key, RegisterRequest,
+};
+
+/// Proofs checked per iteration of the batch benchmark.
+const BATCH: usize = 16;
+/// User hashes each thread computes per iteration of the concurrent benchmark.
+const CONCURRENT_HASHES: usize = 256;
+
+fn circuit_file(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("circuits/secret-proof").join(name)
+}
+
+fn read_r1cs() -> R1CS<Fr> {
+    let file = File::open(circuit_file("secret-proof.r1cs")).expect("cannot read circuit r1cs");
+    R1CSFile::new(BufReader::new(file)).expect("invalid r1cs").into()
+}
+
+fn prover(c: &mut Criterion) {
+    // The witness calculator's file system wants a Tokio reactor.
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let _entered = runtime.enter();
+    let (wasm, r1cs_path) = (circuit_file("secret-proof_js/secret-proof.wasm"), circuit_file("secret-proof.r1cs"));
+    let pk = read_proving_key(default_zkey_path()).expect("cannot read zkey");
+    let r1cs = read_r1cs();
+    let secret = Fr::rand(&mut thread_rng());
+    let commitment = commitment_for(&secret);
+
+    let mut group = c.benchmark_group("prove");
+    // The server does this once per concurrent prover.
+    group.bench_function("load_circuit", |b| b.iter(|| CircomConfig::<Fr>::new(&wasm, &r1cs_path).unwrap()));
+    let mut cfg = CircomConfig::<Fr>::new(&wasm, &r1cs_path).unwrap();
+    group.bench_function("witness", |b| b.iter(|| calculate_witness(&mut cfg, secret, commitment).unwrap()));
+    let witness = calculate_witness(&mut cfg, secret, commitment).unwrap();
+    group.bench_function("groth16", |b| {
+        b.iter_batched(
+            || (r1cs.clone(), witness.clone()),
+            |(r1cs, witness)| prove_from_witness(r1cs, &pk, witness, &mut thread_rng()).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+
+    let pvk = prepare_verifying_key(&pk.vk);
+    let proofs: Vec<(Fr, Proof<Bn254>)> = (0..BATCH)
+        .map(|_| {
+            let secret = Fr::rand(&mut thread_rng());
+            let commitment = commitment_for(&secret);
+            let witness = calculate_witness(&mut cfg, secret, commitment).unwrap();
+            (commitment, prove_from_witness(r1cs.clone(), &pk, witness, &mut thread_rng()).unwrap())
+        })
+        .collect();
+    let mut group = c.benchmark_group("verify");
+    group.bench_function("single", |b| b.iter(|| zk_auth_verify_core::verify(&pvk, &[proofs[0].0], &proofs[0].1).unwrap()));
+    // What /verify-batch does per request body: one pairing check per proof.
+    group.throughput(Throughput::Elements(BATCH as u64));
+    group.bench_function("batch", |b| {
+        b.iter(|| proofs.iter().all(|(commitment, proof)| zk_auth_verify_core::verify(&pvk, &[*commitment], proof).unwrap()))
+    });
+    // What a JSON /verify-batch does: one multi-pairing for the lot.
+    let batch: Vec<(&[Fr], &Proof<Bn254>)> = proofs.iter().map(|(commitment, proof)| (std::slice::from_ref(commitment), proof)).collect();
+    group.bench_function("batch_rlc", |b| {
+        b.iter(|| {
+            let randomizers: Vec<Fr> = (0..BATCH).map(|_| Fr::from(thread_rng().gen::<u128>())).collect();
+            zk_auth_verify_core::verify_batch(&pvk, &batch, &randomizers).unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn poseidon(c: &mut Criterion) {
+    let secret = Fr::rand(&mut thread_rng());
+    let attributes = RegisterRequest {
+        email: "alice@example.com".into(),
+        name: "Alice Doe".into(),
+        age: 35,
+        country: "US".into(),
+        dob: "19900101".into(),
+        phone: None,
+        biometric_hash: None,
+    };
+    let mut group = c.benchmark_group("poseidon");
+    group.bench_function("commitment_1", |b| b.iter(|| commitment_for(&secret)));
+    group.bench_function("child_secret_3", |b| b.iter(|| derive_child_secret(&secret, "bench.example")));
+    group.bench_function("user_hash_5", |b| b.iter(|| derive_user_hash(&attributes)));
+    group.finish();
+
+    // Many threads hashing at once, as under concurrent `/register` load;
+    // with per-thread hashers this scales with the cores.
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let mut group = c.benchmark_group("poseidon_concurrent");
+    group.throughput(Throughput::Elements((threads * CONCURRENT_HASHES) as u64));
+    group.bench_function(format!("user_hash_5_x{threads}"), |b| {
+        b.iter(|| {
+            std::thread::scope(|s| {
+                for _ in 0..threads {
+                    s.spawn(|| (0..CONCURRENT_HASHES).fold(Fr::from(0u64), |acc, _| acc + derive_user_hash(&attributes)));
+                }
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, prover, poseidon);
+criterion_main!(benches);
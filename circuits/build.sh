@@ -0,0 +1,16 @@
+#!/usr/bin/env bash
+# Compile a circuit and run a Groth16 setup against the repo's ptau.
+#   usage: circuits/build.sh <name>      e.g. circuits/build.sh secret-proof-audience
+# Produces circuits/<name>/{<name>.r1cs, <name>_js/<name>.wasm, <name>_final.zkey}.
+# Requires circom 2.1.7+ and snarkjs; circomlib comes from `pnpm install`.
+# PTAU overrides the powers of tau for circuits over 4096 constraints.
+set -euo pipefail
+name=${1:?usage: circuits/build.sh <name>}
+root=$(cd "$(dirname "$0")/.." && pwd)
+ptau=${PTAU:-$root/pot12_final.ptau}
+out="$root/circuits/$name"
+mkdir -p "$out"
+circom "$root/circuits/$name.circom" --r1cs --wasm --sym -l "$root/node_modules" -o "$out"
+snarkjs groth16 setup "$out/$name.r1cs" "$ptau" "$out/${name}_0000.zkey"
+snarkjs zkey contribute "$out/${name}_0000.zkey" "$out/${name}_final.zkey" --name="$name" -e="$(head -c 32 /dev/urandom | xxd -p -c 64)"
+rm "$out/${name}_0000.zkey"
@@ -0,0 +1,30 @@
+pragma circom 2.1.7;
+include "circomlib/circuits/poseidon.circom";
+
+/*  Proves a child commitment descends from a registered master:
+      Poseidon(master)                           = master_commitment
+      Poseidon(Poseidon(1, master, application)) = child_commitment
+    `application` is Keccak256(name) mod p, as in `derive_child_secret`.
+    master_commitment is public here, so this links child to master; the
+    unlinkable form replaces it with membership in a commitment tree.  */
+template ChildDescent() {
+    signal input  master;             // private
+    signal input  master_commitment;  // public
+    signal input  application;        // public
+    signal input  child_commitment;   // public
+
+    component m = Poseidon(1);
+    m.inputs[0] <== master;
+    m.out === master_commitment;
+
+    component child = Poseidon(3);
+    child.inputs[0] <== 1;    // TAG_CHILD
+    child.inputs[1] <== master;
+    child.inputs[2] <== application;
+
+    component c = Poseidon(1);
+    c.inputs[0] <== child.out;
+    c.out === child_commitment;
+}
+
+component main { public [master_commitment, application, child_commitment] } = ChildDescent();
@@ -0,0 +1,5 @@
+pragma circom 2.1.7;
+include "templates/registration.circom";
+
+// /register?proof=true for registrations with base attributes, an optional phone number and a biometric hash.
+component main { public [user_hash, commitment] } = RegistrationProof(7);
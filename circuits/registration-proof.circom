@@ -0,0 +1,5 @@
+pragma circom 2.1.7;
+include "templates/registration.circom";
+
+// /register?proof=true for registrations with email, name, age, country and date of birth.
+component main { public [user_hash, commitment] } = RegistrationProof(5);
@@ -0,0 +1,22 @@
+pragma circom 2.1.7;
+include "circomlib/circuits/poseidon.circom";
+
+/*  Proves knowledge of `secret` s.t. Poseidon(secret) = commitment, for a
+    given `audience` (Keccak256 of the audience string, reduced mod p).  */
+template SecretProofAudience() {
+    signal input  secret;        // private
+    signal input  commitment;    // public
+    signal input  audience;      // public
+
+    component h = Poseidon(1);
+    h.inputs[0] <== secret;
+    h.out === commitment;
+
+    /* A public input that appears in no constraint is not bound by Groth16;
+       squaring it ties the proof to this audience value. */
+    signal audienceSquare;
+    audienceSquare <== audience * audience;
+}
+
+/* Public signals, in order: commitment, audience */
+component main { public [commitment, audience] } = SecretProofAudience();
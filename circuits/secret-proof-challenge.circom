@@ -0,0 +1,23 @@
+pragma circom 2.1.7;
+include "circomlib/circuits/poseidon.circom";
+
+/*  Proves knowledge of `secret` s.t. Poseidon(secret) = commitment, for a
+    server-issued `challenge` from `GET /challenge`.  The challenge is
+    single-use and short-lived, so a captured proof cannot log in again.  */
+template SecretProofChallenge() {
+    signal input  secret;        // private
+    signal input  commitment;    // public
+    signal input  challenge;     // public
+
+    component h = Poseidon(1);
+    h.inputs[0] <== secret;
+    h.out === commitment;
+
+    /* As in secret-proof-audience: bind the otherwise unconstrained
+       public input to the proof. */
+    signal challengeSquare;
+    challengeSquare <== challenge * challenge;
+}
+
+/* Public signals, in order: commitment, challenge */
+component main { public [commitment, challenge] } = SecretProofChallenge();
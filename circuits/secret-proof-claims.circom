@@ -0,0 +1,25 @@
+pragma circom 2.1.7;
+include "circomlib/circuits/poseidon.circom";
+
+/*  Proves knowledge of `secret` s.t. Poseidon(secret) = commitment, for a
+    given claim block:
+      claims = Poseidon(issuer, audience, issued_at, expires_at)
+    with issuer and audience Keccak256 of their strings, reduced mod p, as
+    in `ClaimBlock::hash`.  The verifier recomputes `claims` from the block
+    it was sent and checks the block itself.  */
+template SecretProofClaims() {
+    signal input  secret;        // private
+    signal input  commitment;    // public
+    signal input  claims;        // public
+
+    component h = Poseidon(1);
+    h.inputs[0] <== secret;
+    h.out === commitment;
+
+    /* As in secret-proof-audience: bind the otherwise unused input. */
+    signal claimsSquare;
+    claimsSquare <== claims * claims;
+}
+
+/* Public signals, in order: commitment, claims */
+component main { public [commitment, claims] } = SecretProofClaims();
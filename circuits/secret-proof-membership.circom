@@ -0,0 +1,64 @@
+pragma circom 2.1.7;
+include "circomlib/circuits/poseidon.circom";
+
+/*  Proves that Poseidon(secret) is a leaf of the commitment tree with
+    root `root`, without revealing which leaf, and outputs
+    nullifier = Poseidon(3, secret, app_id).  The tag differs from
+    secret-proof-nullifier's 2, so a membership nullifier cannot be matched
+    against the nullifier a commitment-naming proof publishes.  The verifier
+    learns only that some registered identity proved, once per application.
+
+    The tree is the server's: binary, depth 20, nodes Poseidon(left, right),
+    empty leaves zero.  `siblings` and `path_bits` are the Merkle path of the
+    leaf, built from `GET /roots/delta`; bit i is 1 when the path's node at
+    level i is a right child.  About 5 000 constraints: set up with a pot13
+    or larger ptau.  */
+template MerkleRoot(depth) {
+    signal input  leaf;
+    signal input  siblings[depth];
+    signal input  path_bits[depth];
+    signal output root;
+
+    component hashers[depth];
+    signal nodes[depth + 1];
+    signal left[depth];
+    nodes[0] <== leaf;
+    for (var i = 0; i < depth; i++) {
+        path_bits[i] * (1 - path_bits[i]) === 0;
+        left[i] <== nodes[i] + path_bits[i] * (siblings[i] - nodes[i]);
+        hashers[i] = Poseidon(2);
+        hashers[i].inputs[0] <== left[i];
+        hashers[i].inputs[1] <== nodes[i] + siblings[i] - left[i];
+        nodes[i + 1] <== hashers[i].out;
+    }
+    root <== nodes[depth];
+}
+
+template SecretProofMembership(depth) {
+    signal input  secret;            // private
+    signal input  siblings[depth];   // private
+    signal input  path_bits[depth];  // private
+    signal input  root;              // public
+    signal input  app_id;            // public
+    signal output nullifier;         // public
+
+    component h = Poseidon(1);
+    h.inputs[0] <== secret;
+
+    component tree = MerkleRoot(depth);
+    tree.leaf <== h.out;
+    for (var i = 0; i < depth; i++) {
+        tree.siblings[i] <== siblings[i];
+        tree.path_bits[i] <== path_bits[i];
+    }
+    tree.root === root;
+
+    component n = Poseidon(3);
+    n.inputs[0] <== 3;    // TAG_MEMBERSHIP
+    n.inputs[1] <== secret;
+    n.inputs[2] <== app_id;
+    nullifier <== n.out;
+}
+
+/* Public signals, in order: nullifier, root, app_id */
+component main { public [root, app_id] } = SecretProofMembership(20);
@@ -0,0 +1,19 @@
+pragma circom 2.1.7;
+include "circomlib/circuits/poseidon.circom";
+
+/*  Proves knowledge of all N secrets s.t.
+    Poseidon(secrets[0], ..., secrets[N-1]) = commitment.
+    circomlib's Poseidon takes at most 16 inputs.  */
+template SecretProofMulti(N) {
+    signal input  secrets[N];    // private
+    signal input  commitment;    // public
+
+    component h = Poseidon(N);
+    for (var i = 0; i < N; i++) {
+        h.inputs[i] <== secrets[i];
+    }
+    h.out === commitment;
+}
+
+/* Two secrets (device + user); rebuild with another N for more devices. */
+component main { public [commitment] } = SecretProofMulti(2);
@@ -0,0 +1,30 @@
+pragma circom 2.1.7;
+include "circomlib/circuits/poseidon.circom";
+
+/*  Proves knowledge of `secret` s.t. Poseidon(secret) = commitment, and
+    outputs nullifier = Poseidon(2, secret, app_id).  `app_id` is
+    Keccak256(application) mod p, as in `derive_nullifier`.  The nullifier
+    is the same every time one identity proves to one application, so the
+    application can refuse a second account, while nullifiers for other
+    app ids cannot be linked to it without the secret.  */
+template SecretProofNullifier() {
+    signal input  secret;        // private
+    signal input  commitment;    // public
+    signal input  app_id;        // public
+    signal output nullifier;     // public
+
+    component h = Poseidon(1);
+    h.inputs[0] <== secret;
+    h.out === commitment;
+
+    /* 2 is the nullifier domain tag (`TAG_NULLIFIER`); child secrets use 1,
+       so a published nullifier is never a child secret. */
+    component n = Poseidon(3);
+    n.inputs[0] <== 2;
+    n.inputs[1] <== secret;
+    n.inputs[2] <== app_id;
+    nullifier <== n.out;
+}
+
+/* Public signals, in order: nullifier, commitment, app_id */
+component main { public [commitment, app_id] } = SecretProofNullifier();
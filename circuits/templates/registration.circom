@@ -0,0 +1,37 @@
+pragma circom 2.1.7;
+include "circomlib/circuits/poseidon.circom";
+
+/*  Proves /register derived `commitment` honestly from N attributes:
+        user_hash  = Poseidon(attributes[0], ..., attributes[N-1])
+        secret     = Poseidon(user_hash, nonce)
+        commitment = Poseidon(secret)
+    The attributes are encoded as /register encodes them, in its order:
+        email_hash, name_hash, age, country, dob        (N = 5)
+        ..., phone_hash                                   (N = 6)
+        ..., phone_hash or 0, biometric_hash              (N = 7)
+    email_hash, name_hash and phone_hash are the Keccak256 hashes (or RFC
+    9380 hash_to_field outputs) of the normalised strings, reduced into the
+    field; hashing them stays outside the circuit.  Only user_hash and
+    commitment are public, so a third party holding the attribute digest can
+    check the binding without learning the attributes, the nonce or the
+    secret.  Not built on its own: see registration-proof*.circom.  */
+template RegistrationProof(N) {
+    signal input attributes[N];  // private
+    signal input nonce;          // private, the 16 nonce bytes as an integer
+    signal input user_hash;      // public
+    signal input commitment;     // public
+
+    component attrs = Poseidon(N);
+    for (var i = 0; i < N; i++) {
+        attrs.inputs[i] <== attributes[i];
+    }
+    attrs.out === user_hash;
+
+    component secret = Poseidon(2);
+    secret.inputs[0] <== attrs.out;
+    secret.inputs[1] <== nonce;
+
+    component commit = Poseidon(1);
+    commit.inputs[0] <== secret.out;
+    commit.out === commitment;
+}
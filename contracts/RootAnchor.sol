@@ -0,0 +1,33 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+/// Append-only log of zk-auth commitment-tree roots, posted by the server's
+/// anchoring task (`ZK_ANCHOR_*`).  Only `poster` may append.
+contract RootAnchor {
+    event Anchored(uint256 indexed index, bytes32 root, uint64 leafCount, uint256 timestamp);
+
+    struct Anchor {
+        bytes32 root;
+        uint64 leafCount;
+        uint64 timestamp;
+    }
+
+    address public immutable poster;
+    Anchor[] public anchors;
+
+    constructor(address poster_) {
+        poster = poster_;
+    }
+
+    function anchor(bytes32 root, uint64 leafCount) external {
+        require(msg.sender == poster, "not poster");
+        uint256 n = anchors.length;
+        require(n == 0 || leafCount > anchors[n - 1].leafCount, "tree did not grow");
+        anchors.push(Anchor(root, leafCount, uint64(block.timestamp)));
+        emit Anchored(n, root, leafCount, block.timestamp);
+    }
+
+    function count() external view returns (uint256) {
+        return anchors.length;
+    }
+}
@@ -0,0 +1,138 @@
+//! UniFFI bindings so the iOS and Android apps derive commitments, prove
+//! and verify with the server's own code.
+//!
+//! Values cross the boundary as strings in the server's formats: secrets
+//! `0x` hex, commitments decimal, proofs hex in EVM order as `/generate-proof`
+//! returns them.
+
+use ark_ff::{BigInteger, PrimeField};
+use std::fmt;
+use std::path::Path;
+use zk_auth_api::{commitment_for, derive_secret, derive_user_hash, prove_with_files, public_signals, ProofJson, RegisterRequest};
+use zk_auth_verify_core::{parse_scalar, prepared_key_from_compressed, proof_from_evm_hex, Fr};
+
+uniffi::setup_scaffolding!();
+
+//--------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------
+#[derive(Debug, uniffi::Error)]
+pub enum MobileError {
+    /// An argument is malformed; `field` names it.
+    InvalidInput { field: String, reason: String },
+    /// Circuit files or the zkey could not be read, or proving failed.
+    Proving { reason: String },
+}
+
+impl fmt::Display for MobileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MobileError::InvalidInput { field, reason } => write!(f, "{field}: {reason}"),
+            MobileError::Proving { reason } => write!(f, "proving failed: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for MobileError {}
+
+/// The `/register` attributes.
+#[derive(uniffi::Record)]
+pub struct Attributes {
+    pub email: String,
+    pub name: String,
+    pub age: u32,
+    pub country: String,
+    pub dob: String,
+}
+
+#[derive(uniffi::Record)]
+pub struct Identity {
+    pub secret: String,
+    pub commitment: String,
+}
+
+/// `a` and `c` are `[x, y]`; `b` is `[[x1, x0], [y1, y0]]`.
+#[derive(uniffi::Record)]
+pub struct Groth16Proof {
+    pub a: Vec<String>,
+    pub b: Vec<Vec<String>>,
+    pub c: Vec<String>,
+}
+
+/// Paths of the circuit files shipped with the app.
+#[derive(uniffi::Record)]
+pub struct CircuitFiles {
+    pub wasm: String,
+    pub r1cs: String,
+    pub zkey: String,
+}
+
+//--------------------------------------------------------------------
+// Exports
+//--------------------------------------------------------------------
+/// The user hash `/register` computes, decimal.
+#[uniffi::export]
+pub fn user_hash(attributes: Attributes) -> String {
+    decimal(derive_user_hash(&request(attributes)))
+}
+
+/// The secret and commitment `/register` returns for `attributes` and the
+/// 16-byte hex `nonce`.
+#[uniffi::export]
+pub fn derive_identity(attributes: Attributes, nonce: String) -> Result<Identity, MobileError> {
+    let nonce: [u8; 16] = hex::decode(nonce.trim_start_matches("0x"))
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| invalid("nonce", "must be 16 bytes of hex"))?;
+    let secret = derive_secret(&derive_user_hash(&request(attributes)), &nonce);
+    Ok(Identity { secret: format!("0x{}", hex::encode(secret.into_bigint().to_bytes_be())), commitment: decimal(commitment_for(&secret)) })
+}
+
+/// Prove knowledge of `secret` for `commitment` on the device.  Blocking and
+/// CPU-heavy: call it off the main thread.
+#[uniffi::export]
+pub fn prove(circuit: CircuitFiles, secret: String, commitment: String) -> Result<Groth16Proof, MobileError> {
+    let secret = hex::decode(secret.trim_start_matches("0x"))
+        .ok()
+        .filter(|b| b.len() <= 32)
+        .map(|b| Fr::from_be_bytes_mod_order(&b))
+        .ok_or_else(|| invalid("secret", "must be at most 32 bytes of hex"))?;
+    let commitment = parse_scalar(&commitment).map_err(|e| invalid("commitment", &e.to_string()))?;
+    let proof = prove_with_files(Path::new(&circuit.wasm), Path::new(&circuit.r1cs), Path::new(&circuit.zkey), secret, commitment)
+        .map_err(|e| MobileError::Proving { reason: e.to_string() })?;
+    let ProofJson { a, b, c } = ProofJson::from_proof(&proof);
+    Ok(Groth16Proof { a: a.into(), b: b.into_iter().map(Vec::from).collect(), c: c.into() })
+}
+
+/// Check `proof` against `commitment` under a key in `export-vk`'s format.
+#[uniffi::export]
+pub fn verify(verifying_key: Vec<u8>, commitment: String, proof: Groth16Proof) -> Result<bool, MobileError> {
+    let pvk = prepared_key_from_compressed(&verifying_key).map_err(|e| invalid("verifying_key", &e.to_string()))?;
+    let (a, c) = (pair(&proof.a)?, pair(&proof.c)?);
+    let b = match proof.b.as_slice() {
+        [x, y] => [pair(x)?, pair(y)?],
+        _ => return Err(invalid("proof", "b must have two rows")),
+    };
+    let proof = proof_from_evm_hex(a, b, c).map_err(|e| invalid("proof", &e.to_string()))?;
+    let commitment = parse_scalar(&commitment).map_err(|e| invalid("commitment", &e.to_string()))?;
+    zk_auth_verify_core::verify(&pvk, &[commitment], &proof).map_err(|e| invalid("proof", &e.to_string()))
+}
+
+fn pair(v: &[String]) -> Result<[&str; 2], MobileError> {
+    match v {
+        [x, y] => Ok([x.as_str(), y.as_str()]),
+        _ => Err(invalid("proof", "every coordinate list must have two entries")),
+    }
+}
+
+fn request(a: Attributes) -> RegisterRequest {
+    RegisterRequest { email: a.email, name: a.name, age: a.age, country: a.country, dob: a.dob }
+}
+
+fn decimal(f: Fr) -> String {
+    public_signals(&[f]).remove(0)
+}
+
+fn invalid(field: &str, reason: &str) -> MobileError {
+    MobileError::InvalidInput { field: field.to_owned(), reason: reason.to_owned() }
+}
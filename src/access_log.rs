@@ -0,0 +1,197 @@
+use actix_web::body::{to_bytes, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::web::Bytes;
+use actix_web::Error;
+use rand::Rng;
+use std::collections::HashMap;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::redact::redact_json;
+
+//--------------------------------------------------------------------
+// Configuration
+//--------------------------------------------------------------------
+/// Opt-in access logging.  Records are emitted at `info` under the
+/// `zk_auth_api::access` target.
+#[derive(Clone, Debug)]
+pub struct AccessLogConfig {
+    /// Fraction of requests logged, `0.0..=1.0`.
+    pub sample_rate: f64,
+    /// Attach request and response bodies (redacted, truncated) to records.
+    pub capture_bodies: bool,
+    /// Maximum bytes of each captured body kept in a record.
+    pub max_body_bytes: usize,
+    /// Overrides keyed by endpoint path, e.g. `"/generate-proof"`.  Matched
+    /// against the end of the request path so mount prefixes don't matter.
+    pub endpoints: HashMap<String, EndpointLogConfig>,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self { sample_rate: 1.0, capture_bodies: false, max_body_bytes: 2048, endpoints: HashMap::new() }
+    }
+}
+
+/// Per-endpoint override; unset fields fall back to [`AccessLogConfig`].
+#[derive(Clone, Debug, Default)]
+pub struct EndpointLogConfig {
+    pub sample_rate: Option<f64>,
+    pub capture_bodies: Option<bool>,
+}
+
+impl AccessLogConfig {
+    fn policy_for(&self, path: &str) -> (f64, bool) {
+        let endpoint = self.endpoints.iter().find(|(suffix, _)| path.ends_with(suffix.as_str())).map(|(_, e)| e);
+        let rate = endpoint.and_then(|e| e.sample_rate).unwrap_or(self.sample_rate);
+        let capture = endpoint.and_then(|e| e.capture_bodies).unwrap_or(self.capture_bodies);
+        (rate, capture)
+    }
+}
+
+//--------------------------------------------------------------------
+// Middleware
+//--------------------------------------------------------------------
+static REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Access-log middleware.  Every response carries an `X-Request-Id` (the
+/// client's, if it sent one) whether or not the request was sampled.
+#[derive(Clone)]
+pub struct AccessLog {
+    config: Arc<AccessLogConfig>,
+}
+
+impl AccessLog {
+    pub fn new(config: AccessLogConfig) -> Self {
+        Self { config: Arc::new(config) }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AccessLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = AccessLogService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessLogService { service: Rc::new(service), config: self.config.clone() }))
+    }
+}
+
+pub struct AccessLogService<S> {
+    service: Rc<S>,
+    config: Arc<AccessLogConfig>,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessLogService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let config = self.config.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let request_id = req
+                .headers()
+                .get(&REQUEST_ID)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned)
+                .unwrap_or_else(|| hex::encode(rand::thread_rng().gen::<[u8; 8]>()));
+            let (rate, capture) = config.policy_for(req.path());
+            let sampled = rand::thread_rng().gen_bool(rate.clamp(0.0, 1.0));
+            let method = req.method().to_string();
+            let path = req.path().to_owned();
+
+            let req_body = if sampled && capture {
+                let bytes = req.extract::<Bytes>().await?;
+                let captured = capture_body(&bytes, config.max_body_bytes);
+                req.set_payload(Payload::from(bytes));
+                Some(captured)
+            } else {
+                None
+            };
+
+            let mut res = service.call(req).await?.map_into_boxed_body();
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut().insert(REQUEST_ID.clone(), value);
+            }
+            if !sampled {
+                return Ok(res);
+            }
+
+            let status = res.status().as_u16();
+            let res = if capture {
+                let (http_req, http_res) = res.into_parts();
+                let (http_res, body) = http_res.into_parts();
+                let bytes = to_bytes(body).await.unwrap_or_default();
+                let captured = capture_body(&bytes, config.max_body_bytes);
+                log_record(&request_id, &method, &path, status, started, req_body.as_deref(), Some(&captured));
+                ServiceResponse::new(http_req, http_res.set_body(BoxBody::new(bytes)))
+            } else {
+                log_record(&request_id, &method, &path, status, started, None, None);
+                res
+            };
+            Ok(res)
+        })
+    }
+}
+
+fn log_record(
+    request_id: &str,
+    method: &str,
+    path: &str,
+    status: u16,
+    started: Instant,
+    req_body: Option<&str>,
+    res_body: Option<&str>,
+) {
+    tracing::info!(
+        target: "zk_auth_api::access",
+        request_id,
+        method,
+        path,
+        status,
+        latency_ms = started.elapsed().as_secs_f64() * 1000.0,
+        req_body,
+        res_body,
+    );
+}
+
+/// Redacted, size-capped rendering of a body.  Non-JSON bodies are
+/// summarised by length only because they cannot be redacted field by field.
+fn capture_body(bytes: &[u8], max: usize) -> String {
+    if bytes.is_empty() {
+        return String::new();
+    }
+    let Ok(mut json) = serde_json::from_slice::<serde_json::Value>(bytes) else {
+        return format!("[non-JSON body, {} bytes]", bytes.len());
+    };
+    redact_json(&mut json);
+    let mut text = json.to_string();
+    if text.len() > max {
+        let mut cut = max;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        text.truncate(cut);
+        text.push_str("…[truncated]");
+    }
+    text
+}
@@ -0,0 +1,130 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{HttpServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{get, put, web, Error, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::artifacts::KeyStats;
+use crate::error::ApiError;
+use crate::limits::LimiterStats;
+use crate::prewarm::PrewarmStats;
+use crate::replication::ReplicationStatus;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// /admin
+//--------------------------------------------------------------------
+/// Operator endpoints, all behind `Authorization: Bearer <admin_token>`.
+pub(crate) fn admin_scope() -> impl HttpServiceFactory {
+    let scope = web::scope("/admin")
+        .service(stats)
+        .service(set_concurrency)
+        .service(crate::ipfs::publish)
+        .service(crate::prewarm::prewarm)
+        .service(crate::proof_jobs::list_proof_jobs)
+        .service(crate::revocation::revoke);
+    #[cfg(feature = "chaos")]
+    let scope = scope.service(crate::chaos::get_chaos).service(crate::chaos::set_chaos).service(crate::chaos::clear_chaos);
+    scope.wrap(from_fn(require_token))
+}
+
+async fn require_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let expected = req.app_data::<web::Data<AppState>>().and_then(|s| s.config.admin_token.clone());
+    let given = req
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match (expected, given) {
+        // Compare digests so the check takes the same time for any mismatch.
+        (Some(expected), Some(given)) if Sha256::digest(&expected) == Sha256::digest(given) => next.call(req).await,
+        _ => Err(ApiError::AdminUnauthorized.into()),
+    }
+}
+
+//--------------------------------------------------------------------
+// /admin/stats
+//--------------------------------------------------------------------
+#[derive(Debug, Serialize)]
+pub struct StatsResponse {
+    pub uptime_secs: u64,
+    /// Resident set size of the whole process (Linux only).
+    pub resident_bytes: Option<u64>,
+    pub limiters: Vec<LimiterStats>,
+    pub keys: Vec<KeyStatsEntry>,
+    /// Present when speculative proving is enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prewarm: Option<PrewarmStats>,
+    /// Present on a replica.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replication: Option<ReplicationStatus>,
+}
+
+/// `role` is `active`, `staged` or `historical`.
+#[derive(Debug, Serialize)]
+pub struct KeyStatsEntry {
+    pub role: &'static str,
+    #[serde(flatten)]
+    pub stats: KeyStats,
+}
+
+#[get("/stats")]
+async fn stats(state: web::Data<AppState>) -> impl Responder {
+    let mut keys = vec![KeyStatsEntry { role: "active", stats: state.artifacts.key_stats() }];
+    keys.extend(state.staged.iter().map(|a| KeyStatsEntry { role: "staged", stats: a.key_stats() }));
+    keys.extend(state.historical.iter().map(|a| KeyStatsEntry { role: "historical", stats: a.key_stats() }));
+    HttpResponse::Ok().json(StatsResponse {
+        uptime_secs: state.started.elapsed().as_secs(),
+        resident_bytes: resident_bytes(),
+        limiters: state.concurrency_stats(),
+        keys,
+        prewarm: state.prewarm.as_ref().map(|p| p.stats()),
+        replication: state.replication.as_ref().map(|r| r.status()),
+    })
+}
+
+/// RSS from `/proc/self/statm` (second field, in pages; assumes 4 KiB pages).
+fn resident_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+//--------------------------------------------------------------------
+// /admin/concurrency
+//--------------------------------------------------------------------
+/// New permit counts; omitted classes are left alone.
+#[derive(Debug, Deserialize)]
+pub struct ConcurrencyUpdate {
+    pub prove: Option<usize>,
+    pub verify: Option<usize>,
+    pub register: Option<usize>,
+}
+
+/// Grows apply before the response; shrinks drain in the background, so
+/// the answer is `202` with the limiters as they stand and `capacity`
+/// reaching the new value once in-flight work has finished.
+#[put("/concurrency")]
+async fn set_concurrency(state: web::Data<AppState>, body: web::Json<ConcurrencyUpdate>) -> Result<HttpResponse, ApiError> {
+    let targets = [("prove", body.prove), ("verify", body.verify), ("register", body.register)];
+    if targets.iter().any(|(_, t)| *t == Some(0)) {
+        return Err(ApiError::InvalidInput { field: "concurrency", reason: "limits must be at least 1" });
+    }
+    let mut draining = false;
+    for (name, target) in targets {
+        let Some(target) = target else { continue };
+        if target >= state.limits.by_name(name).capacity() {
+            state.limits.by_name(name).resize(target).await;
+        } else {
+            draining = true;
+            let state = state.clone();
+            actix_web::rt::spawn(async move { state.limits.by_name(name).resize(target).await });
+        }
+    }
+    let limiters = state.concurrency_stats();
+    Ok(if draining { HttpResponse::Accepted().json(limiters) } else { HttpResponse::Ok().json(limiters) })
+}
@@ -0,0 +1,196 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use ark_ff::{BigInteger, PrimeField};
+use k256::ecdsa::SigningKey;
+use rlp::RlpStream;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::snarkjs::public_signals;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Configuration
+//--------------------------------------------------------------------
+/// Periodically posts the commitment-tree root to a contract exposing
+/// `anchor(bytes32 root, uint64 leafCount)` (see `contracts/RootAnchor.sol`).
+#[derive(Clone, Debug)]
+pub struct AnchorConfig {
+    /// Ethereum JSON-RPC endpoint.
+    pub rpc_url: String,
+    /// Anchoring contract address, `0x`-prefixed hex.
+    pub contract: String,
+    /// EIP-155 chain id the transactions are signed for.
+    pub chain_id: u64,
+    /// File holding the 32-byte hex secp256k1 key that pays for anchoring.
+    pub signer_key: PathBuf,
+    /// Time between anchoring attempts.  A root that has not changed since
+    /// the last anchor is not posted again.
+    pub interval: Duration,
+    pub gas_limit: u64,
+}
+
+//--------------------------------------------------------------------
+// Anchoring
+//--------------------------------------------------------------------
+/// A root posted on chain.
+#[derive(Clone, Debug, Serialize)]
+pub struct AnchorRecord {
+    /// Decimal, like commitments; the contract stores it as `bytes32`.
+    pub root: String,
+    pub leaf_count: u64,
+    pub tx_hash: String,
+    /// Unix seconds when the transaction was accepted by the node.
+    pub anchored_at: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnchorsResponse {
+    pub contract: String,
+    pub chain_id: u64,
+    pub signer: String,
+    /// Oldest first.
+    pub anchors: Vec<AnchorRecord>,
+}
+
+pub(crate) struct Anchorer {
+    config: AnchorConfig,
+    key: SigningKey,
+    contract: [u8; 20],
+    client: reqwest::Client,
+    history: Mutex<Vec<AnchorRecord>>,
+}
+
+impl Anchorer {
+    pub(crate) fn new(config: AnchorConfig) -> io::Result<Self> {
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("anchor: {what}"));
+        let seed = hex::decode(std::fs::read_to_string(&config.signer_key)?.trim().trim_start_matches("0x"))
+            .map_err(|_| invalid("signer key is not hex"))?;
+        let key = SigningKey::from_slice(&seed).map_err(|_| invalid("signer key is not a secp256k1 key"))?;
+        let contract = hex::decode(config.contract.trim_start_matches("0x"))
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| invalid("contract is not a 20-byte address"))?;
+        Ok(Self { config, key, contract, client: reqwest::Client::new(), history: Mutex::new(Vec::new()) })
+    }
+
+    /// `0x` address of the signing key.
+    pub(crate) fn signer(&self) -> String {
+        let point = self.key.verifying_key().to_encoded_point(false);
+        format!("0x{}", hex::encode(&Keccak256::digest(&point.as_bytes()[1..])[12..]))
+    }
+
+    fn history(&self) -> Vec<AnchorRecord> {
+        self.history.lock().expect("anchor history poisoned").clone()
+    }
+
+    async fn post(&self, root: ark_bn254::Fr, leaf_count: u64) -> Result<String, String> {
+        let mut data = Keccak256::digest(b"anchor(bytes32,uint64)")[..4].to_vec();
+        data.extend(pad32(&root.into_bigint().to_bytes_be()));
+        data.extend(pad32(&leaf_count.to_be_bytes()));
+
+        let nonce = quantity(&self.rpc("eth_getTransactionCount", json!([self.signer(), "pending"])).await?)?;
+        let gas_price = quantity(&self.rpc("eth_gasPrice", json!([])).await?)?;
+        let raw = self.sign_legacy(nonce, gas_price, &data)?;
+        let tx = self.rpc("eth_sendRawTransaction", json!([format!("0x{}", hex::encode(raw))])).await?;
+        tx.as_str().map(str::to_owned).ok_or_else(|| "node returned no transaction hash".into())
+    }
+
+    /// EIP-155 legacy transaction calling the contract with `data`.
+    fn sign_legacy(&self, nonce: u64, gas_price: u128, data: &[u8]) -> Result<Vec<u8>, String> {
+        let fields = |s: &mut RlpStream| {
+            s.append(&nonce).append(&gas_price).append(&self.config.gas_limit).append(&self.contract.as_slice()).append(&0u8).append(&data);
+        };
+        let mut unsigned = RlpStream::new_list(9);
+        fields(&mut unsigned);
+        unsigned.append(&self.config.chain_id).append(&0u8).append(&0u8);
+        let digest = Keccak256::new_with_prefix(unsigned.out());
+        let (sig, recid) = self.key.sign_digest_recoverable(digest).map_err(|e| e.to_string())?;
+
+        let mut signed = RlpStream::new_list(9);
+        fields(&mut signed);
+        let v = self.config.chain_id * 2 + 35 + u64::from(recid.to_byte());
+        let (r, s) = (sig.r().to_bytes(), sig.s().to_bytes());
+        signed.append(&v).append(&trim(&r)).append(&trim(&s));
+        Ok(signed.out().to_vec())
+    }
+
+    async fn rpc(&self, method: &str, params: Value) -> Result<Value, String> {
+        #[derive(Deserialize)]
+        struct Reply { result: Option<Value>, error: Option<Value> }
+        let body = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+        let reply: Reply = self.client.post(&self.config.rpc_url).json(&body).send().await
+            .and_then(|r| r.error_for_status()).map_err(|e| format!("{method}: {e}"))?
+            .json().await.map_err(|e| format!("{method}: {e}"))?;
+        match (reply.result, reply.error) {
+            (Some(result), None) => Ok(result),
+            (_, error) => Err(format!("{method}: {}", error.unwrap_or_default())),
+        }
+    }
+}
+
+/// Post the tree root every [`AnchorConfig::interval`] while it keeps
+/// changing.  [`crate::run`] spawns this; embedding applications that set
+/// [`crate::AppConfig::anchor`] spawn it on their own runtime.
+pub async fn run_anchoring(state: web::Data<AppState>) {
+    let Some(anchorer) = &state.anchor else { return };
+    let mut ticks = actix_web::rt::time::interval(anchorer.config.interval);
+    let mut anchored = None;
+    loop {
+        ticks.tick().await;
+        let (root, leaf_count) = {
+            let tree = state.tree.read().expect("commitment tree poisoned");
+            (tree.root(), tree.len())
+        };
+        if leaf_count == 0 || anchored == Some(leaf_count) {
+            continue;
+        }
+        match anchorer.post(root, leaf_count).await {
+            Ok(tx_hash) => {
+                tracing::info!(leaf_count, %tx_hash, "commitment root anchored");
+                let anchored_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+                let record = AnchorRecord { root: public_signals(&[root]).remove(0), leaf_count, tx_hash, anchored_at };
+                anchorer.history.lock().expect("anchor history poisoned").push(record);
+                anchored = Some(leaf_count);
+            }
+            // Retried on the next tick.
+            Err(error) => tracing::warn!(leaf_count, %error, "anchoring failed"),
+        }
+    }
+}
+
+fn pad32(bytes: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[32 - bytes.len()..].copy_from_slice(bytes);
+    out
+}
+
+/// Big-endian integer without leading zeros, as RLP wants it.
+fn trim(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().copied().skip_while(|b| *b == 0).collect()
+}
+
+fn quantity<T: TryFrom<u128>>(value: &Value) -> Result<T, String> {
+    let hex = value.as_str().and_then(|s| s.strip_prefix("0x")).ok_or("expected a hex quantity")?;
+    let n = u128::from_str_radix(hex, 16).map_err(|e| e.to_string())?;
+    T::try_from(n).map_err(|_| "quantity out of range".into())
+}
+
+//--------------------------------------------------------------------
+// /anchors
+//--------------------------------------------------------------------
+/// Roots anchored since this process started.
+#[get("/anchors")]
+async fn anchors(state: web::Data<AppState>) -> impl Responder {
+    let Some(anchorer) = &state.anchor else { return HttpResponse::NotFound().finish() };
+    HttpResponse::Ok().json(AnchorsResponse {
+        contract: anchorer.config.contract.clone(),
+        chain_id: anchorer.config.chain_id,
+        signer: anchorer.signer(),
+        anchors: anchorer.history(),
+    })
+}
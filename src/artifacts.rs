@@ -0,0 +1,267 @@
+use ark_bn254::{Bn254, Fr};
+use ark_circom::circom::{R1CSFile, R1CS};
+use ark_circom::{read_zkey, CircomConfig};
+use ark_groth16::{prepare_verifying_key, PreparedVerifyingKey, ProvingKey, VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::{self, BufReader, BufWriter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::{fs::File, path::{Path, PathBuf}};
+
+use crate::config::{AppConfig, CircuitKind, CircuitPaths, Mode};
+
+//--------------------------------------------------------------------
+// Circuit artefacts
+//--------------------------------------------------------------------
+/// Identifier of the bundled circuit, as reported by `/version`.
+pub const CIRCUIT_ID: &str = "secret-proof";
+
+pub(crate) static CIRCUIT_PATH: Lazy<(PathBuf, PathBuf, PathBuf)> = Lazy::new(|| {
+    let mut root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    root.push("circuits/secret-proof");
+    (
+        root.join("secret-proof_js/secret-proof.wasm"),
+        root.join("secret-proof.r1cs"),
+        root.join("secret_final.zkey"),
+    )
+});
+
+/// Keys for the circuit, each loaded on first use.
+///
+/// The zkey holds both keys and is large, so it is only read when a proof is
+/// generated or when no standalone verifying key was configured.
+pub(crate) struct Artifacts {
+    kind: CircuitKind,
+    wasm: PathBuf,
+    r1cs: PathBuf,
+    zkey: PathBuf,
+    vk_file: Option<PathBuf>,
+    fingerprints: OnceCell<Fingerprints>,
+    proving_key: OnceCell<ProvingKey<Bn254>>,
+    verifying_key: OnceCell<VerifyingKey<Bn254>>,
+    prepared_vk: OnceCell<PreparedVerifyingKey<Bn254>>,
+    vk_fingerprint: OnceCell<String>,
+    witness_r1cs: OnceCell<R1CS<Fr>>,
+    calculators: Mutex<Vec<CircomConfig<Fr>>>,
+    key_hits: AtomicU64,
+    key_misses: AtomicU64,
+}
+
+impl Artifacts {
+    pub(crate) fn new(config: &AppConfig) -> Self {
+        let vk_file = match &config.mode {
+            Mode::VerifyOnly { verifying_key } => Some(verifying_key.clone()),
+            Mode::Full | Mode::ProveOnly => None,
+        };
+        Self::from_keys(&config.circuit, config.circuit.zkey.clone(), vk_file)
+    }
+
+    /// Artefacts for a replacement key of the same circuit: a zkey, or on a
+    /// verify-only node an exported verifying key.
+    pub(crate) fn staged(config: &AppConfig, key: &Path) -> Self {
+        match &config.mode {
+            Mode::VerifyOnly { .. } => Self::from_keys(&config.circuit, config.circuit.zkey.clone(), Some(key.to_path_buf())),
+            Mode::Full | Mode::ProveOnly => Self::from_keys(&config.circuit, key.to_path_buf(), None),
+        }
+    }
+
+    /// A registered circuit of [`AppConfig::circuits`].  Its zkey serves
+    /// verification too, even on a verify-only node.
+    pub(crate) fn circuit(paths: &CircuitPaths) -> Self {
+        Self::from_keys(paths, paths.zkey.clone(), None)
+    }
+
+    /// Verification-only artefacts for an exported verifying key.
+    pub(crate) fn historical(circuit: &CircuitPaths, vk: &Path) -> Self {
+        Self::from_keys(circuit, circuit.zkey.clone(), Some(vk.to_path_buf()))
+    }
+
+    fn from_keys(circuit: &CircuitPaths, zkey: PathBuf, vk_file: Option<PathBuf>) -> Self {
+        Self {
+            kind: circuit.kind,
+            wasm: circuit.wasm.clone(),
+            r1cs: circuit.r1cs.clone(),
+            zkey,
+            vk_file,
+            fingerprints: OnceCell::new(),
+            proving_key: OnceCell::new(),
+            verifying_key: OnceCell::new(),
+            prepared_vk: OnceCell::new(),
+            vk_fingerprint: OnceCell::new(),
+            witness_r1cs: OnceCell::new(),
+            calculators: Mutex::new(Vec::new()),
+            key_hits: AtomicU64::new(0),
+            key_misses: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn proving_key(&self) -> &ProvingKey<Bn254> {
+        self.count_lookup(self.proving_key.get().is_some());
+        self.proving_key.get_or_init(|| read_proving_key(&self.zkey).expect("invalid zkey"))
+    }
+
+    pub(crate) fn verifying_key(&self) -> &VerifyingKey<Bn254> {
+        self.verifying_key.get_or_init(|| match &self.vk_file {
+            Some(path) => read_verifying_key(path).expect("invalid verifying key"),
+            None => self.proving_key().vk.clone(),
+        })
+    }
+
+    pub(crate) fn prepared_vk(&self) -> &PreparedVerifyingKey<Bn254> {
+        self.count_lookup(self.prepared_vk.get().is_some());
+        self.prepared_vk.get_or_init(|| prepare_verifying_key(self.verifying_key()))
+    }
+
+    /// SHA-256 of the compressed canonical encoding of the verifying key.
+    /// This identifies the circuit version: it changes whenever the circuit
+    /// or its trusted setup does, regardless of how the key file was stored.
+    pub(crate) fn vk_fingerprint(&self) -> &str {
+        self.vk_fingerprint.get_or_init(|| {
+            let mut bytes = Vec::new();
+            self.verifying_key().serialize_compressed(&mut bytes).expect("vk serialises into a Vec");
+            hex::encode(Sha256::digest(&bytes))
+        })
+    }
+
+    /// The statement proofs under this key prove, as configured.
+    pub(crate) fn kind(&self) -> CircuitKind {
+        self.kind
+    }
+
+    pub(crate) fn wasm_path(&self) -> &Path {
+        &self.wasm
+    }
+
+    pub(crate) fn r1cs_path(&self) -> &Path {
+        &self.r1cs
+    }
+
+    /// The constraints, for proving from a caller's `.wtns`.
+    pub(crate) fn witness_r1cs(&self) -> &R1CS<Fr> {
+        self.witness_r1cs.get_or_init(|| {
+            let file = File::open(&self.r1cs).expect("cannot read circuit r1cs");
+            let mut r1cs: R1CS<Fr> = R1CSFile::new(BufReader::new(file)).expect("invalid r1cs").into();
+            // `.wtns` files are indexed by wire already, as CircomBuilder assumes.
+            r1cs.wire_mapping = None;
+            r1cs
+        })
+    }
+
+    /// A witness calculator with the wasm compiled and the r1cs parsed,
+    /// loaded only when every one already loaded is in use.  Give it back
+    /// with [`Artifacts::release_calculator`], so proofs reuse it instead of
+    /// reloading both files each time.
+    pub(crate) fn take_calculator(&self) -> Result<CircomConfig<Fr>, String> {
+        if let Some(cfg) = self.calculators.lock().expect("witness calculators poisoned").pop() {
+            return Ok(cfg);
+        }
+        CircomConfig::new(&self.wasm, &self.r1cs).map_err(|e| e.to_string())
+    }
+
+    pub(crate) fn release_calculator(&self, cfg: CircomConfig<Fr>) {
+        self.calculators.lock().expect("witness calculators poisoned").push(cfg);
+    }
+
+    /// `None` on a verify-only node, which never reads the zkey.
+    pub(crate) fn zkey_path(&self) -> Option<&Path> {
+        self.vk_file.is_none().then_some(self.zkey.as_path())
+    }
+
+    /// Every file this instance would load.
+    pub(crate) fn files(&self) -> [&Path; 3] {
+        [&self.wasm, &self.r1cs, self.vk_file.as_deref().unwrap_or(&self.zkey)]
+    }
+
+    fn count_lookup(&self, hit: bool) {
+        let counter = if hit { &self.key_hits } else { &self.key_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Approximate in-memory size of the keys loaded so far, without
+    /// loading any; `None` for a key not yet loaded.
+    pub(crate) fn key_stats(&self) -> KeyStats {
+        KeyStats {
+            vk_fingerprint: self.vk_fingerprint.get().cloned(),
+            proving_key_bytes: self.proving_key.get().map(|pk| pk.uncompressed_size() as u64),
+            verifying_key_bytes: self.verifying_key.get().map(|vk| vk.uncompressed_size() as u64),
+            cache_hits: self.key_hits.load(Ordering::Relaxed),
+            cache_misses: self.key_misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// SHA-256 of each artefact file this instance would load.  Files that
+    /// are absent (e.g. the zkey on a verify-only node) are reported as
+    /// `None` rather than treated as an error.
+    pub(crate) fn fingerprints(&self) -> &Fingerprints {
+        self.fingerprints.get_or_init(|| {
+            let zkey = if self.vk_file.is_some() { None } else { sha256_file(&self.zkey) };
+            Fingerprints {
+                circuit_id: CIRCUIT_ID,
+                wasm_sha256: sha256_file(&self.wasm),
+                r1cs_sha256: sha256_file(&self.r1cs),
+                zkey_sha256: zkey,
+                vk_file_sha256: self.vk_file.as_deref().and_then(sha256_file),
+            }
+        })
+    }
+}
+
+/// Key memory and cache counters for one set of artefacts.  A lookup of the
+/// proving or prepared verifying key is a hit when it is already in memory.
+#[derive(Clone, Debug, Serialize)]
+pub struct KeyStats {
+    pub vk_fingerprint: Option<String>,
+    pub proving_key_bytes: Option<u64>,
+    pub verifying_key_bytes: Option<u64>,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+/// Content hashes identifying exactly which artefacts are deployed.
+#[derive(Clone, Debug, Serialize)]
+pub struct Fingerprints {
+    pub circuit_id: &'static str,
+    pub wasm_sha256: Option<String>,
+    pub r1cs_sha256: Option<String>,
+    pub zkey_sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vk_file_sha256: Option<String>,
+}
+
+fn sha256_file(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).ok()?;
+    Some(hex::encode(hasher.finalize()))
+}
+
+/// Parse the proving key out of a snarkjs zkey.
+pub fn read_proving_key(zkey: &Path) -> io::Result<ProvingKey<Bn254>> {
+    let mut rd = BufReader::new(File::open(zkey)?);
+    let (pk, _) = read_zkey(&mut rd).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(pk)
+}
+
+fn read_verifying_key(path: &Path) -> io::Result<VerifyingKey<Bn254>> {
+    let rd = BufReader::new(File::open(path)?);
+    VerifyingKey::deserialize_compressed(rd).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+//--------------------------------------------------------------------
+// Export
+//--------------------------------------------------------------------
+/// Extract the verifying key from `zkey` and write it to `out` in arkworks'
+/// compressed encoding — the file [`Mode::VerifyOnly`] expects.
+pub fn export_verifying_key(zkey: &Path, out: &Path) -> io::Result<()> {
+    let vk = read_proving_key(zkey)?.vk;
+    let mut wr = BufWriter::new(File::create(out)?);
+    vk.serialize_compressed(&mut wr).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Location of the bundled zkey, for tools that export from it.
+pub fn default_zkey_path() -> &'static Path {
+    &CIRCUIT_PATH.2
+}
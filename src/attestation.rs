@@ -0,0 +1,175 @@
+use actix_web::{get, web, HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::error::ApiError;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// TEE attestation
+//--------------------------------------------------------------------
+/// Gramine's SGX attestation pseudo-files.
+const GRAMINE_DIR: &str = "/dev/attestation";
+/// The kernel's configfs-tsm report interface (SEV-SNP and TDX guests).
+const TSM_REPORT_DIR: &str = "/sys/kernel/config/tsm/report";
+
+/// Where this process gets quotes from.
+enum Source {
+    /// SGX enclave under Gramine: write `user_report_data`, read `quote`.
+    Gramine,
+    /// Confidential VM: one configfs-tsm report directory per quote.
+    ConfigFsTsm,
+}
+
+/// Produces quotes binding 64 bytes of report data to the running TEE.
+pub(crate) struct Attester {
+    source: Source,
+    /// Quote generation goes through one shared device; one at a time.
+    lock: Mutex<()>,
+    reports: AtomicU64,
+}
+
+/// What a quote's report data commits to, so a client can recompute it:
+/// SHA-512 of [`AttestationClaims::report_message`].
+#[derive(Clone, Debug, Serialize)]
+pub struct AttestationClaims {
+    /// SHA-256 of the zkey file proofs are generated with; `None` on a
+    /// verify-only node.
+    pub zkey_sha256: Option<String>,
+    pub vk_fingerprint: String,
+    /// Hex Ed25519 key that signs verification receipts.
+    pub receipt_public_key: String,
+    /// The caller's freshness nonce, hex; empty when none was sent.
+    pub nonce: String,
+}
+
+impl AttestationClaims {
+    /// The exact bytes hashed into the report data.
+    pub fn report_message(&self) -> String {
+        format!(
+            "zk-auth-attestation/v1\n{}\n{}\n{}\n{}",
+            self.zkey_sha256.as_deref().unwrap_or(""),
+            self.vk_fingerprint,
+            self.receipt_public_key,
+            self.nonce
+        )
+    }
+}
+
+/// `/attestation` answer.  `tee` is `sgx` for Gramine, otherwise the
+/// configfs-tsm provider (`sev_guest`, `tdx_guest`, …); `quote` is the raw
+/// hardware quote in hex, for the vendor's verification tooling.
+#[derive(Debug, Serialize)]
+pub struct AttestationResponse {
+    pub tee: String,
+    pub quote: String,
+    /// Hex SHA-512 of the claims' report message.
+    pub report_data: String,
+    pub claims: AttestationClaims,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttestationQuery {
+    /// Up to 32 bytes of hex, echoed in the claims so a quote cannot be
+    /// replayed from an earlier request.
+    #[serde(default)]
+    pub nonce: Option<String>,
+}
+
+impl Attester {
+    /// The quote source of the TEE this process runs in, if any.
+    pub(crate) fn detect() -> Option<Self> {
+        let source = if Path::new(GRAMINE_DIR).join("quote").exists() {
+            Source::Gramine
+        } else if Path::new(TSM_REPORT_DIR).is_dir() {
+            Source::ConfigFsTsm
+        } else {
+            return None;
+        };
+        Some(Self { source, lock: Mutex::new(()), reports: AtomicU64::new(0) })
+    }
+
+    /// `(tee, quote)` over `report_data`.
+    fn quote(&self, report_data: &[u8; 64]) -> io::Result<(String, Vec<u8>)> {
+        let _guard = self.lock.lock().expect("attestation lock poisoned");
+        match self.source {
+            Source::Gramine => {
+                let dir = Path::new(GRAMINE_DIR);
+                std::fs::write(dir.join("user_report_data"), report_data)?;
+                Ok(("sgx".to_owned(), std::fs::read(dir.join("quote"))?))
+            }
+            Source::ConfigFsTsm => {
+                let n = self.reports.fetch_add(1, Ordering::Relaxed);
+                let dir = PathBuf::from(TSM_REPORT_DIR).join(format!("zk-auth-{}-{n}", std::process::id()));
+                std::fs::create_dir(&dir)?;
+                let res = (|| {
+                    std::fs::write(dir.join("inblob"), report_data)?;
+                    let quote = std::fs::read(dir.join("outblob"))?;
+                    let provider = std::fs::read_to_string(dir.join("provider"))?;
+                    Ok((provider.trim().to_owned(), quote))
+                })();
+                // configfs entries are removed with rmdir, never remove_dir_all.
+                let _ = std::fs::remove_dir(&dir);
+                res
+            }
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self.source {
+            Source::Gramine => "sgx",
+            Source::ConfigFsTsm => "configfs-tsm",
+        }
+    }
+}
+
+//--------------------------------------------------------------------
+// /attestation
+//--------------------------------------------------------------------
+/// A fresh quote binding the active key and the receipt signing key to this
+/// enclave, for custodial clients deciding whether to hand over a secret.
+#[get("/attestation")]
+async fn attestation(state: web::Data<AppState>, query: web::Query<AttestationQuery>) -> HttpResponse {
+    if state.attester.is_none() {
+        return ApiError::Unavailable("this server is not running inside a supported TEE").error_response();
+    }
+    let nonce = match query.nonce.as_deref().map(|n| hex::decode(n.trim_start_matches("0x"))) {
+        None => Vec::new(),
+        Some(Ok(bytes)) if bytes.len() <= 32 => bytes,
+        Some(Ok(_)) => return ApiError::InvalidInput { field: "nonce", reason: "must be at most 32 bytes" }.error_response(),
+        Some(Err(_)) => return ApiError::InvalidInput { field: "nonce", reason: "not valid hex" }.error_response(),
+    };
+
+    // Hashes the zkey and loads the verifying key on first use.
+    let worker_state = state.clone();
+    let res = web::block(move || {
+        let artifacts = worker_state.active_artifacts();
+        let claims = AttestationClaims {
+            zkey_sha256: if worker_state.config.mode.proves() { artifacts.fingerprints().zkey_sha256.clone() } else { None },
+            vk_fingerprint: artifacts.vk_fingerprint().to_owned(),
+            receipt_public_key: worker_state.receipts.public_key_hex(),
+            nonce: hex::encode(&nonce),
+        };
+        let report_data: [u8; 64] = Sha512::digest(claims.report_message().as_bytes()).into();
+        let attester = worker_state.attester.as_ref().expect("checked above");
+        attester.quote(&report_data).map(|(tee, quote)| AttestationResponse {
+            tee,
+            quote: hex::encode(quote),
+            report_data: hex::encode(report_data),
+            claims,
+        })
+    })
+    .await;
+    match res {
+        Ok(Ok(body)) => HttpResponse::Ok().json(body),
+        Ok(Err(e)) => {
+            tracing::error!(error = %e, "cannot obtain attestation quote");
+            ApiError::Unavailable("the TEE did not produce a quote").error_response()
+        }
+        Err(_) => ApiError::Internal.error_response(),
+    }
+}
@@ -0,0 +1,137 @@
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+//--------------------------------------------------------------------
+// Encrypted state backups
+//--------------------------------------------------------------------
+/// First bytes of every backup file, followed by a format version byte.
+pub const BACKUP_MAGIC: &[u8; 8] = b"ZKBACKUP";
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+
+/// What a backup holds, CBOR-encoded and then sealed with AES-256-GCM.
+///
+/// Only durable state is captured: the commitment tree lives in memory
+/// and is not part of it.  Key files are never read, only the settings
+/// naming them, so keys kept in a KMS or secret store stay there.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupArchive {
+    /// Unix seconds when the backup was taken.
+    pub created_at: u64,
+    /// The server's environment settings, secrets removed.
+    pub config: BTreeMap<String, String>,
+    /// Proof store files, relative to the store directory.
+    pub proofs: Vec<BackupFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupFile {
+    pub path: String,
+    /// Hex SHA-256 of `contents`, checked again on restore.
+    pub sha256: String,
+    #[serde(with = "serde_bytes")]
+    pub contents: Vec<u8>,
+}
+
+/// Read the hex 32-byte backup key in `path`.
+pub fn read_backup_key(path: &Path) -> io::Result<[u8; 32]> {
+    let key = hex::decode(fs::read_to_string(path)?.trim().trim_start_matches("0x")).map_err(|_| invalid("backup key is not hex"))?;
+    key.try_into().map_err(|_| invalid("backup key must be 32 bytes"))
+}
+
+/// Write an encrypted backup of `config` and the proof store at
+/// `proof_store`, if any, to `out`.
+pub fn backup(out: &Path, key: &[u8; 32], config: BTreeMap<String, String>, proof_store: Option<&Path>) -> io::Result<BackupArchive> {
+    let mut proofs = Vec::new();
+    if let Some(dir) = proof_store {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            // Skip half-written `.json.tmp` files.
+            if !path.is_file() || path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            let contents = fs::read(&path)?;
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            proofs.push(BackupFile { path: name, sha256: hex::encode(Sha256::digest(&contents)), contents });
+        }
+        proofs.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    let created_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let archive = BackupArchive { created_at, config, proofs };
+
+    let mut plain = Vec::new();
+    ciborium::into_writer(&archive, &mut plain).expect("backup serialises into a Vec");
+    let mut nonce = [0u8; NONCE_LEN];
+    thread_rng().fill_bytes(&mut nonce);
+    let header = header();
+    let sealed = cipher(key)
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plain, aad: &header })
+        .map_err(|_| invalid("encryption failed"))?;
+    fs::write(out, [header.as_slice(), &nonce, &sealed].concat())?;
+    Ok(archive)
+}
+
+/// Decrypt and check the backup in `input`: the GCM tag covers the whole
+/// archive, and every file's digest is checked as well.
+pub fn open_backup(input: &Path, key: &[u8; 32]) -> io::Result<BackupArchive> {
+    let bytes = fs::read(input)?;
+    let header = header();
+    let rest = bytes.strip_prefix(header.as_slice()).ok_or_else(|| invalid("not a zk-auth backup, or an unsupported version"))?;
+    if rest.len() < NONCE_LEN {
+        return Err(invalid("backup is truncated"));
+    }
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    let plain = cipher(key)
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad: &header })
+        .map_err(|_| invalid("backup does not decrypt: wrong key, or the file was modified"))?;
+    let archive: BackupArchive = ciborium::from_reader(plain.as_slice()).map_err(|e| invalid(format!("malformed backup: {e}")))?;
+    for file in &archive.proofs {
+        if hex::encode(Sha256::digest(&file.contents)) != file.sha256 {
+            return Err(invalid(format!("{}: digest does not match", file.path)));
+        }
+    }
+    Ok(archive)
+}
+
+/// Restore `archive` into `dir`: the proof store under `dir/proofs` and
+/// the settings as `dir/config.env`.  Existing files are not overwritten.
+pub fn restore(archive: &BackupArchive, dir: &Path) -> io::Result<()> {
+    let proofs = dir.join("proofs");
+    fs::create_dir_all(&proofs)?;
+    for file in &archive.proofs {
+        let name = Path::new(&file.path);
+        if !matches!(name.components().collect::<Vec<_>>().as_slice(), [Component::Normal(_)]) {
+            return Err(invalid(format!("{}: not a plain file name", file.path)));
+        }
+        write_new(&proofs.join(name), &file.contents)?;
+    }
+    let env: String = archive.config.iter().map(|(k, v)| format!("{k}={v}\n")).collect();
+    write_new(&dir.join("config.env"), env.as_bytes())
+}
+
+fn write_new(path: &Path, contents: &[u8]) -> io::Result<()> {
+    fs::OpenOptions::new().write(true).create_new(true).open(path)?.write_all(contents)
+}
+
+fn header() -> [u8; 9] {
+    let mut header = [0u8; 9];
+    header[..8].copy_from_slice(BACKUP_MAGIC);
+    header[8] = VERSION;
+    header
+}
+
+fn cipher(key: &[u8; 32]) -> Aes256Gcm {
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
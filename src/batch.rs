@@ -0,0 +1,210 @@
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::web::{Bytes, BytesMut};
+use actix_web::{post, web, HttpRequest, HttpResponse, ResponseError};
+use ark_bn254::Fr;
+use futures_util::stream::{self, StreamExt};
+use rand::{thread_rng, Rng};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::artifacts::Artifacts;
+use crate::error::{ApiError, ErrorBody};
+use crate::handlers::{accepting_key, check_single_use, check_verify_request, spend_single_use, verdict, verify_request, VerifyCheck, VerifyRequest, VerifyResponse};
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// /verify-batch
+//--------------------------------------------------------------------
+/// Longest accepted NDJSON line; a proof request is well under 2 KiB.
+const MAX_LINE: usize = 64 * 1024;
+/// Most requests in one `application/json` batch, and its largest body.
+const MAX_JSON_BATCH: usize = 1024;
+const MAX_JSON_BODY: usize = 4 * 1024 * 1024;
+
+/// One NDJSON output line: the verdict for input line `index` (from 0), or
+/// why it could not be checked.
+#[derive(Debug, Serialize)]
+pub struct BatchLine {
+    pub index: u64,
+    #[serde(flatten)]
+    pub result: BatchResult,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum BatchResult {
+    Verdict(VerifyResponse),
+    Error(ErrorBody),
+}
+
+/// `application/json` answer: `valid` is true when every request is.
+#[derive(Debug, Serialize)]
+pub struct BatchResponse {
+    pub valid: bool,
+    pub results: Vec<BatchLine>,
+}
+
+/// Streams verdicts for a `application/x-ndjson` body of `/verify-proof`
+/// requests.  Lines are read and answered one at a time, so neither side
+/// has to hold the batch in memory; a bad line yields an error line and
+/// the stream carries on.  Blank lines are skipped without an index.
+///
+/// An `application/json` array of requests is instead checked at once, see
+/// [`verify_json_batch`].
+#[post("/verify-batch")]
+async fn verify_batch(state: web::Data<AppState>, req: HttpRequest, payload: web::Payload) -> HttpResponse {
+    let content_type = req.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if content_type.starts_with("application/json") {
+        return verify_json_batch(state, payload).await;
+    }
+    if !content_type.starts_with("application/x-ndjson") {
+        return HttpResponse::UnsupportedMediaType().json(ErrorBody {
+            error: "unsupported_media_type",
+            message: "send application/x-ndjson, one verify request per line, or a JSON array".into(),
+        });
+    }
+
+    let reader = LineReader { payload, buf: BytesMut::new(), done: false };
+    let lines = stream::unfold((reader, state, 0u64), |(mut reader, state, index)| async move {
+        let line = match reader.next_line().await? {
+            Ok(line) => line,
+            Err(e) => return Some((Ok(render(index, Err(e))), (reader, state, index + 1))),
+        };
+        let result = match serde_json::from_slice::<VerifyRequest>(&line) {
+            Ok(body) => verify_request(&state, &body).await,
+            Err(_) => Err(ApiError::InvalidInput { field: "line", reason: "not a JSON verify request" }),
+        };
+        Some((Ok::<_, actix_web::Error>(render(index, result)), (reader, state, index + 1)))
+    });
+    HttpResponse::Ok().content_type("application/x-ndjson").streaming(lines)
+}
+
+fn render(index: u64, result: Result<VerifyResponse, ApiError>) -> Bytes {
+    let mut line = serde_json::to_vec(&batch_line(index, result)).expect("batch line serialises");
+    line.push(b'\n');
+    Bytes::from(line)
+}
+
+fn batch_line(index: u64, result: Result<VerifyResponse, ApiError>) -> BatchLine {
+    let result = match result {
+        Ok(verdict) => BatchResult::Verdict(verdict),
+        Err(e) => BatchResult::Error(ErrorBody { error: e.code(), message: e.to_string() }),
+    };
+    BatchLine { index, result }
+}
+
+//--------------------------------------------------------------------
+// Randomised batch verification
+//--------------------------------------------------------------------
+/// Check a JSON array of `/verify-proof` requests together.  Proofs for
+/// the same key share one multi-pairing, with the equations combined by
+/// random 128-bit weights; only when that fails is each proof of the
+/// group checked alone to find the bad ones.  Answers come in request
+/// order, errors for requests that did not parse included.
+async fn verify_json_batch(state: web::Data<AppState>, payload: web::Payload) -> HttpResponse {
+    let body = match payload.to_bytes_limited(MAX_JSON_BODY).await {
+        Ok(Ok(body)) => body,
+        Ok(Err(_)) => return ApiError::InvalidInput { field: "body", reason: "request body ended abruptly" }.error_response(),
+        Err(_) => return ApiError::InvalidInput { field: "body", reason: "batch exceeds 4 MiB" }.error_response(),
+    };
+    let requests: Vec<VerifyRequest> = match serde_json::from_slice(&body) {
+        Ok(requests) => requests,
+        Err(_) => return ApiError::InvalidInput { field: "body", reason: "not a JSON array of verify requests" }.error_response(),
+    };
+    if requests.len() > MAX_JSON_BATCH {
+        return ApiError::InvalidInput { field: "body", reason: "more than 1024 requests" }.error_response();
+    }
+    let _permit = state.limits.verify.acquire().await;
+    let worker_state = state.clone();
+    match web::block(move || verify_all(&worker_state, &requests)).await {
+        Ok(results) => {
+            let results: Vec<BatchLine> = results.into_iter().enumerate().map(|(i, r)| batch_line(i as u64, r)).collect();
+            let valid = results.iter().all(|line| matches!(&line.result, BatchResult::Verdict(v) if v.valid));
+            HttpResponse::Ok().json(BatchResponse { valid, results })
+        }
+        Err(_) => ApiError::Internal.error_response(),
+    }
+}
+
+pub(crate) fn verify_all(state: &AppState, requests: &[VerifyRequest]) -> Vec<Result<VerifyResponse, ApiError>> {
+    let started = Instant::now();
+    let checks: Vec<Result<VerifyCheck, ApiError>> =
+        requests.iter().map(|r| check_verify_request(state, r).and_then(|check| check_single_use(state, &check).map(|_| check))).collect();
+    let mut accepted: Vec<Option<Option<&Artifacts>>> = vec![None; requests.len()];
+    // Mock proofs hold under no key, so they are only ever checked alone.
+    if !state.mock {
+        let mut groups: HashMap<&str, (&Artifacts, Vec<usize>)> = HashMap::new();
+        for (i, check) in checks.iter().enumerate() {
+            if let Ok(check) = check {
+                groups.entry(check.keys[0].vk_fingerprint()).or_insert((check.keys[0], Vec::new())).1.push(i);
+            }
+        }
+        for (key, members) in groups.into_values() {
+            if members.len() > 1 && batch_holds(key, members.iter().filter_map(|&i| checks[i].as_ref().ok())) {
+                members.iter().for_each(|&i| accepted[i] = Some(Some(key)));
+            }
+        }
+    }
+    checks
+        .into_iter()
+        .zip(requests)
+        .zip(accepted)
+        .map(|((check, request), accepted)| {
+            let check = check?;
+            let accepted_by = accepted.unwrap_or_else(|| accepting_key(state, &check));
+            spend_single_use(state, &check, accepted_by)?;
+            Ok(verdict(state, request, &check, accepted_by, started))
+        })
+        .collect()
+}
+
+/// Whether every proof in `checks` holds under `key`, up to a 2^-128 chance.
+fn batch_holds<'a>(key: &Artifacts, checks: impl Iterator<Item = &'a VerifyCheck<'a>>) -> bool {
+    let inputs: Vec<_> = checks.map(|c| (c.public_inputs(), &c.proof)).collect();
+    let batch: Vec<_> = inputs.iter().map(|(public, proof)| (public.as_slice(), *proof)).collect();
+    let mut rng = thread_rng();
+    let randomizers: Vec<Fr> = batch.iter().map(|_| Fr::from(rng.gen::<u128>())).collect();
+    zk_auth_verify_core::verify_batch(key.prepared_vk(), &batch, &randomizers).unwrap_or(false)
+}
+
+/// Splits the request body into non-blank lines as chunks arrive.
+struct LineReader {
+    payload: web::Payload,
+    buf: BytesMut,
+    done: bool,
+}
+
+impl LineReader {
+    /// `None` at the end of the body.  An over-long line or a broken body is
+    /// reported once and ends the stream, since the framing is lost.
+    async fn next_line(&mut self) -> Option<Result<Bytes, ApiError>> {
+        loop {
+            if let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+                let line = self.buf.split_to(pos + 1).freeze();
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                return Some(Ok(line));
+            }
+            if self.done {
+                let rest = self.buf.split().freeze();
+                return (!rest.iter().all(u8::is_ascii_whitespace)).then_some(Ok(rest));
+            }
+            if self.buf.len() > MAX_LINE {
+                self.done = true;
+                self.buf.clear();
+                return Some(Err(ApiError::InvalidInput { field: "line", reason: "line exceeds 64 KiB" }));
+            }
+            match self.payload.next().await {
+                Some(Ok(chunk)) => self.buf.extend_from_slice(&chunk),
+                Some(Err(_)) => {
+                    self.done = true;
+                    self.buf.clear();
+                    return Some(Err(ApiError::InvalidInput { field: "body", reason: "request body ended abruptly" }));
+                }
+                None => self.done = true,
+            }
+        }
+    }
+}
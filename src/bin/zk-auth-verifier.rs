@@ -0,0 +1,118 @@
+//! Verification-only server for verifier fleets.
+//!
+//! Unlike the main binary it never touches the zk-auth library, so the
+//! witness calculator, circom and the proving code are not linked in: only
+//! `zk-auth-verify-core`, actix and the verifying keys.  It answers the
+//! same `/verify-proof`, `/vk/fingerprint` and `/health` requests.
+
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{get, post, web, App, HttpResponse, HttpServer};
+use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::TcpListener;
+use std::path::Path;
+use tracing_subscriber::EnvFilter;
+use zk_auth_verify_core::{prepare_verifying_key, proof_from_evm_hex, verify, Bn254, Fr, PreparedVerifyingKey, VerifyingKey};
+
+const USAGE: &str = "usage: zk-auth-verifier <vk.bin> [older-vk.bin ...]";
+const VK_HEADER: HeaderName = HeaderName::from_static("x-zk-vk");
+
+/// One exported verifying key; the first is the active one.
+struct Key {
+    prepared: PreparedVerifyingKey<Bn254>,
+    fingerprint: String,
+}
+
+impl Key {
+    fn load(path: &Path) -> std::io::Result<Self> {
+        let vk = VerifyingKey::<Bn254>::deserialize_compressed(std::fs::read(path)?.as_slice())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {e}", path.display())))?;
+        let mut bytes = Vec::new();
+        vk.serialize_compressed(&mut bytes).expect("vk serialises into a Vec");
+        Ok(Self { prepared: prepare_verifying_key(&vk), fingerprint: hex::encode(Sha256::digest(&bytes)) })
+    }
+}
+
+#[derive(Deserialize)]  struct ProofJson { a:[String;2], b:[[String;2];2], c:[String;2] }
+#[derive(Deserialize)]  struct VerifyRequest { commitment:String, proof:ProofJson, #[serde(default)] circuit_version:Option<String> }
+/// The main server's answer without its signed receipt: this binary holds no signing key.
+#[derive(Serialize)]    struct VerifyResponse { valid:bool, #[serde(skip_serializing_if="Option::is_none")] circuit_version:Option<String> }
+#[derive(Serialize)]    struct ErrorBody { error:&'static str, message:String }
+#[derive(Serialize)]    struct VkFingerprintResponse<'a> { algorithm:&'static str, vk_fingerprint:&'a str,
+                                                            #[serde(skip_serializing_if="<[_]>::is_empty")] historical:Vec<&'a str> }
+
+fn invalid(field:&str,reason:&str)->HttpResponse{
+    HttpResponse::BadRequest().json(ErrorBody{error:"invalid_input",message:format!("{field}: {reason}")})
+}
+
+#[post("/verify-proof")]
+async fn verify_proof(keys:web::Data<Vec<Key>>,body:web::Json<VerifyRequest>)->HttpResponse{
+    let body=body.into_inner();
+    let candidates:Vec<usize>=match &body.circuit_version {
+        Some(v) => match keys.iter().position(|k|v.eq_ignore_ascii_case(&k.fingerprint)) {
+            Some(i) => vec![i],
+            None => return HttpResponse::Conflict().json(ErrorBody{error:"circuit_mismatch",
+                message:format!("proof was generated for circuit version {v}, server expects {}",keys[0].fingerprint)}),
+        },
+        None => (0..keys.len()).collect(),
+    };
+    let p=&body.proof;
+    let Ok(proof)=proof_from_evm_hex([&p.a[0],&p.a[1]],[[&p.b[0][0],&p.b[0][1]],[&p.b[1][0],&p.b[1][1]]],[&p.c[0],&p.c[1]]) else {
+        return invalid("proof","not a valid BN254 proof");
+    };
+    // Decimal, reduced into the field as `/register` produces it.
+    let Some(commitment)=BigUint::parse_bytes(body.commitment.as_bytes(),10) else {
+        return invalid("commitment","not a decimal integer");
+    };
+    let commitment=Fr::from_be_bytes_mod_order(&commitment.to_bytes_be());
+
+    let worker_keys=keys.clone();
+    let accepted=web::block(move||candidates.into_iter().find(|&i|verify(&worker_keys[i].prepared,&[commitment],&proof).unwrap_or(false))).await;
+    let Ok(accepted)=accepted else { return HttpResponse::InternalServerError().json(ErrorBody{error:"internal",message:"internal server error".into()}) };
+    let verdict=VerifyResponse{valid:accepted.is_some(),circuit_version:accepted.map(|i|keys[i].fingerprint.clone())};
+    let mut res=if verdict.valid { HttpResponse::Ok().json(verdict) } else { HttpResponse::Unauthorized().json(verdict) };
+    if let Ok(v)=HeaderValue::from_str(&keys[0].fingerprint) { res.headers_mut().insert(VK_HEADER,v); }
+    res
+}
+
+#[get("/vk/fingerprint")]
+async fn vk_fingerprint(keys:web::Data<Vec<Key>>)->HttpResponse{
+    HttpResponse::Ok().json(VkFingerprintResponse{
+        algorithm:"sha256", vk_fingerprint:&keys[0].fingerprint, historical:keys[1..].iter().map(|k|k.fingerprint.as_str()).collect(),
+    })
+}
+
+#[get("/health")]
+async fn health()->HttpResponse{
+    HttpResponse::Ok().json(serde_json::json!({"status":"ok"}))
+}
+
+#[actix_web::main]
+async fn main()->std::io::Result<()>{
+    let filter=EnvFilter::try_from_default_env().unwrap_or_else(|_|EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let paths:Vec<String>=std::env::args().skip(1).collect();
+    if paths.is_empty() || paths.iter().any(|p|p.starts_with('-')) {
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    }
+    let keys=web::Data::new(paths.iter().map(|p|Key::load(p.as_ref())).collect::<std::io::Result<Vec<_>>>()?);
+    let listener=match listenfd::ListenFd::from_env().take_tcp_listener(0)? {
+        Some(listener)=>listener,
+        None=>TcpListener::bind(std::env::var("ZK_BIND").unwrap_or_else(|_|"0.0.0.0:8080".into()))?,
+    };
+    tracing::info!(
+        bind=%listener.local_addr().map(|a|a.to_string()).unwrap_or_default(),
+        version=env!("CARGO_PKG_VERSION"),
+        vk_fingerprint=%keys[0].fingerprint,
+        historical_vks=keys.len()-1,
+        "zk-auth verifier starting"
+    );
+    HttpServer::new(move||App::new().app_data(keys.clone()).service(verify_proof).service(vk_fingerprint).service(health))
+        .listen(listener)?
+        .run()
+        .await
+}
@@ -0,0 +1,124 @@
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::artifacts::{Artifacts, CIRCUIT_ID};
+use crate::config::{AppConfig, CircuitPaths};
+use crate::convert::{normalize_public_input, sniff_proof, ProofRecord};
+use crate::prover::verify;
+use crate::snarkjs::public_signals;
+
+//--------------------------------------------------------------------
+// .zkauth bundles
+//--------------------------------------------------------------------
+/// First bytes of every `.zkauth` file: the name and the format version.
+pub const BUNDLE_MAGIC: &[u8; 8] = b"ZKAUTH\x00\x01";
+
+/// A proof with everything needed to verify it later, stored as
+/// [`BUNDLE_MAGIC`] followed by this struct in CBOR.
+///
+/// `vk_fingerprint` names the verifying key, so an archived key can be
+/// found for the bundle long after the server has rotated away from it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofBundle {
+    pub circuit_id: String,
+    pub vk_fingerprint: String,
+    /// arkworks compressed encoding, 128 bytes.
+    #[serde(with = "serde_bytes")]
+    pub proof: Vec<u8>,
+    /// Decimal, in circuit order.
+    pub public_inputs: Vec<String>,
+    /// Unix seconds when the bundle was made.
+    pub created_at: u64,
+    /// Free-form labels: who the proof was for, a ticket number, …
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl ProofBundle {
+    /// Bundle `record` for the verifying key with `vk_fingerprint`.
+    pub fn new(record: &ProofRecord, vk_fingerprint: &str) -> Self {
+        let mut proof = Vec::new();
+        record.proof.serialize_compressed(&mut proof).expect("proof serialises into a Vec");
+        Self {
+            circuit_id: CIRCUIT_ID.to_owned(),
+            vk_fingerprint: vk_fingerprint.to_owned(),
+            proof,
+            public_inputs: public_signals(&record.public_inputs),
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = BUNDLE_MAGIC.to_vec();
+        ciborium::into_writer(self, &mut out).expect("bundle serialises into a Vec");
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let body = bytes.strip_prefix(BUNDLE_MAGIC.as_slice()).ok_or_else(|| invalid("not a .zkauth bundle (bad magic or version)"))?;
+        ciborium::from_reader(body).map_err(|e| invalid(format!("malformed bundle: {e}")))
+    }
+
+    /// The proof and public inputs, with the points checked on the curve.
+    pub fn record(&self) -> io::Result<ProofRecord> {
+        let proof = CanonicalDeserialize::deserialize_compressed(self.proof.as_slice()).map_err(|e| invalid(e.to_string()))?;
+        let public_inputs = self.public_inputs.iter().map(|s| normalize_public_input(s)).collect::<io::Result<_>>()?;
+        Ok(ProofRecord { proof, public_inputs })
+    }
+}
+
+//--------------------------------------------------------------------
+// CLI
+//--------------------------------------------------------------------
+/// The key in an exported verifying key file, or the bundled zkey's.
+fn key(vk: Option<&Path>) -> Artifacts {
+    match vk {
+        Some(vk) => Artifacts::historical(&CircuitPaths::default(), vk),
+        None => Artifacts::new(&AppConfig::default()),
+    }
+}
+
+/// Bundle the proof in `input` (any JSON format `convert` reads, with its
+/// public inputs) for `vk`, or the bundled zkey's key.  A proof that does
+/// not verify under that key is refused.
+pub fn export_bundle(input: &Path, output: &Path, vk: Option<&Path>, metadata: BTreeMap<String, String>) -> io::Result<ProofBundle> {
+    let record = sniff_proof(None, &fs::read(input)?).ok_or_else(|| invalid(format!("{}: not a recognised proof", input.display())))?;
+    let [commitment] = record.public_inputs[..] else {
+        return Err(invalid(format!("{}: expected exactly one public input (the commitment)", input.display())));
+    };
+    let key = key(vk);
+    if !verify(&key, commitment, &record.proof) {
+        return Err(invalid(format!("{}: proof does not verify under key {}", input.display(), key.vk_fingerprint())));
+    }
+    let bundle = ProofBundle { metadata, ..ProofBundle::new(&record, key.vk_fingerprint()) };
+    fs::write(output, bundle.to_bytes())?;
+    Ok(bundle)
+}
+
+/// Verify `bundle` with whichever of `vks` (or the bundled zkey's key) has
+/// its fingerprint.  `Err` when none does.
+pub fn verify_bundle(bundle: &ProofBundle, vks: &[PathBuf]) -> io::Result<bool> {
+    if bundle.circuit_id != CIRCUIT_ID {
+        return Err(invalid(format!("bundle is for circuit `{}`, not `{CIRCUIT_ID}`", bundle.circuit_id)));
+    }
+    let keys = if vks.is_empty() { vec![key(None)] } else { vks.iter().map(|vk| key(Some(vk))).collect() };
+    let key = keys
+        .iter()
+        .find(|k| k.vk_fingerprint() == bundle.vk_fingerprint)
+        .ok_or_else(|| invalid(format!("no verifying key with fingerprint {}", bundle.vk_fingerprint)))?;
+    let record = bundle.record()?;
+    let [commitment] = record.public_inputs[..] else {
+        return Ok(false);
+    };
+    Ok(verify(key, commitment, &record.proof))
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
@@ -0,0 +1,96 @@
+use actix_web::{post, web, HttpResponse, ResponseError};
+use ark_bn254::{Bn254, Fr};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::Proof;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::error::ApiError;
+use crate::handlers::ProofJson;
+
+//--------------------------------------------------------------------
+// Solidity calldata
+//--------------------------------------------------------------------
+/// The function snarkjs-generated Groth16 verifiers expose for a circuit
+/// with one public input.
+pub const VERIFY_PROOF_SIGNATURE: &str = "verifyProof(uint256[2],uint256[2][2],uint256[2],uint256[1])";
+
+/// ABI-encoded call of [`VERIFY_PROOF_SIGNATURE`]: the selector, then nine
+/// 32-byte words, `a`, `b` with each G2 coordinate's `c1` limb first, `c`
+/// and the commitment.
+pub fn verify_proof_calldata(proof: &Proof<Bn254>, commitment: Fr) -> Vec<u8> {
+    let (a, b, c) = (&proof.a, &proof.b, &proof.c);
+    let mut data = Keccak256::digest(VERIFY_PROOF_SIGNATURE.as_bytes())[..4].to_vec();
+    for word in [a.x, a.y, b.x.c1, b.x.c0, b.y.c1, b.y.c0, c.x, c.y] {
+        data.extend(word.into_bigint().to_bytes_be());
+    }
+    data.extend(commitment.into_bigint().to_bytes_be());
+    data
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CalldataRequest {
+    /// Decimal, as `/register` returns it.
+    pub commitment: String,
+    pub proof: ProofJson,
+}
+
+/// The call both ways: `calldata` for a raw `eth_call` or transaction, and
+/// the arguments for a contract binding, as `snarkjs zkey export
+/// soliditycalldata` prints them.
+#[derive(Debug, Serialize)]
+pub struct CalldataResponse {
+    pub signature: &'static str,
+    pub calldata: String,
+    #[serde(flatten)]
+    pub proof: ProofJson,
+    /// The commitment.
+    pub input: [String; 1],
+}
+
+#[post("/calldata")]
+async fn calldata(body: web::Json<CalldataRequest>) -> HttpResponse {
+    let Some(proof) = body.proof.try_to_proof() else {
+        return ApiError::InvalidInput { field: "proof", reason: "not a valid BN254 proof" }.error_response();
+    };
+    let Some(commitment) = num_bigint::BigUint::parse_bytes(body.commitment.as_bytes(), 10) else {
+        return ApiError::InvalidInput { field: "commitment", reason: "not a decimal integer" }.error_response();
+    };
+    // Reduced, as `/register` and `/verify-proof` read it; the verifier
+    // contract rejects inputs at or above the modulus.
+    let commitment = Fr::from_be_bytes_mod_order(&commitment.to_bytes_be());
+    HttpResponse::Ok().json(CalldataResponse {
+        signature: VERIFY_PROOF_SIGNATURE,
+        calldata: format!("0x{}", hex::encode(verify_proof_calldata(&proof, commitment))),
+        proof: ProofJson::from_proof(&proof),
+        input: [format!("0x{}", hex::encode(commitment.into_bigint().to_bytes_be()))],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{identity, valid_proof};
+
+    #[test]
+    fn encodes_the_evm_word_order() {
+        let identity = identity(12);
+        let json = valid_proof(&identity, 12);
+        let data = verify_proof_calldata(&json.try_to_proof().unwrap(), identity.commitment);
+        assert_eq!(hex::encode(&data[..4]), "43753b4d");
+        assert_eq!(data.len(), 4 + 9 * 32);
+        // ProofJson is already in EVM order, `c1` first in each G2 coordinate.
+        let words: Vec<String> = data[4..].chunks(32).map(|w| format!("0x{}", hex::encode(w))).collect();
+        let expected = [&json.a[..], &json.b[0][..], &json.b[1][..], &json.c[..]].concat();
+        assert_eq!(words[..8], expected[..]);
+        assert_eq!(words[8], format!("0x{}", hex::encode(identity.commitment.into_bigint().to_bytes_be())));
+    }
+
+    #[test]
+    fn pads_small_words_to_32_bytes() {
+        let json = valid_proof(&identity(13), 13);
+        let data = verify_proof_calldata(&json.try_to_proof().unwrap(), Fr::from(1u64));
+        assert_eq!(data.len(), 4 + 9 * 32);
+        assert_eq!(data[4 + 8 * 32..], [[0u8; 31].as_slice(), &[1]].concat()[..]);
+    }
+}
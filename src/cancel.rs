@@ -0,0 +1,97 @@
+use actix_web::web;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::error::ApiError;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Cooperative cancellation
+//--------------------------------------------------------------------
+/// Set once nobody will read a proving result: the client went away or the
+/// deadline passed.  Blocking work cannot be interrupted, so the prover
+/// checks it between phases.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Cancel(Arc<Flag>);
+
+#[derive(Debug, Default)]
+struct Flag {
+    set: AtomicBool,
+    notify: Notify,
+}
+
+impl Cancel {
+    pub(crate) fn cancel(&self) {
+        self.0.set.store(true, Ordering::Relaxed);
+        self.0.notify.notify_waiters();
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.0.set.load(Ordering::Relaxed)
+    }
+
+    /// Resolves once [`Cancel::cancel`] has been called.
+    pub(crate) async fn cancelled(&self) {
+        let mut notified = std::pin::pin!(self.0.notify.notified());
+        notified.as_mut().enable();
+        if !self.is_cancelled() {
+            notified.await;
+        }
+    }
+}
+
+/// Cancels when dropped.  actix drops a handler future when its client
+/// disconnects, and this guard with it.
+struct CancelOnDrop(Cancel);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+//--------------------------------------------------------------------
+// Proving tasks
+//--------------------------------------------------------------------
+/// Run blocking proving `work` under a prove permit, giving up after
+/// `deadline` (queueing included) or when the client disconnects.
+///
+/// A spawned task holds the permit until `work` returns, so a cancelled
+/// request frees its slot only once the CPU is really free; a request
+/// cancelled while queued leaves the queue at once.
+pub(crate) async fn run_proving<T, F>(state: &web::Data<AppState>, deadline: Option<Duration>, work: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce(&AppState, &Cancel) -> Result<T, ApiError> + Send + 'static,
+{
+    let cancel = Cancel::default();
+    let _on_drop = CancelOnDrop(cancel.clone());
+    let (state, task_cancel) = (state.clone(), cancel.clone());
+    let task = tokio::spawn(async move {
+        let _permit = tokio::select! {
+            permit = state.limits.prove.acquire() => permit,
+            _ = task_cancel.cancelled() => return Err(ApiError::DeadlineExceeded),
+        };
+        let worker = state.clone();
+        tokio::task::spawn_blocking(move || work(&worker, &task_cancel)).await.unwrap_or_else(|e| {
+            tracing::error!(error = %e, "prover task failed");
+            Err(ApiError::Internal)
+        })
+    });
+    let joined = match deadline {
+        Some(limit) => match tokio::time::timeout(limit, task).await {
+            Ok(joined) => joined,
+            Err(_) => {
+                tracing::info!(deadline_ms = limit.as_millis() as u64, "proving cancelled: deadline exceeded");
+                return Err(ApiError::DeadlineExceeded);
+            }
+        },
+        None => task.await,
+    };
+    joined.unwrap_or_else(|e| {
+        tracing::error!(error = %e, "prover task failed");
+        Err(ApiError::Internal)
+    })
+}
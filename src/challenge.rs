@@ -0,0 +1,103 @@
+use actix_web::{get, web, HttpResponse, ResponseError};
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use rand::{thread_rng, RngCore};
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::ApiError;
+use crate::snarkjs::public_signals;
+use crate::store::{blocking, Store};
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Login challenges
+//--------------------------------------------------------------------
+/// How long a challenge may wait to be answered by a proof.
+pub(crate) const CHALLENGE_TTL: Duration = Duration::from_secs(60);
+/// Window `AppConfig::challenge_rate` is counted in.
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// A fresh challenge for a proof from `secret-proof-challenge.circom`.
+#[derive(Debug, Serialize)]
+pub struct ChallengeResponse {
+    /// Decimal, the circuit's `challenge` public input as is.
+    pub challenge: String,
+    pub expires_in_secs: u64,
+}
+
+// Issued challenges live in the [`Store`] until a verification uses them
+// up or they expire, so any replica can check one another issued.
+
+fn key(challenge: Fr) -> String {
+    format!("challenge:{}", public_signals(&[challenge]).remove(0))
+}
+
+fn issue(state: &AppState) -> Result<Fr, ApiError> {
+    admit(state)?;
+    // 248 bits: below the modulus, so no reduction skews it.
+    let mut bytes = [0u8; 31];
+    thread_rng().fill_bytes(&mut bytes);
+    let challenge = Fr::from_be_bytes_mod_order(&bytes);
+    state.store.insert(&key(challenge), Some(CHALLENGE_TTL)).map_err(unavailable)?;
+    Ok(challenge)
+}
+
+/// Count one challenge against `AppConfig::challenge_rate`, in fixed
+/// windows like the shared prove admission rate.
+fn admit(state: &AppState) -> Result<(), ApiError> {
+    let Some(rate) = state.config.challenge_rate else { return Ok(()) };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let window = now - now % RATE_WINDOW.as_secs();
+    let count = state.store.increment(&format!("rate:challenge:{window}"), RATE_WINDOW).map_err(unavailable)?;
+    if count > u64::from(rate.max(1)) * RATE_WINDOW.as_secs() {
+        return Err(ApiError::RateLimited { retry_after_secs: (window + RATE_WINDOW.as_secs() - now).max(1) });
+    }
+    Ok(())
+}
+
+/// A challenge issued for one purpose, such as an OpenID Connect
+/// authorization request whose parameters digest to `binding`.  It works
+/// like any other, and [`is_bound`] tells it was issued for that purpose.
+pub(crate) fn issue_bound(state: &AppState, binding: &str) -> Result<Fr, ApiError> {
+    let challenge = issue(state)?;
+    state.store.insert(&format!("{}:{binding}", key(challenge)), Some(CHALLENGE_TTL)).map_err(unavailable)?;
+    Ok(challenge)
+}
+
+/// Whether `challenge` was issued by [`issue_bound`] with `binding` and
+/// has not expired.
+pub(crate) fn is_bound(store: &dyn Store, challenge: Fr, binding: &str) -> Result<bool, ApiError> {
+    store.contains(&format!("{}:{binding}", key(challenge))).map_err(unavailable)
+}
+
+/// Forget that `challenge` was issued with `binding`, once it is used.
+pub(crate) fn unbind(store: &dyn Store, challenge: Fr, binding: &str) -> Result<bool, ApiError> {
+    store.remove(&format!("{}:{binding}", key(challenge))).map_err(unavailable)
+}
+
+/// Issued and neither used nor expired.
+pub(crate) fn is_live(store: &dyn Store, challenge: Fr) -> Result<bool, ApiError> {
+    store.contains(&key(challenge)).map_err(unavailable)
+}
+
+/// Use up a live challenge; `false` if it no longer was.
+pub(crate) fn take(store: &dyn Store, challenge: Fr) -> Result<bool, ApiError> {
+    store.remove(&key(challenge)).map_err(unavailable)
+}
+
+fn unavailable(e: std::io::Error) -> ApiError {
+    tracing::error!(error = %e, "challenge store failed");
+    ApiError::Unavailable("cannot issue or check challenges right now")
+}
+
+#[get("/challenge")]
+async fn issue_challenge(state: web::Data<AppState>) -> HttpResponse {
+    match blocking(&state, issue).await {
+        Ok(challenge) => HttpResponse::Ok().json(ChallengeResponse {
+            challenge: public_signals(&[challenge]).remove(0),
+            expires_in_secs: CHALLENGE_TTL.as_secs(),
+        }),
+        Err(e) => e.error_response(),
+    }
+}
@@ -0,0 +1,95 @@
+use actix_web::{delete, get, put, web, HttpResponse, Responder};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Fault injection
+//--------------------------------------------------------------------
+// Compiled only with the `chaos` feature.  Lets resilience tests make the
+// service slow, flaky or saturated on demand to exercise client retries.
+
+/// Faults currently injected.  Missing fields in a `PUT` reset to off.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosSettings {
+    /// Extra latency added to every proof, inside the proving slot.
+    pub prove_delay_ms: u64,
+    /// Fraction of verifications answered `503 unavailable`, `0.0..=1.0`.
+    pub verify_failure_rate: f64,
+    /// Hold every proving permit so new proof requests queue indefinitely.
+    pub saturate_prove_queue: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct Chaos {
+    prove_delay_ms: AtomicU64,
+    /// `verify_failure_rate` in parts per million.
+    verify_failure_ppm: AtomicU32,
+    /// Dropping the sender releases the held permits.
+    saturation: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl Chaos {
+    pub(crate) fn prove_delay(&self) -> Duration {
+        Duration::from_millis(self.prove_delay_ms.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn fail_verification(&self) -> bool {
+        let ppm = self.verify_failure_ppm.load(Ordering::Relaxed);
+        ppm > 0 && rand::thread_rng().gen_range(0..1_000_000) < ppm
+    }
+
+    fn settings(&self) -> ChaosSettings {
+        ChaosSettings {
+            prove_delay_ms: self.prove_delay_ms.load(Ordering::Relaxed),
+            verify_failure_rate: self.verify_failure_ppm.load(Ordering::Relaxed) as f64 / 1e6,
+            saturate_prove_queue: self.saturation.lock().unwrap().is_some(),
+        }
+    }
+}
+
+fn apply(state: &web::Data<AppState>, settings: &ChaosSettings) {
+    let chaos = &state.chaos;
+    chaos.prove_delay_ms.store(settings.prove_delay_ms, Ordering::Relaxed);
+    let ppm = (settings.verify_failure_rate.clamp(0.0, 1.0) * 1e6) as u32;
+    chaos.verify_failure_ppm.store(ppm, Ordering::Relaxed);
+
+    let mut saturation = chaos.saturation.lock().unwrap();
+    match (settings.saturate_prove_queue, saturation.is_some()) {
+        (true, false) => {
+            let (release, released) = oneshot::channel::<()>();
+            let state = state.clone();
+            actix_web::rt::spawn(async move {
+                let _permits = state.limits.prove.acquire_all().await;
+                let _ = released.await;
+            });
+            *saturation = Some(release);
+        }
+        (false, true) => *saturation = None,
+        _ => {}
+    }
+}
+
+#[get("/chaos")]
+async fn get_chaos(state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(state.chaos.settings())
+}
+
+#[put("/chaos")]
+async fn set_chaos(state: web::Data<AppState>, body: web::Json<ChaosSettings>) -> impl Responder {
+    apply(&state, &body);
+    tracing::warn!(settings = ?state.chaos.settings(), "chaos settings changed");
+    HttpResponse::Ok().json(state.chaos.settings())
+}
+
+#[delete("/chaos")]
+async fn clear_chaos(state: web::Data<AppState>) -> impl Responder {
+    apply(&state, &ChaosSettings::default());
+    HttpResponse::Ok().json(state.chaos.settings())
+}
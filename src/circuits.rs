@@ -0,0 +1,257 @@
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::prover::WitnessFailure;
+use crate::snarkjs::public_signals;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// R1CS header
+//--------------------------------------------------------------------
+/// Counts from the header section of an iden3 `.r1cs` file.
+pub(crate) struct R1csHeader {
+    pub(crate) wires: u32,
+    pub(crate) public_outputs: u32,
+    pub(crate) public_inputs: u32,
+    pub(crate) private_inputs: u32,
+    pub(crate) labels: u64,
+    pub(crate) constraints: u32,
+}
+
+/// Read only the header section; the constraints themselves are skipped.
+pub(crate) fn read_r1cs_header(path: &Path) -> io::Result<R1csHeader> {
+    let invalid = |what| io::Error::new(io::ErrorKind::InvalidData, what);
+    let mut rd = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 4];
+    rd.read_exact(&mut magic)?;
+    if &magic != b"r1cs" {
+        return Err(invalid("not an r1cs file"));
+    }
+    let _version = read_u32(&mut rd)?;
+    for _ in 0..read_u32(&mut rd)? {
+        let (kind, size) = (read_u32(&mut rd)?, read_u64(&mut rd)?);
+        if kind != 1 {
+            rd.seek(SeekFrom::Current(size as i64))?;
+            continue;
+        }
+        let field_bytes = read_u32(&mut rd)?;
+        rd.seek(SeekFrom::Current(field_bytes as i64))?;
+        return Ok(R1csHeader {
+            wires: read_u32(&mut rd)?,
+            public_outputs: read_u32(&mut rd)?,
+            public_inputs: read_u32(&mut rd)?,
+            private_inputs: read_u32(&mut rd)?,
+            labels: read_u64(&mut rd)?,
+            constraints: read_u32(&mut rd)?,
+        });
+    }
+    Err(invalid("r1cs file has no header section"))
+}
+
+fn read_u32(rd: &mut impl Read) -> io::Result<u32> {
+    let mut b = [0u8; 4];
+    rd.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64(rd: &mut impl Read) -> io::Result<u64> {
+    let mut b = [0u8; 8];
+    rd.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+//--------------------------------------------------------------------
+// /circuits/{name}/stats
+//--------------------------------------------------------------------
+/// Size of a circuit, for estimating proving cost.  Public signals are the
+/// outputs followed by the public inputs; `internal_signals` excludes the
+/// constant-one wire and every input and output.  File sizes are bytes and
+/// `null` for files this node does not hold.
+#[derive(Debug, Serialize)]
+pub struct CircuitStats {
+    pub circuit_id: String,
+    pub constraints: u32,
+    pub wires: u32,
+    pub public_outputs: u32,
+    pub public_inputs: u32,
+    pub private_inputs: u32,
+    pub internal_signals: u32,
+    pub labels: u64,
+    pub r1cs_bytes: Option<u64>,
+    pub wasm_bytes: Option<u64>,
+    pub zkey_bytes: Option<u64>,
+}
+
+#[get("/circuits/{name}/stats")]
+async fn circuit_stats(state: web::Data<AppState>, name: web::Path<String>) -> HttpResponse {
+    if state.circuit(&name).is_none() {
+        return HttpResponse::NotFound().finish();
+    }
+    let stats = web::block(move || {
+        let artifacts = state.circuit(&name).expect("circuit checked above");
+        let size = |p: &Path| std::fs::metadata(p).ok().map(|m| m.len());
+        read_r1cs_header(artifacts.r1cs_path()).map(|h| CircuitStats {
+            circuit_id: name.clone(),
+            constraints: h.constraints,
+            wires: h.wires,
+            public_outputs: h.public_outputs,
+            public_inputs: h.public_inputs,
+            private_inputs: h.private_inputs,
+            internal_signals: h.wires.saturating_sub(1 + h.public_outputs + h.public_inputs + h.private_inputs),
+            labels: h.labels,
+            r1cs_bytes: size(artifacts.r1cs_path()),
+            wasm_bytes: size(artifacts.wasm_path()),
+            zkey_bytes: artifacts.zkey_path().and_then(size),
+        })
+    })
+    .await;
+    match stats {
+        Ok(Ok(stats)) => HttpResponse::Ok().json(stats),
+        Ok(Err(e)) => {
+            tracing::warn!(error = %e, "circuit r1cs unreadable");
+            HttpResponse::ServiceUnavailable().finish()
+        }
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+//--------------------------------------------------------------------
+// /circuits/{name}/signals
+//--------------------------------------------------------------------
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    PublicOutput,
+    PublicInput,
+    PrivateInput,
+    Internal,
+}
+
+/// One line of the circuit's `.sym` file.  `name` drops the `main.` prefix,
+/// so top-level inputs appear as the keys a witness input JSON uses.
+/// `wire` is `null` for signals the compiler optimised away.
+#[derive(Debug, Serialize)]
+pub struct SignalInfo {
+    pub name: String,
+    pub label: u64,
+    pub wire: Option<u32>,
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignalsResponse {
+    pub circuit_id: String,
+    pub signals: Vec<SignalInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignalsQuery {
+    /// Include internal signals too; by default only inputs and outputs.
+    #[serde(default)]
+    pub all: bool,
+}
+
+/// Signals from the `.sym` file next to `r1cs`, with visibility from the
+/// wire layout: the constant one, then public outputs, public inputs and
+/// private inputs, then everything else.
+pub(crate) fn read_signals(r1cs: &Path) -> io::Result<Vec<SignalInfo>> {
+    let h = read_r1cs_header(r1cs)?;
+    let (outputs, inputs) = (h.public_outputs, h.public_outputs + h.public_inputs);
+    let private = inputs + h.private_inputs;
+    let visibility = |wire: Option<u32>| match wire {
+        Some(w) if (1..=outputs).contains(&w) => Visibility::PublicOutput,
+        Some(w) if w > outputs && w <= inputs => Visibility::PublicInput,
+        Some(w) if w > inputs && w <= private => Visibility::PrivateInput,
+        _ => Visibility::Internal,
+    };
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed .sym line");
+    std::fs::read_to_string(r1cs.with_extension("sym"))?
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|line| {
+            let mut cols = line.splitn(4, ',');
+            let label = cols.next().and_then(|c| c.parse().ok()).ok_or_else(invalid)?;
+            let wire = cols.next().and_then(|c| c.parse::<i64>().ok()).ok_or_else(invalid)?;
+            let name = cols.nth(1).ok_or_else(invalid)?;
+            let wire = u32::try_from(wire).ok();
+            Ok(SignalInfo { name: name.strip_prefix("main.").unwrap_or(name).to_owned(), label, wire, visibility: visibility(wire) })
+        })
+        .collect()
+}
+
+/// Lets clients building witness inputs check they supply every input
+/// under the right name.
+#[get("/circuits/{name}/signals")]
+async fn circuit_signals(state: web::Data<AppState>, name: web::Path<String>, query: web::Query<SignalsQuery>) -> HttpResponse {
+    let Some(r1cs) = state.circuit(&name).map(|a| a.r1cs_path().to_owned()) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let signals = web::block(move || read_signals(&r1cs)).await;
+    match signals {
+        Ok(Ok(mut signals)) => {
+            if !query.all {
+                signals.retain(|s| s.visibility != Visibility::Internal);
+            }
+            HttpResponse::Ok().json(SignalsResponse { circuit_id: name.into_inner(), signals })
+        }
+        Ok(Err(e)) => {
+            tracing::warn!(error = %e, "circuit symbols unreadable");
+            HttpResponse::ServiceUnavailable().finish()
+        }
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+//--------------------------------------------------------------------
+// Witness diagnostics
+//--------------------------------------------------------------------
+/// Why `/generate-proof` could not build a witness: the first violated
+/// constraint and the signals it reads.  `calculator_error` and `witness`
+/// carry signal values and are only set for `debug=true` requests.
+#[derive(Debug, Serialize)]
+pub struct WitnessDiagnostics {
+    pub constraint: Option<usize>,
+    pub signals: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calculator_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub witness: Option<Vec<WireValue>>,
+}
+
+/// A witness value, decimal, with every signal name that wire carries.
+#[derive(Debug, Serialize)]
+pub struct WireValue {
+    pub wire: usize,
+    pub signals: Vec<String>,
+    pub value: String,
+}
+
+/// Name the wires of `failure` from the `.sym` file next to `r1cs`.  Wire 0
+/// is the constant `one`; wires without a symbol are reported as `wire <n>`.
+pub(crate) fn diagnose(r1cs: &Path, failure: WitnessFailure, debug: bool) -> WitnessDiagnostics {
+    let mut names = HashMap::from([(0, vec!["one".to_owned()])]);
+    match read_signals(r1cs) {
+        Ok(signals) => {
+            for s in signals {
+                if let Some(wire) = s.wire {
+                    names.entry(wire as usize).or_default().push(s.name);
+                }
+            }
+        }
+        Err(e) => tracing::warn!(error = %e, "circuit symbols unreadable"),
+    }
+    let named = |wire: usize| names.get(&wire).cloned().unwrap_or_else(|| vec![format!("wire {wire}")]);
+    WitnessDiagnostics {
+        constraint: failure.constraint,
+        signals: failure.wires.iter().flat_map(|w| named(*w)).collect(),
+        calculator_error: failure.calculator.filter(|_| debug),
+        witness: debug.then(|| {
+            let values = public_signals(&failure.witness);
+            values.into_iter().enumerate().map(|(wire, value)| WireValue { wire, signals: named(wire), value }).collect()
+        }),
+    }
+}
@@ -0,0 +1,68 @@
+use ark_bn254::Fr;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::poseidon_hash;
+use crate::derive::app_id;
+
+//--------------------------------------------------------------------
+// Statement claims
+//--------------------------------------------------------------------
+// claims = Poseidon(Keccak256(issuer) mod p, Keccak256(audience) mod p,
+//                   issued_at, expires_at)
+//
+// The digest is the `claims` public input of the claim-bound circuit, so a
+// proof is tied to who it was minted by, for whom and for how long, as a
+// JWT's iss/aud/iat/exp are.
+
+/// The context a proof is bound to.  Times are Unix seconds.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClaimBlock {
+    pub issuer: String,
+    pub audience: String,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+/// What a verifier accepts.
+#[derive(Clone, Debug, Default)]
+pub struct ClaimPolicy {
+    /// Accepted issuers; empty accepts any.
+    pub issuers: Vec<String>,
+    /// This verifier's own audience.
+    pub audience: String,
+    /// Allowed clock skew, in seconds, on both ends of the validity window.
+    pub leeway_secs: u64,
+    /// Longest accepted `expires_at - issued_at`; 0 for no limit.
+    pub max_lifetime_secs: u64,
+}
+
+impl ClaimBlock {
+    /// The field element the circuit takes as its `claims` public input.
+    pub fn hash(&self) -> Fr {
+        poseidon_hash(&[app_id(&self.issuer), app_id(&self.audience), Fr::from(self.issued_at), Fr::from(self.expires_at)])
+    }
+
+    /// Check the claims against `policy` at time `now`; `Err` names the
+    /// claim that failed.
+    pub fn validate(&self, policy: &ClaimPolicy, now: u64) -> Result<(), &'static str> {
+        if !policy.issuers.is_empty() && !policy.issuers.contains(&self.issuer) {
+            return Err("issuer is not accepted");
+        }
+        if self.audience != policy.audience {
+            return Err("audience is not this verifier");
+        }
+        if self.expires_at <= self.issued_at {
+            return Err("expires_at is not after issued_at");
+        }
+        if policy.max_lifetime_secs > 0 && self.expires_at - self.issued_at > policy.max_lifetime_secs {
+            return Err("lifetime exceeds the allowed maximum");
+        }
+        if self.issued_at > now.saturating_add(policy.leeway_secs) {
+            return Err("issued_at is in the future");
+        }
+        if self.expires_at.saturating_add(policy.leeway_secs) <= now {
+            return Err("claims have expired");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use actix_web::{post, web, HttpResponse, ResponseError};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::ApiError;
+use crate::encoding::derive_user_hash_with;
+use crate::handlers::{check_optional_attributes, issue_identity, RegisterRequest};
+use crate::redact::Redacted;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Commit–reveal registration
+//--------------------------------------------------------------------
+/// How long a commitment waits for its reveal.
+const SESSION_TTL: Duration = Duration::from_secs(600);
+/// Open sessions kept at once; further commits are refused until some
+/// are revealed or expire.
+const MAX_SESSIONS: usize = 100_000;
+
+/// Phase one: a hash of the attributes the client will reveal.
+///
+/// `attributes_hash` is hex SHA-256 of
+/// `"zk-auth-commit/v1\n{salt}\n{email}\n{name}\n{age}\n{country}\n{dob}"`,
+/// with the fields exactly as they will be sent to `/register/reveal` and
+/// `salt` the client's random hex, so the hash does not give away
+/// guessable attributes.
+#[derive(Debug, Deserialize)]
+pub struct CommitRequest {
+    pub attributes_hash: String,
+}
+
+/// The nonce is fixed here, before the server sees the attributes.
+#[derive(Debug, Serialize)]
+pub struct CommitResponse {
+    pub session_id: String,
+    pub nonce: String,
+    pub expires_in_secs: u64,
+}
+
+/// Phase two: the attributes and salt behind the committed hash.
+#[derive(Deserialize)]
+pub struct RevealRequest {
+    pub session_id: String,
+    pub salt: String,
+    #[serde(flatten)]
+    pub attributes: RegisterRequest,
+}
+
+impl std::fmt::Debug for RevealRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RevealRequest")
+            .field("session_id", &self.session_id)
+            .field("salt", &Redacted(&self.salt))
+            .field("attributes", &self.attributes)
+            .finish()
+    }
+}
+
+/// Sessions between commit and reveal, keyed by session id.
+#[derive(Default)]
+pub(crate) struct PendingCommits(Mutex<HashMap<String, Pending>>);
+
+struct Pending {
+    attributes_hash: [u8; 32],
+    nonce: [u8; 16],
+    expires: Instant,
+}
+
+impl PendingCommits {
+    fn open(&self, attributes_hash: [u8; 32], nonce: [u8; 16]) -> Option<String> {
+        let mut sessions = self.0.lock().expect("pending commits poisoned");
+        let now = Instant::now();
+        sessions.retain(|_, p| p.expires > now);
+        if sessions.len() >= MAX_SESSIONS {
+            return None;
+        }
+        let mut id = [0u8; 16];
+        thread_rng().fill_bytes(&mut id);
+        let id = hex::encode(id);
+        sessions.insert(id.clone(), Pending { attributes_hash, nonce, expires: now + SESSION_TTL });
+        Some(id)
+    }
+
+    /// Sessions are single-use: a reveal consumes it whether or not it matches.
+    fn take(&self, id: &str) -> Option<Pending> {
+        self.0.lock().expect("pending commits poisoned").remove(id).filter(|p| p.expires > Instant::now())
+    }
+}
+
+/// The hash a client commits to; see [`CommitRequest`].
+pub fn attributes_hash(salt: &str, attributes: &RegisterRequest) -> [u8; 32] {
+    let RegisterRequest { email, name, age, country, dob, phone, biometric_hash } = attributes;
+    let mut preimage = format!("zk-auth-commit/v1\n{salt}\n{email}\n{name}\n{age}\n{country}\n{dob}");
+    if phone.is_some() || biometric_hash.is_some() {
+        preimage.push_str(&format!("\n{}", phone.as_deref().unwrap_or("")));
+    }
+    if let Some(biometric_hash) = biometric_hash {
+        preimage.push_str(&format!("\n{biometric_hash}"));
+    }
+    Sha256::digest(preimage).into()
+}
+
+#[post("/register/commit")]
+async fn register_commit(state: web::Data<AppState>, body: web::Json<CommitRequest>) -> HttpResponse {
+    let _permit = state.limits.register.acquire().await;
+    let Some(hash) = hex::decode(body.attributes_hash.trim_start_matches("0x")).ok().and_then(|b| <[u8; 32]>::try_from(b).ok()) else {
+        return ApiError::InvalidInput { field: "attributes_hash", reason: "must be 32 bytes of hex" }.error_response();
+    };
+    let mut nonce = [0u8; 16];
+    thread_rng().fill_bytes(&mut nonce);
+    let Some(session_id) = state.commits.open(hash, nonce) else {
+        return ApiError::Unavailable("too many registrations awaiting reveal").error_response();
+    };
+    tracing::info!(%session_id, attributes_hash = %hex::encode(hash), "registration committed");
+    HttpResponse::Ok().json(CommitResponse { session_id, nonce: format!("0x{}", hex::encode(nonce)), expires_in_secs: SESSION_TTL.as_secs() })
+}
+
+/// Completes a committed registration with the nonce issued at commit time;
+/// the response is the same as `/register`'s.
+#[post("/register/reveal")]
+async fn register_reveal(state: web::Data<AppState>, body: web::Json<RevealRequest>) -> HttpResponse {
+    let _permit = state.limits.register.acquire().await;
+    let Some(pending) = state.commits.take(&body.session_id) else {
+        return ApiError::InvalidInput { field: "session_id", reason: "unknown, expired or already revealed" }.error_response();
+    };
+    if attributes_hash(&body.salt, &body.attributes) != pending.attributes_hash {
+        tracing::warn!(session_id = %body.session_id, "registration reveal does not match its commitment");
+        return ApiError::InvalidInput { field: "attributes", reason: "do not match the committed hash" }.error_response();
+    }
+    if let Err(e) = check_optional_attributes(&body.attributes) {
+        return e.error_response();
+    }
+    let res = match issue_identity(&state, derive_user_hash_with(&body.attributes, &state.config.attributes), &pending.nonce, None) {
+        Ok(res) => res,
+        Err(e) => return e.error_response(),
+    };
+    tracing::info!(session_id = %body.session_id, commitment = %res.commitment, leaf_index = res.leaf_index, "registration revealed");
+    HttpResponse::Ok().json(res)
+}
@@ -0,0 +1,474 @@
+use actix_web::http::KeepAlive;
+use serde::{Deserialize, Serialize};
+
+use crate::access_log::AccessLogConfig;
+use crate::anchor::AnchorConfig;
+use crate::artifacts::CIRCUIT_PATH;
+use crate::claims::ClaimPolicy;
+use crate::cosign::ArtifactSigning;
+use crate::credential::CredentialConfig;
+use crate::encoding::AttributeRules;
+use crate::ipfs::IpfsConfig;
+use crate::mock::MockProver;
+use crate::oidc::{OidcClient, OidcConfig};
+use crate::prewarm::PrewarmConfig;
+use crate::proof_jobs::{JobRetention, JobStoreConfig};
+use crate::proof_store::ProofStoreConfig;
+use crate::record::RecordConfig;
+use crate::replication::ReplicationConfig;
+use crate::session::SessionConfig;
+#[cfg(feature = "redis")]
+use crate::store::RedisConfig;
+#[cfg(feature = "grpc")]
+use crate::grpc::GrpcConfig;
+use crate::webhook::WebhookConfig;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+//--------------------------------------------------------------------
+// Runtime configuration
+//--------------------------------------------------------------------
+/// Settings shared by [`crate::run`] and [`crate::zk_auth_scope`].
+#[derive(Clone, Debug, Default)]
+pub struct AppConfig {
+    /// Where the circuit files and proving key live.
+    pub circuit: CircuitPaths,
+    /// Further circuits served next to the bundled one, keyed by the
+    /// `circuit_id` requests name them with.  Each declares its
+    /// [`CircuitKind`]; [`CircuitKind::Secret`] and [`CircuitKind::Multi`]
+    /// circuits are proved with at `/generate-proof`, registration
+    /// circuits at `/register` and descent circuits at `/derive`; the
+    /// others are only verified.  Rotation and historical keys
+    /// apply to the bundled circuit only.
+    pub circuits: BTreeMap<String, CircuitPaths>,
+    /// What claim blocks proofs for a [`CircuitKind::Claims`] circuit may
+    /// carry.  Required when such a circuit is configured.
+    pub claim_policy: Option<ClaimPolicy>,
+    /// Audiences proofs for a [`CircuitKind::Audience`] circuit may be bound
+    /// to: the services this server verifies for.  Must not be empty when
+    /// such a circuit is configured.
+    pub audiences: Vec<String>,
+    /// Concurrency limits per endpoint class.
+    pub concurrency: ConcurrencyLimits,
+    /// Admission rate for proof requests, tightened as the prove queue
+    /// deepens; `None` lets every request join the queue.
+    pub prove_rate: Option<AdaptiveRate>,
+    /// Longest a proof request may take, queueing included, before it is
+    /// cancelled with `504`; requests may ask for less with `deadline_ms`.
+    /// `None` only applies the request's own deadline.
+    pub prove_deadline: Option<Duration>,
+    /// Most challenges `/challenge` and `GET /authorize` issue per second,
+    /// counted fleet-wide when the store is shared.  Caps what a flood of
+    /// requests can leave in the store; `None` issues without limit.
+    pub challenge_rate: Option<u32>,
+    /// Which endpoints this instance serves and which keys it loads.
+    pub mode: Mode,
+    /// How registration normalises and encodes email and name.
+    pub attributes: AttributeRules,
+    /// Reject verification requests that omit `circuit_version`.  When
+    /// false the field is optional but still checked if present.
+    pub require_circuit_version: bool,
+    /// Only accept proofs for commitments in this node's commitment tree,
+    /// answering `unknown_commitment` for others.  The tree is in memory,
+    /// so this suits nodes that register or replicate from one that does.
+    pub require_registered: bool,
+    /// Access logging applied by [`crate::run`]; `None` disables it.
+    /// Embedding applications wrap their own app with [`crate::AccessLog`].
+    pub access_log: Option<AccessLogConfig>,
+    /// Traffic recording for [`crate::replay`], applied by [`crate::run`];
+    /// `None` disables it.  Embedding applications wrap with [`crate::Recorder`].
+    pub record: Option<RecordConfig>,
+    /// HTTP server tuning.  Only [`crate::run`] reads these; an embedding
+    /// application configures its own `HttpServer`.
+    pub server: ServerConfig,
+    /// A staged key to switch to at a scheduled time; `None` keeps the
+    /// current key indefinitely.
+    pub rotation: Option<KeyRotation>,
+    /// Cosign signatures the circuit artefacts must carry, checked at
+    /// startup; `None` loads them unchecked.
+    pub artifact_signing: Option<ArtifactSigning>,
+    /// Exported verifying keys of earlier circuit versions, newest first.
+    /// They are tried after the active key(s) so archived proofs keep
+    /// verifying after an upgrade; they are never used for proving.
+    pub historical_vks: Vec<PathBuf>,
+    /// File holding the 32-byte hex seed of the Ed25519 key that signs
+    /// verification receipts.  `None` generates a key per process.
+    pub receipt_key: Option<PathBuf>,
+    /// Return a signed session token with every valid `/verify-proof`
+    /// answer; `None` returns none and `/.well-known/jwks.json` is `404`.
+    pub session: Option<SessionConfig>,
+    /// Act as an OpenID Connect provider for these clients; `None` leaves
+    /// the OIDC endpoints answering `404`.  Needs [`AppConfig::session`].
+    pub oidc: Option<OidcConfig>,
+    /// Return a signed Verifiable Credential for the commitment with every
+    /// registration; `None` returns none.
+    pub credentials: Option<CredentialConfig>,
+    /// Keep spent nullifiers, login challenges and the prove admission
+    /// rate in Redis, shared by every replica; `None` keeps them in memory.
+    #[cfg(feature = "redis")]
+    pub redis: Option<RedisConfig>,
+    /// Also serve `/verify-proof` as the `zkauth.v1.Verifier` gRPC service
+    /// on its own port; `None` serves HTTP only.
+    #[cfg(feature = "grpc")]
+    pub grpc: Option<GrpcConfig>,
+    /// Bearer token for the `/admin` endpoints; they are not mounted
+    /// without one.
+    pub admin_token: Option<String>,
+    /// Lets `/verify-proof` fetch proofs by IPFS CID; `None` rejects
+    /// CID requests.
+    pub ipfs: Option<IpfsConfig>,
+    /// Periodic on-chain anchoring of the commitment-tree root; `None`
+    /// disables it and `/anchors`.
+    pub anchor: Option<AnchorConfig>,
+    /// Mirror a primary's commitment tree; `None` keeps only this node's
+    /// own registrations.
+    pub replication: Option<ReplicationConfig>,
+    /// Serve as a read-only replica of the [`AppConfig::replication`]
+    /// primary: registration and proving endpoints are not mounted, so the
+    /// tree only changes by replication.
+    pub replica: bool,
+    /// POST each new registration to a provisioning endpoint; `None`
+    /// sends nothing.
+    pub webhook: Option<WebhookConfig>,
+    /// File holding the 32-byte hex seed of the VRF key registration
+    /// nonces are derived with.  `None` keeps random nonces.
+    pub vrf_key: Option<PathBuf>,
+    /// Pre-generation of proofs for logins hinted at `/admin/prewarm`;
+    /// `None` refuses hints.
+    pub prewarm: Option<PrewarmConfig>,
+    /// Keep every generated proof for retrieval at `/proofs/{hash}`;
+    /// `None` keeps nothing and the endpoint answers `503`.
+    pub proof_store: Option<ProofStoreConfig>,
+    /// How long `/proof-jobs` results are kept for collection.
+    pub job_retention: JobRetention,
+    /// Keep `/proof-jobs` jobs on disk, so that jobs accepted before a
+    /// restart are still proved; `None` keeps them in memory.
+    pub job_store: Option<JobStoreConfig>,
+    /// Serve fake proofs for consumers' integration tests; see
+    /// [`MockProver`].  `None`, the default, always proves for real.
+    pub mock_prover: Option<MockProver>,
+}
+
+/// Circuit artefacts.  The default is the circuit bundled in the source
+/// tree, found through the path it was built from, so deployments that
+/// ship the binary alone must set these.
+#[derive(Clone, Debug)]
+pub struct CircuitPaths {
+    /// The witness calculator, `secret-proof.wasm`.
+    pub wasm: PathBuf,
+    pub r1cs: PathBuf,
+    /// The proving key, also the verifying key unless [`Mode::VerifyOnly`]
+    /// names one.
+    pub zkey: PathBuf,
+    /// The statement the circuit proves.  The bundled circuit must be
+    /// [`CircuitKind::Secret`].
+    pub kind: CircuitKind,
+}
+
+impl Default for CircuitPaths {
+    fn default() -> Self {
+        let (wasm, r1cs, zkey) = CIRCUIT_PATH.clone();
+        Self { wasm, r1cs, zkey, kind: CircuitKind::Secret }
+    }
+}
+
+/// What a circuit proves, which fixes its public signals and where its
+/// proofs are verified.  Configured per circuit rather than read from the
+/// key: the nullifier and membership circuits have the same number of
+/// public inputs, and a proof for one must never pass as the other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CircuitKind {
+    /// `commitment`: knowledge of the secret behind it, like the bundled
+    /// circuit.
+    #[default]
+    Secret,
+    /// `nullifier, commitment, app_id`, from `secret-proof-nullifier.circom`.
+    Nullifier,
+    /// `commitment, challenge`, from `secret-proof-challenge.circom`.
+    Challenge,
+    /// `nullifier, root, app_id`, from `secret-proof-membership.circom`.
+    /// Only `/verify-membership` accepts these proofs.
+    Membership,
+    /// `commitment, claims`, from `secret-proof-claims.circom`.  Requests
+    /// carry the claim block, checked against [`AppConfig::claim_policy`].
+    Claims,
+    /// `commitment, audience`, from `secret-proof-audience.circom`.
+    /// Requests name the audience, one of [`AppConfig::audiences`].
+    Audience,
+    /// `commitment`, from `secret-proof-multi.circom`: the commitment is
+    /// [`crate::commitment_for_secrets`] of several secrets, all of which
+    /// the prover knows.  Verified like [`CircuitKind::Secret`];
+    /// `/generate-proof` proves with every secret in `secrets_hex`.
+    Multi,
+    /// `user_hash, commitment`, from `registration-proof.circom`: the
+    /// commitment `/register?proof=true` returned is the Poseidon chain of
+    /// the five base attributes and the nonce.  Checked offline against
+    /// the key `/verification-key` serves, not at `/verify-proof`.
+    Registration,
+    /// [`CircuitKind::Registration`] for registrations with a phone, from
+    /// `registration-proof-phone.circom`.
+    #[serde(rename = "registration-phone")]
+    RegistrationPhone,
+    /// [`CircuitKind::Registration`] for registrations with a biometric
+    /// hash, phone or not, from `registration-proof-biometric.circom`.
+    #[serde(rename = "registration-biometric")]
+    RegistrationBiometric,
+    /// `master_commitment, app_id, child_commitment`, from
+    /// `child-descent.circom`: the child commitment `/derive?proof=true`
+    /// returned descends from the master.  Only `/verify-descent` accepts
+    /// these proofs.
+    Descent,
+}
+
+impl CircuitKind {
+    /// The registration kind proving a user hash over `attributes` encoded
+    /// attributes; `None` for counts `/register` never hashes.
+    pub fn registration(attributes: usize) -> Option<Self> {
+        match attributes {
+            5 => Some(CircuitKind::Registration),
+            6 => Some(CircuitKind::RegistrationPhone),
+            7 => Some(CircuitKind::RegistrationBiometric),
+            _ => None,
+        }
+    }
+
+    /// Proved by `/register` rather than by the prover's own endpoints.
+    pub fn is_registration(self) -> bool {
+        matches!(self, CircuitKind::Registration | CircuitKind::RegistrationPhone | CircuitKind::RegistrationBiometric)
+    }
+}
+
+/// How many requests of each class may run at once; the rest queue.
+#[derive(Clone, Debug)]
+pub struct ConcurrencyLimits {
+    /// Proof generation (CPU- and memory-heavy).
+    pub prove: usize,
+    /// Pairing checks.
+    pub verify: usize,
+    /// Commitment derivation.
+    pub register: usize,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        let cpus = num_cpus::get();
+        Self { prove: cpus, verify: cpus * 4, register: cpus * 16 }
+    }
+}
+
+/// Proof requests admitted per second as a function of the prove queue:
+/// `max_per_sec` while at most `target_queue` requests wait, halving for
+/// every further `target_queue` waiting, never below `min_per_sec`.
+/// Requests over the rate get `429` with `Retry-After` instead of queueing,
+/// which keeps queueing delay, and so tail latency, bounded under bursts.
+#[derive(Clone, Debug)]
+pub struct AdaptiveRate {
+    /// Also the burst size: a full second's worth of requests may arrive at once.
+    pub max_per_sec: f64,
+    pub min_per_sec: f64,
+    pub target_queue: usize,
+}
+
+impl Default for AdaptiveRate {
+    fn default() -> Self {
+        let cpus = num_cpus::get();
+        Self { max_per_sec: cpus as f64 * 2.0, min_per_sec: 0.5, target_queue: cpus }
+    }
+}
+
+/// Deployment mode.
+#[derive(Clone, Debug, Default)]
+pub enum Mode {
+    /// Register, prove and verify; keys come from the zkey.
+    #[default]
+    Full,
+    /// Verification only, for edge nodes.  The multi-GB zkey is never read:
+    /// the verifying key is loaded from a file produced by
+    /// [`crate::export_verifying_key`], and `/generate-proof` is not mounted.
+    VerifyOnly { verifying_key: PathBuf },
+    /// Proving only, for dedicated prover nodes.  `/verify-proof` is not
+    /// mounted, so no verifying key is ever prepared.
+    ProveOnly,
+}
+
+impl Mode {
+    pub(crate) fn proves(&self) -> bool {
+        !matches!(self, Mode::VerifyOnly { .. })
+    }
+
+    pub(crate) fn verifies(&self) -> bool {
+        !matches!(self, Mode::ProveOnly)
+    }
+}
+
+/// Scheduled key rotation.  Proving switches to `next_key` at `cutover`;
+/// verification accepts both keys from `cutover - overlap` until
+/// `cutover + overlap`, so proofs generated just before the switch, or by a
+/// node whose clock is slightly off, still verify.
+#[derive(Clone, Debug)]
+pub struct KeyRotation {
+    /// The new zkey, or on a verify-only node its exported verifying key.
+    pub next_key: PathBuf,
+    pub cutover: SystemTime,
+    pub overlap: Duration,
+}
+
+/// Knobs forwarded to actix's `HttpServer`.  Defaults match actix's own,
+/// except that half-closed HTTP/1 connections are not allowed.
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Worker threads; `None` uses one per physical core.
+    pub workers: Option<usize>,
+    /// Idle keep-alive between requests on one connection.  Raise this when
+    /// a proxy in front keeps connections open longer than 5 s, otherwise it
+    /// may reuse a socket the server has already closed.
+    pub keep_alive: KeepAlive,
+    /// Time allowed for a client to send the request head (0 disables).
+    pub client_request_timeout: Duration,
+    /// Time allowed for connection shutdown after a response (0 disables).
+    pub client_disconnect_timeout: Duration,
+    /// Accept cleartext HTTP/2 (h2c, prior knowledge) alongside HTTP/1.1.
+    pub http2: bool,
+    /// Keep serving an HTTP/1 request after the client shuts down its side of
+    /// the connection.  Off, the server notices a client that hangs up and
+    /// cancels its proof instead of finishing it for nobody.
+    pub h1_allow_half_closed: bool,
+    /// Where the binary listens when not socket-activated.  [`crate::run`]
+    /// serves the listener it is handed and does not read this.
+    pub bind: SocketAddr,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            workers: None,
+            keep_alive: KeepAlive::Timeout(Duration::from_secs(5)),
+            client_request_timeout: Duration::from_secs(5),
+            client_disconnect_timeout: Duration::from_secs(1),
+            http2: false,
+            h1_allow_half_closed: false,
+            bind: SocketAddr::from(([0, 0, 0, 0], 8080)),
+        }
+    }
+}
+
+//--------------------------------------------------------------------
+// Config file
+//--------------------------------------------------------------------
+/// The settings a TOML config file may hold; anything left out keeps its
+/// default.
+///
+/// ```toml
+/// bind = "0.0.0.0:8080"
+///
+/// [circuit]
+/// wasm = "/opt/zk-auth/secret-proof.wasm"
+/// r1cs = "/opt/zk-auth/secret-proof.r1cs"
+/// zkey = "/opt/zk-auth/secret_final.zkey"
+///
+/// [circuits.partner-ceremony]
+/// wasm = "/opt/zk-auth/secret-proof.wasm"
+/// r1cs = "/opt/zk-auth/secret-proof.r1cs"
+/// zkey = "/opt/zk-auth/partner_final.zkey"
+///
+/// [circuits.nullifier]
+/// kind = "nullifier"
+/// wasm = "/opt/zk-auth/secret-proof-nullifier.wasm"
+/// r1cs = "/opt/zk-auth/secret-proof-nullifier.r1cs"
+/// zkey = "/opt/zk-auth/nullifier_final.zkey"
+///
+/// [concurrency]
+/// prove = 4
+///
+/// [oidc.clients.wiki]
+/// redirect_uris = ["https://wiki.example.com/oidc/callback"]
+/// secret = "…"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    bind: Option<SocketAddr>,
+    #[serde(default)]
+    circuit: CircuitFile,
+    #[serde(default)]
+    concurrency: ConcurrencyFile,
+    /// `[circuits.<id>]` tables, each with all three paths and optionally a
+    /// `kind`.
+    #[serde(default)]
+    circuits: BTreeMap<String, CircuitFile>,
+    oidc: Option<OidcFile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OidcFile {
+    code_ttl_secs: Option<u64>,
+    /// HTML for `GET /authorize`.
+    login_page: Option<PathBuf>,
+    /// `[oidc.clients.<client_id>]` tables.
+    #[serde(default)]
+    clients: BTreeMap<String, OidcClient>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CircuitFile {
+    kind: Option<CircuitKind>,
+    wasm: Option<PathBuf>,
+    r1cs: Option<PathBuf>,
+    zkey: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConcurrencyFile {
+    prove: Option<usize>,
+    verify: Option<usize>,
+    register: Option<usize>,
+}
+
+impl AppConfig {
+    /// Defaults overridden by the TOML file at `path`.  Relative paths in
+    /// the file are taken from the file's directory.
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let file: ConfigFile = toml::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", path.display())))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut config = AppConfig::default();
+        let c = file.circuit;
+        if c.kind.is_some_and(|k| k != CircuitKind::Secret) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: the bundled circuit must be of kind `secret`", path.display())));
+        }
+        for (slot, value) in [(&mut config.circuit.wasm, c.wasm), (&mut config.circuit.r1cs, c.r1cs), (&mut config.circuit.zkey, c.zkey)] {
+            if let Some(value) = value {
+                *slot = dir.join(value);
+            }
+        }
+        let n = file.concurrency;
+        for (slot, value) in [(&mut config.concurrency.prove, n.prove), (&mut config.concurrency.verify, n.verify), (&mut config.concurrency.register, n.register)] {
+            if let Some(value) = value {
+                *slot = value;
+            }
+        }
+        if let Some(bind) = file.bind {
+            config.server.bind = bind;
+        }
+        for (id, c) in file.circuits {
+            let (Some(wasm), Some(r1cs), Some(zkey)) = (c.wasm, c.r1cs, c.zkey) else {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: circuit `{id}` needs wasm, r1cs and zkey", path.display())));
+            };
+            let kind = c.kind.unwrap_or_default();
+            config.circuits.insert(id, CircuitPaths { wasm: dir.join(wasm), r1cs: dir.join(r1cs), zkey: dir.join(zkey), kind });
+        }
+        if let Some(o) = file.oidc {
+            let login_page = o.login_page.map(|page| std::fs::read_to_string(dir.join(page))).transpose()?;
+            let code_ttl = o.code_ttl_secs.map(Duration::from_secs).unwrap_or(OidcConfig::default().code_ttl);
+            config.oidc = Some(OidcConfig { clients: o.clients, code_ttl, login_page });
+        }
+        Ok(config)
+    }
+}
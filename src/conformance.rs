@@ -0,0 +1,86 @@
+use ark_bn254::Fr;
+use ark_ff::UniformRand;
+use rand::thread_rng;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::artifacts::Artifacts;
+use crate::cancel::Cancel;
+use crate::crypto::poseidon_hash;
+use crate::prover;
+use crate::snarkjs::{public_signals, SnarkjsProof, SnarkjsVerificationKey};
+use crate::AppConfig;
+
+//--------------------------------------------------------------------
+// snarkjs conformance
+//--------------------------------------------------------------------
+// Release qualification: prove in-process, export the snarkjs JSON files,
+// and check `snarkjs groth16 verify` reaches the same verdict as we do.
+
+/// One verification case and the verdict from each side.
+#[derive(Debug, Serialize)]
+pub struct ConformanceCase {
+    pub name: &'static str,
+    pub expected: bool,
+    pub in_process: bool,
+    pub snarkjs: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConformanceReport {
+    /// Directory holding the exported files, kept for inspection.
+    pub dir: PathBuf,
+    pub cases: Vec<ConformanceCase>,
+}
+
+impl ConformanceReport {
+    /// Both verifiers agree with each other and with the expected verdict.
+    pub fn passed(&self) -> bool {
+        self.cases.iter().all(|c| c.in_process == c.expected && c.snarkjs == c.expected)
+    }
+}
+
+/// Run the conformance cases against `snarkjs`, a command line such as
+/// `"npx snarkjs"` or `"snarkjs"`.  Blocks for one proof plus the snarkjs runs.
+pub fn run_conformance(config: &AppConfig, snarkjs: &str) -> io::Result<ConformanceReport> {
+    let artifacts = Artifacts::new(config);
+    let mut rng = thread_rng();
+    let secret = Fr::rand(&mut rng);
+    let commitment = poseidon_hash(&[secret]);
+    let proof = prover::prove(&artifacts, secret, commitment, &mut rng, &Cancel::default()).map_err(|e| io::Error::other(e.to_string()))?;
+
+    let dir = std::env::temp_dir().join(format!("zk-auth-conformance-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let vk_path = write_json(&dir, "verification_key.json", &SnarkjsVerificationKey::from_vk(artifacts.verifying_key()))?;
+    let proof_path = write_json(&dir, "proof.json", &SnarkjsProof::from_proof(&proof))?;
+
+    let mut cases = Vec::new();
+    for (name, public, expected) in [("valid", commitment, true), ("tampered-public-input", commitment + Fr::from(1u64), false)] {
+        let public_path = write_json(&dir, &format!("public-{name}.json"), &public_signals(&[public]))?;
+        cases.push(ConformanceCase {
+            name,
+            expected,
+            in_process: prover::verify(&artifacts, public, &proof),
+            snarkjs: snarkjs_verify(snarkjs, &vk_path, &public_path, &proof_path)?,
+        });
+    }
+    Ok(ConformanceReport { dir, cases })
+}
+
+fn write_json<T: Serialize>(dir: &Path, name: &str, value: &T) -> io::Result<PathBuf> {
+    let path = dir.join(name);
+    fs::write(&path, serde_json::to_vec_pretty(value)?)?;
+    Ok(path)
+}
+
+/// snarkjs prints `OK!` on success; older releases exit 0 either way, so
+/// the output is checked as well as the status.
+fn snarkjs_verify(snarkjs: &str, vk: &Path, public: &Path, proof: &Path) -> io::Result<bool> {
+    let mut argv = snarkjs.split_whitespace();
+    let program = argv.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty snarkjs command"))?;
+    let output = Command::new(program).args(argv).args(["groth16", "verify"]).arg(vk).arg(public).arg(proof).output()?;
+    Ok(output.status.success() && String::from_utf8_lossy(&output.stdout).contains("OK"))
+}
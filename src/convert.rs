@@ -0,0 +1,401 @@
+use actix_web::{post, web, HttpResponse, ResponseError};
+use ark_bn254::{Bn254, Fq, Fq2, Fr};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::Proof;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::ApiError;
+use crate::handlers::ProofJson;
+use crate::snarkjs::{checked_g1, checked_g2, public_signals, SnarkjsProof};
+
+//--------------------------------------------------------------------
+// Proof formats
+//--------------------------------------------------------------------
+/// Encodings a stored proof may be in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofFormat {
+    /// Our API's hex `ProofJson`, optionally wrapped with its `commitment`
+    /// as in a `/verify-proof` body.
+    Hex,
+    /// snarkjs `proof.json`, optionally wrapped as `{proof, publicSignals}`.
+    Snarkjs,
+    /// gnark's raw (uncompressed) encoding: `Ar ‖ Bs ‖ Krs`, 256 bytes.
+    Gnark,
+    /// arkworks canonical compressed encoding, 128 bytes.
+    Ark,
+}
+
+impl FromStr for ProofFormat {
+    type Err = io::Error;
+    fn from_str(s: &str) -> io::Result<Self> {
+        match s {
+            "hex" => Ok(ProofFormat::Hex),
+            "snarkjs" => Ok(ProofFormat::Snarkjs),
+            "gnark" => Ok(ProofFormat::Gnark),
+            "ark" => Ok(ProofFormat::Ark),
+            _ => Err(invalid(format!("unknown proof format `{s}` (hex, snarkjs, gnark, ark)"))),
+        }
+    }
+}
+
+impl ProofFormat {
+    /// The name [`FromStr`] accepts.
+    pub fn name(self) -> &'static str {
+        match self {
+            ProofFormat::Hex => "hex",
+            ProofFormat::Snarkjs => "snarkjs",
+            ProofFormat::Gnark => "gnark",
+            ProofFormat::Ark => "ark",
+        }
+    }
+
+    /// File extension used when converting a directory.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ProofFormat::Hex | ProofFormat::Snarkjs => "json",
+            ProofFormat::Gnark | ProofFormat::Ark => "bin",
+        }
+    }
+
+    /// Whether the encoding carries public inputs alongside the proof.
+    pub fn has_public_inputs(self) -> bool {
+        matches!(self, ProofFormat::Hex | ProofFormat::Snarkjs)
+    }
+}
+
+/// A proof and its public inputs, independent of encoding.
+#[derive(Clone, Debug)]
+pub struct ProofRecord {
+    pub proof: Proof<Bn254>,
+    pub public_inputs: Vec<Fr>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HexIn { Wrapped { proof: ProofJson, commitment: Option<String> }, Bare(ProofJson) }
+#[derive(Serialize)]
+struct HexOut<'a> { #[serde(skip_serializing_if = "Option::is_none")] commitment: Option<String>, proof: &'a ProofJson }
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SnarkjsIn { Wrapped { proof: SnarkjsProof, #[serde(rename = "publicSignals", default)] public_signals: Vec<String> }, Bare(SnarkjsProof) }
+#[derive(Serialize)]
+struct SnarkjsOut { proof: SnarkjsProof, #[serde(rename = "publicSignals")] public_signals: Vec<String> }
+
+/// Decode `bytes` in `format`.  Points are checked to be on the curve and
+/// public inputs are normalised (see [`normalize_public_input`]).
+pub fn decode_proof(format: ProofFormat, bytes: &[u8]) -> io::Result<ProofRecord> {
+    match format {
+        ProofFormat::Hex => {
+            let (proof, commitment) = match serde_json::from_slice(bytes)? {
+                HexIn::Wrapped { proof, commitment } => (proof, commitment),
+                HexIn::Bare(proof) => (proof, None),
+            };
+            let public_inputs = commitment.iter().map(|c| normalize_public_input(c)).collect::<io::Result<_>>()?;
+            Ok(ProofRecord { proof: proof.try_to_proof().ok_or_else(|| invalid("malformed hex proof"))?, public_inputs })
+        }
+        ProofFormat::Snarkjs => {
+            let (proof, public) = match serde_json::from_slice(bytes)? {
+                SnarkjsIn::Wrapped { proof, public_signals } => (proof, public_signals),
+                SnarkjsIn::Bare(proof) => (proof, Vec::new()),
+            };
+            let public_inputs = public.iter().map(|c| normalize_public_input(c)).collect::<io::Result<_>>()?;
+            Ok(ProofRecord { proof: proof.to_proof().ok_or_else(|| invalid("malformed snarkjs proof"))?, public_inputs })
+        }
+        ProofFormat::Gnark => {
+            let proof = proof_from_gnark(bytes).ok_or_else(|| invalid("malformed gnark proof"))?;
+            Ok(ProofRecord { proof, public_inputs: Vec::new() })
+        }
+        ProofFormat::Ark => {
+            let proof = Proof::deserialize_compressed(bytes).map_err(|e| invalid(e.to_string()))?;
+            Ok(ProofRecord { proof, public_inputs: Vec::new() })
+        }
+    }
+}
+
+/// Encode `record` in `format`.  Binary formats drop the public inputs.
+pub fn encode_proof(format: ProofFormat, record: &ProofRecord) -> io::Result<Vec<u8>> {
+    match format {
+        ProofFormat::Hex => {
+            let commitment = match record.public_inputs.as_slice() {
+                [] => None,
+                [c] => Some(public_signals(&[*c]).remove(0)),
+                _ => return Err(invalid("hex proofs carry exactly one public input (the commitment)")),
+            };
+            Ok(serde_json::to_vec_pretty(&HexOut { commitment, proof: &ProofJson::from_proof(&record.proof) })?)
+        }
+        ProofFormat::Snarkjs => {
+            let out = SnarkjsOut { proof: SnarkjsProof::from_proof(&record.proof), public_signals: public_signals(&record.public_inputs) };
+            Ok(serde_json::to_vec_pretty(&out)?)
+        }
+        ProofFormat::Gnark => Ok(proof_to_gnark(&record.proof)),
+        ProofFormat::Ark => {
+            let mut out = Vec::new();
+            record.proof.serialize_compressed(&mut out).map_err(|e| invalid(e.to_string()))?;
+            Ok(out)
+        }
+    }
+}
+
+/// Parse a public input given in decimal or `0x` hex and reject values
+/// outside the scalar field, which older deployments silently reduced.
+pub fn normalize_public_input(s: &str) -> io::Result<Fr> {
+    let s = s.trim();
+    zk_auth_verify_core::parse_scalar(s).map_err(|_| {
+        let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"));
+        match hex.map_or_else(|| BigUint::parse_bytes(s.as_bytes(), 10), |h| BigUint::parse_bytes(h.as_bytes(), 16)) {
+            Some(_) => invalid(format!("public input `{s}` is not a BN254 scalar")),
+            None => invalid(format!("public input `{s}` is not a decimal or 0x-hex integer")),
+        }
+    })
+}
+
+/// Decode `bytes` in `format`, or guess it: JSON is tried as snarkjs then
+/// hex, binary by length (128 bytes is a compressed ark proof, 256 or more
+/// a gnark one).
+pub(crate) fn sniff_proof(format: Option<ProofFormat>, bytes: &[u8]) -> Option<ProofRecord> {
+    let guesses = match format {
+        Some(f) => vec![f],
+        None if bytes.trim_ascii_start().starts_with(b"{") => vec![ProofFormat::Snarkjs, ProofFormat::Hex],
+        None if bytes.len() == 128 => vec![ProofFormat::Ark],
+        None => vec![ProofFormat::Gnark],
+    };
+    guesses.iter().find_map(|f| decode_proof(*f, bytes).ok())
+}
+
+/// Public inputs as a JSON array (snarkjs `public.json`) or as values
+/// separated by whitespace or commas.
+pub(crate) fn parse_public_inputs(text: &str) -> io::Result<Vec<Fr>> {
+    let text = text.trim();
+    let values: Vec<String> = if text.starts_with('[') {
+        serde_json::from_str(text)?
+    } else {
+        text.split(|c: char| c.is_whitespace() || c == ',').filter(|s| !s.is_empty()).map(str::to_owned).collect()
+    };
+    values.iter().map(|v| normalize_public_input(v)).collect()
+}
+
+//--------------------------------------------------------------------
+// Files and directories
+//--------------------------------------------------------------------
+/// Convert one file, or every file in a directory into `output` (created
+/// if needed, one file per input with the target extension).  Returns the
+/// number of proofs written.
+pub fn convert_path(from: ProofFormat, to: ProofFormat, input: &Path, output: &Path) -> io::Result<usize> {
+    if !input.is_dir() {
+        fs::write(output, encode_proof(to, &decode_file(from, to, input)?)?)?;
+        return Ok(1);
+    }
+    fs::create_dir_all(output)?;
+    let mut converted = 0;
+    for entry in fs::read_dir(input)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let name = path.file_stem().unwrap_or_default();
+        let out = output.join(name).with_extension(to.extension());
+        fs::write(out, encode_proof(to, &decode_file(from, to, &path)?)?)?;
+        converted += 1;
+    }
+    Ok(converted)
+}
+
+fn decode_file(from: ProofFormat, to: ProofFormat, path: &Path) -> io::Result<ProofRecord> {
+    let record = decode_proof(from, &fs::read(path)?)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+    if !record.public_inputs.is_empty() && !to.has_public_inputs() {
+        tracing::warn!(path = %path.display(), "public inputs dropped: target format carries none");
+    }
+    Ok(record)
+}
+
+//--------------------------------------------------------------------
+// Conversion over HTTP
+//--------------------------------------------------------------------
+/// A proof to re-encode.  `proof` is the JSON document for `hex` and
+/// `snarkjs`, as `convert` reads it from a file, and a base64 or `0x` hex
+/// string for the binary `gnark` and `ark` encodings.
+#[derive(Debug, Deserialize)]
+pub struct ConvertProofRequest {
+    pub from: String,
+    pub to: String,
+    pub proof: serde_json::Value,
+    /// Public inputs for a binary proof, which carries none; ignored when
+    /// `proof` brings its own.
+    #[serde(default)]
+    pub public_inputs: Vec<String>,
+}
+
+/// `proof` in the requested encoding, shaped as in [`ConvertProofRequest`].
+/// `public_inputs` are returned even when the target format drops them.
+#[derive(Debug, Serialize)]
+pub struct ConvertProofResponse {
+    pub format: &'static str,
+    pub proof: serde_json::Value,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub public_inputs: Vec<String>,
+}
+
+#[post("/convert-proof")]
+async fn convert_proof(body: web::Json<ConvertProofRequest>) -> HttpResponse {
+    let Ok(from) = body.from.parse::<ProofFormat>() else {
+        return ApiError::InvalidInput { field: "from", reason: "expected hex, snarkjs, gnark or ark" }.error_response();
+    };
+    let Ok(to) = body.to.parse::<ProofFormat>() else {
+        return ApiError::InvalidInput { field: "to", reason: "expected hex, snarkjs, gnark or ark" }.error_response();
+    };
+    let bytes = if from.has_public_inputs() {
+        Some(body.proof.to_string().into_bytes())
+    } else {
+        body.proof.as_str().and_then(binary_proof)
+    };
+    let Some(Ok(mut record)) = bytes.map(|b| decode_proof(from, &b)) else {
+        return ApiError::InvalidInput { field: "proof", reason: "not a valid proof in the `from` format" }.error_response();
+    };
+    if record.public_inputs.is_empty() {
+        let Ok(public_inputs) = body.public_inputs.iter().map(|s| normalize_public_input(s)).collect() else {
+            return ApiError::InvalidInput { field: "public_inputs", reason: "not a scalar field element" }.error_response();
+        };
+        record.public_inputs = public_inputs;
+    }
+    let encoded = match encode_proof(to, &record) {
+        Ok(encoded) => encoded,
+        Err(_) => {
+            return ApiError::InvalidInput { field: "public_inputs", reason: "hex proofs carry exactly one public input" }
+                .error_response()
+        }
+    };
+    let proof = if to.has_public_inputs() {
+        serde_json::from_slice(&encoded).expect("encode_proof writes JSON")
+    } else {
+        serde_json::Value::String(BASE64.encode(encoded))
+    };
+    HttpResponse::Ok().json(ConvertProofResponse { format: to.name(), proof, public_inputs: public_signals(&record.public_inputs) })
+}
+
+fn binary_proof(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(h) => hex::decode(h).ok(),
+        None => BASE64.decode(s).ok(),
+    }
+}
+
+//--------------------------------------------------------------------
+// Encodings
+//--------------------------------------------------------------------
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+// gnark writes coordinates big-endian, G2 limbs as `A1 ‖ A0`, and uses the
+// top two bits of the first byte as flags; uncompressed points have none
+// set.  Trailing commitment data from newer gnark versions is ignored.
+
+/// Read a proof gnark's `groth16.Proof.WriteRawTo` wrote, checking every
+/// point is on the curve.
+pub fn proof_from_gnark(bytes: &[u8]) -> Option<Proof<Bn254>> {
+    if bytes.len() < 256 || bytes[0] >> 6 != 0 || bytes[64] >> 6 != 0 || bytes[192] >> 6 != 0 {
+        return None;
+    }
+    let fq = |at: usize| Fq::from_be_bytes_mod_order(&bytes[at..at + 32]);
+    Some(Proof {
+        a: checked_g1(fq(0), fq(32))?,
+        b: checked_g2(Fq2::new(fq(96), fq(64)), Fq2::new(fq(160), fq(128)))?,
+        c: checked_g1(fq(192), fq(224))?,
+    })
+}
+
+/// The 256 bytes gnark's `groth16.Proof.ReadFrom` expects for `proof`.
+pub fn proof_to_gnark(proof: &Proof<Bn254>) -> Vec<u8> {
+    let limbs = [
+        proof.a.x, proof.a.y,
+        proof.b.x.c1, proof.b.x.c0, proof.b.y.c1, proof.b.y.c0,
+        proof.c.x, proof.c.y,
+    ];
+    limbs.iter().flat_map(|f| {
+        let b = f.into_bigint().to_bytes_be();
+        [vec![0u8; 32 - b.len()], b].concat()
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{identity, valid_proof};
+
+    const FORMATS: [ProofFormat; 4] = [ProofFormat::Hex, ProofFormat::Snarkjs, ProofFormat::Gnark, ProofFormat::Ark];
+
+    fn record(seed: u64) -> ProofRecord {
+        let identity = identity(seed);
+        let proof = valid_proof(&identity, seed).try_to_proof().expect("valid proof parses");
+        ProofRecord { proof, public_inputs: vec![identity.commitment] }
+    }
+
+    #[test]
+    fn every_format_round_trips_a_real_proof() {
+        let record = record(21);
+        for format in FORMATS {
+            let decoded = decode_proof(format, &encode_proof(format, &record).unwrap()).unwrap();
+            assert_eq!(decoded.proof, record.proof, "{}", format.name());
+            let expected = if format.has_public_inputs() { record.public_inputs.clone() } else { Vec::new() };
+            assert_eq!(decoded.public_inputs, expected, "{}", format.name());
+            assert_eq!(sniff_proof(None, &encode_proof(format, &record).unwrap()).map(|r| r.proof), Some(record.proof.clone()), "{}", format.name());
+        }
+    }
+
+    #[test]
+    fn gnark_writes_g2_limbs_c1_first() {
+        let record = record(22);
+        let bytes = proof_to_gnark(&record.proof);
+        assert_eq!(bytes.len(), 256);
+        let limb = |at: usize| Fq::from_be_bytes_mod_order(&bytes[at..at + 32]);
+        let b = record.proof.b;
+        assert_eq!([limb(64), limb(96), limb(128), limb(160)], [b.x.c1, b.x.c0, b.y.c1, b.y.c0]);
+
+        let mut flagged = bytes.clone();
+        flagged[64] |= 0x80;
+        assert_eq!(proof_from_gnark(&flagged), None);
+        assert_eq!(proof_from_gnark(&bytes[..255]), None);
+    }
+
+    #[test]
+    fn hex_commitments_decode_like_decimal_ones() {
+        let record = record(23);
+        let decimal = public_signals(&record.public_inputs).remove(0);
+        let hex = format!("0x{}", hex::encode(record.public_inputs[0].into_bigint().to_bytes_be()));
+        let proof = ProofJson::from_proof(&record.proof);
+        for commitment in [decimal, hex] {
+            let bytes = serde_json::to_vec(&serde_json::json!({"commitment": commitment, "proof": proof})).unwrap();
+            assert_eq!(decode_proof(ProofFormat::Hex, &bytes).unwrap().public_inputs, record.public_inputs);
+        }
+    }
+
+    #[test]
+    fn public_inputs_must_be_scalars() {
+        let modulus = BigUint::from(Fr::MODULUS);
+        assert_eq!(normalize_public_input(" 42 ").unwrap(), Fr::from(42u64));
+        assert_eq!(normalize_public_input("0x2a").unwrap(), Fr::from(42u64));
+        assert_eq!(normalize_public_input("0X2A").unwrap(), Fr::from(42u64));
+        assert_eq!(normalize_public_input(&(modulus.clone() - 1u32).to_string()).unwrap(), -Fr::from(1u64));
+        assert!(normalize_public_input(&modulus.to_string()).unwrap_err().to_string().contains("not a BN254 scalar"));
+        assert!(normalize_public_input("forty-two").unwrap_err().to_string().contains("not a decimal or 0x-hex integer"));
+    }
+
+    #[test]
+    fn parses_public_input_lists() {
+        let expected = vec![Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)];
+        assert_eq!(parse_public_inputs(r#"["1", "0x2", "3"]"#).unwrap(), expected);
+        assert_eq!(parse_public_inputs("1, 0x2\n3").unwrap(), expected);
+        assert!(parse_public_inputs("1 x").is_err());
+    }
+}
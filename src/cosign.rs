@@ -0,0 +1,194 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use p256::ecdsa::{DerSignature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::GeneralName;
+use x509_parser::pem::Pem;
+
+//--------------------------------------------------------------------
+// Artefact signatures
+//--------------------------------------------------------------------
+/// Fulcio's OIDC issuer extension, DER UTF8String (current form).
+const OID_ISSUER_V2: &str = "1.3.6.1.4.1.57264.1.8";
+/// The same, as raw bytes (deprecated form, still issued alongside).
+const OID_ISSUER_V1: &str = "1.3.6.1.4.1.57264.1.1";
+/// Longest accepted chain, leaf included.
+const MAX_CHAIN: usize = 4;
+
+/// Detached cosign signatures on the circuit artefacts, checked when the
+/// server starts.
+///
+/// Each artefact `f` is signed as `cosign sign-blob --output-signature f.sig`,
+/// keyless signatures adding `--output-certificate f.pem`.  A signature is
+/// accepted if any configured key, or the keyless identity, verifies it; a
+/// signature that verifies under none of them always stops the server.
+#[derive(Clone, Debug, Default)]
+pub struct ArtifactSigning {
+    /// PEM public keys, as `cosign generate-key-pair` writes `cosign.pub`.
+    pub keys: Vec<PathBuf>,
+    pub keyless: Option<KeylessIdentity>,
+    /// Refuse to start when an artefact has no signature; otherwise that
+    /// is only logged.
+    pub strict: bool,
+}
+
+/// A Fulcio-issued signing certificate for one OIDC identity.
+///
+/// The chain is checked up to `roots`, but the transparency log is not
+/// consulted, so the short-lived certificate's validity period is not
+/// compared with a signing time.
+#[derive(Clone, Debug)]
+pub struct KeylessIdentity {
+    /// PEM bundle with the Fulcio root and intermediate certificates.
+    pub roots: PathBuf,
+    /// Certificate subject: an email address or a workflow URI.
+    pub identity: String,
+    /// OIDC issuer, e.g. `https://token.actions.githubusercontent.com`.
+    pub issuer: String,
+}
+
+/// Check the signature of each file in `files`, logging who signed it.
+pub(crate) fn verify_artifacts(policy: &ArtifactSigning, files: &[&Path]) -> io::Result<()> {
+    let keys = policy
+        .keys
+        .iter()
+        .map(|path| {
+            let pem = std::fs::read_to_string(path)?;
+            VerifyingKey::from_public_key_pem(&pem).map_err(|_| invalid(format!("{}: not a PEM P-256 public key", path.display())))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let roots = match &policy.keyless {
+        Some(keyless) => read_certs(&std::fs::read(&keyless.roots)?)?,
+        None => Vec::new(),
+    };
+
+    for file in files {
+        let sig_path = with_suffix(file, "sig");
+        let sig = match std::fs::read_to_string(&sig_path) {
+            Ok(sig) => sig,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if policy.strict {
+                    return Err(invalid(format!("{}: unsigned artefact ({} missing)", file.display(), sig_path.display())));
+                }
+                tracing::warn!(artifact = %file.display(), "artefact has no cosign signature");
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let sig = BASE64
+            .decode(sig.trim())
+            .ok()
+            .and_then(|der| DerSignature::from_bytes(&der).ok())
+            .ok_or_else(|| invalid(format!("{}: not a base64 ECDSA signature", sig_path.display())))?;
+        // The zkey runs to gigabytes: hash it streaming and verify the digest.
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(file)?, &mut hasher)?;
+        let digest = hasher.finalize();
+
+        let signer = if let Some(i) = keys.iter().position(|k| k.verify_prehash(&digest, &sig).is_ok()) {
+            policy.keys[i].display().to_string()
+        } else if let Some(keyless) = &policy.keyless {
+            verify_keyless(keyless, &roots, &with_suffix(file, "pem"), &digest, &sig)
+                .map_err(|reason| invalid(format!("{}: {reason}", file.display())))?
+        } else {
+            return Err(invalid(format!("{}: signature does not verify under any configured key", file.display())));
+        };
+        tracing::info!(artifact = %file.display(), signer, "artefact signature verified");
+    }
+    Ok(())
+}
+
+/// The certificate's identity if it chains to `roots`, names the expected
+/// identity and issuer, and its key made `sig`.
+fn verify_keyless(keyless: &KeylessIdentity, roots: &[Pem], cert_path: &Path, digest: &[u8], sig: &DerSignature) -> Result<String, String> {
+    let raw = std::fs::read(cert_path).map_err(|e| format!("{}: {e}", cert_path.display()))?;
+    // cosign writes the PEM certificate base64-encoded once more.
+    let raw = if raw.starts_with(b"-----BEGIN") { raw } else { BASE64.decode(raw.trim_ascii()).map_err(|_| "certificate is neither PEM nor base64")? };
+    let leaf_pem = read_certs(&raw).map_err(|e| e.to_string())?.into_iter().next().ok_or("no certificate in file")?;
+    let leaf = leaf_pem.parse_x509().map_err(|_| "certificate does not parse")?;
+
+    let key = VerifyingKey::from_public_key_der(leaf.public_key().raw).map_err(|_| "certificate key is not P-256")?;
+    key.verify_prehash(digest, sig).map_err(|_| "signature does not verify under the certificate or any configured key")?;
+
+    let roots = roots.iter().filter_map(|pem| pem.parse_x509().ok()).collect::<Vec<_>>();
+    check_chain(&leaf, &roots)?;
+
+    let identities = leaf
+        .subject_alternative_name()
+        .ok()
+        .flatten()
+        .map(|san| {
+            san.value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::RFC822Name(s) | GeneralName::URI(s) => Some(s.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if !identities.contains(&keyless.identity) {
+        return Err(format!("certificate identity {identities:?} is not {}", keyless.identity));
+    }
+    let issuer = leaf.iter_extensions().find_map(|ext| match ext.oid.to_id_string().as_str() {
+        OID_ISSUER_V2 => der_utf8(ext.value),
+        OID_ISSUER_V1 => std::str::from_utf8(ext.value).ok(),
+        _ => None,
+    });
+    if issuer != Some(keyless.issuer.as_str()) {
+        return Err(format!("certificate issuer {issuer:?} is not {}", keyless.issuer));
+    }
+    Ok(keyless.identity.clone())
+}
+
+/// Walk issuer links from `leaf` to a self-signed certificate in `roots`,
+/// checking every signature on the way.
+fn check_chain(leaf: &X509Certificate<'_>, roots: &[X509Certificate<'_>]) -> Result<(), &'static str> {
+    let mut cert = leaf;
+    for _ in 0..MAX_CHAIN {
+        let issuer = roots
+            .iter()
+            .find(|c| c.subject().as_raw() == cert.issuer().as_raw() && c.is_ca())
+            .ok_or("certificate does not chain to the configured Fulcio roots")?;
+        cert.verify_signature(Some(issuer.public_key())).map_err(|_| "certificate chain signature does not verify")?;
+        if issuer.subject().as_raw() == issuer.issuer().as_raw() {
+            return issuer.verify_signature(None).map_err(|_| "root certificate is not validly self-signed");
+        }
+        cert = issuer;
+    }
+    Err("certificate chain is too long")
+}
+
+fn read_certs(bytes: &[u8]) -> io::Result<Vec<Pem>> {
+    Pem::iter_from_buffer(bytes)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid("malformed PEM certificate bundle".to_owned()))
+}
+
+/// Contents of a short DER UTF8String.
+fn der_utf8(der: &[u8]) -> Option<&str> {
+    let (len, body) = match der {
+        [0x0c, len, body @ ..] if *len < 0x80 => (*len as usize, body),
+        [0x0c, 0x81, len, body @ ..] => (*len as usize, body),
+        _ => return None,
+    };
+    std::str::from_utf8(body.get(..len)?).ok()
+}
+
+fn with_suffix(file: &Path, suffix: &str) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,122 @@
+use ark_bn254::Fr;
+use rand::{thread_rng, RngCore};
+use serde::Serialize;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::session::JwtSigner;
+use crate::snarkjs::public_signals;
+
+//--------------------------------------------------------------------
+// Verifiable credentials
+//--------------------------------------------------------------------
+/// Return a W3C Verifiable Credential with every registration, so SSI
+/// wallets can hold the commitment and verifiers can check who issued it.
+///
+/// Credentials use the JWT encoding of the VC Data Model 1.1 and are
+/// signed with Ed25519 (`alg: EdDSA`).  The key is published next to the
+/// session key at `GET /.well-known/jwks.json`.
+#[derive(Clone, Debug)]
+pub struct CredentialConfig {
+    /// File holding the 32-byte hex seed of the issuer key.  `None`
+    /// generates a key per process, so earlier credentials can no longer be
+    /// checked after a restart.
+    pub key: Option<PathBuf>,
+    /// The `iss` claim, a DID or URL naming this issuer.
+    pub issuer: String,
+    /// Credentials expire this long after issuance; `None` issues them
+    /// without `exp`.
+    pub validity: Option<Duration>,
+}
+
+impl Default for CredentialConfig {
+    fn default() -> Self {
+        Self { key: None, issuer: "zk-auth".into(), validity: None }
+    }
+}
+
+/// The JWT claims of a credential.
+#[derive(Clone, Debug, Serialize)]
+pub struct CredentialClaims {
+    pub iss: String,
+    /// Unix seconds.
+    pub nbf: u64,
+    pub iat: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    /// A random `urn:uuid:`.
+    pub jti: String,
+    pub vc: Credential,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Credential {
+    #[serde(rename = "@context")]
+    pub context: [&'static str; 1],
+    /// `VerifiableCredential` and `ZkAuthCommitmentCredential`.
+    #[serde(rename = "type")]
+    pub types: [&'static str; 2],
+    #[serde(rename = "credentialSubject")]
+    pub credential_subject: CommitmentSubject,
+}
+
+/// What the credential attests: a registered commitment.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitmentSubject {
+    /// Decimal, as `/register` returns it.
+    pub commitment: String,
+    /// The Poseidon digest of the attribute hashes, decimal.  The attribute
+    /// hashes themselves are left out because short attributes such as age
+    /// and country are easy to recover from their hashes.
+    pub user_hash: String,
+    /// Position of the commitment in the commitment tree.
+    pub leaf_index: u64,
+}
+
+pub(crate) struct CredentialIssuer {
+    config: CredentialConfig,
+    signer: JwtSigner,
+}
+
+impl CredentialIssuer {
+    pub(crate) fn new(config: CredentialConfig) -> io::Result<Self> {
+        Ok(Self { signer: JwtSigner::new(config.key.as_deref(), "credential key")?, config })
+    }
+
+    pub(crate) fn signer(&self) -> &JwtSigner {
+        &self.signer
+    }
+
+    /// The signed JWT-VC for a commitment just added to the tree.
+    pub(crate) fn issue(&self, commitment: &str, user_hash: Fr, leaf_index: u64) -> String {
+        let iat = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.signer.sign(&CredentialClaims {
+            iss: self.config.issuer.clone(),
+            nbf: iat,
+            iat,
+            exp: self.config.validity.map(|v| iat + v.as_secs()),
+            jti: format!("urn:uuid:{}", uuid_v4()),
+            vc: Credential {
+                context: ["https://www.w3.org/2018/credentials/v1"],
+                types: ["VerifiableCredential", "ZkAuthCommitmentCredential"],
+                credential_subject: CommitmentSubject {
+                    commitment: commitment.to_owned(),
+                    user_hash: public_signals(&[user_hash]).remove(0),
+                    leaf_index,
+                },
+            },
+        })
+    }
+}
+
+/// RFC 9562 version 4 UUID.
+fn uuid_v4() -> String {
+    let mut b = [0u8; 16];
+    thread_rng().fill_bytes(&mut b);
+    b[6] = (b[6] & 0x0f) | 0x40;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    let h = hex::encode(b);
+    format!("{}-{}-{}-{}-{}", &h[..8], &h[8..12], &h[12..16], &h[16..20], &h[20..])
+}
@@ -0,0 +1,36 @@
+use ark_bn254::{Fr, Fq, Fq2, G1Affine, G2Affine};
+use ark_ff::{BigInteger, PrimeField};
+use hex::encode as hex_encode;
+use light_poseidon::{Poseidon, PoseidonHasher};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+//--------------------------------------------------------------------
+// Poseidon Hashers
+//--------------------------------------------------------------------
+// Poseidon hashers keyed by input length.  `hash` needs `&mut self`, so
+// each thread keeps its own set instead of sharing one behind a lock:
+// concurrent `/register` calls then never wait on each other, and the
+// round constants are built once per thread and arity.
+thread_local! {
+    static POSEIDON: RefCell<HashMap<usize, Poseidon<Fr>>> = RefCell::new(HashMap::new());
+}
+
+/// Compute Poseidon hash using this thread's hasher for the number of inputs.
+pub(crate) fn poseidon_hash(inputs:&[Fr])->Fr {
+    POSEIDON.with(|hashers| {
+        let mut hashers = hashers.borrow_mut();
+        let h = hashers
+            .entry(inputs.len())
+            .or_insert_with(|| Poseidon::<Fr>::new_circom(inputs.len()).expect("poseidon init"));
+        h.hash(inputs).expect("poseidon hash")
+    })
+}
+
+//--------------------------------------------------------------------
+// Helpers
+//--------------------------------------------------------------------
+pub(crate) fn fq_to_hex(f:&Fq)->String{let mut bytes=f.into_bigint().to_bytes_be();if bytes.len()<32{bytes=[vec![0u8;32-bytes.len()],bytes].concat();}format!("0x{}",hex_encode(bytes))}
+pub(crate) fn fq2_to_hex(f2:&Fq2)->(String,String){(fq_to_hex(&f2.c0),fq_to_hex(&f2.c1))}
+pub(crate) fn g1_to_hex(p:&G1Affine)->(String,String){(fq_to_hex(&p.x),fq_to_hex(&p.y))}
+pub(crate) fn g2_to_hex(p:&G2Affine)->(String,String,String,String){let(x0,x1)=fq2_to_hex(&p.x);let(y0,y1)=fq2_to_hex(&p.y);(x1,x0,y1,y0)}
@@ -0,0 +1,189 @@
+use actix_web::{post, web, HttpResponse, ResponseError};
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use hex::encode as hex_encode;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::config::CircuitKind;
+use crate::convert::normalize_public_input;
+use crate::crypto::poseidon_hash;
+use crate::error::ApiError;
+use crate::handlers::{prove_statement, ProofJson};
+use crate::mock;
+use crate::prover;
+use crate::redact::Redacted;
+use crate::snarkjs::public_signals;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Child-secret derivation
+//--------------------------------------------------------------------
+// child = Poseidon(1, parent, Keccak256(application) mod p).  Children are
+// secrets in their own right, so deriving again from a child gives a
+// hierarchy; without the parent, sibling commitments cannot be linked.
+//
+// Child secrets and nullifiers hash the same two values, so each starts
+// with its own domain tag.  Without the tags the nullifier a proof
+// publishes would be the child secret for the same application.
+
+/// First Poseidon input of a child secret.
+pub const TAG_CHILD: u64 = 1;
+/// First Poseidon input of a nullifier.
+pub const TAG_NULLIFIER: u64 = 2;
+/// First Poseidon input of a membership nullifier, distinct from
+/// [`TAG_NULLIFIER`] so membership proofs cannot be linked to the
+/// commitment a nullifier-circuit proof names.
+pub const TAG_MEMBERSHIP: u64 = 3;
+
+/// `Keccak256(application) mod p`, the field element an application name
+/// is bound to.
+pub fn app_id(application: &str) -> Fr {
+    let mut k = Keccak256::new();
+    k.update(application.as_bytes());
+    Fr::from_be_bytes_mod_order(&k.finalize())
+}
+
+/// Derive the child secret of `parent` for `application`.
+pub fn derive_child_secret(parent: &Fr, application: &str) -> Fr {
+    poseidon_hash(&[Fr::from(TAG_CHILD), *parent, app_id(application)])
+}
+
+/// Derive along a path of applications, e.g. `["mail", "work"]`.
+pub fn derive_path(master: &Fr, path: &[&str]) -> Fr {
+    path.iter().fold(*master, |secret, app| derive_child_secret(&secret, app))
+}
+
+/// The scoped nullifier `Poseidon(2, secret, app_id(application))` that
+/// `circuits/secret-proof-nullifier.circom` outputs: one per identity and
+/// application, unlinkable across applications.
+pub fn derive_nullifier(secret: &Fr, application: &str) -> Fr {
+    poseidon_hash(&[Fr::from(TAG_NULLIFIER), *secret, app_id(application)])
+}
+
+/// The membership nullifier `Poseidon(3, secret, app_id(application))` that
+/// `circuits/secret-proof-membership.circom` outputs.
+pub fn derive_membership_nullifier(secret: &Fr, application: &str) -> Fr {
+    poseidon_hash(&[Fr::from(TAG_MEMBERSHIP), *secret, app_id(application)])
+}
+
+/// The public commitment for a secret, `Poseidon(secret)`.
+pub fn commitment_for(secret: &Fr) -> Fr {
+    poseidon_hash(&[*secret])
+}
+
+/// Most secrets [`commitment_for_secrets`] takes, the widest Poseidon it
+/// computes.
+pub const MAX_SECRETS: usize = 12;
+
+/// The commitment over several independent secrets, such as a device
+/// secret and a user secret, `Poseidon(secrets[0], …, secrets[N-1])` as
+/// `secret-proof-multi.circom` checks it.  `None` for no secrets or more
+/// than [`MAX_SECRETS`].
+pub fn commitment_for_secrets(secrets: &[Fr]) -> Option<Fr> {
+    (1..=MAX_SECRETS).contains(&secrets.len()).then(|| poseidon_hash(secrets))
+}
+
+//--------------------------------------------------------------------
+// /derive
+//--------------------------------------------------------------------
+/// `path` is the chain of application names below `secret_hex`.
+#[derive(Deserialize)]  pub struct DeriveRequest { pub secret_hex:String, pub path:Vec<String> }
+/// `proof=true` also proves the child descends from `secret_hex`; the path must then be one application long.
+#[derive(Debug,Default,Deserialize)]  pub struct DeriveQuery { #[serde(default)] pub proof:bool }
+/// `descent` is set when `proof=true` was asked for.
+#[derive(Serialize)]    pub struct DeriveResponse { pub secret:String, pub commitment:String,
+                                                          #[serde(skip_serializing_if="Option::is_none")] pub descent:Option<DescentProof> }
+/// A proof, from the descent circuit `circuit_id`, that `child_commitment` is the commitment of the child secret of
+/// the master behind `master_commitment` for `application`.  Its public signals are `master_commitment`,
+/// `Keccak256(application) mod p` and `child_commitment`; `/verify-descent` checks it.
+#[derive(Clone,Debug,Serialize,Deserialize)] pub struct DescentProof { pub proof:ProofJson, pub master_commitment:String, pub application:String,
+                                                          pub child_commitment:String, pub circuit_id:String, pub circuit_version:String }
+/// A [`DescentProof`] to check; `circuit_version` is optional unless the server requires it.
+#[derive(Debug,Deserialize)]  pub struct DescentVerifyRequest { pub circuit_id:String, #[serde(default)] pub circuit_version:Option<String>,
+                                                          pub master_commitment:String, pub application:String, pub child_commitment:String,
+                                                          pub proof:ProofJson }
+#[derive(Debug,Serialize)]    pub struct DescentVerifyResponse { pub valid:bool, pub circuit_version:String }
+
+impl std::fmt::Debug for DeriveRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeriveRequest").field("secret_hex", &Redacted(&self.secret_hex)).field("path", &self.path).finish()
+    }
+}
+impl std::fmt::Debug for DeriveResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeriveResponse").field("secret", &Redacted(&self.secret)).field("commitment", &self.commitment).field("descent", &self.descent).finish()
+    }
+}
+
+#[post("/derive")]
+async fn derive(state:web::Data<AppState>,body:web::Json<DeriveRequest>,query:web::Query<DeriveQuery>)->HttpResponse{
+    let permit=state.limits.register.acquire().await;
+    let master=match hex::decode(body.secret_hex.trim_start_matches("0x")) {
+        Ok(b) if b.len()<=32 => Fr::from_be_bytes_mod_order(&b),
+        _ => return ApiError::InvalidInput{field:"secret_hex",reason:"must be at most 32 bytes of hex"}.error_response(),
+    };
+    let path:Vec<&str>=body.path.iter().map(String::as_str).collect();
+    let child=derive_path(&master,&path);
+
+    let secret_hex={let mut b=child.into_bigint().to_bytes_be();if b.len()<32{b=[vec![0u8;32-b.len()],b].concat();}format!("0x{}",hex_encode(b))};
+    let commitment_dec=BigUint::from_bytes_be(&commitment_for(&child).into_bigint().to_bytes_be()).to_string();
+    let descent=match (query.proof,&path[..]) {
+        (false,_) => None,
+        // Proving takes seconds under a prove permit; other derivations need not wait for it.
+        (true,[application]) => { drop(permit); match prove_descent(&state,master,application).await { Ok(p)=>Some(p), Err(e)=>return e.error_response() } }
+        (true,_) => return ApiError::InvalidInput{field:"path",reason:"descent proofs cover a single application"}.error_response(),
+    };
+    HttpResponse::Ok().json(DeriveResponse{secret:secret_hex,commitment:commitment_dec,descent})
+}
+
+/// Prove `child_commitment` descends from `master` for `application` with the first configured descent circuit.
+async fn prove_descent(state:&web::Data<AppState>,master:Fr,application:&str)->Result<DescentProof,ApiError>{
+    let circuit_id=state.config.circuits.iter().find(|(_,c)|c.kind==CircuitKind::Descent).map(|(id,_)|id.clone())
+        .ok_or(ApiError::InvalidInput{field:"proof",reason:"no descent circuit on this server"})?;
+    let (master_commitment,app,child_commitment)=(commitment_for(&master),app_id(application),commitment_for(&derive_child_secret(&master,application)));
+    let inputs=vec![("master",vec![master]),("master_commitment",vec![master_commitment]),("application",vec![app]),("child_commitment",vec![child_commitment])];
+    let response=prove_statement(state,circuit_id.clone(),inputs,&[master_commitment,app,child_commitment]).await?;
+    let [master_commitment,child_commitment]:[String;2]=public_signals(&[master_commitment,child_commitment]).try_into().expect("two signals");
+    Ok(DescentProof{proof:response.proof,master_commitment,application:application.to_owned(),child_commitment,circuit_id,circuit_version:response.circuit_version})
+}
+
+//--------------------------------------------------------------------
+// /verify-descent
+//--------------------------------------------------------------------
+/// `200` for a valid proof and `401` with `valid: false` for an invalid one, like `/verify-proof`.  The master must
+/// be registered and not revoked; the child need not be.
+#[post("/verify-descent")]
+async fn verify_descent(state:web::Data<AppState>,body:web::Json<DescentVerifyRequest>)->HttpResponse{
+    match check_descent(&state,&body).await {
+        Ok(verdict) if verdict.valid => HttpResponse::Ok().json(verdict),
+        Ok(verdict) => HttpResponse::Unauthorized().json(verdict),
+        Err(e) => e.error_response(),
+    }
+}
+
+async fn check_descent(state:&web::Data<AppState>,body:&DescentVerifyRequest)->Result<DescentVerifyResponse,ApiError>{
+    let _permit=state.limits.verify.acquire().await;
+    let key=state.circuits.get(&body.circuit_id).ok_or(ApiError::InvalidInput{field:"circuit_id",reason:"no such circuit on this server"})?;
+    if key.kind()!=CircuitKind::Descent { return Err(ApiError::InvalidInput{field:"circuit_id",reason:"not a descent circuit"}); }
+    match &body.circuit_version {
+        Some(v) if !v.eq_ignore_ascii_case(key.vk_fingerprint()) => return Err(ApiError::CircuitMismatch{expected:key.vk_fingerprint().to_owned(),got:Some(v.clone())}),
+        None if state.config.require_circuit_version => return Err(ApiError::CircuitMismatch{expected:key.vk_fingerprint().to_owned(),got:None}),
+        _ => {}
+    }
+    let proof=body.proof.try_to_proof().ok_or(ApiError::InvalidInput{field:"proof",reason:"not a valid BN254 proof"})?;
+    let scalar=|field,s:&str|normalize_public_input(s).map_err(|_|ApiError::InvalidInput{field,reason:"not a scalar field element"});
+    let master=scalar("master_commitment",&body.master_commitment)?;
+    let child=scalar("child_commitment",&body.child_commitment)?;
+    // Before the pairing: the tree's leaves are public anyway.
+    if !state.tree.read().expect("commitment tree poisoned").contains(master) { return Err(ApiError::UnknownCommitment); }
+    if state.revocations.is_revoked(master) { return Err(ApiError::Revoked); }
+    let public=[master,app_id(&body.application),child];
+    // The pairing is CPU-bound; keep it off the async worker.
+    let (worker_state,circuit_id)=(state.clone(),body.circuit_id.clone());
+    let valid=web::block(move||{
+        worker_state.mock&&mock::verify_public(&public,&proof) || prover::verify_inputs(&worker_state.circuits[&circuit_id],&public,&proof)
+    }).await.map_err(|_|ApiError::Internal)?;
+    Ok(DescentVerifyResponse{valid,circuit_version:key.vk_fingerprint().to_owned()})
+}
@@ -0,0 +1,382 @@
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::crypto::poseidon_hash;
+use crate::handlers::RegisterRequest;
+
+//--------------------------------------------------------------------
+// Attribute encoding
+//--------------------------------------------------------------------
+// user_hash  = Poseidon(email, name, age, country, dob)
+//              Poseidon(email, name, age, country, dob, phone) with a phone
+//              Poseidon(email, name, age, country, dob, phone | 0, biometric)
+//                with a biometric template hash
+// secret     = Poseidon(user_hash, nonce)
+// commitment = Poseidon(secret)
+//
+// These are the functions `/register` itself uses, so a client that calls
+// them (or ports them exactly) gets the server's commitment bit for bit.
+// Email and name are normalised by `normalize_email` and `normalize_name`,
+// then Keccak digests by default; with `AttributeEncoding::HashToField`
+// they go through RFC 9380 instead.
+
+/// RFC 9380 domain separation tag for the email attribute.
+pub const DST_EMAIL: &[u8] = b"ZK-AUTH-V1-EMAIL_XMD:SHA-256_BN254-FR";
+/// RFC 9380 domain separation tag for the name attribute.
+pub const DST_NAME: &[u8] = b"ZK-AUTH-V1-NAME_XMD:SHA-256_BN254-FR";
+/// RFC 9380 domain separation tag for the phone attribute.
+pub const DST_PHONE: &[u8] = b"ZK-AUTH-V1-PHONE_XMD:SHA-256_BN254-FR";
+
+/// How the string attributes (email and name) become field elements.  The
+/// numeric attributes are exact either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttributeEncoding {
+    /// Keccak-256, big-endian, reduced mod p.
+    #[default]
+    Keccak,
+    /// RFC 9380 `hash_to_field` with `expand_message_xmd` over SHA-256,
+    /// `k = 128` (48 bytes per element), one element, and the per-attribute
+    /// tags [`DST_EMAIL`] and [`DST_NAME`].  Reproducible from the RFC alone.
+    HashToField,
+}
+
+/// Case mapping applied after NFKC normalisation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaseFold {
+    /// Case is significant.
+    #[default]
+    Keep,
+    /// Unicode lowercasing (`str::to_lowercase`).
+    Lower,
+    /// Unicode full default case folding, so `"STRASSE"` and `"straße"` agree.
+    Fold,
+}
+
+/// Which `+tag` suffixes are dropped from the local part.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlusAddressing {
+    #[default]
+    Keep,
+    /// Only at [`PLUS_ADDRESSING_DOMAINS`], where the tag is known to
+    /// reach the same mailbox.
+    KnownProviders,
+    /// At every domain.  Also merges the rare mailboxes whose name has a `+`.
+    AllDomains,
+}
+
+/// Domains whose mailboxes accept `local+anything`.
+pub const PLUS_ADDRESSING_DOMAINS: &[&str] = &[
+    "gmail.com",
+    "googlemail.com",
+    "outlook.com",
+    "hotmail.com",
+    "live.com",
+    "icloud.com",
+    "me.com",
+    "mac.com",
+    "fastmail.com",
+    "protonmail.com",
+    "proton.me",
+];
+
+/// Domains where dots in the local part are ignored.
+const GMAIL_DOMAINS: &[&str] = &["gmail.com", "googlemail.com"];
+
+/// Provider-aware rewriting of the email, so one mailbox yields one
+/// identity.  Everything is off by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EmailCanonicalization {
+    /// Convert the domain to its IDNA ASCII (punycode) form, so the Unicode
+    /// and `xn--` spellings agree.
+    pub idn_domains: bool,
+    pub plus_addressing: PlusAddressing,
+    /// Drop dots from Gmail local parts and spell `googlemail.com` as
+    /// `gmail.com`.
+    pub gmail_dots: bool,
+}
+
+/// A deployment's rules for turning email and name into field elements.
+/// Any change alters existing commitments, so fix them once per deployment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttributeRules {
+    pub encoding: AttributeEncoding,
+    pub email_case: CaseFold,
+    pub email_canonicalization: EmailCanonicalization,
+    pub name_case: CaseFold,
+}
+
+impl Default for AttributeRules {
+    fn default() -> Self {
+        Self {
+            encoding: AttributeEncoding::Keccak,
+            email_case: CaseFold::Lower,
+            email_canonicalization: EmailCanonicalization::default(),
+            name_case: CaseFold::Keep,
+        }
+    }
+}
+
+impl AttributeRules {
+    pub fn email(&self, email: &str) -> Fr {
+        let email = normalize_email(email, self);
+        match self.encoding {
+            AttributeEncoding::Keccak => keccak_fr(email.as_bytes()),
+            AttributeEncoding::HashToField => hash_to_field(email.as_bytes(), DST_EMAIL),
+        }
+    }
+
+    pub fn name(&self, name: &str) -> Fr {
+        let name = normalize_name(name, self);
+        match self.encoding {
+            AttributeEncoding::Keccak => keccak_fr(name.as_bytes()),
+            AttributeEncoding::HashToField => hash_to_field(name.as_bytes(), DST_NAME),
+        }
+    }
+
+    /// The E.164 form of `phone` (see [`normalize_phone`]) hashed like the
+    /// email; a number that does not parse is hashed trimmed, as given.
+    pub fn phone(&self, phone: &str, country: &str) -> Fr {
+        let phone = normalize_phone(phone, country).unwrap_or_else(|| phone.trim().to_owned());
+        match self.encoding {
+            AttributeEncoding::Keccak => keccak_fr(phone.as_bytes()),
+            AttributeEncoding::HashToField => hash_to_field(phone.as_bytes(), DST_PHONE),
+        }
+    }
+}
+
+/// The email as it is hashed: NFKC, then the case mapping, then NFKC
+/// again (case mapping can denormalise), then canonicalisation.  Not trimmed.
+pub fn normalize_email(email: &str, rules: &AttributeRules) -> String {
+    canonicalize_email(normalize_text(email, rules.email_case), &rules.email_canonicalization)
+}
+
+/// The name as it is hashed: NFKC, surrounding whitespace trimmed, then
+/// the case mapping and NFKC again.  NFKC first, so that composed and
+/// decomposed `"José"` and full-width spaces all agree.
+pub fn normalize_name(name: &str, rules: &AttributeRules) -> String {
+    normalize_text(name.nfkc().collect::<String>().trim(), rules.name_case)
+}
+
+/// The number in E.164 (`+14155552671`), or `None` if it is not a valid
+/// number.  Numbers without a `+` prefix are read in the region the
+/// `country` attribute names, so `"(415) 555-2671"` with `"US"` and
+/// `"+1 415 555 2671"` agree.
+pub fn normalize_phone(phone: &str, country: &str) -> Option<String> {
+    let region = country.trim().to_ascii_uppercase().parse::<phonenumber::country::Id>().ok();
+    let number = phonenumber::parse(region, phone).ok()?;
+    phonenumber::is_valid(&number).then(|| number.format().mode(phonenumber::Mode::E164).to_string())
+}
+
+/// Split at the last `@`; text without one is left alone, as is a domain
+/// IDNA rejects.  Providers are matched case-insensitively.
+fn canonicalize_email(email: String, rules: &EmailCanonicalization) -> String {
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return email;
+    };
+    let mut domain = domain.to_owned();
+    if rules.idn_domains {
+        if let Ok(ascii) = idna::domain_to_ascii(&domain) {
+            domain = ascii;
+        }
+    }
+    let provider = domain.to_ascii_lowercase();
+    let mut local = local;
+    let strip_plus = match rules.plus_addressing {
+        PlusAddressing::Keep => false,
+        PlusAddressing::KnownProviders => PLUS_ADDRESSING_DOMAINS.contains(&provider.as_str()),
+        PlusAddressing::AllDomains => true,
+    };
+    if strip_plus {
+        local = local.split_once('+').map_or(local, |(base, _)| base);
+    }
+    if rules.gmail_dots && GMAIL_DOMAINS.contains(&provider.as_str()) {
+        return format!("{}@gmail.com", local.replace('.', ""));
+    }
+    format!("{local}@{domain}")
+}
+
+fn normalize_text(text: &str, case: CaseFold) -> String {
+    let text: String = text.nfkc().collect();
+    match case {
+        CaseFold::Keep => text,
+        CaseFold::Lower => text.to_lowercase().nfkc().collect(),
+        CaseFold::Fold => caseless::default_case_fold_str(&text).nfkc().collect(),
+    }
+}
+
+/// Keccak-256 of [`normalize_email`] with lowercasing, big-endian and
+/// reduced mod p: the default rules.
+pub fn encode_email(email: &str) -> Fr {
+    AttributeRules::default().email(email)
+}
+
+/// Keccak-256 of [`normalize_name`] with case kept, big-endian and
+/// reduced mod p: the default rules.
+pub fn encode_name(name: &str) -> Fr {
+    AttributeRules::default().name(name)
+}
+
+pub fn encode_age(age: u32) -> Fr {
+    Fr::from(age as u64)
+}
+
+/// The first two bytes of the code as a big-endian `u16`, missing bytes
+/// zero: `"US"` is `0x5553`.  The code is not case-folded.
+pub fn country_u16(code: &str) -> u16 {
+    let b = code.as_bytes();
+    ((b.first().copied().unwrap_or(0) as u16) << 8) | (b.get(1).copied().unwrap_or(0) as u16)
+}
+
+pub fn encode_country(code: &str) -> Fr {
+    Fr::from(country_u16(code) as u64)
+}
+
+/// The date with its dashes removed, read as a decimal integer:
+/// `"1994-01-15"` is `19940115`.  Anything that does not parse encodes as 0.
+pub fn encode_dob(dob: &str) -> Fr {
+    Fr::from(dob.replace('-', "").parse::<u64>().unwrap_or(0))
+}
+
+/// A biometric template hash: exactly 32 bytes of hex, `0x` optional, read
+/// big-endian and reduced mod p.  The template itself never leaves the
+/// client; only this digest is committed to.
+pub fn encode_biometric(hash: &str) -> Option<Fr> {
+    let hash = hash.trim();
+    let bytes = hex::decode(hash.strip_prefix("0x").unwrap_or(hash)).ok().filter(|b| b.len() == 32)?;
+    Some(Fr::from_be_bytes_mod_order(&bytes))
+}
+
+/// Poseidon digest of the encoded attributes, in the order above, under
+/// the default [`AttributeRules`].  Without a phone the digest is what it
+/// was before the phone attribute existed.
+pub fn derive_user_hash(attributes: &RegisterRequest) -> Fr {
+    derive_user_hash_with(attributes, &AttributeRules::default())
+}
+
+/// [`derive_user_hash`] with email and name encoded by `rules`.
+pub fn derive_user_hash_with(attributes: &RegisterRequest, rules: &AttributeRules) -> Fr {
+    poseidon_hash(&encode_attributes_with(attributes, rules))
+}
+
+/// The encoded attributes [`derive_user_hash_with`] hashes, in order: five,
+/// six with a phone, seven with a biometric hash.
+pub fn encode_attributes_with(attributes: &RegisterRequest, rules: &AttributeRules) -> Vec<Fr> {
+    let mut inputs = vec![
+        rules.email(&attributes.email),
+        rules.name(&attributes.name),
+        encode_age(attributes.age),
+        encode_country(&attributes.country),
+        encode_dob(&attributes.dob),
+    ];
+    let phone = attributes.phone.as_ref().map(|phone| rules.phone(phone, &attributes.country));
+    // The handlers reject a malformed biometric hash before it gets here.
+    match attributes.biometric_hash.as_deref().and_then(encode_biometric) {
+        Some(biometric) => inputs.extend([phone.unwrap_or_default(), biometric]),
+        None => inputs.extend(phone),
+    }
+    inputs
+}
+
+/// `Poseidon(user_hash, nonce)`, the nonce read as a big-endian integer.
+pub fn derive_secret(user_hash: &Fr, nonce: &[u8; 16]) -> Fr {
+    poseidon_hash(&[*user_hash, Fr::from_be_bytes_mod_order(nonce)])
+}
+
+/// The commitment `/register` returns for `attributes` and `nonce`.
+pub fn derive_commitment(attributes: &RegisterRequest, nonce: &[u8; 16]) -> Fr {
+    poseidon_hash(&[derive_secret(&derive_user_hash(attributes), nonce)])
+}
+
+fn keccak_fr(bytes: &[u8]) -> Fr {
+    Fr::from_be_bytes_mod_order(&Keccak256::digest(bytes))
+}
+
+/// RFC 9380 §5.2 `hash_to_field` for one BN254 scalar: 48 uniform bytes
+/// from `expand_message_xmd`, read big-endian and reduced mod r.
+pub fn hash_to_field(msg: &[u8], dst: &[u8]) -> Fr {
+    Fr::from_be_bytes_mod_order(&expand_message_xmd::<48>(msg, dst))
+}
+
+/// RFC 9380 §5.3.1 with SHA-256; `hash_to_field` takes `LEN = 48`.
+fn expand_message_xmd<const LEN: usize>(msg: &[u8], dst: &[u8]) -> [u8; LEN] {
+    let ell = LEN.div_ceil(32);
+    assert!(ell <= 255 && LEN <= u16::MAX as usize, "expand_message_xmd output too long");
+    assert!(dst.len() <= 255, "domain separation tag over 255 bytes");
+    let dst_prime = [dst, &[dst.len() as u8]].concat();
+    let b0 = Sha256::new()
+        .chain_update([0u8; 64])
+        .chain_update(msg)
+        .chain_update((LEN as u16).to_be_bytes())
+        .chain_update([0u8])
+        .chain_update(&dst_prime)
+        .finalize();
+    let mut out = [0u8; LEN];
+    let mut b = Sha256::new().chain_update(b0).chain_update([1u8]).chain_update(&dst_prime).finalize();
+    for (i, chunk) in out.chunks_mut(32).enumerate() {
+        if i > 0 {
+            let mixed: Vec<u8> = b0.iter().zip(&b).map(|(x, y)| x ^ y).collect();
+            b = Sha256::new().chain_update(mixed).chain_update([i as u8 + 1]).chain_update(&dst_prime).finalize();
+        }
+        chunk.copy_from_slice(&b[..chunk.len()]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const QUUX_DST: &[u8] = b"QUUX-V01-CS02-with-expander-SHA256-128";
+
+    fn messages() -> [Vec<u8>; 5] {
+        [b"".to_vec(), b"abc".to_vec(), b"abcdef0123456789".to_vec(), [&b"q128_"[..], &[b'q'; 128]].concat(), [&b"a512_"[..], &[b'a'; 512]].concat()]
+    }
+
+    /// RFC 9380 Appendix K.1, `len_in_bytes = 0x20`.
+    #[test]
+    fn expands_the_rfc_vectors_to_32_bytes() {
+        let expected = [
+            "68a985b87eb6b46952128911f2a4412bbc302a9d759667f87f7a21d803f07235",
+            "d8ccab23b5985ccea865c6c97b6e5b8350e794e603b4b97902f53a8a0d605615",
+            "eff31487c770a893cfb36f912fbfcbff40d5661771ca4b2cb4eafe524333f5c1",
+            "b23a1d2b4d97b2ef7785562a7e8bac7eed54ed6e97e29aa51bfe3f12ddad1ff9",
+            "4623227bcc01293b8c130bf771da8c298dede7383243dc0993d2d94823958c4c",
+        ];
+        for (msg, uniform) in messages().iter().zip(expected) {
+            assert_eq!(hex::encode(expand_message_xmd::<32>(msg, QUUX_DST)), uniform);
+        }
+    }
+
+    /// RFC 9380 Appendix K.1, `len_in_bytes = 0x80`.
+    #[test]
+    fn expands_the_rfc_vectors_to_128_bytes() {
+        let expected = [
+            "af84c27ccfd45d41914fdff5df25293e221afc53d8ad2ac06d5e3e29485dadbee0d121587713a3e0dd4d5e69e93eb7cd4f5df4cd103e188cf60cb02edc3edf18eda8576c412b18ffb658e3dd6ec849469b979d444cf7b26911a08e63cf31f9dcc541708d3491184472c2c29bb749d4286b004ceb5ee6b9a7fa5b646c993f0ced",
+            "abba86a6129e366fc877aab32fc4ffc70120d8996c88aee2fe4b32d6c7b6437a647e6c3163d40b76a73cf6a5674ef1d890f95b664ee0afa5359a5c4e07985635bbecbac65d747d3d2da7ec2b8221b17b0ca9dc8a1ac1c07ea6a1e60583e2cb00058e77b7b72a298425cd1b941ad4ec65e8afc50303a22c0f99b0509b4c895f40",
+            "ef904a29bffc4cf9ee82832451c946ac3c8f8058ae97d8d629831a74c6572bd9ebd0df635cd1f208e2038e760c4994984ce73f0d55ea9f22af83ba4734569d4bc95e18350f740c07eef653cbb9f87910d833751825f0ebefa1abe5420bb52be14cf489b37fe1a72f7de2d10be453b2c9d9eb20c7e3f6edc5a60629178d9478df",
+            "80be107d0884f0d881bb460322f0443d38bd222db8bd0b0a5312a6fedb49c1bbd88fd75d8b9a09486c60123dfa1d73c1cc3169761b17476d3c6b7cbbd727acd0e2c942f4dd96ae3da5de368d26b32286e32de7e5a8cb2949f866a0b80c58116b29fa7fabb3ea7d520ee603e0c25bcaf0b9a5e92ec6a1fe4e0391d1cdbce8c68a",
+            "546aff5444b5b79aa6148bd81728704c32decb73a3ba76e9e75885cad9def1d06d6792f8a7d12794e90efed817d96920d728896a4510864370c207f99bd4a608ea121700ef01ed879745ee3e4ceef777eda6d9e5e38b90c86ea6fb0b36504ba4a45d22e86f6db5dd43d98a294bebb9125d5b794e9d2a81181066eb954966a487",
+        ];
+        for (msg, uniform) in messages().iter().zip(expected) {
+            assert_eq!(hex::encode(expand_message_xmd::<128>(msg, QUUX_DST)), uniform);
+        }
+    }
+
+    /// Pinned so a client porting [`AttributeEncoding::HashToField`] can
+    /// check itself, and so a changed tag cannot slip through.
+    #[test]
+    fn hashes_each_attribute_to_a_pinned_element() {
+        let cases = [
+            (DST_EMAIL, "alice@example.com", "5321467451799219607270558596705310239006539038791218604162329631134199966100"),
+            (DST_NAME, "Alice Smith", "9009335821031012380873249296601231633407715730157101920789871981955620535967"),
+            (DST_PHONE, "+14155550100", "3287634605752267344439443423690370942700835417944262313593485742158657397864"),
+        ];
+        for (dst, msg, expected) in cases {
+            assert_eq!(hash_to_field(msg.as_bytes(), dst), Fr::from_str(expected).unwrap(), "{}", String::from_utf8_lossy(dst));
+        }
+    }
+}
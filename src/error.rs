@@ -0,0 +1,164 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+
+use crate::circuits::WitnessDiagnostics;
+
+//--------------------------------------------------------------------
+// API errors
+//--------------------------------------------------------------------
+/// Errors returned to clients as `{"error": <code>, "message": <text>}`.
+///
+/// Messages must never include secrets or witness values; see
+/// [`crate::redact`].
+#[derive(Debug)]
+pub enum ApiError {
+    /// The proof was generated against a different verifying key than the
+    /// one this server holds, or the request omitted the version while the
+    /// server requires it.
+    CircuitMismatch { expected: String, got: Option<String> },
+    /// A request field could not be parsed.  `reason` must not echo the value.
+    InvalidInput { field: &'static str, reason: &'static str },
+    /// Missing or wrong admin bearer token.
+    AdminUnauthorized,
+    /// The service cannot handle the request right now; clients may retry.
+    Unavailable(&'static str),
+    /// Over the admission rate; retry after the given number of seconds.
+    RateLimited { retry_after_secs: u64 },
+    /// The proof inputs admit no witness; the body carries the diagnostics.
+    WitnessFailed(Box<WitnessDiagnostics>),
+    /// This node's circuit files and proving key do not belong together; it
+    /// cannot prove until an operator fixes them.  Clients may use another node.
+    ArtifactMismatch,
+    /// A bug on the server; the request may well have been fine.
+    Internal,
+    /// Proving was cancelled at the request's deadline.
+    DeadlineExceeded,
+    /// The requested resource does not exist.
+    NotFound(&'static str),
+    /// The proof's commitment was never registered here.
+    UnknownCommitment,
+    /// A group membership proof names a root the commitment tree never had,
+    /// or had only before a commitment was revoked.
+    UnknownRoot,
+    /// A proof token that is unknown, expired or already redeemed.
+    InvalidToken,
+    /// The proof's nullifier was spent by an earlier verification.
+    AlreadyUsed,
+    /// A login challenge that this server never issued, that expired or
+    /// that an earlier proof used.
+    InvalidChallenge,
+    /// The proof's commitment was revoked by an operator.
+    Revoked,
+    /// The claim block of a claims proof fails this server's policy; the
+    /// reason names the claim.
+    InvalidClaims(&'static str),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    pub error: &'static str,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct WitnessErrorBody<'a> {
+    #[serde(flatten)]
+    body: ErrorBody,
+    #[serde(flatten)]
+    diagnostics: &'a WitnessDiagnostics,
+}
+
+impl ApiError {
+    /// Stable, machine-readable error code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::CircuitMismatch { .. } => "circuit_mismatch",
+            ApiError::InvalidInput { .. } => "invalid_input",
+            ApiError::AdminUnauthorized => "admin_unauthorized",
+            ApiError::Unavailable(_) => "unavailable",
+            ApiError::RateLimited { .. } => "rate_limited",
+            ApiError::WitnessFailed(_) => "witness_failed",
+            ApiError::ArtifactMismatch => "artifact_mismatch",
+            ApiError::Internal => "internal",
+            ApiError::DeadlineExceeded => "deadline_exceeded",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::UnknownCommitment => "unknown_commitment",
+            ApiError::UnknownRoot => "unknown_root",
+            ApiError::InvalidToken => "invalid_token",
+            ApiError::AlreadyUsed => "already_used",
+            ApiError::InvalidChallenge => "invalid_challenge",
+            ApiError::Revoked => "revoked",
+            ApiError::InvalidClaims(_) => "invalid_claims",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::CircuitMismatch { expected, got: Some(got) } => {
+                write!(f, "proof was generated for circuit version {got}, server expects {expected}")
+            }
+            ApiError::CircuitMismatch { expected, got: None } => {
+                write!(f, "circuit_version is required; server expects {expected}")
+            }
+            ApiError::InvalidInput { field, reason } => write!(f, "{field}: {reason}"),
+            ApiError::AdminUnauthorized => f.write_str("a valid admin bearer token is required"),
+            ApiError::Unavailable(reason) => f.write_str(reason),
+            ApiError::RateLimited { retry_after_secs } => write!(f, "too many requests; retry in {retry_after_secs}s"),
+            ApiError::WitnessFailed(d) => match d.constraint {
+                Some(c) => write!(f, "inputs violate constraint {c}"),
+                None => f.write_str("witness calculation failed"),
+            },
+            ApiError::ArtifactMismatch => f.write_str("this node's circuit artefacts are misconfigured; try another node"),
+            ApiError::Internal => f.write_str("internal server error"),
+            ApiError::DeadlineExceeded => f.write_str("proving did not finish before the deadline"),
+            ApiError::NotFound(what) => f.write_str(what),
+            ApiError::UnknownCommitment => f.write_str("commitment is not registered"),
+            ApiError::UnknownRoot => f.write_str("root is not one the commitment tree has had since its last revocation"),
+            ApiError::InvalidToken => f.write_str("token is unknown, expired or already redeemed"),
+            ApiError::AlreadyUsed => f.write_str("nullifier was already used"),
+            ApiError::InvalidChallenge => f.write_str("challenge is unknown, expired or already used"),
+            ApiError::Revoked => f.write_str("commitment has been revoked"),
+            ApiError::InvalidClaims(reason) => write!(f, "claims: {reason}"),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::CircuitMismatch { .. } => StatusCode::CONFLICT,
+            ApiError::InvalidInput { .. } => StatusCode::BAD_REQUEST,
+            ApiError::AdminUnauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::WitnessFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::ArtifactMismatch => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::UnknownCommitment => StatusCode::FORBIDDEN,
+            ApiError::UnknownRoot => StatusCode::FORBIDDEN,
+            ApiError::InvalidToken => StatusCode::UNAUTHORIZED,
+            ApiError::AlreadyUsed => StatusCode::CONFLICT,
+            ApiError::InvalidChallenge => StatusCode::UNAUTHORIZED,
+            ApiError::Revoked => StatusCode::FORBIDDEN,
+            ApiError::InvalidClaims(_) => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status_code());
+        if let ApiError::RateLimited { retry_after_secs } = self {
+            res.insert_header((header::RETRY_AFTER, retry_after_secs.to_string()));
+        }
+        let body = ErrorBody { error: self.code(), message: self.to_string() };
+        match self {
+            ApiError::WitnessFailed(diagnostics) => res.json(WitnessErrorBody { body, diagnostics }),
+            _ => res.json(body),
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use actix_web::http::header::{EntityTag, Header, IfNoneMatch, ETAG};
+use actix_web::{HttpRequest, HttpResponse};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+//--------------------------------------------------------------------
+// Conditional GETs
+//--------------------------------------------------------------------
+/// `body` as JSON with an `ETag` over its bytes, or `304 Not Modified`
+/// when the request's `If-None-Match` already names it.
+///
+/// The tag is weak because `Compress` may re-encode the body; the JSON
+/// itself is the same for the same keys and tree, so clients polling for
+/// key rotation or new roots only download a change.
+pub(crate) fn json_with_etag<T: Serialize>(req: &HttpRequest, body: &T) -> HttpResponse {
+    let json = serde_json::to_vec(body).expect("response serialises to JSON");
+    let tag = EntityTag::new_weak(hex::encode(&Sha256::digest(&json)[..16]));
+    let unchanged = match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(items)) => items.iter().any(|t| t.weak_eq(&tag)),
+        Err(_) => false,
+    };
+    let mut res = if unchanged { HttpResponse::NotModified() } else { HttpResponse::Ok() };
+    res.insert_header((ETAG, tag));
+    if unchanged {
+        res.finish()
+    } else {
+        res.content_type("application/json").body(json)
+    }
+}
@@ -0,0 +1,97 @@
+use actix_web::{post, web, HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+
+use crate::config::CircuitKind;
+use crate::convert::normalize_public_input;
+use crate::derive::app_id;
+use crate::error::ApiError;
+use crate::handlers::ProofJson;
+use crate::nullifier;
+use crate::prover;
+use crate::snarkjs::public_signals;
+use crate::store::blocking;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Group membership
+//--------------------------------------------------------------------
+/// A proof that the prover's commitment is one of the registered ones,
+/// Semaphore-style, made with a circuit built from
+/// `secret-proof-membership.circom`.  The verifier learns the tree root and
+/// the application's nullifier, not which commitment proved.
+#[derive(Debug, Deserialize)]
+pub struct MembershipRequest {
+    /// The `[circuits.<id>]` entry holding the membership circuit.
+    pub circuit_id: String,
+    #[serde(default)]
+    pub circuit_version: Option<String>,
+    /// A root the commitment tree has had, decimal.
+    pub root: String,
+    pub nullifier: String,
+    pub application: String,
+    pub proof: ProofJson,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MembershipResponse {
+    pub valid: bool,
+    pub root: String,
+    pub nullifier: String,
+    pub circuit_version: String,
+}
+
+/// `200` for a valid proof and `401` with `valid: false` for an invalid
+/// one, like `/verify-proof`.  A valid proof spends its nullifier.
+#[post("/verify-membership")]
+async fn verify_membership(state: web::Data<AppState>, body: web::Json<MembershipRequest>) -> HttpResponse {
+    match check_membership(&state, &body).await {
+        Ok(verdict) if verdict.valid => HttpResponse::Ok().json(verdict),
+        Ok(verdict) => HttpResponse::Unauthorized().json(verdict),
+        Err(e) => e.error_response(),
+    }
+}
+
+async fn check_membership(state: &web::Data<AppState>, body: &MembershipRequest) -> Result<MembershipResponse, ApiError> {
+    let _permit = state.limits.verify.acquire().await;
+    let key = state
+        .circuits
+        .get(&body.circuit_id)
+        .ok_or(ApiError::InvalidInput { field: "circuit_id", reason: "no such circuit on this server" })?;
+    // The nullifier circuit has the same number of public inputs, so the
+    // configured kind decides, not the key.
+    if key.kind() != CircuitKind::Membership {
+        return Err(ApiError::InvalidInput { field: "circuit_id", reason: "not a membership circuit" });
+    }
+    match &body.circuit_version {
+        Some(v) if !v.eq_ignore_ascii_case(key.vk_fingerprint()) => {
+            return Err(ApiError::CircuitMismatch { expected: key.vk_fingerprint().to_owned(), got: Some(v.clone()) });
+        }
+        None if state.config.require_circuit_version => {
+            return Err(ApiError::CircuitMismatch { expected: key.vk_fingerprint().to_owned(), got: None });
+        }
+        _ => {}
+    }
+    let proof = body.proof.try_to_proof().ok_or(ApiError::InvalidInput { field: "proof", reason: "not a valid BN254 proof" })?;
+    let root = normalize_public_input(&body.root).map_err(|_| ApiError::InvalidInput { field: "root", reason: "not a scalar field element" })?;
+    let nullifier =
+        normalize_public_input(&body.nullifier).map_err(|_| ApiError::InvalidInput { field: "nullifier", reason: "not a scalar field element" })?;
+    let app_id = app_id(&body.application);
+    // Before the pairing, as for single commitments.
+    if !state.tree.read().expect("commitment tree poisoned").accepts_root(root) {
+        return Err(ApiError::UnknownRoot);
+    }
+    if blocking(state, move |state| nullifier::is_membership_spent(state.store.as_ref(), app_id, nullifier)).await? {
+        return Err(ApiError::AlreadyUsed);
+    }
+    // The pairing is CPU-bound; keep it off the async worker.
+    let worker_state = state.clone();
+    let circuit_id = body.circuit_id.clone();
+    let valid = web::block(move || prover::verify_inputs(&worker_state.circuits[&circuit_id], &[nullifier, root, app_id], &proof))
+        .await
+        .map_err(|_| ApiError::Internal)?;
+    if valid && !blocking(state, move |state| nullifier::spend_membership(state.store.as_ref(), app_id, nullifier)).await? {
+        return Err(ApiError::AlreadyUsed);
+    }
+    let [root, nullifier]: [String; 2] = public_signals(&[root, nullifier]).try_into().expect("two signals");
+    Ok(MembershipResponse { valid, root, nullifier, circuit_version: key.vk_fingerprint().to_owned() })
+}
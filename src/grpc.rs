@@ -0,0 +1,357 @@
+use actix_web::web;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::io;
+use std::net::SocketAddr;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tonic::codec::ProstCodec;
+use tonic::codegen::{http, Body, BoxFuture, Service, StdError};
+use tonic::server::{Grpc, NamedService, StreamingService};
+use tonic::transport::server::TcpIncoming;
+use tonic::{Status, Streaming};
+
+use crate::batch::verify_all;
+use crate::error::ApiError;
+use crate::handlers::{ProofJson, VerifyRequest, VerifyResponse};
+use crate::AppState;
+
+use self::proto::{verify_stream_response, VerifyStreamRequest, VerifyStreamResponse};
+
+//--------------------------------------------------------------------
+// gRPC verification
+//--------------------------------------------------------------------
+/// Most requests checked as one batch.
+const MAX_STREAM_BATCH: usize = 256;
+/// How long a batch waits for more requests after its first one.
+const BATCH_WINDOW: Duration = Duration::from_millis(2);
+/// Verdicts buffered per stream while the client is slow to read them.
+const VERDICT_BUFFER: usize = 2 * MAX_STREAM_BATCH;
+
+const VERIFY_STREAM_PATH: &str = "/zkauth.v1.Verifier/VerifyStream";
+
+/// Where [`serve_grpc`] listens.
+#[derive(Clone, Debug)]
+pub struct GrpcConfig {
+    pub bind: SocketAddr,
+}
+
+/// The messages of `proto/zkauth.proto`, written by hand so the build
+/// needs no `protoc`.
+pub mod proto {
+    use crate::claims;
+    use crate::handlers::VerifyResponse;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Proof {
+        #[prost(string, repeated, tag = "1")]
+        pub a: Vec<String>,
+        #[prost(string, repeated, tag = "2")]
+        pub b: Vec<String>,
+        #[prost(string, repeated, tag = "3")]
+        pub c: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ClaimBlock {
+        #[prost(string, tag = "1")]
+        pub issuer: String,
+        #[prost(string, tag = "2")]
+        pub audience: String,
+        #[prost(uint64, tag = "3")]
+        pub issued_at: u64,
+        #[prost(uint64, tag = "4")]
+        pub expires_at: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct VerifyStreamRequest {
+        #[prost(uint64, tag = "1")]
+        pub id: u64,
+        #[prost(string, tag = "2")]
+        pub commitment: String,
+        #[prost(message, optional, tag = "3")]
+        pub proof: Option<Proof>,
+        #[prost(string, optional, tag = "4")]
+        pub circuit_version: Option<String>,
+        #[prost(string, optional, tag = "5")]
+        pub circuit_id: Option<String>,
+        #[prost(string, optional, tag = "6")]
+        pub nullifier: Option<String>,
+        #[prost(string, optional, tag = "7")]
+        pub application: Option<String>,
+        #[prost(string, optional, tag = "8")]
+        pub challenge: Option<String>,
+        #[prost(message, optional, tag = "9")]
+        pub claims: Option<ClaimBlock>,
+        #[prost(string, optional, tag = "10")]
+        pub audience: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct VerifyStreamResponse {
+        #[prost(uint64, tag = "1")]
+        pub id: u64,
+        #[prost(oneof = "verify_stream_response::Result", tags = "2, 3")]
+        pub result: Option<verify_stream_response::Result>,
+    }
+
+    pub mod verify_stream_response {
+        #[derive(Clone, PartialEq, prost::Oneof)]
+        pub enum Result {
+            #[prost(message, tag = "2")]
+            Verdict(super::Verdict),
+            #[prost(message, tag = "3")]
+            Error(super::Error),
+        }
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Verdict {
+        #[prost(bool, tag = "1")]
+        pub valid: bool,
+        #[prost(string, optional, tag = "2")]
+        pub circuit_version: Option<String>,
+        #[prost(message, optional, tag = "3")]
+        pub receipt: Option<Receipt>,
+        #[prost(message, optional, tag = "4")]
+        pub session: Option<SessionToken>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Receipt {
+        #[prost(string, tag = "1")]
+        pub proof_hash: String,
+        #[prost(string, tag = "2")]
+        pub commitment: String,
+        #[prost(string, tag = "3")]
+        pub vk_fingerprint: String,
+        #[prost(uint64, tag = "4")]
+        pub timestamp: u64,
+        #[prost(uint64, tag = "5")]
+        pub latency_us: u64,
+        #[prost(bool, tag = "6")]
+        pub valid: bool,
+        #[prost(string, tag = "7")]
+        pub key_id: String,
+        #[prost(string, tag = "8")]
+        pub signature: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SessionToken {
+        #[prost(string, tag = "1")]
+        pub access_token: String,
+        #[prost(string, tag = "2")]
+        pub token_type: String,
+        #[prost(uint64, tag = "3")]
+        pub expires_in: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Error {
+        #[prost(string, tag = "1")]
+        pub error: String,
+        #[prost(string, tag = "2")]
+        pub message: String,
+    }
+
+    impl From<ClaimBlock> for claims::ClaimBlock {
+        fn from(c: ClaimBlock) -> Self {
+            Self { issuer: c.issuer, audience: c.audience, issued_at: c.issued_at, expires_at: c.expires_at }
+        }
+    }
+
+    impl From<VerifyResponse> for Verdict {
+        fn from(v: VerifyResponse) -> Self {
+            let r = v.receipt;
+            Self {
+                valid: v.valid,
+                circuit_version: v.circuit_version,
+                receipt: Some(Receipt {
+                    proof_hash: r.proof_hash,
+                    commitment: r.commitment,
+                    vk_fingerprint: r.vk_fingerprint,
+                    timestamp: r.timestamp,
+                    latency_us: r.latency_us,
+                    valid: r.valid,
+                    key_id: r.key_id,
+                    signature: r.signature,
+                }),
+                session: v.session.map(|s| SessionToken { access_token: s.access_token, token_type: s.token_type.to_owned(), expires_in: s.expires_in }),
+            }
+        }
+    }
+}
+
+/// The `zkauth.v1.Verifier` service, for embedding applications that run
+/// their own `tonic` server.
+#[derive(Clone)]
+pub struct VerifierService {
+    state: web::Data<AppState>,
+}
+
+impl VerifierService {
+    pub fn new(state: web::Data<AppState>) -> Self {
+        Self { state }
+    }
+}
+
+impl NamedService for VerifierService {
+    const NAME: &'static str = "zkauth.v1.Verifier";
+}
+
+impl<B> Service<http::Request<B>> for VerifierService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        if req.uri().path() != VERIFY_STREAM_PATH {
+            return Box::pin(async { Ok(Status::unimplemented("no such method").into_http()) });
+        }
+        let method = VerifyStream(self.state.clone());
+        Box::pin(async move { Ok(Grpc::new(ProstCodec::default()).streaming(method, req).await) })
+    }
+}
+
+struct VerifyStream(web::Data<AppState>);
+
+impl StreamingService<VerifyStreamRequest> for VerifyStream {
+    type Response = VerifyStreamResponse;
+    type ResponseStream = BoxStream<'static, Result<VerifyStreamResponse, Status>>;
+    type Future = Ready<Result<tonic::Response<Self::ResponseStream>, Status>>;
+
+    fn call(&mut self, request: tonic::Request<Streaming<VerifyStreamRequest>>) -> Self::Future {
+        let (tx, rx) = mpsc::channel(VERDICT_BUFFER);
+        tokio::spawn(verify_batches(self.0.clone(), request.into_inner(), tx));
+        let verdicts = stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|verdict| (verdict, rx)) });
+        ready(Ok(tonic::Response::new(verdicts.boxed())))
+    }
+}
+
+/// Read requests as they come and check them in batches: whatever arrives
+/// within [`BATCH_WINDOW`] of a batch's first request joins it, up to
+/// [`MAX_STREAM_BATCH`].  Verdicts go out in request order.  Ends with the
+/// client's stream, or when the client stops reading.
+async fn verify_batches(state: web::Data<AppState>, mut requests: Streaming<VerifyStreamRequest>, tx: mpsc::Sender<Result<VerifyStreamResponse, Status>>) {
+    loop {
+        let first = match requests.message().await {
+            Ok(Some(first)) => first,
+            Ok(None) => return,
+            Err(status) => {
+                let _ = tx.send(Err(status)).await;
+                return;
+            }
+        };
+        let mut batch = vec![first];
+        let window = tokio::time::sleep(BATCH_WINDOW);
+        tokio::pin!(window);
+        let mut ended = None;
+        while batch.len() < MAX_STREAM_BATCH {
+            tokio::select! {
+                _ = &mut window => break,
+                next = requests.message() => match next {
+                    Ok(Some(next)) => batch.push(next),
+                    Ok(None) => {
+                        ended = Some(Ok(()));
+                        break;
+                    }
+                    Err(status) => {
+                        ended = Some(Err(status));
+                        break;
+                    }
+                },
+            }
+        }
+        for verdict in verify_batch(&state, batch).await {
+            if tx.send(Ok(verdict)).await.is_err() {
+                return;
+            }
+        }
+        match ended {
+            None => {}
+            Some(Ok(())) => return,
+            Some(Err(status)) => {
+                let _ = tx.send(Err(status)).await;
+                return;
+            }
+        }
+    }
+}
+
+/// Check `batch` as [`verify_all`] does for a JSON `/verify-batch`.
+async fn verify_batch(state: &web::Data<AppState>, batch: Vec<VerifyStreamRequest>) -> Vec<VerifyStreamResponse> {
+    let ids: Vec<u64> = batch.iter().map(|r| r.id).collect();
+    let parsed: Vec<Result<VerifyRequest, ApiError>> = batch.into_iter().map(verify_request).collect();
+    let requests: Vec<VerifyRequest> = parsed.iter().filter_map(|r| r.as_ref().ok().cloned()).collect();
+    let results = {
+        let _permit = state.limits.verify.acquire().await;
+        let worker_state = state.clone();
+        tokio::task::spawn_blocking(move || verify_all(&worker_state, &requests)).await
+    };
+    let mut verdicts = match results {
+        Ok(results) => results.into_iter(),
+        Err(_) => Vec::new().into_iter(),
+    };
+    ids.into_iter()
+        .zip(parsed)
+        .map(|(id, parsed)| {
+            let result = parsed.and_then(|_| verdicts.next().unwrap_or(Err(ApiError::Internal)));
+            VerifyStreamResponse { id, result: Some(response(result)) }
+        })
+        .collect()
+}
+
+fn response(result: Result<VerifyResponse, ApiError>) -> verify_stream_response::Result {
+    match result {
+        Ok(verdict) => verify_stream_response::Result::Verdict(verdict.into()),
+        Err(e) => verify_stream_response::Result::Error(proto::Error { error: e.code().to_owned(), message: e.to_string() }),
+    }
+}
+
+/// The `/verify-proof` request `r` carries, if its proof has the right
+/// shape.
+fn verify_request(r: VerifyStreamRequest) -> Result<VerifyRequest, ApiError> {
+    let shape = |_| ApiError::InvalidInput { field: "proof", reason: "a and c need 2 elements, b 4" };
+    let proof = r.proof.ok_or(ApiError::InvalidInput { field: "proof", reason: "missing" })?;
+    let a: [String; 2] = proof.a.try_into().map_err(shape)?;
+    let c: [String; 2] = proof.c.try_into().map_err(shape)?;
+    let [b00, b01, b10, b11]: [String; 4] = proof.b.try_into().map_err(shape)?;
+    Ok(VerifyRequest {
+        commitment: r.commitment,
+        proof: ProofJson { a, b: [[b00, b01], [b10, b11]], c },
+        circuit_version: r.circuit_version,
+        circuit_id: r.circuit_id,
+        nullifier: r.nullifier,
+        application: r.application,
+        challenge: r.challenge,
+        claims: r.claims.map(Into::into),
+        audience: r.audience,
+    })
+}
+
+/// Bind `config.bind` and return the server to spawn.  Binding happens
+/// here, so an address in use is reported before serving starts.  Call it
+/// within a Tokio runtime; [`crate::run`] does this when
+/// `AppConfig::grpc` is set.
+pub fn serve_grpc(state: web::Data<AppState>, config: &GrpcConfig) -> io::Result<impl std::future::Future<Output = ()>> {
+    let incoming = TcpIncoming::new(config.bind, true, None).map_err(io::Error::other)?;
+    let bind = config.bind;
+    Ok(async move {
+        let server = tonic::transport::Server::builder().add_service(VerifierService::new(state));
+        if let Err(error) = server.serve_with_incoming(incoming).await {
+            tracing::error!(%bind, %error, "gRPC server stopped");
+        }
+    })
+}
@@ -0,0 +1,885 @@
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{get, post, routes, web, HttpRequest, HttpResponse, Responder, ResponseError};
+use ark_bn254::{Bn254, Fr};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::Proof;
+use ark_serialize::CanonicalSerialize;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hex::encode as hex_encode;
+use num_bigint::BigUint;
+use rand::RngCore;
+use rand::thread_rng;
+#[cfg(feature = "deterministic")]
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::artifacts::{Artifacts, CIRCUIT_ID};
+use crate::cancel::run_proving;
+use crate::challenge;
+use crate::claims::ClaimBlock;
+use crate::config::CircuitKind;
+use crate::convert::normalize_public_input;
+use crate::derive::{app_id, commitment_for_secrets, MAX_SECRETS};
+use crate::encoding::{derive_secret, derive_user_hash_with, encode_attributes_with, encode_biometric, normalize_phone, AttributeEncoding, AttributeRules};
+use crate::error::ApiError;
+use crate::etag::json_with_etag;
+use crate::ipfs::IpfsVerifyRequest;
+use crate::mock;
+use crate::nullifier;
+use crate::prewarm::cached_proof;
+use crate::proof_store::{store_response, ProofStatement};
+use crate::prover;
+use crate::receipt::Receipt;
+use crate::redact::Redacted;
+use crate::vrf::NonceProof;
+use crate::crypto::{g1_to_hex, g2_to_hex, poseidon_hash};
+use crate::session::{with_session, SessionToken};
+use crate::snarkjs::{public_signals, SnarkjsProof, SnarkjsVerificationKey};
+use crate::store::blocking;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// DTOs
+//--------------------------------------------------------------------
+/// `phone` is optional; national numbers are read in the `country` region and normalised to E.164.
+/// `biometric_hash` is an optional 32-byte hex digest of a biometric template, computed client-side.
+#[derive(Deserialize)]  pub struct RegisterRequest { pub email:String, pub name:String, pub age:u32, pub country:String, pub dob:String,
+                                                            #[serde(default)] pub phone:Option<String>,
+                                                            #[serde(default)] pub biometric_hash:Option<String> }
+/// `/register/blind` body: `email_hash` is 0x Keccak-256 of the lowercased email, `name_hash` of the trimmed
+/// name (or, under RFC 9380 attribute encoding, their `hash_to_field` scalars); `age`, `country` (the two code letters as a big-endian u16) and `dob` (`YYYYMMDD`) are decimal or 0x-hex field elements.
+/// `phone_hash`, if any, is encoded like `email_hash` from the E.164 number; `biometric_hash` is sent as for `/register`.
+#[derive(Deserialize)]  pub struct BlindRegisterRequest { pub email_hash:String, pub name_hash:String, pub age:String, pub country:String, pub dob:String,
+                                                            #[serde(default)] pub phone_hash:Option<String>,
+                                                            #[serde(default)] pub biometric_hash:Option<String> }
+/// `/register/multi` body: the secrets one commitment binds, in order, such as the user secret `/register` returned
+/// and a device secret, each 0x-hex of at most 32 bytes.
+#[derive(Deserialize)]  pub struct MultiRegisterRequest { pub secrets_hex:Vec<String> }
+#[derive(Debug,Serialize)]    pub struct MultiRegisterResponse { pub commitment:String, pub leaf_index:u64 }
+/// `proof=true` also proves the commitment was derived from the submitted attributes; see [`RegistrationProof`].
+#[derive(Debug,Default,Deserialize)]  pub struct RegisterQuery { #[serde(default)] pub proof:bool }
+/// `credential` is a JWT-encoded W3C Verifiable Credential for the commitment, when the server issues them.
+/// `registration_proof` is set when `proof=true` was asked for.
+#[derive(Serialize)]    pub struct RegisterResponse { pub secret:String, pub nonce:String, pub commitment:String, pub leaf_index:u64,
+                                                            #[serde(skip_serializing_if="Option::is_none")] pub nonce_proof:Option<NonceProof>,
+                                                            #[serde(skip_serializing_if="Option::is_none")] pub credential:Option<String>,
+                                                            #[serde(skip_serializing_if="Option::is_none")] pub registration_proof:Option<RegistrationProof> }
+/// A proof, from the registration circuit `circuit_id`, that `commitment` is the Poseidon chain of the submitted attributes
+/// and the nonce.  Its public signals are `user_hash` (decimal) and the commitment; check it against the key
+/// `/verification-key?circuit_id=` serves.
+#[derive(Clone,Debug,Serialize)] pub struct RegistrationProof { pub proof:ProofJson, pub user_hash:String, pub circuit_id:String, pub circuit_version:String }
+/// `secret_hex` is required except by `multi` circuits, which take all their secrets, in order, as `secrets_hex`.
+#[derive(Clone,Serialize,Deserialize)]  pub struct ProofRequest {
+    #[serde(default)] pub secret_hex:String, pub commitment:String,
+    #[serde(default,skip_serializing_if="Vec::is_empty")] pub secrets_hex:Vec<String>,
+    /// 32-byte hex seed for the proof randomness; same inputs and seed give the same proof.
+    #[cfg(feature="deterministic")] #[serde(default)] pub seed:Option<String>,
+    /// Give up after this many milliseconds, queueing included; the server's own deadline still applies.
+    #[serde(default)] pub deadline_ms:Option<u64>,
+    /// A circuit registered in `AppConfig::circuits`; the bundled circuit when absent.
+    #[serde(default)] pub circuit_id:Option<String>,
+}
+/// `debug=true` returns the computed witness when witness generation fails; `witness-debug` builds only.
+#[derive(Debug,Deserialize)]  pub struct ProveQuery { #[serde(default)] pub debug:bool }
+#[derive(Clone,Debug,Serialize,Deserialize)] pub struct ProofJson { pub a:[String;2], pub b:[[String;2];2], pub c:[String;2] }
+/// `circuit_version` is the SHA-256 fingerprint of the verifying key the proof was made for;
+/// `proof_hash` is set when the proof store kept the proof, which `/proofs/{hash}` then serves.
+#[derive(Clone,Debug,Serialize,Deserialize)] pub struct ProofResponse { pub proof:ProofJson, pub circuit_version:String,
+                                                            #[serde(skip_serializing_if="Option::is_none")] pub proof_hash:Option<String> }
+/// `circuit_id` names a registered circuit, as for [`ProofRequest`].  `nullifier` (decimal) and `application` are
+/// required by `nullifier` circuits, `challenge` (decimal, from `/challenge`) by `challenge` circuits, `claims` by
+/// `claims` circuits and `audience` by `audience` circuits; other circuits reject them.
+#[derive(Clone,Debug,Deserialize)]  pub struct VerifyRequest { pub commitment:String, pub proof:ProofJson, #[serde(default)] pub circuit_version:Option<String>,
+                                                            #[serde(default)] pub circuit_id:Option<String>,
+                                                            #[serde(default)] pub nullifier:Option<String>, #[serde(default)] pub application:Option<String>,
+                                                            #[serde(default)] pub challenge:Option<String>, #[serde(default)] pub claims:Option<ClaimBlock>,
+                                                            #[serde(default)] pub audience:Option<String> }
+/// `format=snarkjs` returns the proof as snarkjs's `proof.json` with its `publicSignals`;
+/// `format=compressed` as base64 of the 128-byte arkworks compressed encoding.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq,Deserialize)] #[serde(rename_all="lowercase")] pub enum ResponseFormat { #[default] Hex, Snarkjs, Compressed }
+#[derive(Debug,Deserialize)]  pub struct VerificationKeyQuery { #[serde(default)] pub circuit_id:Option<String>, #[serde(default)] pub circuit_version:Option<String> }
+#[derive(Debug,Deserialize)]  pub struct FormatQuery { #[serde(default)] pub format:ResponseFormat }
+#[derive(Debug,Serialize)]    pub struct SnarkjsProofResponse { pub proof:SnarkjsProof, #[serde(rename="publicSignals")] pub public_signals:Vec<String>, pub circuit_version:String,
+                                                            #[serde(skip_serializing_if="Option::is_none")] pub proof_hash:Option<String> }
+/// A `/verify-proof` body holding snarkjs's `proof.json` and `public.json`, with the fields of [`VerifyRequest`] that
+/// scope the proof; see [`SnarkjsVerifyRequest::to_verify_request`] for how they meet the public signals.
+#[derive(Debug,Deserialize)]  pub struct SnarkjsVerifyRequest { pub proof:SnarkjsProof, #[serde(rename="publicSignals")] pub public_signals:Vec<String>,
+                                                            #[serde(default)] pub circuit_version:Option<String>,
+                                                            #[serde(default)] pub circuit_id:Option<String>,
+                                                            #[serde(default)] pub nullifier:Option<String>, #[serde(default)] pub application:Option<String>,
+                                                            #[serde(default)] pub challenge:Option<String>, #[serde(default)] pub claims:Option<ClaimBlock>,
+                                                            #[serde(default)] pub audience:Option<String> }
+#[derive(Debug,Serialize)]    pub struct CompressedProofResponse { pub proof:String, pub circuit_version:String,
+                                                            #[serde(skip_serializing_if="Option::is_none")] pub proof_hash:Option<String> }
+/// A `/verify-proof` body whose `proof` is base64 of the arkworks compressed encoding, as `format=compressed` returns it.
+/// The other fields are those of [`VerifyRequest`].
+#[derive(Debug,Deserialize)]  pub struct CompressedVerifyRequest { pub commitment:String, pub proof:String,
+                                                            #[serde(default)] pub circuit_version:Option<String>,
+                                                            #[serde(default)] pub circuit_id:Option<String>,
+                                                            #[serde(default)] pub nullifier:Option<String>, #[serde(default)] pub application:Option<String>,
+                                                            #[serde(default)] pub challenge:Option<String>, #[serde(default)] pub claims:Option<ClaimBlock>,
+                                                            #[serde(default)] pub audience:Option<String> }
+/// `circuit_version` is the fingerprint of the key that accepted the proof.
+/// `/verify-proof` accepts an inline proof, in our hex, snarkjs's or the compressed form, or IPFS CIDs.
+#[derive(Debug,Deserialize)]  #[serde(untagged)] pub enum VerifyBody { Inline(VerifyRequest), Snarkjs(SnarkjsVerifyRequest), Compressed(CompressedVerifyRequest),
+                                                                           Ipfs(IpfsVerifyRequest) }
+/// `session` is set on valid answers from `/verify-proof` when session tokens are enabled and the proof spent a nullifier
+/// or a challenge, so it cannot be replayed for another session.
+#[derive(Debug,Serialize)]    pub struct VerifyResponse { pub valid:bool, #[serde(skip_serializing_if="Option::is_none")] pub circuit_version:Option<String>, pub receipt:Receipt,
+                                                            #[serde(skip_serializing_if="Option::is_none")] pub session:Option<SessionToken>,
+                                                            #[serde(skip)] pub(crate) single_use:bool }
+#[derive(Debug,Serialize)]    pub struct VkFingerprintResponse { pub circuit_id:&'static str, pub algorithm:&'static str, pub vk_fingerprint:String,
+                                                            #[serde(skip_serializing_if="Option::is_none")] pub rotation:Option<RotationInfo>,
+                                                            #[serde(skip_serializing_if="Vec::is_empty")] pub historical:Vec<String> }
+/// A scheduled key change: `from` and `to` are vk fingerprints, `cutover` is Unix seconds.
+#[derive(Debug,Serialize)]    pub struct RotationInfo { pub from:String, pub to:String, pub cutover:u64, pub overlap_secs:u64 }
+
+/// Header carrying the verifying-key fingerprint on every verify response.
+pub const VK_HEADER: HeaderName = HeaderName::from_static("x-zk-vk");
+
+impl ProofJson {
+    /// Hex affine coordinates; G2 limbs are emitted `[c1, c0]` (EVM order).
+    pub fn from_proof(proof:&Proof<Bn254>)->Self{
+        let (a_x, a_y)               = g1_to_hex(&proof.a);
+        let (b_x1, b_x0, b_y1, b_y0) = g2_to_hex(&proof.b);
+        let (c_x, c_y)               = g1_to_hex(&proof.c);
+        ProofJson { a: [a_x, a_y], b: [[b_x1, b_x0], [b_y1, b_y0]], c: [c_x, c_y] }
+    }
+
+    /// `None` for malformed hex or points off the curve; requests map
+    /// that to `invalid_input` rather than a panic.
+    pub fn try_to_proof(&self)->Option<Proof<Bn254>>{
+        let (a,b,c)=(&self.a,&self.b,&self.c);
+        zk_auth_verify_core::proof_from_evm_hex([&a[0],&a[1]],[[&b[0][0],&b[0][1]],[&b[1][0],&b[1][1]]],[&c[0],&c[1]]).ok()
+    }
+
+    /// Base64 of the arkworks compressed encoding: 172 characters instead of
+    /// the roughly 800 bytes of hex JSON.
+    pub fn to_compressed_base64(&self)->Option<String>{
+        let mut bytes=Vec::new();
+        self.try_to_proof()?.serialize_compressed(&mut bytes).expect("proof serialises into a Vec");
+        Some(BASE64.encode(bytes))
+    }
+
+    /// `None` unless `s` is standard base64 of a compressed proof whose
+    /// points are in the prime-order subgroups.
+    pub fn from_compressed_base64(s:&str)->Option<Self>{
+        let bytes=BASE64.decode(s.trim()).ok()?;
+        zk_auth_verify_core::proof_from_compressed(&bytes).ok().map(|p|Self::from_proof(&p))
+    }
+}
+
+impl SnarkjsProofResponse {
+    /// `response` for a proof of `commitment`, converted to snarkjs's form.  `internal` should the proof not be
+    /// valid points, which a proof this server made always is.
+    pub fn from_response(response:ProofResponse,commitment:Fr)->Result<Self,ApiError>{
+        let Some(proof)=response.proof.try_to_proof() else {
+            tracing::error!(circuit_version=%response.circuit_version,"served proof is not valid points");
+            return Err(ApiError::Internal);
+        };
+        Ok(SnarkjsProofResponse{ proof:SnarkjsProof::from_proof(&proof), public_signals:public_signals(&[commitment]),
+                                 circuit_version:response.circuit_version, proof_hash:response.proof_hash })
+    }
+}
+
+impl CompressedProofResponse {
+    /// `internal` should the proof not be valid points, as for [`SnarkjsProofResponse::from_response`].
+    pub fn from_response(response:ProofResponse)->Result<Self,ApiError>{
+        let Some(proof)=response.proof.to_compressed_base64() else {
+            tracing::error!(circuit_version=%response.circuit_version,"served proof is not valid points");
+            return Err(ApiError::Internal);
+        };
+        Ok(CompressedProofResponse{ proof, circuit_version:response.circuit_version, proof_hash:response.proof_hash })
+    }
+}
+
+impl CompressedVerifyRequest {
+    /// The equivalent hex [`VerifyRequest`].
+    pub fn to_verify_request(&self)->Result<VerifyRequest,ApiError>{
+        let proof=ProofJson::from_compressed_base64(&self.proof).ok_or(ApiError::InvalidInput{field:"proof",reason:"not base64 of a compressed BN254 proof"})?;
+        Ok(VerifyRequest{ commitment:self.commitment.clone(), proof, circuit_version:self.circuit_version.clone(), circuit_id:self.circuit_id.clone(),
+                          nullifier:self.nullifier.clone(), application:self.application.clone(), challenge:self.challenge.clone(),
+                          claims:self.claims.clone(), audience:self.audience.clone() })
+    }
+}
+
+impl SnarkjsVerifyRequest {
+    /// The equivalent hex [`VerifyRequest`].  `publicSignals` are in circuit order: the commitment alone;
+    /// `commitment, x` where `x` is the challenge, or the hash of `claims` or `audience` when one of those is set; or
+    /// `nullifier, commitment, app_id`, which needs `application`.  A `nullifier` or `challenge` left out of the body
+    /// is taken from the signals; every field given must match its signal.
+    pub fn to_verify_request(&self)->Result<VerifyRequest,ApiError>{
+        let proof=self.proof.to_proof().ok_or(ApiError::InvalidInput{field:"proof",reason:"not a valid snarkjs BN254 proof"})?;
+        let signals=self.public_signals.iter().map(|s|normalize_public_input(s)).collect::<Result<Vec<Fr>,_>>()
+            .map_err(|_|ApiError::InvalidInput{field:"publicSignals",reason:"not scalar field elements"})?;
+        let mismatch=|field|ApiError::InvalidInput{field,reason:"does not match publicSignals"};
+        // The field as given when it matches `signal`, else the signal in decimal.
+        let agreeing=|field,given:&Option<String>,signal:Fr|match given {
+            Some(s) if normalize_public_input(s).ok()!=Some(signal) => Err(mismatch(field)),
+            _ => Ok(Some(public_signals(&[signal]).remove(0))),
+        };
+        let (mut nullifier,mut challenge)=(self.nullifier.clone(),self.challenge.clone());
+        let commitment=match signals[..] {
+            [commitment] => commitment,
+            [commitment,x] => {
+                match (&self.claims,&self.audience) {
+                    (Some(claims),_) => if claims.hash()!=x { return Err(mismatch("claims")); },
+                    (None,Some(audience)) => if app_id(audience)!=x { return Err(mismatch("audience")); },
+                    (None,None) => challenge=agreeing("challenge",&self.challenge,x)?,
+                }
+                commitment
+            }
+            [signal,commitment,app] => {
+                let application=self.application.as_ref().ok_or(ApiError::InvalidInput{field:"application",reason:"required with three public signals"})?;
+                if app_id(application)!=app { return Err(mismatch("application")); }
+                nullifier=agreeing("nullifier",&self.nullifier,signal)?;
+                commitment
+            }
+            _ => return Err(ApiError::InvalidInput{field:"publicSignals",reason:"expected one to three public signals"}),
+        };
+        Ok(VerifyRequest{ commitment:public_signals(&[commitment]).remove(0), proof:ProofJson::from_proof(&proof),
+                          circuit_version:self.circuit_version.clone(), circuit_id:self.circuit_id.clone(), nullifier,
+                          application:self.application.clone(), challenge, claims:self.claims.clone(), audience:self.audience.clone() })
+    }
+}
+
+// Secret-bearing DTOs print their sensitive fields as `[REDACTED]`.
+impl std::fmt::Debug for RegisterRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegisterRequest")
+            .field("email", &Redacted(&self.email)).field("name", &Redacted(&self.name)).field("age", &Redacted(self.age))
+            .field("country", &Redacted(&self.country)).field("dob", &Redacted(&self.dob)).field("phone", &self.phone.as_ref().map(Redacted))
+            .field("biometric_hash", &self.biometric_hash.as_ref().map(Redacted)).finish()
+    }
+}
+impl std::fmt::Debug for BlindRegisterRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlindRegisterRequest")
+            .field("email_hash", &Redacted(&self.email_hash)).field("name_hash", &Redacted(&self.name_hash)).field("age", &Redacted(&self.age))
+            .field("country", &Redacted(&self.country)).field("dob", &Redacted(&self.dob)).field("phone_hash", &self.phone_hash.as_ref().map(Redacted))
+            .field("biometric_hash", &self.biometric_hash.as_ref().map(Redacted)).finish()
+    }
+}
+impl std::fmt::Debug for MultiRegisterRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiRegisterRequest").field("secrets_hex", &Redacted(&self.secrets_hex)).finish()
+    }
+}
+impl std::fmt::Debug for RegisterResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegisterResponse")
+            .field("secret", &Redacted(&self.secret)).field("nonce", &Redacted(&self.nonce)).field("commitment", &self.commitment).field("leaf_index", &self.leaf_index).field("nonce_proof", &self.nonce_proof)
+            .field("credential", &self.credential).field("registration_proof", &self.registration_proof).finish()
+    }
+}
+impl std::fmt::Debug for ProofRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut d=f.debug_struct("ProofRequest");
+        d.field("secret_hex", &Redacted(&self.secret_hex)).field("secrets_hex", &Redacted(&self.secrets_hex)).field("commitment", &self.commitment)
+         .field("circuit_id", &self.circuit_id);
+        #[cfg(feature="deterministic")] d.field("seed", &self.seed.as_ref().map(Redacted));
+        d.finish()
+    }
+}
+
+//--------------------------------------------------------------------
+// /register
+//--------------------------------------------------------------------
+#[post("/register")]
+async fn register(state:web::Data<AppState>,body:web::Json<RegisterRequest>,query:web::Query<RegisterQuery>)->HttpResponse{
+    if let Err(e)=check_optional_attributes(&body){ return e.error_response(); }
+    let attributes=encode_attributes_with(&body,&state.config.attributes);
+    match register_attributes(&state,attributes,query.proof).await {
+        Ok(res)=>HttpResponse::Ok().json(res),
+        Err(e)=>e.error_response(),
+    }
+}
+
+/// A phone or biometric hash, if given, must be well-formed: unlike the
+/// other attributes they are rejected rather than hashed as typed.
+pub(crate) fn check_optional_attributes(body:&RegisterRequest)->Result<(),ApiError>{
+    if body.phone.as_ref().is_some_and(|phone|normalize_phone(phone,&body.country).is_none()) {
+        return Err(ApiError::InvalidInput{field:"phone",reason:"not a valid phone number for the country"});
+    }
+    if body.biometric_hash.as_ref().is_some_and(|hash|encode_biometric(hash).is_none()) {
+        return Err(ApiError::InvalidInput{field:"biometric_hash",reason:"must be 32 bytes of hex"});
+    }
+    Ok(())
+}
+
+/// Completes the Poseidon chain from attributes the client encoded itself,
+/// so the plain email, name and birth date never reach the server.
+#[post("/register/blind")]
+async fn register_blind(state:web::Data<AppState>,body:web::Json<BlindRegisterRequest>,query:web::Query<RegisterQuery>)->HttpResponse{
+    let attributes=match blind_attributes(&body,&state.config.attributes){Ok(a)=>a,Err(e)=>return e.error_response()};
+    match register_attributes(&state,attributes,query.proof).await {
+        Ok(res)=>HttpResponse::Ok().json(res),
+        Err(e)=>e.error_response(),
+    }
+}
+
+/// Registers the commitment over several secrets for `multi` circuits, as [`commitment_for_secrets`] computes it.
+/// The secrets are not kept; the client proves with them at `/generate-proof`.
+#[post("/register/multi")]
+async fn register_multi(state:web::Data<AppState>,body:web::Json<MultiRegisterRequest>)->HttpResponse{
+    let _permit=state.limits.register.acquire().await;
+    let secrets=match parse_secrets(&body.secrets_hex){Ok(s)=>s,Err(e)=>return e.error_response()};
+    let commitment=commitment_for_secrets(&secrets).expect("count checked by parse_secrets");
+    match append_commitment(&state,commitment) {
+        Ok(leaf_index)=>HttpResponse::Ok().json(MultiRegisterResponse{commitment:public_signals(&[commitment]).remove(0),leaf_index}),
+        Err(e)=>e.error_response(),
+    }
+}
+
+/// Register the identity for encoded `attributes`, proving its derivation first if `proof` is set, so that a
+/// registration whose proof failed never reaches the tree.
+async fn register_attributes(state:&web::Data<AppState>,attributes:Vec<Fr>,proof:bool)->Result<RegisterResponse,ApiError>{
+    let permit=state.limits.register.acquire().await;
+    let user_hash=poseidon_hash(&attributes);
+    let (nonce,nonce_proof)=pick_nonce(state,user_hash);
+    // Proving takes seconds under a prove permit; other registrations need not wait for it.
+    let registration_proof=match proof {
+        true => { drop(permit); Some(prove_registration(state,attributes,user_hash,&nonce).await?) }
+        false => None,
+    };
+    Ok(RegisterResponse{registration_proof,..issue_identity(state,user_hash,&nonce,nonce_proof)?})
+}
+
+/// Prove `Poseidon(Poseidon(Poseidon(attributes), nonce)) = commitment` with the first configured registration
+/// circuit for this many attributes.
+async fn prove_registration(state:&web::Data<AppState>,attributes:Vec<Fr>,user_hash:Fr,nonce:&[u8;16])->Result<RegistrationProof,ApiError>{
+    let kind=CircuitKind::registration(attributes.len()).expect("/register encodes five to seven attributes");
+    let circuit_id=state.config.circuits.iter().find(|(_,c)|c.kind==kind).map(|(id,_)|id.clone())
+        .ok_or(ApiError::InvalidInput{field:"proof",reason:"no registration circuit for these attributes on this server"})?;
+    let (_,commitment)=identity_for(user_hash,nonce);
+    let inputs=vec![("attributes",attributes),("nonce",vec![Fr::from_be_bytes_mod_order(nonce)]),
+                    ("user_hash",vec![user_hash]),("commitment",vec![commitment])];
+    let response=prove_statement(state,circuit_id.clone(),inputs,&[user_hash,commitment]).await?;
+    Ok(RegistrationProof{proof:response.proof,user_hash:public_signals(&[user_hash]).remove(0),circuit_id,circuit_version:response.circuit_version})
+}
+
+/// With a VRF key the nonce is a verifiable function of the attributes;
+/// otherwise it is random.
+fn pick_nonce(state:&AppState,user_hash:Fr)->([u8;16],Option<NonceProof>){
+    match &state.vrf {
+        Some(vrf) => { let (n,p)=vrf.nonce(&fr_to_bytes(user_hash)); (n,Some(p)) }
+        None => { let mut n=[0u8;16]; thread_rng().fill_bytes(&mut n); (n,None) }
+    }
+}
+
+/// Derive the identity for `user_hash` and `nonce` and add its commitment to the tree.
+/// `503 unavailable` once the tree is full.
+pub(crate) fn issue_identity(state:&AppState,user_hash:Fr,nonce:&[u8;16],nonce_proof:Option<NonceProof>)->Result<RegisterResponse,ApiError>{
+    let (secret_fe,commitment_fe)=identity_for(user_hash,nonce);
+    let leaf_index=append_commitment(state,commitment_fe)?;
+
+    let secret_hex={let mut b=secret_fe.into_bigint().to_bytes_be();if b.len()<32{b=[vec![0u8;32-b.len()],b].concat();}format!("0x{}",hex_encode(b))};
+    let nonce_hex=format!("0x{}",hex_encode(nonce));
+    let commitment_dec=BigUint::from_bytes_be(&commitment_fe.into_bigint().to_bytes_be()).to_string();
+
+    let credential=state.credentials.as_ref().map(|c|c.issue(&commitment_dec,user_hash,leaf_index));
+
+    Ok(RegisterResponse{secret:secret_hex,nonce:nonce_hex,commitment:commitment_dec,leaf_index,nonce_proof,credential,registration_proof:None})
+}
+
+/// Add `commitment` to the tree; `503 unavailable` once the tree is full.
+fn append_commitment(state:&AppState,commitment:Fr)->Result<u64,ApiError>{
+    let mut tree=state.tree.write().expect("commitment tree poisoned");
+    let leaf_index=tree.append(commitment)?;
+    // Registering a revoked commitment again must not restore its membership.
+    if state.revocations.is_revoked(commitment) { tree.revoke(commitment); }
+    Ok(leaf_index)
+}
+
+/// `(secret, commitment)` for the attributes in `body` and `nonce`.
+pub(crate) fn derive_identity(body:&RegisterRequest,nonce:&[u8;16],rules:&AttributeRules)->(Fr,Fr){
+    identity_for(derive_user_hash_with(body,rules),nonce)
+}
+
+fn identity_for(user_hash:Fr,nonce:&[u8;16])->(Fr,Fr){
+    let secret_fe=derive_secret(&user_hash,nonce);
+    (secret_fe,poseidon_hash(&[secret_fe]))
+}
+
+/// [`encode_attributes_with`] from client-encoded attributes.  The Keccak digests may be
+/// given whole and are reduced like the server reduces its own; under
+/// [`AttributeEncoding::HashToField`] they are the client's `hash_to_field`
+/// outputs, and like the other values must already be field elements.
+fn blind_attributes(body:&BlindRegisterRequest,rules:&AttributeRules)->Result<Vec<Fr>,ApiError>{
+    let digest=|field:&'static str,s:&str|{
+        let h=s.trim().strip_prefix("0x").ok_or(ApiError::InvalidInput{field,reason:"must be 0x-hex"})?;
+        let b=hex::decode(h).ok().filter(|b|b.len()<=32).ok_or(ApiError::InvalidInput{field,reason:"must be at most 32 bytes of hex"})?;
+        Ok::<_,ApiError>(Fr::from_be_bytes_mod_order(&b))
+    };
+    let scalar=|field:&'static str,s:&str|normalize_public_input(s).map_err(|_|ApiError::InvalidInput{field,reason:"not a BN254 scalar in decimal or 0x-hex"});
+    let string=|field:&'static str,s:&str|match rules.encoding {
+        AttributeEncoding::Keccak => digest(field,s),
+        AttributeEncoding::HashToField => scalar(field,s),
+    };
+    let mut inputs=vec![string("email_hash",&body.email_hash)?,string("name_hash",&body.name_hash)?,
+                        scalar("age",&body.age)?,scalar("country",&body.country)?,scalar("dob",&body.dob)?];
+    let phone=body.phone_hash.as_deref().map(|h|string("phone_hash",h)).transpose()?;
+    match &body.biometric_hash {
+        Some(h)=>{
+            let biometric=encode_biometric(h).ok_or(ApiError::InvalidInput{field:"biometric_hash",reason:"must be 32 bytes of hex"})?;
+            inputs.extend([phone.unwrap_or_default(),biometric]);
+        }
+        None=>inputs.extend(phone),
+    }
+    Ok(inputs)
+}
+
+fn fr_to_bytes(f:Fr)->[u8;32]{
+    let b=f.into_bigint().to_bytes_be();
+    let mut out=[0u8;32];out[32-b.len()..].copy_from_slice(&b);out
+}
+
+//--------------------------------------------------------------------
+// /generate-proof
+//--------------------------------------------------------------------
+// `/proof` is the name older builds of the binary served.
+#[routes]
+#[post("/generate-proof")]
+#[post("/proof")]
+async fn generate_proof(
+    state: web::Data<AppState>,
+    body: web::Json<ProofRequest>,
+    query: web::Query<ProveQuery>,
+    format: web::Query<FormatQuery>,
+) -> impl Responder {
+    let parsed = match parse_proof_request(&state, &body, query.debug) {
+        Ok(parsed) => parsed,
+        Err(e) => return e.error_response(),
+    };
+    let commitment = parsed.commitment;
+    match (prove_parsed(&state, parsed).await, format.format) {
+        (Ok(response), ResponseFormat::Hex) => HttpResponse::Ok().json(response),
+        (Ok(response), ResponseFormat::Snarkjs) => match SnarkjsProofResponse::from_response(response, commitment) {
+            Ok(response) => HttpResponse::Ok().json(response),
+            Err(e) => e.error_response(),
+        },
+        (Ok(response), ResponseFormat::Compressed) => match CompressedProofResponse::from_response(response) {
+            Ok(response) => HttpResponse::Ok().json(response),
+            Err(e) => e.error_response(),
+        },
+        (Err(e), _) => e.error_response(),
+    }
+}
+
+/// A [`ProofRequest`] whose inputs parsed, ready for [`prove_parsed`].
+pub(crate) struct ParsedProofRequest {
+    /// One secret, or a `multi` circuit's secrets.
+    secrets: Vec<Fr>,
+    commitment: Fr,
+    circuit_id: String,
+    kind: CircuitKind,
+    #[cfg(feature = "deterministic")]
+    seed: Option<[u8; 32]>,
+    deadline: Option<Duration>,
+    debug: bool,
+}
+
+/// Check a proof request without proving anything.
+pub(crate) fn parse_proof_request(state: &AppState, body: &ProofRequest, debug: bool) -> Result<ParsedProofRequest, ApiError> {
+    // ---------- Parse inputs (cheap) ----------
+    // Malformed inputs are the client's mistake: 400, never a panic.
+    let commitment = match BigUint::parse_bytes(body.commitment.as_bytes(), 10) {
+        Some(dec) => Fr::from_be_bytes_mod_order(&dec.to_bytes_be()),
+        None => return Err(ApiError::InvalidInput { field: "commitment", reason: "must be a decimal integer" }),
+    };
+
+    #[cfg(feature = "deterministic")]
+    let seed = body.seed.as_deref().map(parse_seed).transpose()?;
+    // The witness holds the secret: never hand it out from a production build.
+    if debug && !cfg!(feature = "witness-debug") {
+        return Err(ApiError::InvalidInput { field: "debug", reason: "witness debugging is not enabled on this server" });
+    }
+
+    let circuit_id = body.circuit_id.clone().unwrap_or_else(|| CIRCUIT_ID.to_owned());
+    let kind = match state.circuit(&circuit_id).map(|a| a.kind()) {
+        None => return Err(ApiError::InvalidInput { field: "circuit_id", reason: "no such circuit on this server" }),
+        Some(kind) if kind.is_registration() => {
+            return Err(ApiError::InvalidInput { field: "circuit_id", reason: "registration circuits are proved by /register?proof=true" })
+        }
+        Some(CircuitKind::Descent) => {
+            return Err(ApiError::InvalidInput { field: "circuit_id", reason: "descent circuits are proved by /derive?proof=true" })
+        }
+        // The witness calculator is only fed the secrets and `commitment`.
+        Some(kind) if !matches!(kind, CircuitKind::Secret | CircuitKind::Multi) => {
+            return Err(ApiError::InvalidInput { field: "circuit_id", reason: "this circuit is served for verification only" })
+        }
+        Some(kind) => kind,
+    };
+    let secrets = match (kind, body.secrets_hex.is_empty()) {
+        (CircuitKind::Multi, _) if !body.secret_hex.is_empty() => {
+            return Err(ApiError::InvalidInput { field: "secret_hex", reason: "this circuit takes secrets_hex instead" })
+        }
+        (CircuitKind::Multi, _) => parse_secrets(&body.secrets_hex)?,
+        (_, true) => vec![parse_secret("secret_hex", &body.secret_hex)?],
+        (_, false) => return Err(ApiError::InvalidInput { field: "secrets_hex", reason: "only multi circuits take secrets_hex" }),
+    };
+    let deadline = [state.config.prove_deadline, body.deadline_ms.map(Duration::from_millis)].into_iter().flatten().min();
+    Ok(ParsedProofRequest {
+        secrets,
+        commitment,
+        circuit_id,
+        kind,
+        #[cfg(feature = "deterministic")]
+        seed,
+        deadline,
+        debug,
+    })
+}
+
+/// A secret as 0x-hex of at most 32 bytes.  The hex decode error echoes the
+/// offending character, so it is not passed on.
+fn parse_secret(field: &'static str, hex: &str) -> Result<Fr, ApiError> {
+    match hex::decode(hex.trim_start_matches("0x")) {
+        Ok(bytes) if bytes.is_empty() => Err(ApiError::InvalidInput { field, reason: "required" }),
+        Ok(bytes) if bytes.len() <= 32 => Ok(Fr::from_be_bytes_mod_order(&bytes)),
+        Ok(_) => Err(ApiError::InvalidInput { field, reason: "must be at most 32 bytes" }),
+        Err(_) => Err(ApiError::InvalidInput { field, reason: "not valid hex" }),
+    }
+}
+
+/// The secrets of a `multi` commitment: one to [`MAX_SECRETS`] of them.
+fn parse_secrets(hexes: &[String]) -> Result<Vec<Fr>, ApiError> {
+    if hexes.is_empty() || hexes.len() > MAX_SECRETS {
+        return Err(ApiError::InvalidInput { field: "secrets_hex", reason: "must hold one to twelve secrets" });
+    }
+    hexes.iter().map(|hex| parse_secret("secrets_hex", hex)).collect()
+}
+
+/// Prove a parsed request as `/generate-proof` does, storing the proof if
+/// the proof store is enabled.
+pub(crate) async fn prove_parsed(state: &web::Data<AppState>, req: ParsedProofRequest) -> Result<ProofResponse, ApiError> {
+    let ParsedProofRequest { secrets, commitment: commitment_fe, circuit_id, kind, deadline, debug, .. } = req;
+    #[cfg(feature = "deterministic")]
+    let seed = req.seed;
+    let artifacts = state.circuit(&circuit_id).expect("circuit checked by parse_proof_request");
+    let statement = ProofStatement { kind, ..ProofStatement::secret(&circuit_id) };
+
+    // A proof made ahead of a hinted login, for the bundled circuit; deterministic requests want their own.
+    #[cfg(feature = "deterministic")]
+    let cached = if seed.is_none() && circuit_id == CIRCUIT_ID { cached_proof(state, secrets[0], commitment_fe) } else { None };
+    #[cfg(not(feature = "deterministic"))]
+    let cached = if circuit_id == CIRCUIT_ID { cached_proof(state, secrets[0], commitment_fe) } else { None };
+    if let Some(hit) = cached { return Ok(store_response(state, hit, commitment_fe, statement).await); }
+
+    if state.mock {
+        let circuit_version = artifacts.vk_fingerprint().to_owned();
+        let p = mock::prove(&secrets, commitment_fe).map_err(|e| e.into_api_error(artifacts.r1cs_path(), debug))?;
+        let response = ProofResponse { proof: ProofJson::from_proof(&p), circuit_version, proof_hash: None };
+        return Ok(store_response(state, response, commitment_fe, statement).await);
+    }
+
+    // ---------- Heavy work under semaphore ----------
+    let inputs = vec![(if kind == CircuitKind::Multi { "secrets" } else { "secret" }, secrets), ("commitment", vec![commitment_fe])];
+    blocking(state, |state| state.limits.admit_prove(state.store.as_ref())).await?;
+    let (proof, circuit_version) = run_proving(state, deadline, move |worker_state, cancel| {
+        #[cfg(feature = "chaos")]
+        std::thread::sleep(worker_state.chaos.prove_delay());
+        let artifacts = worker_state.circuit(&circuit_id).expect("circuit checked by parse_proof_request");
+        let circuit_version = artifacts.vk_fingerprint().to_owned();
+        #[cfg(feature = "deterministic")]
+        let proof = match seed {
+            Some(seed) => prover::prove_inputs(artifacts, &inputs, &mut rand::rngs::StdRng::from_seed(seed), cancel),
+            None => prover::prove_inputs(artifacts, &inputs, &mut thread_rng(), cancel),
+        };
+        #[cfg(not(feature = "deterministic"))]
+        let proof = prover::prove_inputs(artifacts, &inputs, &mut thread_rng(), cancel);
+        // serialise proof into JSON strings (do heavy EC->hex in this thread)
+        proof.map(|p| (ProofJson::from_proof(&p), circuit_version))
+            .map_err(|e| e.into_api_error(artifacts.r1cs_path(), debug))
+    }).await?;
+
+    let response = ProofResponse { proof, circuit_version, proof_hash: None };
+    Ok(store_response(state, response, commitment_fe, statement).await)
+}
+
+/// Prove `inputs` with `circuit_id` for a statement the server derived itself, under the prove permit and deadline like
+/// [`prove_parsed`] but without storing the proof.  In mock mode, the mock proof for `public`.
+pub(crate) async fn prove_statement(
+    state: &web::Data<AppState>,
+    circuit_id: String,
+    inputs: prover::Inputs,
+    public: &[Fr],
+) -> Result<ProofResponse, ApiError> {
+    let artifacts = state.circuit(&circuit_id).ok_or(ApiError::InvalidInput { field: "circuit_id", reason: "no such circuit on this server" })?;
+    if state.mock {
+        let proof = ProofJson::from_proof(&mock::prove_public(public));
+        return Ok(ProofResponse { proof, circuit_version: artifacts.vk_fingerprint().to_owned(), proof_hash: None });
+    }
+    blocking(state, |state| state.limits.admit_prove(state.store.as_ref())).await?;
+    let (proof, circuit_version) = run_proving(state, state.config.prove_deadline, move |worker_state, cancel| {
+        let artifacts = worker_state.circuit(&circuit_id).expect("circuit checked by prove_statement");
+        prover::prove_inputs(artifacts, &inputs, &mut thread_rng(), cancel)
+            .map(|p| (ProofJson::from_proof(&p), artifacts.vk_fingerprint().to_owned()))
+            .map_err(|e| e.into_api_error(artifacts.r1cs_path(), false))
+    }).await?;
+    Ok(ProofResponse { proof, circuit_version, proof_hash: None })
+}
+
+#[cfg(feature = "deterministic")]
+fn parse_seed(seed: &str) -> Result<[u8; 32], ApiError> {
+    let bytes = hex::decode(seed.trim_start_matches("0x"))
+        .map_err(|_| ApiError::InvalidInput { field: "seed", reason: "not valid hex" })?;
+    bytes.try_into().map_err(|_| ApiError::InvalidInput { field: "seed", reason: "must be 32 bytes" })
+}
+
+//--------------------------------------------------------------------
+// /verify-proof
+//--------------------------------------------------------------------
+// `/verify` likewise.
+#[routes]
+#[post("/verify-proof")]
+#[post("/verify")]
+async fn verify_proof(state:web::Data<AppState>,body:web::Json<VerifyBody>)->HttpResponse{
+    let verdict=match body.into_inner() {
+        VerifyBody::Inline(req) => verify_request(&state,&req).await,
+        VerifyBody::Snarkjs(req) => match req.to_verify_request() { Ok(req)=>verify_request(&state,&req).await, Err(e)=>Err(e) },
+        VerifyBody::Compressed(req) => match req.to_verify_request() { Ok(req)=>verify_request(&state,&req).await, Err(e)=>Err(e) },
+        VerifyBody::Ipfs(req) => match &state.ipfs {
+            Some(ipfs) => match ipfs.resolve(&req).await { Ok(req)=>verify_request(&state,&req).await, Err(e)=>Err(e) },
+            None => Err(ApiError::InvalidInput{field:"proof_cid",reason:"IPFS fetching is not enabled on this server"}),
+        },
+    };
+    let mut res=match verdict {
+        Ok(v) if v.valid => HttpResponse::Ok().json(with_session(&state,v)),
+        Ok(v)            => HttpResponse::Unauthorized().json(v),
+        Err(e)           => e.error_response(),
+    };
+    // Lets clients notice key rotation (and stale Solidity verifiers) from any verify call.
+    if let Ok(v)=HeaderValue::from_str(state.active_artifacts().vk_fingerprint()) { res.headers_mut().insert(VK_HEADER,v); }
+    res
+}
+
+/// Verify one request; an `Ok` may still carry `valid: false`.
+pub(crate) async fn verify_request(state:&web::Data<AppState>,body:&VerifyRequest)->Result<VerifyResponse,ApiError>{
+    let started=Instant::now();
+    let _permit=state.limits.verify.acquire().await;
+    let body=body.clone();
+    blocking(state,move|state|{
+        let check=check_verify_request(state,&body)?;
+        check_single_use(state,&check)?;
+        let accepted_by=accepting_key(state,&check);
+        spend_single_use(state,&check,accepted_by)?;
+        Ok(verdict(state,&body,&check,accepted_by,started))
+    }).await
+}
+
+/// Verify a request again, as `/proofs/{hash}` does for a stored proof: whether it holds under the keys
+/// accepted now.  A spent nullifier or used-up challenge is not refused, and nothing is spent.
+pub(crate) async fn reverify(state:&web::Data<AppState>,body:VerifyRequest)->Result<VerifyResponse,ApiError>{
+    let started=Instant::now();
+    let _permit=state.limits.verify.acquire().await;
+    // No store calls, but the pairing still belongs off the async worker.
+    let worker_state=state.clone();
+    web::block(move||{
+        let check=check_verify_request(&worker_state,&body)?;
+        let accepted_by=accepting_key(&worker_state,&check);
+        Ok(verdict(&worker_state,&body,&check,accepted_by,started))
+    }).await.map_err(|_|ApiError::Internal)?
+}
+
+/// A verify request checked up to the pairing.  `nullifier` is `(app_id, nullifier)` for the nullifier circuit,
+/// `challenge` is set for the challenge circuit, `claims`, the claim block's hash, for the claims circuit and
+/// `audience`, hashed like an application, for the audience circuit.
+pub(crate) struct VerifyCheck<'a> { pub(crate) keys:Vec<&'a Artifacts>, pub(crate) commitment:Fr, pub(crate) proof:Proof<Bn254>,
+                                    pub(crate) nullifier:Option<(Fr,Fr)>, pub(crate) challenge:Option<Fr>, pub(crate) claims:Option<Fr>,
+                                    pub(crate) audience:Option<Fr> }
+
+impl VerifyCheck<'_> {
+    /// The public signals in circuit order: `nullifier, commitment, app_id` for the nullifier circuit, otherwise
+    /// the commitment followed by the challenge, claims or audience input, if the circuit has one.
+    pub(crate) fn public_inputs(&self)->Vec<Fr>{
+        match self.nullifier {
+            Some((app_id,nullifier)) => vec![nullifier,self.commitment,app_id],
+            None => [Some(self.commitment),self.challenge,self.claims,self.audience].into_iter().flatten().collect(),
+        }
+    }
+}
+
+/// Everything [`verify_request`] checks before the pairing but [`check_single_use`].  `keys` are the
+/// ones the proof may be valid under, the active key first.
+pub(crate) fn check_verify_request<'a>(state:&'a AppState,body:&VerifyRequest)->Result<VerifyCheck<'a>,ApiError>{
+    #[cfg(feature="chaos")]
+    if state.chaos.fail_verification() { return Err(ApiError::Unavailable("injected verification failure")); }
+
+    // Reject proofs made for another key before spending a pairing on them.
+    // During a rotation window either key is accepted; the version picks one.
+    let candidates=state.verifying_artifacts_for(body.circuit_id.as_deref().unwrap_or(CIRCUIT_ID));
+    if candidates.is_empty() { return Err(ApiError::InvalidInput{field:"circuit_id",reason:"no such circuit on this server"}); }
+    let mismatch=|got|ApiError::CircuitMismatch{expected:candidates[0].vk_fingerprint().to_owned(),got};
+    let keys=match &body.circuit_version {
+        Some(v) => match candidates.iter().find(|a|v.eq_ignore_ascii_case(a.vk_fingerprint())) {
+            Some(a) => vec![*a],
+            None => return Err(mismatch(Some(v.clone()))),
+        },
+        None if state.config.require_circuit_version => return Err(mismatch(None)),
+        None => candidates.clone(),
+    };
+    // Its public signals are `nullifier, root, app_id`: checked here, the root would pass as the commitment.
+    if keys[0].kind()==CircuitKind::Membership { return Err(ApiError::InvalidInput{field:"circuit_id",reason:"membership proofs are verified at /verify-membership"}); }
+    if keys[0].kind()==CircuitKind::Descent { return Err(ApiError::InvalidInput{field:"circuit_id",reason:"descent proofs are verified at /verify-descent"}); }
+    // `user_hash, commitment`: the user hash would pass as the commitment.
+    if keys[0].kind().is_registration() { return Err(ApiError::InvalidInput{field:"circuit_id",reason:"registration proofs are checked offline against /verification-key"}); }
+    let proof=body.proof.try_to_proof().ok_or(ApiError::InvalidInput{field:"proof",reason:"not a valid BN254 proof"})?;
+    let commitment=parse_commitment(&body.commitment)?;
+    // Before the pairing: the tree's leaves are public anyway.
+    if state.config.require_registered && !state.tree.read().expect("commitment tree poisoned").contains(commitment) {
+        return Err(ApiError::UnknownCommitment);
+    }
+    if state.revocations.is_revoked(commitment) { return Err(ApiError::Revoked); }
+    let nullifier=check_nullifier(body,keys[0])?;
+    let challenge=check_challenge(body,keys[0])?;
+    let claims=check_claims(state,body,keys[0])?;
+    let audience=check_audience(state,body,keys[0])?;
+    Ok(VerifyCheck{keys,commitment,proof,nullifier,challenge,claims,audience})
+}
+
+/// `(app_id, nullifier)` when `key`'s circuit outputs a nullifier.
+fn check_nullifier(body:&VerifyRequest,key:&Artifacts)->Result<Option<(Fr,Fr)>,ApiError>{
+    let scoped=key.kind()==CircuitKind::Nullifier;
+    match (scoped,&body.nullifier,&body.application) {
+        (false,None,None) => Ok(None),
+        (false,_,_) => Err(ApiError::InvalidInput{field:"nullifier",reason:"this circuit has no nullifier"}),
+        (true,Some(nullifier),Some(application)) => {
+            let nullifier=normalize_public_input(nullifier).map_err(|_|ApiError::InvalidInput{field:"nullifier",reason:"not a scalar field element"})?;
+            Ok(Some((app_id(application),nullifier)))
+        }
+        (true,_,_) => Err(ApiError::InvalidInput{field:"nullifier",reason:"this circuit requires nullifier and application"}),
+    }
+}
+
+/// The challenge when `key`'s circuit takes one.
+fn check_challenge(body:&VerifyRequest,key:&Artifacts)->Result<Option<Fr>,ApiError>{
+    let challenged=key.kind()==CircuitKind::Challenge;
+    match (challenged,&body.challenge) {
+        (false,None) => Ok(None),
+        (false,Some(_)) => Err(ApiError::InvalidInput{field:"challenge",reason:"this circuit takes no challenge"}),
+        (true,Some(challenge)) => {
+            Ok(Some(normalize_public_input(challenge).map_err(|_|ApiError::InvalidInput{field:"challenge",reason:"not a scalar field element"})?))
+        }
+        (true,None) => Err(ApiError::InvalidInput{field:"challenge",reason:"this circuit requires a challenge from /challenge"}),
+    }
+}
+
+/// The claim block's hash when `key`'s circuit is bound to one.  Blocks the
+/// claim policy refuses, expired ones included, are refused here, before the pairing.
+fn check_claims(state:&AppState,body:&VerifyRequest,key:&Artifacts)->Result<Option<Fr>,ApiError>{
+    match (key.kind()==CircuitKind::Claims,&body.claims) {
+        (false,None) => Ok(None),
+        (false,Some(_)) => Err(ApiError::InvalidInput{field:"claims",reason:"this circuit takes no claims"}),
+        (true,Some(claims)) => {
+            let policy=state.config.claim_policy.as_ref().expect("claim policy checked at startup");
+            let now=SystemTime::now().duration_since(UNIX_EPOCH).map(|d|d.as_secs()).unwrap_or(0);
+            claims.validate(policy,now).map_err(ApiError::InvalidClaims)?;
+            Ok(Some(claims.hash()))
+        }
+        (true,None) => Err(ApiError::InvalidInput{field:"claims",reason:"this circuit requires a claim block"}),
+    }
+}
+
+/// `Keccak256(audience) mod p` when `key`'s circuit is bound to an audience.  Audiences this server does not
+/// verify for are refused here, before the pairing: the proof was meant for another service.
+fn check_audience(state:&AppState,body:&VerifyRequest,key:&Artifacts)->Result<Option<Fr>,ApiError>{
+    match (key.kind()==CircuitKind::Audience,&body.audience) {
+        (false,None) => Ok(None),
+        (false,Some(_)) => Err(ApiError::InvalidInput{field:"audience",reason:"this circuit takes no audience"}),
+        (true,Some(audience)) if state.config.audiences.contains(audience) => Ok(Some(app_id(audience))),
+        (true,Some(_)) => Err(ApiError::InvalidInput{field:"audience",reason:"not an audience this server verifies for"}),
+        (true,None) => Err(ApiError::InvalidInput{field:"audience",reason:"this circuit requires an audience"}),
+    }
+}
+
+/// Refuse a spent nullifier and an unknown, expired or used challenge before the pairing.
+pub(crate) fn check_single_use(state:&AppState,check:&VerifyCheck)->Result<(),ApiError>{
+    if let Some((app_id,nullifier))=check.nullifier { if nullifier::is_spent(state.store.as_ref(),app_id,nullifier)? { return Err(ApiError::AlreadyUsed); } }
+    if let Some(challenge)=check.challenge { if !challenge::is_live(state.store.as_ref(),challenge)? { return Err(ApiError::InvalidChallenge); } }
+    Ok(())
+}
+
+/// The pairing check: the first of `check.keys` the proof holds under.
+pub(crate) fn accepting_key<'a>(state:&AppState,check:&VerifyCheck<'a>)->Option<&'a Artifacts>{
+    // use pre‑computed processed verifying key
+    if state.mock&&mock::verify(check.commitment,&check.proof) { Some(check.keys[0]) }
+    else { let public=check.public_inputs(); check.keys.iter().copied().find(|a|prover::verify_inputs(a,&public,&check.proof)) }
+}
+
+/// Spend the nullifier or use up the challenge of a proof [`accepting_key`]
+/// accepted.  Catches a replay that raced the original past [`check_verify_request`].
+pub(crate) fn spend_single_use(state:&AppState,check:&VerifyCheck,accepted_by:Option<&Artifacts>)->Result<(),ApiError>{
+    if accepted_by.is_none() { return Ok(()); }
+    if let Some((app_id,nullifier))=check.nullifier { if !nullifier::spend(state.store.as_ref(),app_id,nullifier)? { return Err(ApiError::AlreadyUsed); } }
+    if let Some(challenge)=check.challenge { if !challenge::take(state.store.as_ref(),challenge)? { return Err(ApiError::InvalidChallenge); } }
+    Ok(())
+}
+
+/// The signed answer for a request [`accepting_key`] settled.
+pub(crate) fn verdict(state:&AppState,body:&VerifyRequest,check:&VerifyCheck,accepted_by:Option<&Artifacts>,started:Instant)->VerifyResponse{
+    let key_fp=accepted_by.unwrap_or(check.keys[0]).vk_fingerprint();
+    let mut proof_bytes=Vec::new();
+    check.proof.serialize_compressed(&mut proof_bytes).expect("proof serialises into a Vec");
+    let receipt=state.receipts.sign(&proof_bytes,&body.commitment,key_fp,started.elapsed(),accepted_by.is_some());
+    let single_use=check.nullifier.is_some()||check.challenge.is_some();
+    VerifyResponse{valid:accepted_by.is_some(),circuit_version:accepted_by.map(|_|key_fp.to_owned()),receipt,session:None,single_use}
+}
+
+/// Decimal commitment, reduced into the field as `/register` produces it.
+fn parse_commitment(s:&str)->Result<Fr,ApiError>{
+    let dec=BigUint::parse_bytes(s.as_bytes(),10).ok_or(ApiError::InvalidInput{field:"commitment",reason:"not a decimal integer"})?;
+    Ok(Fr::from_be_bytes_mod_order(&dec.to_bytes_be()))
+}
+
+//--------------------------------------------------------------------
+// /vk/fingerprint
+//--------------------------------------------------------------------
+#[get("/vk/fingerprint")]
+async fn vk_fingerprint(state:web::Data<AppState>,req:HttpRequest)->impl Responder{
+    // First call may load the key from disk; keep that off the async worker.
+    let worker_state=state.clone();
+    let res=web::block(move||{
+        let rotation=worker_state.config.rotation.as_ref().zip(worker_state.staged.as_ref()).map(|(r,staged)|RotationInfo{
+            from:worker_state.artifacts.vk_fingerprint().to_owned(), to:staged.vk_fingerprint().to_owned(),
+            cutover:r.cutover.duration_since(UNIX_EPOCH).map(|d|d.as_secs()).unwrap_or(0), overlap_secs:r.overlap.as_secs(),
+        });
+        let historical=worker_state.historical.iter().map(|a|a.vk_fingerprint().to_owned()).collect();
+        VkFingerprintResponse{circuit_id:CIRCUIT_ID,algorithm:"sha256",vk_fingerprint:worker_state.active_artifacts().vk_fingerprint().to_owned(),rotation,historical}
+    }).await;
+    match res {
+        Ok(body)=>json_with_etag(&req,&body),
+        Err(_)=>HttpResponse::InternalServerError().finish(),
+    }
+}
+
+//--------------------------------------------------------------------
+// /verification-key
+//--------------------------------------------------------------------
+/// Lets auditors check proofs offline with `snarkjs groth16 verify` against
+/// exactly the key the server holds.  `circuit_version` picks any key the
+/// server verifies with; the active key by default.
+#[get("/verification-key")]
+async fn verification_key(state:web::Data<AppState>,req:HttpRequest,query:web::Query<VerificationKeyQuery>)->HttpResponse{
+    // First call may load the key from disk; keep that off the async worker.
+    let worker_state=state.clone();
+    let res=web::block(move||{
+        let keys=worker_state.verifying_artifacts_for(query.circuit_id.as_deref().unwrap_or(CIRCUIT_ID));
+        let key=match &query.circuit_version {
+            Some(v) => keys.into_iter().find(|a|v.eq_ignore_ascii_case(a.vk_fingerprint())),
+            None    => keys.into_iter().next(),
+        }.ok_or(ApiError::NotFound("no such circuit or key on this server"))?;
+        Ok::<_,ApiError>((SnarkjsVerificationKey::from_vk(key.verifying_key()),key.vk_fingerprint().to_owned()))
+    }).await;
+    match res {
+        Ok(Ok((vk,fingerprint)))=>{
+            let mut res=json_with_etag(&req,&vk);
+            if let Ok(v)=HeaderValue::from_str(&fingerprint) { res.headers_mut().insert(VK_HEADER,v); }
+            res
+        }
+        Ok(Err(e))=>e.error_response(),
+        Err(_)=>HttpResponse::InternalServerError().finish(),
+    }
+}
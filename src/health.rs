@@ -0,0 +1,120 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::cancel::Cancel;
+use crate::handlers::{derive_identity, RegisterRequest};
+use crate::prover;
+use crate::replication::ReplicationLag;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// /health
+//--------------------------------------------------------------------
+/// Default budget for a deep check; debug builds prove in ~8 s.
+const DEEP_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_DEEP_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Deserialize)]
+pub struct HealthQuery {
+    #[serde(default)]
+    pub deep: bool,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HealthStep {
+    pub name: &'static str,
+    pub ok: bool,
+    pub elapsed_ms: f64,
+}
+
+/// `status` is `ok`, `fail` or `timeout`; `steps` lists the deep-check
+/// stages that finished, in order.  `replication` is present on nodes that
+/// follow a primary; a diverged replica fails.
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<HealthStep>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replication: Option<ReplicationLag>,
+}
+
+/// Liveness by default.  With `?deep=true` it registers a synthetic identity,
+/// proves and verifies it (as far as the node's mode allows) and answers 503
+/// unless every stage passes within `timeout_ms` (default 30 s, max 120 s).
+#[get("/health")]
+async fn health(state: web::Data<AppState>, query: web::Query<HealthQuery>) -> impl Responder {
+    let replication = state.replication.as_ref().map(|r| r.lag());
+    if replication.as_ref().is_some_and(|r| r.diverged) {
+        return HttpResponse::ServiceUnavailable().json(HealthResponse { status: "fail", steps: Vec::new(), replication });
+    }
+    if !query.deep {
+        return HttpResponse::Ok().json(HealthResponse { status: "ok", steps: Vec::new(), replication });
+    }
+    let budget = query.timeout_ms.map(Duration::from_millis).unwrap_or(DEEP_TIMEOUT).min(MAX_DEEP_TIMEOUT);
+    let steps = Arc::new(Mutex::new(Vec::new()));
+
+    // Shares the prover limit with real traffic; a saturated queue is
+    // reported as a timeout, which is what a client would see too.
+    let _permit = state.limits.prove.acquire().await;
+    let worker_state = state.clone();
+    let progress = steps.clone();
+    let cancel = Cancel::default();
+    let task_cancel = cancel.clone();
+    let task = tokio::task::spawn_blocking(move || self_test(&worker_state, &progress, &task_cancel));
+    let status = match tokio::time::timeout(budget, task).await {
+        Ok(Ok(true)) => "ok",
+        Ok(_) => "fail",
+        // The blocking task stops at the prover's next checkpoint.
+        Err(_) => {
+            cancel.cancel();
+            "timeout"
+        }
+    };
+    let steps = steps.lock().unwrap().clone();
+    let body = HealthResponse { status, steps, replication };
+    if status == "ok" { HttpResponse::Ok().json(body) } else { HttpResponse::ServiceUnavailable().json(body) }
+}
+
+fn self_test(state: &AppState, steps: &Mutex<Vec<HealthStep>>, cancel: &Cancel) -> bool {
+    let step = |name, started: Instant, ok| {
+        steps.lock().unwrap().push(HealthStep { name, ok, elapsed_ms: started.elapsed().as_secs_f64() * 1000.0 });
+        ok
+    };
+
+    let started = Instant::now();
+    let identity = RegisterRequest {
+        email: "health-check@localhost".into(),
+        name: "health check".into(),
+        age: 0,
+        country: "ZZ".into(),
+        dob: "1970-01-01".into(),
+        phone: None,
+        biometric_hash: None,
+    };
+    let mut nonce = [0u8; 16];
+    thread_rng().fill_bytes(&mut nonce);
+    let (secret, commitment) = derive_identity(&identity, &nonce, &state.config.attributes);
+    step("register", started, true);
+
+    let mode = &state.config.mode;
+    if !mode.proves() {
+        return true;
+    }
+    let started = Instant::now();
+    let artifacts = state.active_artifacts();
+    let Ok(proof) = prover::prove(artifacts, secret, commitment, &mut thread_rng(), cancel) else {
+        return step("prove", started, false);
+    };
+    step("prove", started, true);
+
+    if !mode.verifies() {
+        return true;
+    }
+    let started = Instant::now();
+    step("verify", started, prover::verify(artifacts, commitment, &proof))
+}
@@ -0,0 +1,218 @@
+use actix_web::{post, web, HttpResponse, ResponseError};
+use ark_serialize::CanonicalSerialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::convert::{parse_public_inputs, sniff_proof};
+use crate::error::ApiError;
+use crate::handlers::{ProofJson, VerifyRequest};
+use crate::snarkjs::{public_signals, SnarkjsVerificationKey};
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Configuration
+//--------------------------------------------------------------------
+/// Where `/verify-proof` fetches `proof_cid` and `public_cid` content from.
+///
+/// The gateway is trusted to return the content the CID names; point it at
+/// your own node rather than a public gateway.
+#[derive(Clone, Debug)]
+pub struct IpfsConfig {
+    /// Gateway base URL; content is fetched from `{gateway}/ipfs/{cid}`.
+    pub gateway: String,
+    /// Largest object fetched; bigger ones are rejected mid-download.
+    pub max_bytes: usize,
+    /// Whole-request timeout per fetch.
+    pub timeout: Duration,
+    /// Objects kept in memory.  CIDs are immutable, so entries never go
+    /// stale; the oldest is dropped when full.
+    pub cache_entries: usize,
+    /// Kubo RPC base URL (e.g. `http://127.0.0.1:5001`) used by
+    /// `POST /admin/ipfs/publish`; `None` disables publishing.
+    pub api: Option<String>,
+}
+
+impl Default for IpfsConfig {
+    fn default() -> Self {
+        Self { gateway: "http://127.0.0.1:8080".into(), max_bytes: 1024 * 1024, timeout: Duration::from_secs(10), cache_entries: 1024, api: None }
+    }
+}
+
+/// `/verify-proof` body naming IPFS objects instead of carrying the proof.
+///
+/// `proof_cid` may hold any format the `convert` subcommand reads (gnark
+/// binaries included).  The commitment comes from `commitment`, else from
+/// `public_cid` (a snarkjs `public.json`), else from the proof file itself.
+#[derive(Debug, Deserialize)]
+pub struct IpfsVerifyRequest {
+    pub proof_cid: String,
+    #[serde(default)]
+    pub public_cid: Option<String>,
+    #[serde(default)]
+    pub commitment: Option<String>,
+    #[serde(default)]
+    pub circuit_version: Option<String>,
+    #[serde(default)]
+    pub circuit_id: Option<String>,
+}
+
+//--------------------------------------------------------------------
+// Fetching
+//--------------------------------------------------------------------
+pub(crate) struct IpfsFetcher {
+    config: IpfsConfig,
+    client: reqwest::Client,
+    cache: Mutex<Cache>,
+}
+
+#[derive(Default)]
+struct Cache {
+    objects: HashMap<String, Arc<[u8]>>,
+    order: VecDeque<String>,
+}
+
+impl IpfsFetcher {
+    pub(crate) fn new(config: IpfsConfig) -> Self {
+        let client = reqwest::Client::builder().timeout(config.timeout).build().expect("default TLS backend is available");
+        Self { config, client, cache: Default::default() }
+    }
+
+    /// Resolve `req` into an ordinary verify request.
+    pub(crate) async fn resolve(&self, req: &IpfsVerifyRequest) -> Result<VerifyRequest, ApiError> {
+        let proof = self.fetch("proof_cid", &req.proof_cid).await?;
+        let record = sniff_proof(None, &proof).ok_or(ApiError::InvalidInput { field: "proof_cid", reason: "not a recognised BN254 proof" })?;
+        let commitment = match (&req.commitment, &req.public_cid) {
+            (Some(c), _) => c.clone(),
+            (None, Some(cid)) => {
+                let public = self.fetch("public_cid", cid).await?;
+                let inputs = std::str::from_utf8(&public).ok().and_then(|t| parse_public_inputs(t).ok());
+                single_commitment(inputs.unwrap_or_default(), "public_cid")?
+            }
+            (None, None) => single_commitment(record.public_inputs, "proof_cid")?,
+        };
+        Ok(VerifyRequest {
+            commitment,
+            proof: ProofJson::from_proof(&record.proof),
+            circuit_version: req.circuit_version.clone(),
+            circuit_id: req.circuit_id.clone(),
+            nullifier: None,
+            application: None,
+            challenge: None,
+            claims: None,
+            audience: None,
+        })
+    }
+
+    async fn fetch(&self, field: &'static str, cid: &str) -> Result<Arc<[u8]>, ApiError> {
+        if !is_cid(cid) {
+            return Err(ApiError::InvalidInput { field, reason: "not an IPFS CID" });
+        }
+        if let Some(hit) = self.cache.lock().expect("ipfs cache poisoned").objects.get(cid) {
+            return Ok(hit.clone());
+        }
+        let url = format!("{}/ipfs/{cid}", self.config.gateway.trim_end_matches('/'));
+        let unavailable = |_| ApiError::Unavailable("IPFS gateway fetch failed");
+        let mut res = self.client.get(url).send().await.and_then(|r| r.error_for_status()).map_err(unavailable)?;
+        let mut body = Vec::new();
+        while let Some(chunk) = res.chunk().await.map_err(unavailable)? {
+            if body.len() + chunk.len() > self.config.max_bytes {
+                return Err(ApiError::InvalidInput { field, reason: "object exceeds the IPFS size cap" });
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let body: Arc<[u8]> = body.into();
+        self.insert(cid, body.clone());
+        Ok(body)
+    }
+
+    /// Add `bytes` to the node behind [`IpfsConfig::api`] and pin it.
+    async fn pin(&self, name: &str, bytes: Vec<u8>) -> Result<String, ApiError> {
+        #[derive(Deserialize)]
+        struct Added { #[serde(rename = "Hash")] hash: String }
+        let api = self.config.api.as_deref().ok_or(ApiError::Unavailable("IPFS publishing is not configured"))?;
+        let url = format!("{}/api/v0/add?pin=true&cid-version=1", api.trim_end_matches('/'));
+        let form = reqwest::multipart::Form::new().part("file", reqwest::multipart::Part::bytes(bytes).file_name(name.to_owned()));
+        let res = self.client.post(url).multipart(form).send().await.and_then(|r| r.error_for_status());
+        let added: Added = match res {
+            Ok(res) => res.json().await.map_err(|_| ApiError::Unavailable("IPFS node returned an unexpected reply"))?,
+            Err(_) => return Err(ApiError::Unavailable("IPFS node rejected the upload")),
+        };
+        tracing::info!(name, cid = %added.hash, "pinned to IPFS");
+        Ok(added.hash)
+    }
+
+    fn insert(&self, cid: &str, body: Arc<[u8]>) {
+        let mut cache = self.cache.lock().expect("ipfs cache poisoned");
+        if self.config.cache_entries == 0 || cache.objects.insert(cid.to_owned(), body).is_some() {
+            return;
+        }
+        cache.order.push_back(cid.to_owned());
+        while cache.order.len() > self.config.cache_entries {
+            if let Some(old) = cache.order.pop_front() {
+                cache.objects.remove(&old);
+            }
+        }
+    }
+}
+
+fn single_commitment(inputs: Vec<ark_bn254::Fr>, field: &'static str) -> Result<String, ApiError> {
+    match inputs.as_slice() {
+        [c] => Ok(public_signals(&[*c]).remove(0)),
+        _ => Err(ApiError::InvalidInput { field, reason: "expected exactly one public input (the commitment)" }),
+    }
+}
+
+/// CIDv0 (`Qm…`, base58) or a CIDv1 in a multibase of letters and digits.
+/// Only the shape is checked; it keeps the value safe to put in a URL.
+fn is_cid(cid: &str) -> bool {
+    (46..=128).contains(&cid.len()) && cid.bytes().all(|b| b.is_ascii_alphanumeric()) && (cid.starts_with("Qm") || cid.starts_with(['b', 'z', 'f']))
+}
+
+//--------------------------------------------------------------------
+// /admin/ipfs/publish
+//--------------------------------------------------------------------
+/// CIDs of the trust material for the active key.
+#[derive(Debug, Serialize)]
+pub struct PublishResponse {
+    pub vk_fingerprint: String,
+    /// snarkjs `verification_key.json`.
+    pub verification_key_json: String,
+    /// Compressed arkworks encoding, as written by `export-vk`; its SHA-256
+    /// is `vk_fingerprint`.
+    pub verifying_key_bin: String,
+}
+
+/// Pins the active verifying key so third parties can fetch it by CID
+/// instead of trusting this server's HTTPS endpoint.
+#[post("/ipfs/publish")]
+async fn publish(state: web::Data<AppState>) -> HttpResponse {
+    let Some(ipfs) = &state.ipfs else {
+        return ApiError::Unavailable("IPFS publishing is not configured").error_response();
+    };
+    let worker_state = state.clone();
+    let Ok((vk_fingerprint, json, bin)) = web::block(move || {
+        let artifacts = worker_state.active_artifacts();
+        let vk = artifacts.verifying_key();
+        let json = serde_json::to_vec_pretty(&SnarkjsVerificationKey::from_vk(vk)).expect("vk serialises to JSON");
+        let mut bin = Vec::new();
+        vk.serialize_compressed(&mut bin).expect("vk serialises into a Vec");
+        (artifacts.vk_fingerprint().to_owned(), json, bin)
+    })
+    .await
+    else {
+        return HttpResponse::InternalServerError().finish();
+    };
+    let res = async {
+        Ok::<_, ApiError>(PublishResponse {
+            verification_key_json: ipfs.pin("verification_key.json", json).await?,
+            verifying_key_bin: ipfs.pin(&format!("vk-{vk_fingerprint}.bin"), bin).await?,
+            vk_fingerprint,
+        })
+    };
+    match res.await {
+        Ok(body) => HttpResponse::Ok().json(body),
+        Err(e) => e.error_response(),
+    }
+}
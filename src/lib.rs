@@ -0,0 +1,366 @@
+//! Zero-knowledge authentication endpoints (register → prove → verify) built
+//! on Groth16 over BN254 and circom-compatible Poseidon.
+//!
+//! The binary in `main.rs` is a thin wrapper around [`run`]; applications
+//! that already run their own actix server can mount [`zk_auth_scope`]
+//! instead.
+
+use actix_web::dev::Server;
+use actix_web::middleware::{Compress, Condition};
+use actix_web::{web, App, HttpServer, Scope};
+use std::net::TcpListener;
+
+mod access_log;
+mod admin;
+mod anchor;
+mod artifacts;
+mod attestation;
+mod backup;
+mod batch;
+mod bundle;
+mod calldata;
+mod cancel;
+mod challenge;
+mod claims;
+#[cfg(feature = "chaos")]
+mod chaos;
+mod circuits;
+mod commit_reveal;
+mod config;
+#[cfg(feature = "conformance")]
+mod conformance;
+mod convert;
+mod cosign;
+mod credential;
+mod crypto;
+mod derive;
+mod encoding;
+mod error;
+mod etag;
+mod group;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod handlers;
+mod health;
+mod ipfs;
+mod limits;
+mod mock;
+mod multipart;
+mod nullifier;
+mod oidc;
+#[cfg(feature = "playground")]
+mod playground;
+mod prewarm;
+mod proof_jobs;
+mod proof_store;
+mod proof_token;
+mod prover;
+mod receipt;
+mod record;
+mod redact;
+mod replication;
+mod revocation;
+mod session;
+mod snarkjs;
+mod state;
+mod store;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+mod tree;
+mod version;
+mod vrf;
+mod webhook;
+
+pub use access_log::{AccessLog, AccessLogConfig, EndpointLogConfig};
+pub use anchor::{run_anchoring, AnchorConfig, AnchorRecord, AnchorsResponse};
+pub use admin::{ConcurrencyUpdate, KeyStatsEntry, StatsResponse};
+pub use attestation::{AttestationClaims, AttestationQuery, AttestationResponse};
+pub use backup::{backup, open_backup, read_backup_key, restore, BackupArchive, BackupFile, BACKUP_MAGIC};
+pub use batch::{BatchLine, BatchResponse, BatchResult};
+pub use bundle::{export_bundle, verify_bundle, ProofBundle, BUNDLE_MAGIC};
+pub use calldata::{verify_proof_calldata, CalldataRequest, CalldataResponse, VERIFY_PROOF_SIGNATURE};
+pub use challenge::ChallengeResponse;
+pub use artifacts::{default_zkey_path, export_verifying_key, read_proving_key, Fingerprints, KeyStats, CIRCUIT_ID};
+#[cfg(feature = "conformance")]
+pub use conformance::{run_conformance, ConformanceCase, ConformanceReport};
+#[cfg(feature = "chaos")]
+pub use chaos::ChaosSettings;
+pub use claims::{ClaimBlock, ClaimPolicy};
+pub use circuits::{CircuitStats, SignalInfo, SignalsQuery, SignalsResponse, Visibility, WireValue, WitnessDiagnostics};
+pub use commit_reveal::{attributes_hash, CommitRequest, CommitResponse, RevealRequest};
+pub use config::{AdaptiveRate, AppConfig, CircuitKind, CircuitPaths, ConcurrencyLimits, KeyRotation, Mode, ServerConfig};
+pub use cosign::{ArtifactSigning, KeylessIdentity};
+pub use convert::{
+    convert_path, decode_proof, encode_proof, normalize_public_input, proof_from_gnark, proof_to_gnark, ConvertProofRequest,
+    ConvertProofResponse, ProofFormat, ProofRecord,
+};
+pub use credential::{CommitmentSubject, Credential, CredentialClaims, CredentialConfig};
+pub use derive::{
+    app_id, commitment_for, commitment_for_secrets, derive_child_secret, derive_membership_nullifier, derive_nullifier, derive_path,
+    DeriveQuery, DeriveRequest, DeriveResponse, DescentProof, DescentVerifyRequest, DescentVerifyResponse, MAX_SECRETS, TAG_CHILD,
+    TAG_MEMBERSHIP, TAG_NULLIFIER,
+};
+pub use encoding::{
+    country_u16, derive_commitment, derive_secret, derive_user_hash, derive_user_hash_with, encode_age, encode_attributes_with,
+    encode_country, encode_dob, encode_email, encode_name, hash_to_field, normalize_email, normalize_name, AttributeEncoding,
+    AttributeRules, CaseFold, DST_EMAIL, DST_NAME, DST_PHONE, normalize_phone, encode_biometric, EmailCanonicalization, PlusAddressing,
+    PLUS_ADDRESSING_DOMAINS,
+};
+pub use error::{ApiError, ErrorBody};
+pub use handlers::{
+    BlindRegisterRequest, CompressedProofResponse, CompressedVerifyRequest, FormatQuery, MultiRegisterRequest, MultiRegisterResponse,
+    ProofJson, ProofRequest, ProofResponse, ProveQuery, RegisterQuery, RegisterRequest, RegisterResponse, RegistrationProof,
+    ResponseFormat, RotationInfo, SnarkjsProofResponse, SnarkjsVerifyRequest, VerificationKeyQuery, VerifyBody, VerifyRequest,
+    VerifyResponse, VkFingerprintResponse, VK_HEADER,
+};
+pub use group::{MembershipRequest, MembershipResponse};
+#[cfg(feature = "grpc")]
+pub use grpc::{proto, serve_grpc, GrpcConfig, VerifierService};
+pub use health::{HealthQuery, HealthResponse, HealthStep};
+pub use ipfs::{IpfsConfig, IpfsVerifyRequest, PublishResponse};
+pub use limits::LimiterStats;
+pub use mock::MockProver;
+pub use prewarm::{PrewarmConfig, PrewarmRequest, PrewarmResponse, PrewarmStats};
+pub use oidc::{
+    AuthorizationParams, AuthorizationRequest, AuthorizeRequest, AuthorizeResponse, DiscoveryDocument, IdTokenClaims, OidcClient, OidcConfig,
+    TokenRequest, TokenResponse, LOGIN_PAGE_SLOT,
+};
+pub use proof_jobs::{
+    resume_proof_jobs, run_job_gc, FileJobStore, JobRetention, JobStore, JobStoreConfig, MemoryJobStore, ProofJob, ProofJobAccepted, ProofJobSummary, ProofJobsQuery, ProofJobsResponse,
+};
+pub use proof_store::{ProofStatement, ProofStoreConfig, StoredProof, StoredProofResponse};
+pub use proof_token::{ProofTokenResponse, RedeemRequest, RedeemResponse};
+pub use prover::{calculate_witness, prove_from_witness, prove_with_files, ProveError, WitnessFailure};
+pub use receipt::{Receipt, ReceiptKeyResponse};
+pub use record::{replay, RecordConfig, RecordedExchange, Recorder, ReplayMismatch, ReplayReport};
+pub use replication::{run_replication, ReplicationConfig, ReplicationLag, ReplicationStatus};
+pub use revocation::{RevocationEntry, RevocationsDelta, RevocationsQuery, RevokeRequest, RevokeResponse};
+pub use redact::{redact_json, redact_query, Redacted, REDACTED, SENSITIVE_KEYS};
+pub use session::{Jwk, Jwks, SessionClaims, SessionConfig, SessionToken};
+pub use snarkjs::{public_signals, SnarkjsProof, SnarkjsVerificationKey};
+pub use state::AppState;
+#[cfg(feature = "redis")]
+pub use store::{RedisConfig, RedisStore};
+pub use store::{MemoryStore, Store};
+pub use tree::{DeltaQuery, RootResponse, RootsDelta, TREE_DEPTH};
+pub use vrf::{vrf_verify, NonceProof, VrfKeyResponse, VRF_SUITE};
+pub use version::{enabled_features, VersionResponse, GIT_HASH};
+pub use webhook::{run_webhooks, RegistrationEvent, WebhookConfig, WEBHOOK_KEY_ID_HEADER, WEBHOOK_SIGNATURE_HEADER};
+
+//--------------------------------------------------------------------
+// Embedding
+//--------------------------------------------------------------------
+/// All endpoints as a mountable actix [`Scope`].
+///
+/// The scope has an empty prefix, so nest it to choose one:
+///
+/// ```no_run
+/// use actix_web::{web, App};
+/// use zk_auth_api::{zk_auth_scope, AppConfig};
+///
+/// let app = App::new().service(web::scope("/auth").service(zk_auth_scope(AppConfig::default())?));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// Every call builds fresh [`AppState`].  When mounting inside an
+/// `HttpServer` factory, which runs once per worker, build the state once
+/// and use [`zk_auth_scope_with_state`] so limits are shared across workers.
+/// Fails as [`AppState::new`] does.
+pub fn zk_auth_scope(config: AppConfig) -> std::io::Result<Scope> {
+    Ok(zk_auth_scope_with_state(web::Data::new(AppState::new(config)?)))
+}
+
+/// Like [`zk_auth_scope`] but reuses already-built shared state.
+pub fn zk_auth_scope_with_state(state: web::Data<AppState>) -> Scope {
+    let mode = state.config.mode.clone();
+    let replica = state.config.replica;
+    let admin = state.config.admin_token.is_some();
+    let anchoring = state.config.anchor.is_some();
+    let mut scope = web::scope("")
+        .app_data(state)
+        .service(version::version)
+        .service(health::health)
+        .service(handlers::vk_fingerprint)
+        .service(handlers::verification_key)
+        .service(circuits::circuit_stats)
+        .service(circuits::circuit_signals)
+        .service(receipt::receipt_key)
+        .service(attestation::attestation)
+        .service(proof_store::get_proof)
+        .service(vrf::vrf_key)
+        .service(tree::roots_delta)
+        .service(tree::current_root)
+        .service(revocation::revocations)
+        .service(derive::derive)
+        .service(calldata::calldata)
+        .service(convert::convert_proof);
+    // A replica's tree only changes by replication.
+    if !replica {
+        scope = scope
+            .service(handlers::register)
+            .service(handlers::register_blind)
+            .service(handlers::register_multi)
+            .service(commit_reveal::register_commit)
+            .service(commit_reveal::register_reveal);
+    }
+    if mode.proves() && !replica {
+        scope = scope
+            .service(multipart::generate_proof_multipart)
+            .service(handlers::generate_proof)
+            .service(proof_jobs::submit_proof_job)
+            .service(proof_jobs::proof_job)
+            .service(proof_jobs::cancel_proof_job);
+    }
+    if mode.verifies() {
+        scope = scope
+            .service(multipart::verify_proof_multipart)
+            .service(handlers::verify_proof)
+            .service(batch::verify_batch)
+            .service(group::verify_membership)
+            .service(derive::verify_descent)
+            .service(challenge::issue_challenge)
+            .service(session::jwks)
+            .service(oidc::discovery)
+            .service(oidc::authorization_page)
+            .service(oidc::authorize)
+            .service(oidc::token)
+            .service(proof_token::proof_token)
+            .service(proof_token::redeem);
+    }
+    if anchoring {
+        scope = scope.service(anchor::anchors);
+    }
+    if admin {
+        scope = scope.service(admin::admin_scope());
+    }
+    #[cfg(feature = "playground")]
+    let scope = scope.service(playground::playground);
+    scope
+}
+
+//--------------------------------------------------------------------
+// Socket activation
+//--------------------------------------------------------------------
+/// The first listening socket passed in by systemd socket activation
+/// (`LISTEN_FDS`/`LISTEN_PID`), or `None` when the process was started
+/// normally.  The environment variables are consumed, so call this once.
+pub fn activated_listener() -> std::io::Result<Option<TcpListener>> {
+    listenfd::ListenFd::from_env().take_tcp_listener(0)
+}
+
+//--------------------------------------------------------------------
+// Standalone server
+//--------------------------------------------------------------------
+/// Serve all endpoints on `listener` with a dedicated actix server.
+///
+/// Worker count, keep-alive, client timeouts and HTTP/2 come from
+/// [`AppConfig::server`].
+pub fn run(listener: TcpListener, config: AppConfig) -> std::io::Result<Server> {
+    let tuning = config.server.clone();
+    let access_log = config.access_log.clone();
+    let recorder = config.record.clone().map(Recorder::new).transpose()?;
+    let state = web::Data::new(AppState::new(config)?);
+    // Replication does not carry spent nullifiers or used challenges.
+    if state.replication.is_some() && state.config.mode.verifies() && !state.store.is_shared() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "a verifying replica needs a shared store (ZK_REDIS_URL) so nullifiers and challenges are spent across nodes",
+        ));
+    }
+    log_startup(&listener, &state);
+    // The first call connects, so a bad URL or an old Redis shows up here.
+    if state.store.is_shared() {
+        if let Err(e) = state.store.contains("startup") {
+            tracing::error!(error = %e, "shared store unreachable; single-use proofs get 503 until it answers");
+        }
+    }
+    if state.anchor.is_some() {
+        actix_web::rt::spawn(run_anchoring(state.clone()));
+    }
+    if state.replication.is_some() {
+        actix_web::rt::spawn(run_replication(state.clone()));
+    }
+    if state.webhook.is_some() {
+        actix_web::rt::spawn(run_webhooks(state.clone()));
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(grpc) = &state.config.grpc {
+        if !state.config.mode.verifies() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "the gRPC service verifies; this node does not"));
+        }
+        actix_web::rt::spawn(serve_grpc(state.clone(), grpc)?);
+        tracing::info!(bind = %grpc.bind, "gRPC verification listening");
+    }
+    if state.config.mode.proves() && !state.config.replica {
+        resume_proof_jobs(&state);
+        actix_web::rt::spawn(run_job_gc(state.clone()));
+    }
+    let mut server = HttpServer::new(move || {
+        let logger = access_log.clone().map(AccessLog::new);
+        App::new()
+            .wrap(Condition::new(recorder.is_some(), recorder.clone().unwrap_or_default()))
+            .wrap(Condition::new(logger.is_some(), logger.unwrap_or_else(|| AccessLog::new(Default::default()))))
+            // gzip, brotli or zstd, whichever the client's `Accept-Encoding` prefers.
+            .wrap(Compress::default())
+            .service(zk_auth_scope_with_state(state.clone()))
+    })
+        .keep_alive(tuning.keep_alive)
+        .client_request_timeout(tuning.client_request_timeout)
+        .client_disconnect_timeout(tuning.client_disconnect_timeout)
+        .h1_allow_half_closed(tuning.h1_allow_half_closed);
+    if let Some(workers) = tuning.workers {
+        server = server.workers(workers);
+    }
+    let server = if tuning.http2 { server.listen_auto_h2c(listener)? } else { server.listen(listener)? };
+    Ok(server.run())
+}
+
+/// One structured record with everything needed to identify a deployment.
+fn log_startup(listener: &TcpListener, state: &AppState) {
+    let fp = state.artifacts.fingerprints();
+    let config = state.config();
+    tracing::info!(
+        bind = %listener.local_addr().map(|a| a.to_string()).unwrap_or_default(),
+        version = env!("CARGO_PKG_VERSION"),
+        git_hash = GIT_HASH,
+        mode = ?config.mode,
+        attributes = ?config.attributes,
+        circuit_id = fp.circuit_id,
+        wasm_sha256 = fp.wasm_sha256.as_deref().unwrap_or("-"),
+        r1cs_sha256 = fp.r1cs_sha256.as_deref().unwrap_or("-"),
+        zkey_sha256 = fp.zkey_sha256.as_deref().unwrap_or("-"),
+        vk_file_sha256 = fp.vk_file_sha256.as_deref().unwrap_or("-"),
+        prove_permits = config.concurrency.prove,
+        verify_permits = config.concurrency.verify,
+        register_permits = config.concurrency.register,
+        prove_rate = ?config.prove_rate.as_ref().map(|r| r.max_per_sec),
+        challenge_rate = ?config.challenge_rate,
+        prove_deadline_ms = ?config.prove_deadline.map(|d| d.as_millis() as u64),
+        workers = ?config.server.workers,
+        http2 = config.server.http2,
+        access_log = config.access_log.is_some(),
+        record = ?config.record.as_ref().map(|r| r.path.display().to_string()),
+        rotation_cutover = ?config.rotation.as_ref().map(|r| r.cutover),
+        historical_vks = config.historical_vks.len(),
+        receipt_key_id = state.receipts.key_id(),
+        tee = state.attester.as_ref().map(|a| a.name()).unwrap_or("-"),
+        vrf_nonces = state.vrf.is_some(),
+        prewarm_ttl_secs = ?config.prewarm.as_ref().map(|p| p.ttl.as_secs()),
+        replica = config.replica,
+        replicate_from = config.replication.as_ref().map(|r| r.primary.as_str()).unwrap_or("-"),
+        webhook = config.webhook.as_ref().map(|w| w.url.as_str()).unwrap_or("-"),
+        anchor_signer = state.anchor.as_ref().map(|a| a.signer()).unwrap_or_default(),
+        ipfs_gateway = config.ipfs.as_ref().map(|i| i.gateway.as_str()).unwrap_or("-"),
+        features = ?enabled_features(),
+        "zk-auth server starting"
+    );
+    if cfg!(feature = "deterministic") {
+        tracing::warn!("built with `deterministic`: /generate-proof accepts caller seeds; not for production");
+    }
+    if state.mock {
+        tracing::warn!("mock prover enabled: /generate-proof returns fake proofs that /verify-proof accepts; not for production");
+    }
+    if cfg!(feature = "witness-debug") {
+        tracing::warn!("built with `witness-debug`: /generate-proof can return witnesses; not for production");
+    }
+}
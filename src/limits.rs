@@ -0,0 +1,249 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+
+use crate::config::{AdaptiveRate, ConcurrencyLimits};
+use crate::error::ApiError;
+use crate::store::Store;
+
+//--------------------------------------------------------------------
+// Per-endpoint concurrency limits
+//--------------------------------------------------------------------
+/// One limiter per endpoint class so a burst on one (typically proving)
+/// cannot starve the others.
+pub(crate) struct Limits {
+    pub(crate) prove: Limiter,
+    pub(crate) verify: Limiter,
+    pub(crate) register: Limiter,
+    prove_rate: Option<RateGate>,
+}
+
+impl Limits {
+    pub(crate) fn new(config: &ConcurrencyLimits, prove_rate: Option<AdaptiveRate>) -> Self {
+        Self {
+            prove: Limiter::new("prove", config.prove),
+            verify: Limiter::new("verify", config.verify),
+            register: Limiter::new("register", config.register),
+            prove_rate: prove_rate.map(RateGate::new),
+        }
+    }
+
+    /// Call before queueing for [`Limits::prove`]; see [`AdaptiveRate`].
+    /// Counts admissions in `store` when it is shared.
+    pub(crate) fn admit_prove(&self, store: &dyn Store) -> Result<(), ApiError> {
+        let Some(gate) = &self.prove_rate else { return Ok(()) };
+        let queued = self.prove.waiting.load(Ordering::Relaxed);
+        let admitted = if store.is_shared() { gate.admit_shared(queued, store) } else { gate.admit(queued) };
+        admitted.map_err(|retry_after_secs| ApiError::RateLimited { retry_after_secs })
+    }
+
+    /// The limiter for `"prove"`, `"verify"` or `"register"`.
+    pub(crate) fn by_name(&self, name: &str) -> &Limiter {
+        match name {
+            "prove" => &self.prove,
+            "verify" => &self.verify,
+            "register" => &self.register,
+            _ => panic!("unknown limiter {name}"),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> Vec<LimiterStats> {
+        let mut prove = self.prove.stats();
+        if let Some(gate) = &self.prove_rate {
+            prove.rate_per_sec = Some(gate.rate(prove.waiting));
+            prove.rate_limited = gate.rejected.load(Ordering::Relaxed);
+        }
+        vec![prove, self.verify.stats(), self.register.stats()]
+    }
+}
+
+/// A semaphore that also counts how many callers are queued on it.
+pub(crate) struct Limiter {
+    name: &'static str,
+    /// Permits in circulation; trails a shrink until it has drained.
+    capacity: AtomicUsize,
+    sem: Semaphore,
+    /// Serialises resizes so concurrent ones cannot interleave.
+    resizing: Mutex<()>,
+    waiting: AtomicUsize,
+    peak_waiting: AtomicUsize,
+    admitted: AtomicU64,
+}
+
+impl Limiter {
+    fn new(name: &'static str, capacity: usize) -> Self {
+        Self {
+            name,
+            capacity: AtomicUsize::new(capacity),
+            sem: Semaphore::new(capacity),
+            resizing: Mutex::new(()),
+            waiting: AtomicUsize::new(0),
+            peak_waiting: AtomicUsize::new(0),
+            admitted: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) async fn acquire(&self) -> SemaphorePermit<'_> {
+        let queued = QueueGuard::enter(self);
+        let permit = self.sem.acquire().await.expect("limiter semaphore is never closed");
+        drop(queued);
+        self.admitted.fetch_add(1, Ordering::Relaxed);
+        permit
+    }
+
+    /// A permit only if one is free and nobody is queued, for background
+    /// work that must never delay a request.
+    pub(crate) fn try_acquire_idle(&self) -> Option<SemaphorePermit<'_>> {
+        if self.waiting.load(Ordering::Relaxed) > 0 {
+            return None;
+        }
+        let permit = self.sem.try_acquire().ok()?;
+        self.admitted.fetch_add(1, Ordering::Relaxed);
+        Some(permit)
+    }
+
+    /// Take every permit, e.g. to simulate a saturated queue.
+    #[cfg(feature = "chaos")]
+    pub(crate) async fn acquire_all(&self) -> SemaphorePermit<'_> {
+        self.sem.acquire_many(self.capacity() as u32).await.expect("limiter semaphore is never closed")
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Change the number of permits.  Growing takes effect at once.
+    /// Shrinking takes the surplus permits out of circulation as in-flight
+    /// work releases them, so it may wait; requests already queued are
+    /// admitted in order behind it.
+    pub(crate) async fn resize(&self, target: usize) {
+        let _serial = self.resizing.lock().await;
+        let current = self.capacity();
+        if target > current {
+            self.sem.add_permits(target - current);
+        } else if target < current {
+            let surplus = (current - target) as u32;
+            self.sem.acquire_many(surplus).await.expect("limiter semaphore is never closed").forget();
+        }
+        self.capacity.store(target, Ordering::Relaxed);
+        tracing::info!(endpoint = self.name, from = current, to = target, "limiter resized");
+    }
+
+    pub(crate) fn stats(&self) -> LimiterStats {
+        let capacity = self.capacity();
+        LimiterStats {
+            endpoint: self.name,
+            capacity,
+            in_use: capacity.saturating_sub(self.sem.available_permits()),
+            waiting: self.waiting.load(Ordering::Relaxed),
+            peak_waiting: self.peak_waiting.load(Ordering::Relaxed),
+            admitted: self.admitted.load(Ordering::Relaxed),
+            rate_per_sec: None,
+            rate_limited: 0,
+        }
+    }
+}
+
+/// Keeps `waiting` accurate even when the caller's future is dropped while
+/// still queued (e.g. the client disconnected).
+struct QueueGuard<'a>(&'a Limiter);
+
+impl<'a> QueueGuard<'a> {
+    fn enter(limiter: &'a Limiter) -> Self {
+        let now = limiter.waiting.fetch_add(1, Ordering::Relaxed) + 1;
+        limiter.peak_waiting.fetch_max(now, Ordering::Relaxed);
+        Self(limiter)
+    }
+}
+
+impl Drop for QueueGuard<'_> {
+    fn drop(&mut self) {
+        self.0.waiting.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Point-in-time view of one limiter.
+#[derive(Clone, Debug, Serialize)]
+pub struct LimiterStats {
+    pub endpoint: &'static str,
+    pub capacity: usize,
+    pub in_use: usize,
+    pub waiting: usize,
+    pub peak_waiting: usize,
+    pub admitted: u64,
+    /// Current admission rate, for limiters behind an [`AdaptiveRate`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_per_sec: Option<f64>,
+    /// Requests turned away by the admission rate.
+    pub rate_limited: u64,
+}
+
+//--------------------------------------------------------------------
+// Adaptive admission rate
+//--------------------------------------------------------------------
+/// Length of the windows a shared admission count is kept in.  Long enough
+/// that a rate below one per second still admits some requests.
+const SHARED_WINDOW: Duration = Duration::from_secs(10);
+
+/// Token bucket whose refill rate follows the queue depth.
+struct RateGate {
+    config: AdaptiveRate,
+    bucket: std::sync::Mutex<Bucket>,
+    rejected: AtomicU64,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateGate {
+    fn new(config: AdaptiveRate) -> Self {
+        let bucket = Bucket { tokens: config.max_per_sec.max(1.0), refilled: Instant::now() };
+        Self { config, bucket: std::sync::Mutex::new(bucket), rejected: AtomicU64::new(0) }
+    }
+
+    fn rate(&self, queued: usize) -> f64 {
+        let target = self.config.target_queue.max(1);
+        let over = queued.saturating_sub(target) as f64 / target as f64;
+        (self.config.max_per_sec * 0.5f64.powf(over)).max(self.config.min_per_sec)
+    }
+
+    /// Take a token, or return the seconds until one is due.
+    fn admit(&self, queued: usize) -> Result<(), u64> {
+        let rate = self.rate(queued);
+        let mut bucket = self.bucket.lock().expect("rate bucket poisoned");
+        let now = Instant::now();
+        let burst = self.config.max_per_sec.max(1.0);
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.refilled).as_secs_f64() * rate).min(burst);
+        bucket.refilled = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        Err(((1.0 - bucket.tokens) / rate).ceil().max(1.0) as u64)
+    }
+
+    /// [`RateGate::admit`] against a count shared by every replica, kept in
+    /// fixed windows.  The rate then applies to the fleet, while each node
+    /// still derives it from its own queue.  Falls back to this node's
+    /// bucket if the store fails.
+    fn admit_shared(&self, queued: usize, store: &dyn Store) -> Result<(), u64> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let window = now - now % SHARED_WINDOW.as_secs();
+        let allowed = (self.rate(queued) * SHARED_WINDOW.as_secs_f64()).floor().max(1.0) as u64;
+        match store.increment(&format!("rate:prove:{window}"), SHARED_WINDOW) {
+            Ok(count) if count <= allowed => Ok(()),
+            Ok(_) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                Err((window + SHARED_WINDOW.as_secs() - now).max(1))
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "shared rate counter failed; using this node's");
+                self.admit(queued)
+            }
+        }
+    }
+}
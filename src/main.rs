@@ -1,236 +1,419 @@
+use std::collections::BTreeMap;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+use tracing_subscriber::EnvFilter;
+use zk_auth_api::{
+    activated_listener, convert_path, open_backup, read_backup_key, restore, encode_proof, export_bundle, export_verifying_key, run, verify_bundle, AccessLogConfig, AdaptiveRate, AnchorConfig,
+    AppConfig, ArtifactSigning, AttributeEncoding, ClaimPolicy, CredentialConfig, AttributeRules, CaseFold, EmailCanonicalization, PlusAddressing, IpfsConfig, JobStoreConfig, KeylessIdentity, KeyRotation, MockProver, Mode, PrewarmConfig, ProofBundle, ProofStoreConfig, RecordConfig, ReplicationConfig, SessionConfig, WebhookConfig,
+};
 
-use actix_web::{post, web, App, HttpResponse, HttpServer, Responder};
-use ark_bn254::{Bn254, Fr, Fq, Fq2, G1Affine, G2Affine};
-use ark_circom::{read_zkey, CircomBuilder, CircomConfig, CircomReduction};
-use ark_ff::{BigInteger, PrimeField};
-use ark_groth16::{Groth16, ProvingKey, VerifyingKey, Proof, prepare_verifying_key};
-use ark_snark::SNARK;
-use hex::encode as hex_encode;
-use num_bigint::{BigInt, BigUint, Sign};
-use once_cell::sync::Lazy;
-use rand::RngCore;
-use rand::thread_rng;
-
-use rand::rngs::SmallRng;
-use rand::SeedableRng;
-use serde::{Deserialize, Serialize};
-use sha3::{Digest, Keccak256};
-use light_poseidon::{Poseidon, PoseidonHasher};         
-use std::{fs::File, io::BufReader, path::PathBuf};
-use std::sync::{Mutex};
-use tokio::sync::Semaphore;
+const USAGE: &str = "\
+usage: zk-auth-api [--replica] [--verify-only <vk.bin> | --prove-only]
+       zk-auth-api export-vk <vk.bin> [zkey]
+       zk-auth-api convert <hex|snarkjs|gnark|ark> <hex|snarkjs|gnark|ark> <in> <out>
+       zk-auth-api replay <recording.ndjson> [base-url]
+       zk-auth-api export <proof.json> <out.zkauth> [vk.bin] [key=value ...]
+       zk-auth-api import <in.zkauth> <out> [hex|snarkjs|gnark|ark]
+       zk-auth-api verify-bundle <in.zkauth> [vk.bin ...]
+       zk-auth-api backup <out.zkbak>            (key in ZK_BACKUP_KEY)
+       zk-auth-api restore <in.zkbak> <dir>      (key in ZK_BACKUP_KEY)
+       zk-auth-api conformance [snarkjs-cmd]   (feature `conformance`)";
 
 //--------------------------------------------------------------------
-// Static artefacts
-//--------------------------------------------------------------------
-static CIRCUIT_PATH: Lazy<(PathBuf, PathBuf, PathBuf)> = Lazy::new(|| {
-    let mut root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    root.push("circuits/secret-proof");
-    (
-        root.join("secret-proof_js/secret-proof.wasm"),
-        root.join("secret-proof.r1cs"),
-        root.join("secret_final.zkey"),
-    )
-});
-
-static PROVING_KEY: Lazy<ProvingKey<Bn254>> = Lazy::new(|| {
-    let (_, _, zkey) = &*CIRCUIT_PATH;
-    let mut rd = BufReader::new(File::open(zkey).expect("proving key missing"));
-    read_zkey(&mut rd).expect("invalid zkey").0
-});
-static VERIFYING_KEY: Lazy<VerifyingKey<Bn254>> = Lazy::new(|| {
-    let (_, _, zkey) = &*CIRCUIT_PATH;
-    let mut rd = BufReader::new(File::open(zkey).expect("vk missing"));
-    let (pk, _) = read_zkey(&mut rd).expect("invalid zkey");
-    pk.vk.clone()
-});
-
-use ark_groth16::PreparedVerifyingKey;
-static PROC_VERIFY_KEY: Lazy<PreparedVerifyingKey<Bn254>> = Lazy::new(|| {
-    prepare_verifying_key(&*VERIFYING_KEY)
-});
-
-static PROOF_SEM: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(4));
-
-//--------------------------------------------------------------------
-// DTOs (unchanged)
-//--------------------------------------------------------------------
-#[derive(Deserialize)]  struct RegisterRequest { email:String, name:String, age:u32, country:String, dob:String }
-#[derive(Serialize)]    struct RegisterResponse { secret:String, nonce:String, commitment:String }
-#[derive(Deserialize)]  struct ProofRequest { secret_hex:String, commitment:String }
-#[derive(Serialize,Deserialize)] struct ProofJson { a:[String;2], b:[[String;2];2], c:[String;2] }
-#[derive(Serialize)]    struct ProofResponse { proof:ProofJson }
-#[derive(Deserialize)]  struct VerifyRequest { commitment:String, proof:ProofJson }
-#[derive(Serialize)]    struct VerifyResponse { valid:bool }
-
-//--------------------------------------------------------------------
-// Poseidon Hashers
+// main
 //--------------------------------------------------------------------
-/// Poseidon hashers keyed by input length.  Creating a Poseidon instance
-/// involves computing constants and s‑box parameters; reusing them
-/// eliminates that overhead on every call.  The hasher objects are
-/// wrapped in `Arc` because `Poseidon` is not `Copy` and cloning it
-/// performs a cheap reference count increase.
-// Poseidon hashers keyed by input length.  The hash method on Poseidon
-// requires a mutable reference.  We wrap each hasher in a Mutex to allow
-// mutable access even though the static is immutable.  Using a Mutex here is
-// acceptable because hashing is extremely fast relative to the cost of
-// acquiring a lock.
-static POSEIDON_1: Lazy<Mutex<Poseidon<Fr>>> = Lazy::new(|| {
-    Mutex::new(Poseidon::<Fr>::new_circom(1).expect("poseidon init"))
-});
-static POSEIDON_2: Lazy<Mutex<Poseidon<Fr>>> = Lazy::new(|| {
-    Mutex::new(Poseidon::<Fr>::new_circom(2).expect("poseidon init"))
-});
-static POSEIDON_5: Lazy<Mutex<Poseidon<Fr>>> = Lazy::new(|| {
-    Mutex::new(Poseidon::<Fr>::new_circom(5).expect("poseidon init"))
-});
+#[actix_web::main]
+async fn main()->std::io::Result<()>{
+    init_logging();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // The file gives the base settings; environment variables override it.
+    let base = match std::env::var_os("ZK_CONFIG") {
+        Some(path) => AppConfig::from_file(path.as_ref())?,
+        None => AppConfig::default(),
+    };
+    let mut config = AppConfig { access_log: access_log_from_env(), rotation: rotation_from_env(), ..base };
+    artifact_paths_from_env(&mut config);
+    config.receipt_key = std::env::var_os("ZK_RECEIPT_KEY").map(PathBuf::from);
+    config.session = session_from_env();
+    config.credentials = credentials_from_env();
+    config.claim_policy = claim_policy_from_env();
+    // Comma-separated, like ZK_CLAIMS_ISSUERS.
+    if let Some(audiences) = std::env::var("ZK_AUDIENCES").ok().filter(|v| !v.is_empty()) {
+        config.audiences = audiences.split(',').map(|a| a.trim().to_owned()).collect();
+    }
+    #[cfg(feature = "redis")]
+    {
+        config.redis = redis_from_env();
+    }
+    #[cfg(feature = "grpc")]
+    {
+        config.grpc = std::env::var("ZK_GRPC_BIND").ok().and_then(|b| b.parse().ok()).map(|bind| zk_auth_api::GrpcConfig { bind });
+    }
+    config.admin_token = std::env::var("ZK_ADMIN_TOKEN").ok().filter(|t| !t.is_empty());
+    config.ipfs = ipfs_from_env();
+    config.anchor = anchor_from_env();
+    config.replication = replication_from_env();
+    config.webhook = std::env::var("ZK_WEBHOOK_URL").ok().filter(|u| !u.is_empty()).map(|url| WebhookConfig { url, ..Default::default() });
+    config.vrf_key = std::env::var_os("ZK_VRF_KEY").map(PathBuf::from);
+    config.prove_rate = prove_rate_from_env();
+    // On unless set to 0: every challenge takes a store entry for a minute.
+    config.challenge_rate = match std::env::var("ZK_CHALLENGE_RATE").ok().and_then(|r| r.parse().ok()) {
+        Some(0) => None,
+        Some(rate) => Some(rate),
+        None => Some(100),
+    };
+    config.prewarm = prewarm_from_env();
+    config.proof_store = std::env::var_os("ZK_PROOF_STORE").map(|dir| ProofStoreConfig { dir: PathBuf::from(dir) });
+    config.record = record_from_env();
+    config.job_store = std::env::var_os("ZK_JOB_STORE").map(|dir| JobStoreConfig { dir: PathBuf::from(dir) });
+    // Seconds, like ZK_CREDENTIAL_TTL.
+    for (var, slot) in [("ZK_JOB_DONE_TTL", &mut config.job_retention.done), ("ZK_JOB_FAILED_TTL", &mut config.job_retention.failed)] {
+        if let Some(secs) = std::env::var(var).ok().and_then(|s| s.parse().ok()) {
+            *slot = Duration::from_secs(secs);
+        }
+    }
+    // Release builds also need ZK_MOCK_PROVER_IN_RELEASE=1; see MockProver.
+    if std::env::var("ZK_MOCK_PROVER").is_ok_and(|v| v == "1") {
+        config.mock_prover = Some(MockProver { allow_in_release: std::env::var("ZK_MOCK_PROVER_IN_RELEASE").is_ok_and(|v| v == "1") });
+    }
+    config.require_registered = std::env::var("ZK_REQUIRE_REGISTERED").is_ok_and(|v| v == "1");
+    config.prove_deadline = std::env::var("ZK_PROVE_DEADLINE_MS").ok().and_then(|ms| ms.parse().ok()).map(Duration::from_millis);
+    // Colon-separated, newest first, like PATH.
+    if let Some(paths) = std::env::var_os("ZK_HISTORICAL_VKS") {
+        config.historical_vks = std::env::split_paths(&paths).collect();
+    }
+    config.artifact_signing = artifact_signing_from_env()?;
+    config.attributes = attribute_rules_from_env();
+    // Defaults to one proof per core, as for embedders of the library.
+    for (var, slot) in [
+        ("ZK_PROVE_CONCURRENCY", &mut config.concurrency.prove),
+        ("ZK_VERIFY_CONCURRENCY", &mut config.concurrency.verify),
+        ("ZK_REGISTER_CONCURRENCY", &mut config.concurrency.register),
+    ] {
+        if let Some(n) = std::env::var(var).ok().and_then(|n| n.parse().ok()) {
+            *slot = n;
+        }
+    }
 
-//--------------------------------------------------------------------
-// Helpers 
-//--------------------------------------------------------------------
-fn country_u16(code:&str)->u16{let b=code.as_bytes();((b.get(0).copied().unwrap_or(0)as u16)<<8)|(b.get(1).copied().unwrap_or(0)as u16)}
-
-/// Compute Poseidon hash using a cached hasher based on the number of inputs.
-fn poseidon_hash(inputs:&[Fr])->Fr {
-    match inputs.len() {
-        1 => {
-            let mut h = POSEIDON_1.lock().unwrap();
-            h.hash(inputs).expect("poseidon hash")
+    let mut args: Vec<&str> = args.iter().map(String::as_str).collect();
+    if args.first() == Some(&"--replica") {
+        args.remove(0);
+        config.replica = true;
+    }
+    match args.as_slice() {
+        [] => {}
+        ["--verify-only", vk] => config.mode = Mode::VerifyOnly { verifying_key: PathBuf::from(vk) },
+        ["--prove-only"] => config.mode = Mode::ProveOnly,
+        ["export-vk", out, rest @ ..] if rest.len() <= 1 => {
+            let zkey = rest.first().map(PathBuf::from).unwrap_or_else(|| config.circuit.zkey.clone());
+            export_verifying_key(&zkey, out.as_ref())?;
+            tracing::info!(%out, zkey = %zkey.display(), "verifying key exported");
+            return Ok(());
+        }
+        ["convert", from, to, input, output] => {
+            let count = convert_path(from.parse()?, to.parse()?, input.as_ref(), output.as_ref())?;
+            tracing::info!(count, %from, %to, "proofs converted");
+            return Ok(());
+        }
+        ["export", input, output, rest @ ..] => {
+            let (metadata, vk): (Vec<&str>, Vec<&str>) = rest.iter().partition(|arg| arg.contains('='));
+            if vk.len() > 1 {
+                eprintln!("{USAGE}");
+                std::process::exit(2);
+            }
+            let metadata = metadata.iter().filter_map(|kv| kv.split_once('=')).map(|(k, v)| (k.to_owned(), v.to_owned())).collect();
+            let bundle = export_bundle(input.as_ref(), output.as_ref(), vk.first().map(|vk| vk.as_ref()), metadata)?;
+            tracing::info!(%output, vk_fingerprint = %bundle.vk_fingerprint, "proof bundle written");
+            return Ok(());
+        }
+        ["import", input, output, rest @ ..] if rest.len() <= 1 => {
+            let format = rest.first().copied().unwrap_or("hex").parse()?;
+            let bundle = ProofBundle::from_bytes(&std::fs::read(input)?)?;
+            std::fs::write(output, encode_proof(format, &bundle.record()?)?)?;
+            tracing::info!(%output, vk_fingerprint = %bundle.vk_fingerprint, metadata = ?bundle.metadata, "proof imported");
+            return Ok(());
         }
-        2 => {
-            let mut h = POSEIDON_2.lock().unwrap();
-            h.hash(inputs).expect("poseidon hash")
+        ["verify-bundle", input, vks @ ..] => {
+            let bundle = ProofBundle::from_bytes(&std::fs::read(input)?)?;
+            let vks: Vec<PathBuf> = vks.iter().map(PathBuf::from).collect();
+            let valid = verify_bundle(&bundle, &vks)?;
+            tracing::info!(valid, vk_fingerprint = %bundle.vk_fingerprint, created_at = bundle.created_at, metadata = ?bundle.metadata, "bundle verified");
+            std::process::exit(if valid { 0 } else { 1 });
         }
-        5 => {
-            let mut h = POSEIDON_5.lock().unwrap();
-            h.hash(inputs).expect("poseidon hash")
+        ["backup", output] => {
+            let proof_store = config.proof_store.as_ref().map(|p| p.dir.as_path());
+            let archive = zk_auth_api::backup(output.as_ref(), &backup_key()?, backup_config(), proof_store)?;
+            tracing::info!(%output, proofs = archive.proofs.len(), settings = archive.config.len(), "backup written");
+            return Ok(());
+        }
+        ["restore", input, dir] => {
+            let archive = open_backup(input.as_ref(), &backup_key()?)?;
+            restore(&archive, dir.as_ref())?;
+            tracing::info!(%dir, created_at = archive.created_at, proofs = archive.proofs.len(), "backup restored");
+            return Ok(());
+        }
+        ["replay", recording, rest @ ..] if rest.len() <= 1 => {
+            let base_url = rest.first().copied().unwrap_or("http://127.0.0.1:8080");
+            let report = zk_auth_api::replay(recording.as_ref(), base_url).await?;
+            for m in &report.mismatches {
+                tracing::warn!(line = m.line, uri = %m.uri, reason = %m.reason, "replay mismatch");
+            }
+            tracing::info!(replayed = report.replayed, skipped = report.skipped, mismatches = report.mismatches.len(), "replay finished");
+            std::process::exit(if report.passed() { 0 } else { 1 });
+        }
+        #[cfg(feature = "conformance")]
+        ["conformance", rest @ ..] if rest.len() <= 1 => {
+            let report = zk_auth_api::run_conformance(&config, rest.first().copied().unwrap_or("npx snarkjs"))?;
+            for case in &report.cases {
+                tracing::info!(case = case.name, expected = case.expected, in_process = case.in_process, snarkjs = case.snarkjs);
+            }
+            tracing::info!(dir = %report.dir.display(), passed = report.passed(), "conformance finished");
+            std::process::exit(if report.passed() { 0 } else { 1 });
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
         }
-        _ => Poseidon::<Fr>::new_circom(inputs.len()).unwrap().hash(inputs).unwrap(),
     }
-}
 
-fn fq_to_hex(f:&Fq)->String{let mut bytes=f.into_bigint().to_bytes_be();if bytes.len()<32{bytes=[vec![0u8;32-bytes.len()],bytes].concat();}format!("0x{}",hex_encode(bytes))}
-fn fq2_to_hex(f2:&Fq2)->(String,String){(fq_to_hex(&f2.c0),fq_to_hex(&f2.c1))}
-fn g1_to_hex(p:&G1Affine)->(String,String){(fq_to_hex(&p.x),fq_to_hex(&p.y))}
-fn g2_to_hex(p:&G2Affine)->(String,String,String,String){let(x0,x1)=fq2_to_hex(&p.x);let(y0,y1)=fq2_to_hex(&p.y);(x1,x0,y1,y0)}
-fn fq_from_hex(h:&str)->Fq{let mut bytes=hex::decode(h.trim_start_matches("0x")).unwrap();if bytes.len()<32{bytes=[vec![0u8;32-bytes.len()],bytes].concat();}Fq::from_be_bytes_mod_order(&bytes)}
+    let listener = match activated_listener()? {
+        Some(listener) => listener,
+        None => TcpListener::bind(config.server.bind)?,
+    };
+    run(listener, config)?.await
+}
 
-//--------------------------------------------------------------------
-// /register
-//--------------------------------------------------------------------
-#[post("/register")]
-async fn register(body:web::Json<RegisterRequest>)->impl Responder{
-    // Compute keccak hashes of email and name; convert to field elements.
-    let mut k=Keccak256::new();k.update(body.email.to_lowercase());let email_hash=Fr::from_be_bytes_mod_order(&k.finalize());
-    let mut k=Keccak256::new();k.update(body.name.trim());let name_hash=Fr::from_be_bytes_mod_order(&k.finalize());
+/// `ZK_WASM`, `ZK_R1CS`, `ZK_ZKEY` and `ZK_BIND`, for deployments that keep
+/// the circuit outside the source tree.
+fn artifact_paths_from_env(config: &mut AppConfig) {
+    for (var, slot) in [("ZK_WASM", &mut config.circuit.wasm), ("ZK_R1CS", &mut config.circuit.r1cs), ("ZK_ZKEY", &mut config.circuit.zkey)] {
+        if let Some(path) = std::env::var_os(var) {
+            *slot = PathBuf::from(path);
+        }
+    }
+    if let Some(bind) = std::env::var("ZK_BIND").ok().and_then(|b| b.parse().ok()) {
+        config.server.bind = bind;
+    }
+}
 
-    let age_fe=Fr::from(body.age as u64);
-    let country_fe=Fr::from(country_u16(&body.country) as u64);
-    let dob_fe=Fr::from(body.dob.replace('-',"").parse::<u64>().unwrap_or(0));
+/// The key file named by `ZK_BACKUP_KEY`: 32 bytes of hex.
+fn backup_key() -> std::io::Result<[u8; 32]> {
+    let path = std::env::var_os("ZK_BACKUP_KEY").ok_or_else(|| std::io::Error::other("ZK_BACKUP_KEY is not set"))?;
+    read_backup_key(path.as_ref())
+}
 
-    let user_hash=poseidon_hash(&[email_hash,name_hash,age_fe,country_fe,dob_fe]);
+/// The settings a backup records: every `ZK_*` and `ACCESS_LOG_*`
+/// variable except the admin token.  Key files are named, not read.
+fn backup_config() -> BTreeMap<String, String> {
+    std::env::vars()
+        .filter(|(k, _)| (k.starts_with("ZK_") || k.starts_with("ACCESS_LOG_")) && k != "ZK_ADMIN_TOKEN")
+        .collect()
+}
 
-    let mut nonce = [0u8; 16];
-    thread_rng().fill_bytes(&mut nonce);
-    let nonce_fe=Fr::from_be_bytes_mod_order(&{let mut pad=[0u8;32];pad[16..].copy_from_slice(&nonce);pad});
+/// Replication is off unless `ZK_REPLICATE_FROM` names the primary;
+/// `ZK_REPLICATE_INTERVAL_MS` (default 2000) sets the poll interval.
+fn replication_from_env() -> Option<ReplicationConfig> {
+    let primary = std::env::var("ZK_REPLICATE_FROM").ok().filter(|p| !p.is_empty())?;
+    let defaults = ReplicationConfig::default();
+    let interval = std::env::var("ZK_REPLICATE_INTERVAL_MS").ok().and_then(|ms| ms.parse().ok()).map(Duration::from_millis);
+    Some(ReplicationConfig { primary, interval: interval.unwrap_or(defaults.interval), ..defaults })
+}
 
-    let secret_fe=poseidon_hash(&[user_hash,nonce_fe]);
-    let commitment_fe=poseidon_hash(&[secret_fe]);
+/// Access logging is off unless `ACCESS_LOG_SAMPLE_RATE` is set;
+/// `ACCESS_LOG_BODIES=1` additionally captures redacted bodies.
+fn access_log_from_env() -> Option<AccessLogConfig> {
+    let sample_rate = std::env::var("ACCESS_LOG_SAMPLE_RATE").ok()?.parse().ok()?;
+    let capture_bodies = std::env::var("ACCESS_LOG_BODIES").is_ok_and(|v| v == "1" || v == "true");
+    Some(AccessLogConfig { sample_rate, capture_bodies, ..AccessLogConfig::default() })
+}
 
-    let secret_hex={let mut b=secret_fe.into_bigint().to_bytes_be();if b.len()<32{b=[vec![0u8;32-b.len()],b].concat();}format!("0x{}",hex_encode(b))};
-    let nonce_hex=format!("0x{}",hex_encode(nonce));
-    let commitment_dec=BigUint::from_bytes_be(&commitment_fe.into_bigint().to_bytes_be()).to_string();
+/// Traffic recording is off unless `ZK_RECORD_PATH` is set;
+/// `ZK_RECORD_SAMPLE_RATE` (default 1.0) records a fraction of requests.
+fn record_from_env() -> Option<RecordConfig> {
+    let path = PathBuf::from(std::env::var_os("ZK_RECORD_PATH")?);
+    let defaults = RecordConfig::default();
+    let sample_rate = std::env::var("ZK_RECORD_SAMPLE_RATE").ok().and_then(|v| v.parse().ok()).unwrap_or(defaults.sample_rate);
+    Some(RecordConfig { path, sample_rate, ..defaults })
+}
 
-    HttpResponse::Ok().json(RegisterResponse{secret:secret_hex,nonce:nonce_hex,commitment:commitment_dec})
+/// `ZK_NEXT_KEY` stages a replacement key that takes over at `ZK_CUTOVER`
+/// (Unix seconds), with `ZK_ROTATION_OVERLAP` seconds (default 300) either
+/// side during which both keys verify.
+fn rotation_from_env() -> Option<KeyRotation> {
+    let next_key = PathBuf::from(std::env::var_os("ZK_NEXT_KEY")?);
+    let cutover = std::env::var("ZK_CUTOVER").ok()?.parse().ok()?;
+    let overlap = std::env::var("ZK_ROTATION_OVERLAP").ok().and_then(|v| v.parse().ok()).unwrap_or(300);
+    Some(KeyRotation {
+        next_key,
+        cutover: UNIX_EPOCH + Duration::from_secs(cutover),
+        overlap: Duration::from_secs(overlap),
+    })
 }
 
-//--------------------------------------------------------------------
-// /generate-proof 
-//--------------------------------------------------------------------
-#[post("/generate-proof")]
-async fn generate_proof(body: web::Json<ProofRequest>) -> impl Responder {
-    // ---------- Parse inputs (cheap) ----------
-    let secret_fe = {
-        let bytes = hex::decode(body.secret_hex.trim_start_matches("0x")).unwrap();
-        Fr::from_be_bytes_mod_order(&{
-            let mut pad = [0u8; 32];
-            pad[32 - bytes.len()..].copy_from_slice(&bytes);
-            pad
-        })
-    };
-    let commitment_fe = {
-        let dec = BigUint::parse_bytes(body.commitment.as_bytes(), 10).unwrap();
-        Fr::from_be_bytes_mod_order(&{
-            let mut b = dec.to_bytes_be();
-            if b.len() < 32 { b = [vec![0u8; 32 - b.len()], b].concat(); }
-            b
-        })
+/// IPFS support is off unless `ZK_IPFS_GATEWAY` (CID fetching) or
+/// `ZK_IPFS_API` (publishing) is set; `ZK_IPFS_MAX_BYTES` overrides the
+/// 1 MiB fetch cap.
+/// Artefact signatures are checked when `ZK_COSIGN_KEYS` (PEM public keys,
+/// path-separated) or `ZK_COSIGN_IDENTITY` is set.  Keyless signing also
+/// needs `ZK_COSIGN_ROOTS` (Fulcio PEM bundle) and `ZK_COSIGN_ISSUER`;
+/// `ZK_COSIGN_STRICT=1` refuses unsigned artefacts.
+fn artifact_signing_from_env() -> std::io::Result<Option<ArtifactSigning>> {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    let required = |name| var(name).ok_or_else(|| std::io::Error::other(format!("ZK_COSIGN_IDENTITY needs {name}")));
+    let keys: Vec<PathBuf> = std::env::var_os("ZK_COSIGN_KEYS").map(|p| std::env::split_paths(&p).collect()).unwrap_or_default();
+    let keyless = match var("ZK_COSIGN_IDENTITY") {
+        Some(identity) => Some(KeylessIdentity { roots: PathBuf::from(required("ZK_COSIGN_ROOTS")?), identity, issuer: required("ZK_COSIGN_ISSUER")? }),
+        None => None,
     };
+    if keys.is_empty() && keyless.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(ArtifactSigning { keys, keyless, strict: var("ZK_COSIGN_STRICT").as_deref() == Some("1") }))
+}
 
-    // ---------- Heavy work under semaphore ----------
-    let permit = PROOF_SEM.acquire().await.unwrap();
-    let secret_input = BigInt::from_bytes_be(Sign::Plus, &secret_fe.into_bigint().to_bytes_be());
-    let commit_input = BigInt::from_bytes_be(Sign::Plus, &commitment_fe.into_bigint().to_bytes_be());
-    let handle = tokio::task::spawn_blocking(move || {
+/// `ZK_ATTRIBUTE_ENCODING=rfc9380` selects RFC 9380 hash-to-field;
+/// `ZK_EMAIL_CASE` and `ZK_NAME_CASE` take `keep`, `lower` or `fold`.
+/// `ZK_EMAIL_IDN=1`, `ZK_EMAIL_PLUS=known|all` and `ZK_EMAIL_GMAIL_DOTS=1`
+/// canonicalise emails.
+fn attribute_rules_from_env() -> AttributeRules {
+    let case = |name, default| match std::env::var(name).as_deref() {
+        Ok("keep") => CaseFold::Keep,
+        Ok("lower") => CaseFold::Lower,
+        Ok("fold") => CaseFold::Fold,
+        _ => default,
+    };
+    let defaults = AttributeRules::default();
+    AttributeRules {
+        encoding: if std::env::var("ZK_ATTRIBUTE_ENCODING").as_deref() == Ok("rfc9380") {
+            AttributeEncoding::HashToField
+        } else {
+            defaults.encoding
+        },
+        email_case: case("ZK_EMAIL_CASE", defaults.email_case),
+        email_canonicalization: EmailCanonicalization {
+            idn_domains: std::env::var("ZK_EMAIL_IDN").as_deref() == Ok("1"),
+            plus_addressing: match std::env::var("ZK_EMAIL_PLUS").as_deref() {
+                Ok("known") => PlusAddressing::KnownProviders,
+                Ok("all") => PlusAddressing::AllDomains,
+                _ => PlusAddressing::Keep,
+            },
+            gmail_dots: std::env::var("ZK_EMAIL_GMAIL_DOTS").as_deref() == Ok("1"),
+        },
+        name_case: case("ZK_NAME_CASE", defaults.name_case),
+    }
+}
 
-        let (wasm, r1cs, _) = &*CIRCUIT_PATH;
-        let cfg = CircomConfig::<Fr>::new(wasm, r1cs).expect("cannot read circuit artefacts");
-        let mut builder = CircomBuilder::new(cfg);
-        builder.push_input("secret", secret_input);
-        builder.push_input("commitment", commit_input);
-        let circuit = builder.build().expect("witness");
+fn ipfs_from_env() -> Option<IpfsConfig> {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    let (gateway, api) = (var("ZK_IPFS_GATEWAY"), var("ZK_IPFS_API"));
+    if gateway.is_none() && api.is_none() {
+        return None;
+    }
+    let defaults = IpfsConfig::default();
+    let max_bytes = var("ZK_IPFS_MAX_BYTES").and_then(|v| v.parse().ok()).unwrap_or(defaults.max_bytes);
+    Some(IpfsConfig { gateway: gateway.unwrap_or(defaults.gateway), max_bytes, api, ..Default::default() })
+}
 
-        // 2. prove using a fast RNG
-        let mut rng = thread_rng();
-        let proof = Groth16::<Bn254, CircomReduction>::prove(&*PROVING_KEY, circuit, &mut rng)
-        .expect("proving failed");
+/// Anchoring is off unless `ZK_ANCHOR_RPC`, `ZK_ANCHOR_CONTRACT`,
+/// `ZK_ANCHOR_CHAIN_ID` and `ZK_ANCHOR_KEY` are all set;
+/// `ZK_ANCHOR_INTERVAL` (seconds, default 3600) and `ZK_ANCHOR_GAS_LIMIT`
+/// (default 100000) are optional.
+fn anchor_from_env() -> Option<AnchorConfig> {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    Some(AnchorConfig {
+        rpc_url: var("ZK_ANCHOR_RPC")?,
+        contract: var("ZK_ANCHOR_CONTRACT")?,
+        chain_id: var("ZK_ANCHOR_CHAIN_ID")?.parse().ok()?,
+        signer_key: PathBuf::from(var("ZK_ANCHOR_KEY")?),
+        interval: Duration::from_secs(var("ZK_ANCHOR_INTERVAL").and_then(|v| v.parse().ok()).unwrap_or(3600)),
+        gas_limit: var("ZK_ANCHOR_GAS_LIMIT").and_then(|v| v.parse().ok()).unwrap_or(100_000),
+    })
+}
 
-        // 3. serialise proof into JSON strings (do heavy EC->hex in this thread)
-        let (a_x, a_y)               = g1_to_hex(&proof.a);
-        let (b_x1, b_x0, b_y1, b_y0) = g2_to_hex(&proof.b);
-        let (c_x, c_y)               = g1_to_hex(&proof.c);
-        ProofJson { a: [a_x, a_y], b: [[b_x1, b_x0], [b_y1, b_y0]], c: [c_x, c_y] }
-    });
-    let proof_json = handle.await.expect("join");
-    drop(permit);
+/// Adaptive proof admission is off unless `ZK_PROVE_RATE` (requests per
+/// second with a short queue) is set; `ZK_PROVE_RATE_MIN` (default 0.5)
+/// and `ZK_PROVE_QUEUE_TARGET` (default: the CPU count) are optional.
+fn prove_rate_from_env() -> Option<AdaptiveRate> {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    let defaults = AdaptiveRate::default();
+    Some(AdaptiveRate {
+        max_per_sec: var("ZK_PROVE_RATE")?.parse().ok().filter(|r: &f64| *r > 0.0)?,
+        min_per_sec: var("ZK_PROVE_RATE_MIN").and_then(|v| v.parse().ok()).filter(|r: &f64| *r > 0.0).unwrap_or(defaults.min_per_sec),
+        target_queue: var("ZK_PROVE_QUEUE_TARGET").and_then(|v| v.parse().ok()).unwrap_or(defaults.target_queue),
+    })
+}
 
-    HttpResponse::Ok().json(ProofResponse { proof: proof_json })
+/// Speculative proving is off unless `ZK_PREWARM_TTL` (seconds a hinted
+/// login is kept) is set; `ZK_PREWARM_MAX` (default 1024) caps the hints.
+fn prewarm_from_env() -> Option<PrewarmConfig> {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    let defaults = PrewarmConfig::default();
+    Some(PrewarmConfig {
+        ttl: Duration::from_secs(var("ZK_PREWARM_TTL")?.parse().ok()?),
+        max_entries: var("ZK_PREWARM_MAX").and_then(|v| v.parse().ok()).unwrap_or(defaults.max_entries),
+    })
 }
 
-//--------------------------------------------------------------------
-// /verify-proof
-//--------------------------------------------------------------------
-#[post("/verify-proof")]
-async fn verify_proof(body:web::Json<VerifyRequest>)->impl Responder{
-    let a=G1Affine::new(fq_from_hex(&body.proof.a[0]),fq_from_hex(&body.proof.a[1]));
-    let b=G2Affine::new(Fq2::new(fq_from_hex(&body.proof.b[0][1]),fq_from_hex(&body.proof.b[0][0])),
-                        Fq2::new(fq_from_hex(&body.proof.b[1][1]),fq_from_hex(&body.proof.b[1][0])));
-    let c=G1Affine::new(fq_from_hex(&body.proof.c[0]),fq_from_hex(&body.proof.c[1]));
-    let proof_ark=Proof{a,b,c};
+/// Session tokens are off unless `ZK_SESSION_TTL` (seconds) is set;
+/// `ZK_SESSION_KEY`, `ZK_SESSION_ISSUER` and `ZK_SESSION_AUDIENCE` refine them.
+fn session_from_env() -> Option<SessionConfig> {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    let defaults = SessionConfig::default();
+    Some(SessionConfig {
+        ttl: Duration::from_secs(var("ZK_SESSION_TTL")?.parse().ok()?),
+        key: std::env::var_os("ZK_SESSION_KEY").map(PathBuf::from),
+        issuer: var("ZK_SESSION_ISSUER").unwrap_or(defaults.issuer),
+        audience: var("ZK_SESSION_AUDIENCE"),
+    })
+}
 
-    let commitment_f={let dec=BigUint::parse_bytes(body.commitment.as_bytes(),10).unwrap();
-                      Fr::from_be_bytes_mod_order(&{let mut b=dec.to_bytes_be();if b.len()<32{b=[vec![0u8;32-b.len()],b].concat();}b})};
+/// Registration credentials are off unless `ZK_CREDENTIAL_ISSUER` is set;
+/// `ZK_CREDENTIAL_KEY` and `ZK_CREDENTIAL_TTL` (seconds) refine them.
+fn credentials_from_env() -> Option<CredentialConfig> {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    Some(CredentialConfig {
+        issuer: var("ZK_CREDENTIAL_ISSUER")?,
+        key: std::env::var_os("ZK_CREDENTIAL_KEY").map(PathBuf::from),
+        validity: var("ZK_CREDENTIAL_TTL").and_then(|v| v.parse().ok()).map(Duration::from_secs),
+    })
+}
 
-    // use pre‑computed processed verifying key
-    let ok=Groth16::<Bn254,CircomReduction>::verify_with_processed_vk(
-              &*PROC_VERIFY_KEY,&[commitment_f],&proof_ark).unwrap_or(false);
+/// The claim policy is unset unless `ZK_CLAIMS_AUDIENCE` is set;
+/// `ZK_CLAIMS_ISSUERS` (comma-separated), `ZK_CLAIMS_LEEWAY` and
+/// `ZK_CLAIMS_MAX_LIFETIME` (seconds) refine it.
+fn claim_policy_from_env() -> Option<ClaimPolicy> {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    let secs = |name| var(name).and_then(|v| v.parse().ok()).unwrap_or(0);
+    Some(ClaimPolicy {
+        audience: var("ZK_CLAIMS_AUDIENCE")?,
+        issuers: var("ZK_CLAIMS_ISSUERS").map(|v| v.split(',').map(|i| i.trim().to_owned()).collect()).unwrap_or_default(),
+        leeway_secs: secs("ZK_CLAIMS_LEEWAY"),
+        max_lifetime_secs: secs("ZK_CLAIMS_MAX_LIFETIME"),
+    })
+}
 
-    if ok {HttpResponse::Ok().json(VerifyResponse{valid:true})}
-    else  {HttpResponse::Unauthorized().json(VerifyResponse{valid:false})}
+/// Shared state is off unless `ZK_REDIS_URL` is set; `ZK_REDIS_PREFIX`
+/// namespaces the keys.
+#[cfg(feature = "redis")]
+fn redis_from_env() -> Option<zk_auth_api::RedisConfig> {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    let defaults = zk_auth_api::RedisConfig::default();
+    Some(zk_auth_api::RedisConfig {
+        url: var("ZK_REDIS_URL")?,
+        key_prefix: var("ZK_REDIS_PREFIX").unwrap_or(defaults.key_prefix),
+        ..defaults
+    })
 }
 
-//--------------------------------------------------------------------
-// main
-//--------------------------------------------------------------------
-#[actix_web::main]
-async fn main()->std::io::Result<()>{
-    println!("🔒 Optimised ZK‑Auth API listening on http://localhost:8080");
-    HttpServer::new(||App::new()
-        .service(register)
-        .service(generate_proof)
-        .service(verify_proof))
-        .bind(("0.0.0.0",8080))?
-        .run()
-        .await
-}
\ No newline at end of file
+/// `RUST_LOG` picks the level (default `info`); `LOG_FORMAT=json` switches
+/// to one JSON object per line for log shippers.
+fn init_logging() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    if std::env::var("LOG_FORMAT").is_ok_and(|f| f.eq_ignore_ascii_case("json")) {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+}
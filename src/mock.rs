@@ -0,0 +1,76 @@
+use ark_bn254::g1::{G1_GENERATOR_X, G1_GENERATOR_Y};
+use ark_bn254::g2::{G2_GENERATOR_X, G2_GENERATOR_Y};
+use ark_bn254::{Bn254, Fr, G1Affine, G2Affine};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::Proof;
+use sha2::{Digest, Sha256};
+
+use crate::derive::commitment_for_secrets;
+use crate::prover::{ProveError, WitnessFailure};
+
+//--------------------------------------------------------------------
+// Mock prover
+//--------------------------------------------------------------------
+// Consumers' CI wants the API's shapes and status codes, not minutes of
+// Groth16.  A mock proof is a fixed function of the commitment: made of
+// real curve points, so every parser accepts it, but no pairing holds and
+// only a server in mock mode calls it valid.
+
+/// Run the mock prover?  Release builds refuse unless explicitly allowed.
+pub(crate) fn enabled(config: Option<&MockProver>) -> bool {
+    match config {
+        Some(mock) if cfg!(debug_assertions) || mock.allow_in_release => true,
+        Some(_) => {
+            tracing::error!("mock prover requested in a release build without allow_in_release; proving for real");
+            false
+        }
+        None => false,
+    }
+}
+
+/// Fake proofs from `/generate-proof` that `/verify-proof` accepts, for
+/// integration tests of services built on this API.  Never for production:
+/// anyone can compute the fake proof for any commitment.
+#[derive(Clone, Debug, Default)]
+pub struct MockProver {
+    /// Mock even in release builds, where it is otherwise refused.
+    pub allow_in_release: bool,
+}
+
+/// The mock proof for `secrets`, one or a `multi` circuit's, and
+/// `commitment`; the inputs must still be consistent, as the real circuit
+/// demands.
+pub(crate) fn prove(secrets: &[Fr], commitment: Fr) -> Result<Proof<Bn254>, ProveError> {
+    if commitment_for_secrets(secrets) != Some(commitment) {
+        let calculator = Some("mock prover: commitment is not Poseidon(secrets)".to_owned());
+        return Err(ProveError::Witness(WitnessFailure { calculator, constraint: None, wires: Vec::new(), witness: Vec::new() }));
+    }
+    Ok(proof_for(&[commitment]))
+}
+
+/// Whether `proof` is the mock proof for `commitment`.
+pub(crate) fn verify(commitment: Fr, proof: &Proof<Bn254>) -> bool {
+    *proof == proof_for(&[commitment])
+}
+
+/// The mock proof for a circuit with the public inputs `public`, in circuit
+/// order.  Unlike [`prove`] nothing is checked: the caller derived the
+/// witness itself.
+pub(crate) fn prove_public(public: &[Fr]) -> Proof<Bn254> {
+    proof_for(public)
+}
+
+/// Whether `proof` is the mock proof for `public`.
+pub(crate) fn verify_public(public: &[Fr], proof: &Proof<Bn254>) -> bool {
+    *proof == proof_for(public)
+}
+
+/// A single input hashes as it always has, so mock proofs for one
+/// commitment are unchanged.
+fn proof_for(public: &[Fr]) -> Proof<Bn254> {
+    let digest = public.iter().fold(Sha256::new().chain_update(b"zk-auth-mock/v1"), |h, x| h.chain_update(x.into_bigint().to_bytes_be())).finalize();
+    let k = Fr::from_be_bytes_mod_order(&digest);
+    let g1 = G1Affine::new(G1_GENERATOR_X, G1_GENERATOR_Y);
+    let g2 = G2Affine::new(G2_GENERATOR_X, G2_GENERATOR_Y);
+    Proof { a: (g1 * k).into(), b: (g2 * k).into(), c: (g1 * (k + Fr::from(1u64))).into() }
+}
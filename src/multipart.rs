@@ -0,0 +1,163 @@
+use actix_multipart::{Field, Multipart};
+use actix_web::guard::GuardContext;
+use actix_web::http::header::{ContentType, HeaderValue};
+use actix_web::{routes, web, HttpResponse, ResponseError};
+use futures_util::StreamExt;
+use rand::thread_rng;
+
+use crate::artifacts::CIRCUIT_ID;
+use crate::cancel::run_proving;
+use crate::convert::{parse_public_inputs, sniff_proof, ProofFormat};
+use crate::error::ApiError;
+use crate::handlers::{verify_request, ProofJson, ProofResponse, VerifyRequest, VK_HEADER};
+use crate::proof_store::{store_response, ProofStatement};
+use crate::prover;
+use crate::session::with_session;
+use crate::snarkjs::public_signals;
+use crate::store::blocking;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// multipart/form-data uploads
+//--------------------------------------------------------------------
+/// Largest accepted part; a `.wtns` for this circuit is a few KiB.
+const MAX_PART: usize = 16 * 1024 * 1024;
+
+/// Routes `multipart/form-data` bodies here; everything else falls through
+/// to the JSON handlers registered after these.
+fn is_multipart(ctx: &GuardContext<'_>) -> bool {
+    ctx.header::<ContentType>()
+        .is_some_and(|ct| ct.essence_str() == "multipart/form-data")
+}
+
+/// Proves from an uploaded witness: part `witness` holds a snarkjs/iden3
+/// `.wtns` file computed by the caller, so the secret never reaches the
+/// server.  The witness is checked against the circuit before the proof
+/// is returned.
+#[routes]
+#[post("/generate-proof", guard = "is_multipart")]
+#[post("/proof", guard = "is_multipart")]
+async fn generate_proof_multipart(state: web::Data<AppState>, form: Multipart) -> HttpResponse {
+    let parts = match read_parts(form).await {
+        Ok(parts) => parts,
+        Err(e) => return e.error_response(),
+    };
+    let Some(witness) = parts.get("witness") else {
+        return ApiError::InvalidInput { field: "witness", reason: "missing part" }.error_response();
+    };
+    let Some(witness) = prover::read_wtns(witness) else {
+        return ApiError::InvalidInput { field: "witness", reason: "not a BN254 .wtns file" }.error_response();
+    };
+    // Wire 0 is the constant 1; the commitment, the only public input, follows.
+    let commitment = witness.get(1).copied().unwrap_or_default();
+
+    if let Err(e) = blocking(&state, |state| state.limits.admit_prove(state.store.as_ref())).await {
+        return e.error_response();
+    }
+    let res = run_proving(&state, state.config.prove_deadline, move |worker_state, cancel| {
+        let artifacts = worker_state.active_artifacts();
+        let proof = prover::prove_witness(artifacts, witness, &mut thread_rng(), cancel).map_err(|e| e.into_api_error(artifacts.r1cs_path(), false))?;
+        Ok((ProofJson::from_proof(&proof), artifacts.vk_fingerprint().to_owned()))
+    })
+    .await;
+
+    match res {
+        Ok((proof, circuit_version)) => {
+            let response = ProofResponse { proof, circuit_version, proof_hash: None };
+            HttpResponse::Ok().json(store_response(&state, response, commitment, ProofStatement::secret(CIRCUIT_ID)).await)
+        }
+        Err(e) => e.error_response(),
+    }
+}
+
+/// Verifies an uploaded proof.  Part `proof` may be any format the
+/// `convert` subcommand reads; it is sniffed unless a `format` part names
+/// it.  Part `public` holds the public inputs as a JSON array or
+/// whitespace-separated text, and may be omitted when the proof file
+/// carries them.  An optional `circuit_version` part pins the key.
+#[routes]
+#[post("/verify-proof", guard = "is_multipart")]
+#[post("/verify", guard = "is_multipart")]
+async fn verify_proof_multipart(state: web::Data<AppState>, form: Multipart) -> HttpResponse {
+    let req = match read_parts(form).await.and_then(|parts| verify_form(&parts)) {
+        Ok(req) => req,
+        Err(e) => return e.error_response(),
+    };
+    let mut res = match verify_request(&state, &req).await {
+        Ok(v) if v.valid => HttpResponse::Ok().json(with_session(&state, v)),
+        Ok(v) => HttpResponse::Unauthorized().json(v),
+        Err(e) => e.error_response(),
+    };
+    if let Ok(v) = HeaderValue::from_str(state.active_artifacts().vk_fingerprint()) {
+        res.headers_mut().insert(VK_HEADER, v);
+    }
+    res
+}
+
+/// Form parts by name; a repeated name keeps the last part.
+struct Parts(Vec<(String, Vec<u8>)>);
+
+impl Parts {
+    fn get(&self, name: &str) -> Option<&[u8]> {
+        self.0.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v.as_slice())
+    }
+
+    fn text(&self, name: &'static str) -> Result<Option<&str>, ApiError> {
+        self.get(name)
+            .map(|v| std::str::from_utf8(v).map(str::trim))
+            .transpose()
+            .map_err(|_| ApiError::InvalidInput { field: name, reason: "not UTF-8 text" })
+    }
+}
+
+async fn read_parts(mut form: Multipart) -> Result<Parts, ApiError> {
+    let mut parts = Vec::new();
+    while let Some(field) = form.next().await {
+        let field = field.map_err(|_| ApiError::InvalidInput { field: "body", reason: "malformed multipart body" })?;
+        let name = field.name().unwrap_or_default().to_owned();
+        parts.push((name, read_field(field).await?));
+    }
+    Ok(Parts(parts))
+}
+
+async fn read_field(mut field: Field) -> Result<Vec<u8>, ApiError> {
+    let mut buf = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(|_| ApiError::InvalidInput { field: "body", reason: "malformed multipart body" })?;
+        if buf.len() + chunk.len() > MAX_PART {
+            return Err(ApiError::InvalidInput { field: "body", reason: "part exceeds 16 MiB" });
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf)
+}
+
+fn verify_form(parts: &Parts) -> Result<VerifyRequest, ApiError> {
+    let proof = parts.get("proof").ok_or(ApiError::InvalidInput { field: "proof", reason: "missing part" })?;
+    let format = parts
+        .text("format")?
+        .map(|f| f.parse::<ProofFormat>())
+        .transpose()
+        .map_err(|_| ApiError::InvalidInput { field: "format", reason: "expected hex, snarkjs, gnark or ark" })?;
+    let record = sniff_proof(format, proof).ok_or(ApiError::InvalidInput { field: "proof", reason: "not a recognised BN254 proof" })?;
+
+    let public = match parts.text("public")? {
+        Some(text) => parse_public_inputs(text)
+            .map_err(|_| ApiError::InvalidInput { field: "public", reason: "expected field elements in decimal or 0x hex" })?,
+        None => record.public_inputs,
+    };
+    let [commitment] = public.as_slice() else {
+        return Err(ApiError::InvalidInput { field: "public", reason: "expected exactly one public input (the commitment)" });
+    };
+    Ok(VerifyRequest {
+        commitment: public_signals(&[*commitment]).remove(0),
+        proof: ProofJson::from_proof(&record.proof),
+        circuit_version: parts.text("circuit_version")?.map(str::to_owned),
+        circuit_id: parts.text("circuit_id")?.map(str::to_owned),
+        nullifier: None,
+        application: None,
+        challenge: None,
+        claims: None,
+        audience: None,
+    })
+}
@@ -0,0 +1,54 @@
+use ark_bn254::Fr;
+
+use crate::error::ApiError;
+use crate::snarkjs::public_signals;
+use crate::store::Store;
+
+//--------------------------------------------------------------------
+// Spent nullifiers
+//--------------------------------------------------------------------
+// Nullifiers of proofs already accepted, so a captured proof for the
+// nullifier circuit verifies once.  Each is scoped by the `app_id` it was
+// proved for: the same identity has a different nullifier per application.
+// They never expire, since a replay stays possible for as long as the
+// commitment is valid.
+//
+// Membership nullifiers have their own domain tag and their own keys, so a
+// spent membership nullifier neither blocks nor links to a nullifier-circuit
+// proof for the same identity and application.
+
+fn key(app_id: Fr, nullifier: Fr) -> String {
+    let signals = public_signals(&[app_id, nullifier]);
+    format!("nullifier:{}:{}", signals[0], signals[1])
+}
+
+fn membership_key(app_id: Fr, nullifier: Fr) -> String {
+    let signals = public_signals(&[app_id, nullifier]);
+    format!("membership:{}:{}", signals[0], signals[1])
+}
+
+/// Whether [`spend`] has recorded `nullifier` for `app_id`.
+pub(crate) fn is_spent(store: &dyn Store, app_id: Fr, nullifier: Fr) -> Result<bool, ApiError> {
+    store.contains(&key(app_id, nullifier)).map_err(unavailable)
+}
+
+/// Record `nullifier` as spent for `app_id`; `false` if it already was.
+pub(crate) fn spend(store: &dyn Store, app_id: Fr, nullifier: Fr) -> Result<bool, ApiError> {
+    store.insert(&key(app_id, nullifier), None).map_err(unavailable)
+}
+
+/// Whether [`spend_membership`] has recorded `nullifier` for `app_id`.
+pub(crate) fn is_membership_spent(store: &dyn Store, app_id: Fr, nullifier: Fr) -> Result<bool, ApiError> {
+    store.contains(&membership_key(app_id, nullifier)).map_err(unavailable)
+}
+
+/// Record the membership `nullifier` as spent for `app_id`; `false` if it
+/// already was.
+pub(crate) fn spend_membership(store: &dyn Store, app_id: Fr, nullifier: Fr) -> Result<bool, ApiError> {
+    store.insert(&membership_key(app_id, nullifier), None).map_err(unavailable)
+}
+
+fn unavailable(e: std::io::Error) -> ApiError {
+    tracing::error!(error = %e, "nullifier store failed");
+    ApiError::Unavailable("cannot check the nullifier right now")
+}
@@ -0,0 +1,448 @@
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{get, post, web, HttpResponse, ResponseError};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL;
+use base64::Engine;
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::challenge;
+use crate::convert::normalize_public_input;
+use crate::error::ApiError;
+use crate::handlers::{verify_request, VerifyRequest};
+use crate::redact::Redacted;
+use crate::snarkjs::public_signals;
+use crate::store::blocking;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// OpenID Connect provider
+//--------------------------------------------------------------------
+/// Unredeemed authorization codes kept at once; further logins are
+/// refused until some are redeemed or expire.
+const MAX_CODES: usize = 100_000;
+
+/// Where [`OidcConfig::login_page`] takes the authorization request.
+pub const LOGIN_PAGE_SLOT: &str = "{{authorization_request}}";
+
+/// Served when no [`OidcConfig::login_page`] is configured: the bound
+/// request for a prover script, such as a wallet extension, to pick up.
+const DEFAULT_LOGIN_PAGE: &str = r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>Sign in</title></head>
+<body><p>Sign in with your zk-auth secret.</p>
+<script type="application/json" id="authorization-request">{{authorization_request}}</script>
+</body></html>
+"#;
+
+/// Serve as a minimal OpenID Connect provider for the authorization code
+/// flow, with a proof in place of the password check.
+///
+/// Tokens are signed with the session key, so [`crate::AppConfig::session`]
+/// must be set too; its `issuer` must be the URL this server is reached at,
+/// since discovery derives the endpoints from it.
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    /// Relying parties, by `client_id`.
+    pub clients: BTreeMap<String, OidcClient>,
+    /// How long an authorization code may wait to be redeemed.
+    pub code_ttl: Duration,
+    /// HTML served at `GET /authorize`, with [`LOGIN_PAGE_SLOT`] where the
+    /// authorization request goes.  It must prove with the challenge there
+    /// and post the proof to `POST /authorize`.  `None` serves a bare page
+    /// holding the request.
+    pub login_page: Option<String>,
+}
+
+impl Default for OidcConfig {
+    fn default() -> Self {
+        Self { clients: BTreeMap::new(), code_ttl: Duration::from_secs(60), login_page: None }
+    }
+}
+
+/// A registered relying party.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OidcClient {
+    /// Exact redirect URIs codes may be sent to.
+    pub redirect_uris: Vec<String>,
+    /// Confidential clients send it to `/token` as `client_secret`; public
+    /// clients have none and must use PKCE instead.
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+impl std::fmt::Debug for OidcClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OidcClient")
+            .field("redirect_uris", &self.redirect_uris)
+            .field("secret", &self.secret.as_ref().map(Redacted))
+            .finish()
+    }
+}
+
+/// `GET /.well-known/openid-configuration`.
+#[derive(Debug, Serialize)]
+pub struct DiscoveryDocument {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    pub response_types_supported: [&'static str; 1],
+    pub grant_types_supported: [&'static str; 1],
+    pub subject_types_supported: [&'static str; 1],
+    pub id_token_signing_alg_values_supported: [&'static str; 1],
+    pub scopes_supported: [&'static str; 1],
+    pub token_endpoint_auth_methods_supported: [&'static str; 2],
+    pub code_challenge_methods_supported: [&'static str; 1],
+}
+
+/// The authorization request's parameters, as the relying party sends them
+/// to `GET /authorize`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuthorizationParams {
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    /// Space-separated; must include `openid`.
+    pub scope: String,
+    #[serde(default)]
+    pub state: Option<String>,
+    /// Echoed in the `id_token`.
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub code_challenge: Option<String>,
+    /// Only `S256`.
+    #[serde(default)]
+    pub code_challenge_method: Option<String>,
+}
+
+/// What `GET /authorize` puts in the login page: the request and the
+/// challenge bound to it, which the proof must be made with.
+#[derive(Debug, Serialize)]
+pub struct AuthorizationRequest {
+    #[serde(flatten)]
+    pub params: AuthorizationParams,
+    /// Decimal, as `/challenge` returns it.
+    pub challenge: String,
+    pub expires_in_secs: u64,
+}
+
+/// The parameters the login page received, together with the proof it
+/// generated.  The proof must be for a `challenge` circuit, with the
+/// challenge `GET /authorize` bound to these parameters.
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeRequest {
+    #[serde(flatten)]
+    pub params: AuthorizationParams,
+    #[serde(flatten)]
+    pub proof: VerifyRequest,
+}
+
+/// Where the login page sends the browser next: `redirect_uri` with the
+/// code and `state`.
+#[derive(Debug, Serialize)]
+pub struct AuthorizeResponse {
+    pub redirect_to: String,
+}
+
+/// `POST /token` form.
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: String,
+    pub redirect_uri: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    #[serde(default)]
+    pub code_verifier: Option<String>,
+}
+
+impl std::fmt::Debug for TokenRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenRequest")
+            .field("grant_type", &self.grant_type)
+            .field("code", &Redacted(&self.code))
+            .field("redirect_uri", &self.redirect_uri)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &self.client_secret.as_ref().map(Redacted))
+            .field("code_verifier", &self.code_verifier.as_ref().map(Redacted))
+            .finish()
+    }
+}
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    /// A session token, as `/verify-proof` returns.
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: u64,
+    pub id_token: String,
+}
+
+impl std::fmt::Debug for TokenResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenResponse")
+            .field("access_token", &Redacted(&self.access_token))
+            .field("token_type", &self.token_type)
+            .field("expires_in", &self.expires_in)
+            .field("id_token", &Redacted(&self.id_token))
+            .finish()
+    }
+}
+
+/// The claims of an `id_token`.
+#[derive(Clone, Debug, Serialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    /// The commitment the proof was for, decimal.
+    pub sub: String,
+    /// The `client_id`.
+    pub aud: String,
+    pub iat: u64,
+    pub exp: u64,
+    /// When the proof was verified.
+    pub auth_time: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+/// RFC 6749 §5.2 error body; OAuth clients expect it rather than ours.
+#[derive(Debug, Serialize)]
+struct OAuthError {
+    error: &'static str,
+    error_description: &'static str,
+}
+
+/// Issued codes, keyed by code.
+#[derive(Default)]
+pub(crate) struct AuthorizationCodes(Mutex<HashMap<String, Grant>>);
+
+struct Grant {
+    client_id: String,
+    redirect_uri: String,
+    code_challenge: Option<String>,
+    nonce: Option<String>,
+    commitment: String,
+    circuit_version: String,
+    auth_time: u64,
+    expires: Instant,
+}
+
+impl AuthorizationCodes {
+    fn issue(&self, grant: Grant) -> Option<String> {
+        let mut codes = self.0.lock().expect("authorization codes poisoned");
+        let now = Instant::now();
+        codes.retain(|_, g| g.expires > now);
+        if codes.len() >= MAX_CODES {
+            return None;
+        }
+        let mut code = [0u8; 32];
+        thread_rng().fill_bytes(&mut code);
+        let code = BASE64URL.encode(code);
+        codes.insert(code.clone(), grant);
+        Some(code)
+    }
+
+    /// Codes are single-use: the first redemption removes it.
+    fn redeem(&self, code: &str) -> Option<Grant> {
+        self.0.lock().expect("authorization codes poisoned").remove(code).filter(|g| g.expires > Instant::now())
+    }
+}
+
+#[get("/.well-known/openid-configuration")]
+async fn discovery(state: web::Data<AppState>) -> HttpResponse {
+    let Some(issuer) = state.sessions.as_ref().filter(|_| state.config.oidc.is_some()) else {
+        return ApiError::NotFound("OpenID Connect is not enabled on this server").error_response();
+    };
+    let issuer = issuer.config().issuer.trim_end_matches('/');
+    HttpResponse::Ok().json(DiscoveryDocument {
+        issuer: issuer.to_owned(),
+        authorization_endpoint: format!("{issuer}/authorize"),
+        token_endpoint: format!("{issuer}/token"),
+        jwks_uri: format!("{issuer}/.well-known/jwks.json"),
+        response_types_supported: ["code"],
+        grant_types_supported: ["authorization_code"],
+        subject_types_supported: ["public"],
+        id_token_signing_alg_values_supported: ["EdDSA"],
+        scopes_supported: ["openid"],
+        token_endpoint_auth_methods_supported: ["client_secret_post", "none"],
+        code_challenge_methods_supported: ["S256"],
+    })
+}
+
+/// Check the authorization request's parameters, returning the redirect
+/// URI.  Unknown clients and redirect URIs are `400` and never redirected to.
+fn check_params(oidc: &OidcConfig, params: &AuthorizationParams) -> Result<url::Url, ApiError> {
+    let Some(client) = oidc.clients.get(&params.client_id) else {
+        return Err(ApiError::InvalidInput { field: "client_id", reason: "unknown client" });
+    };
+    if !client.redirect_uris.contains(&params.redirect_uri) {
+        return Err(ApiError::InvalidInput { field: "redirect_uri", reason: "not registered for this client" });
+    }
+    let Ok(redirect) = url::Url::parse(&params.redirect_uri) else {
+        return Err(ApiError::InvalidInput { field: "redirect_uri", reason: "not a URL" });
+    };
+    if params.response_type != "code" {
+        return Err(ApiError::InvalidInput { field: "response_type", reason: "only `code` is supported" });
+    }
+    if !params.scope.split(' ').any(|s| s == "openid") {
+        return Err(ApiError::InvalidInput { field: "scope", reason: "must include `openid`" });
+    }
+    match (&params.code_challenge, params.code_challenge_method.as_deref()) {
+        (Some(_), Some("S256")) => {}
+        (Some(_), _) => return Err(ApiError::InvalidInput { field: "code_challenge_method", reason: "only `S256` is supported" }),
+        (None, _) if client.secret.is_none() => return Err(ApiError::InvalidInput { field: "code_challenge", reason: "public clients must use PKCE" }),
+        (None, _) => {}
+    }
+    Ok(redirect)
+}
+
+/// Digest of the parameters a challenge is bound to.
+fn binding(params: &AuthorizationParams) -> String {
+    let json = serde_json::to_vec(params).expect("authorization parameters serialise to JSON");
+    format!("authorize:{}", hex::encode(Sha256::digest(json)))
+}
+
+/// The authorization endpoint, where the relying party sends the browser.
+/// Serves the login page with a challenge bound to this request, so a proof
+/// made for it cannot log in to another client or with another `nonce`.
+#[get("/authorize")]
+async fn authorization_page(state: web::Data<AppState>, query: web::Query<AuthorizationParams>) -> HttpResponse {
+    let Some(oidc) = &state.config.oidc else {
+        return ApiError::NotFound("OpenID Connect is not enabled on this server").error_response();
+    };
+    if let Err(e) = check_params(oidc, &query) {
+        return e.error_response();
+    }
+    let binding = binding(&query);
+    let challenge = match blocking(&state, move |state| challenge::issue_bound(state, &binding)).await {
+        Ok(challenge) => challenge,
+        Err(e) => return e.error_response(),
+    };
+    let request = AuthorizationRequest {
+        params: query.into_inner(),
+        challenge: public_signals(&[challenge]).remove(0),
+        expires_in_secs: challenge::CHALLENGE_TTL.as_secs(),
+    };
+    // `<` escaped, so no parameter can close the script element.
+    let json = serde_json::to_string(&request).expect("authorization request serialises to JSON").replace('<', "\\u003c");
+    let page = oidc.login_page.as_deref().unwrap_or(DEFAULT_LOGIN_PAGE).replace(LOGIN_PAGE_SLOT, &json);
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .content_type("text/html; charset=utf-8")
+        .body(page)
+}
+
+/// Verify the proof as `/verify-proof` does and, if it holds, issue an
+/// authorization code for the client.  The proof's challenge must be the one
+/// `GET /authorize` bound to these parameters.  An invalid proof is the `401`
+/// verdict `/verify-proof` gives.
+#[post("/authorize")]
+async fn authorize(state: web::Data<AppState>, body: web::Json<AuthorizeRequest>) -> HttpResponse {
+    let Some(oidc) = &state.config.oidc else {
+        return ApiError::NotFound("OpenID Connect is not enabled on this server").error_response();
+    };
+    let mut redirect = match check_params(oidc, &body.params) {
+        Ok(redirect) => redirect,
+        Err(e) => return e.error_response(),
+    };
+    // Checked before the pairing; `verify_request` then uses the challenge up.
+    let binding = binding(&body.params);
+    let Some(challenge) = body.proof.challenge.as_deref() else {
+        return ApiError::InvalidInput { field: "challenge", reason: "prove with the challenge from GET /authorize" }.error_response();
+    };
+    let Ok(challenge) = normalize_public_input(challenge) else {
+        return ApiError::InvalidInput { field: "challenge", reason: "not a scalar field element" }.error_response();
+    };
+    let bound = binding.clone();
+    match blocking(&state, move |state| challenge::is_bound(state.store.as_ref(), challenge, &bound)).await {
+        Ok(true) => {}
+        Ok(false) => return ApiError::InvalidChallenge.error_response(),
+        Err(e) => return e.error_response(),
+    }
+
+    let verdict = match verify_request(&state, &body.proof).await {
+        Ok(verdict) => verdict,
+        Err(e) => return e.error_response(),
+    };
+    let Some(circuit_version) = verdict.circuit_version.clone().filter(|_| verdict.valid) else {
+        return HttpResponse::Unauthorized().json(verdict);
+    };
+    // The challenge itself is used up already; this only tidies the store.
+    if let Err(e) = blocking(&state, move |state| challenge::unbind(state.store.as_ref(), challenge, &binding)).await {
+        return e.error_response();
+    }
+    let AuthorizeRequest { params, proof } = body.into_inner();
+    let grant = Grant {
+        client_id: params.client_id,
+        redirect_uri: params.redirect_uri,
+        code_challenge: params.code_challenge,
+        nonce: params.nonce,
+        commitment: proof.commitment,
+        circuit_version,
+        auth_time: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        expires: Instant::now() + oidc.code_ttl,
+    };
+    let Some(code) = state.authorization_codes.issue(grant) else {
+        return ApiError::Unavailable("too many authorization codes awaiting redemption").error_response();
+    };
+    redirect.query_pairs_mut().append_pair("code", &code);
+    if let Some(s) = &params.state {
+        redirect.query_pairs_mut().append_pair("state", s);
+    }
+    HttpResponse::Ok().json(AuthorizeResponse { redirect_to: redirect.into() })
+}
+
+/// Redeem a code from `/authorize` for an `id_token` and access token.
+#[post("/token")]
+async fn token(state: web::Data<AppState>, form: web::Form<TokenRequest>) -> HttpResponse {
+    let oauth_error = |error, error_description| HttpResponse::BadRequest().json(OAuthError { error, error_description });
+    let (Some(oidc), Some(sessions)) = (&state.config.oidc, &state.sessions) else {
+        return ApiError::NotFound("OpenID Connect is not enabled on this server").error_response();
+    };
+    if form.grant_type != "authorization_code" {
+        return oauth_error("unsupported_grant_type", "only authorization_code is supported");
+    }
+    let Some(client) = oidc.clients.get(&form.client_id) else {
+        return HttpResponse::Unauthorized().json(OAuthError { error: "invalid_client", error_description: "unknown client" });
+    };
+    // Compare digests so the check takes the same time for any mismatch, as for the admin token.
+    let digest = |s: &Option<String>| s.as_deref().map(Sha256::digest);
+    if client.secret.is_some() && digest(&client.secret) != digest(&form.client_secret) {
+        return HttpResponse::Unauthorized().json(OAuthError { error: "invalid_client", error_description: "wrong client_secret" });
+    }
+    // Redeemed even when a check below fails: a code is tried once.
+    let Some(grant) = state.authorization_codes.redeem(&form.code) else {
+        return oauth_error("invalid_grant", "code is unknown, expired or already used");
+    };
+    if grant.client_id != form.client_id || grant.redirect_uri != form.redirect_uri {
+        return oauth_error("invalid_grant", "code was issued to another client or redirect_uri");
+    }
+    if let Some(challenge) = &grant.code_challenge {
+        let verified = form.code_verifier.as_ref().is_some_and(|v| BASE64URL.encode(Sha256::digest(v.as_bytes())) == *challenge);
+        if !verified {
+            return oauth_error("invalid_grant", "code_verifier does not match code_challenge");
+        }
+    }
+
+    let session = sessions.mint(&grant.commitment, &grant.circuit_version);
+    let iat = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let config = sessions.config();
+    let id_token = sessions.sign(&IdTokenClaims {
+        iss: config.issuer.trim_end_matches('/').to_owned(),
+        sub: grant.commitment,
+        aud: grant.client_id,
+        iat,
+        exp: iat + config.ttl.as_secs(),
+        auth_time: grant.auth_time,
+        nonce: grant.nonce,
+    });
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .json(TokenResponse { access_token: session.access_token, token_type: "Bearer", expires_in: session.expires_in, id_token })
+}
@@ -0,0 +1,12 @@
+use actix_web::{get, HttpResponse, Responder};
+
+//--------------------------------------------------------------------
+// /playground
+//--------------------------------------------------------------------
+/// Static single page that drives register → proof → verify from a browser.
+static PLAYGROUND_HTML: &str = include_str!("../static/playground.html");
+
+#[get("/playground")]
+async fn playground() -> impl Responder {
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(PLAYGROUND_HTML)
+}
@@ -0,0 +1,240 @@
+use actix_web::{post, web, HttpResponse};
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use num_bigint::BigUint;
+use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::cancel::Cancel;
+use crate::crypto::poseidon_hash;
+use crate::error::ApiError;
+use crate::handlers::{ProofJson, ProofResponse};
+use crate::prover;
+use crate::redact::Redacted;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Speculative proving
+//--------------------------------------------------------------------
+/// How often a hinted proof looks for an idle proving slot.
+const IDLE_POLL: Duration = Duration::from_millis(250);
+
+/// Custodial pre-generation: a trusted backend hands over a user's secret
+/// ahead of their login and the proof is made while the prover is idle.
+#[derive(Clone, Debug)]
+pub struct PrewarmConfig {
+    /// How long a hint, and then its proof, is kept for the login.
+    pub ttl: Duration,
+    /// Hints pending or proven at once; further hints are refused.
+    pub max_entries: usize,
+}
+
+impl Default for PrewarmConfig {
+    fn default() -> Self {
+        Self { ttl: Duration::from_secs(300), max_entries: 1024 }
+    }
+}
+
+/// `/admin/prewarm` body: the inputs `/generate-proof` will be called with.
+#[derive(Deserialize)]
+pub struct PrewarmRequest {
+    pub secret_hex: String,
+    pub commitment: String,
+}
+
+impl std::fmt::Debug for PrewarmRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrewarmRequest").field("secret_hex", &Redacted(&self.secret_hex)).field("commitment", &self.commitment).finish()
+    }
+}
+
+/// `already_queued` is true when the commitment was hinted before; its
+/// expiry is extended instead of proving it twice.
+#[derive(Debug, Serialize)]
+pub struct PrewarmResponse {
+    pub commitment: String,
+    pub expires_in_secs: u64,
+    pub already_queued: bool,
+}
+
+/// Cache occupancy and outcomes for `/admin/stats`.
+#[derive(Clone, Debug, Serialize)]
+pub struct PrewarmStats {
+    pub pending: usize,
+    pub ready: usize,
+    pub hits: u64,
+    pub expired: u64,
+}
+
+/// Hinted commitments, keyed by the commitment's field element.
+pub(crate) struct Prewarm {
+    config: PrewarmConfig,
+    entries: Mutex<HashMap<Fr, Entry>>,
+    hits: AtomicU64,
+    expired: AtomicU64,
+}
+
+struct Entry {
+    expires: Instant,
+    /// `None` until the background proof is done.
+    proof: Option<ProofResponse>,
+}
+
+impl Prewarm {
+    pub(crate) fn new(config: PrewarmConfig) -> Self {
+        Self { config, entries: Mutex::new(HashMap::new()), hits: AtomicU64::new(0), expired: AtomicU64::new(0) }
+    }
+
+    /// Record a hint; `Ok(true)` if it was already known.
+    fn hint(&self, commitment: Fr) -> Result<bool, ApiError> {
+        let mut entries = self.entries.lock().expect("prewarm cache poisoned");
+        self.evict(&mut entries);
+        let expires = Instant::now() + self.config.ttl;
+        if let Some(entry) = entries.get_mut(&commitment) {
+            entry.expires = expires;
+            return Ok(true);
+        }
+        if entries.len() >= self.config.max_entries {
+            return Err(ApiError::Unavailable("too many logins awaiting pre-generated proofs"));
+        }
+        entries.insert(commitment, Entry { expires, proof: None });
+        Ok(false)
+    }
+
+    /// Whether the hint still stands and has no proof yet.
+    fn wanted(&self, commitment: &Fr) -> bool {
+        let entries = self.entries.lock().expect("prewarm cache poisoned");
+        entries.get(commitment).is_some_and(|e| e.proof.is_none() && e.expires > Instant::now())
+    }
+
+    fn fill(&self, commitment: Fr, proof: Option<ProofResponse>) {
+        let mut entries = self.entries.lock().expect("prewarm cache poisoned");
+        match proof {
+            Some(proof) => {
+                if let Some(entry) = entries.get_mut(&commitment) {
+                    entry.proof = Some(proof);
+                }
+            }
+            None => {
+                entries.remove(&commitment);
+            }
+        }
+    }
+
+    /// The cached proof for `commitment`, consumed so each hint serves one
+    /// login.  Callers must have checked that the client knows the secret.
+    pub(crate) fn take(&self, commitment: &Fr) -> Option<ProofResponse> {
+        let mut entries = self.entries.lock().expect("prewarm cache poisoned");
+        entries.get(commitment)?.proof.as_ref()?;
+        let entry = entries.remove(commitment)?;
+        if entry.expires <= Instant::now() {
+            self.expired.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        entry.proof
+    }
+
+    fn evict(&self, entries: &mut HashMap<Fr, Entry>) {
+        let now = Instant::now();
+        let before = entries.len();
+        entries.retain(|_, e| e.expires > now);
+        self.expired.fetch_add((before - entries.len()) as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self) -> PrewarmStats {
+        let mut entries = self.entries.lock().expect("prewarm cache poisoned");
+        self.evict(&mut entries);
+        let ready = entries.values().filter(|e| e.proof.is_some()).count();
+        PrewarmStats {
+            pending: entries.len() - ready,
+            ready,
+            hits: self.hits.load(Ordering::Relaxed),
+            expired: self.expired.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A cached proof for `/generate-proof`, if `secret` opens `commitment`.
+pub(crate) fn cached_proof(state: &AppState, secret: Fr, commitment: Fr) -> Option<ProofResponse> {
+    let cache = state.prewarm.as_ref()?;
+    // Knowing the commitment alone must not yield someone else's proof.
+    if poseidon_hash(&[secret]) != commitment {
+        return None;
+    }
+    let hit = cache.take(&commitment)?;
+    // Proofs made before a key rotation would no longer verify everywhere.
+    (hit.circuit_version == state.active_artifacts().vk_fingerprint()).then_some(hit)
+}
+
+//--------------------------------------------------------------------
+// /admin/prewarm
+//--------------------------------------------------------------------
+/// Hint that the holder of `commitment` will log in soon.  The proof is
+/// generated once a proving slot is free with nobody queued for it, so
+/// interactive requests always come first.
+#[post("/prewarm")]
+async fn prewarm(state: web::Data<AppState>, body: web::Json<PrewarmRequest>) -> Result<HttpResponse, ApiError> {
+    let cache = state.prewarm.as_ref().ok_or(ApiError::Unavailable("speculative proving is not enabled on this server"))?;
+    if !state.config.mode.proves() {
+        return Err(ApiError::Unavailable("this node does not generate proofs"));
+    }
+    let secret = match hex::decode(body.secret_hex.trim_start_matches("0x")) {
+        Ok(bytes) if bytes.len() <= 32 => Fr::from_be_bytes_mod_order(&bytes),
+        Ok(_) => return Err(ApiError::InvalidInput { field: "secret_hex", reason: "must be at most 32 bytes" }),
+        Err(_) => return Err(ApiError::InvalidInput { field: "secret_hex", reason: "not valid hex" }),
+    };
+    let commitment = BigUint::parse_bytes(body.commitment.as_bytes(), 10)
+        .map(|dec| Fr::from_be_bytes_mod_order(&dec.to_bytes_be()))
+        .ok_or(ApiError::InvalidInput { field: "commitment", reason: "must be a decimal integer" })?;
+    if poseidon_hash(&[secret]) != commitment {
+        return Err(ApiError::InvalidInput { field: "secret_hex", reason: "does not open the commitment" });
+    }
+
+    let already_queued = cache.hint(commitment)?;
+    if !already_queued {
+        actix_web::rt::spawn(prove_when_idle(state.clone(), secret, commitment));
+    }
+    tracing::info!(commitment = %body.commitment, already_queued, "login hinted for speculative proving");
+    Ok(HttpResponse::Accepted().json(PrewarmResponse {
+        commitment: body.commitment.clone(),
+        expires_in_secs: cache.config.ttl.as_secs(),
+        already_queued,
+    }))
+}
+
+async fn prove_when_idle(state: web::Data<AppState>, secret: Fr, commitment: Fr) {
+    let Some(cache) = state.prewarm.as_ref() else { return };
+    let permit = loop {
+        if !cache.wanted(&commitment) {
+            return;
+        }
+        if let Some(permit) = state.limits.prove.try_acquire_idle() {
+            break permit;
+        }
+        tokio::time::sleep(IDLE_POLL).await;
+    };
+    let worker_state = state.clone();
+    let res = tokio::task::spawn_blocking(move || {
+        let artifacts = worker_state.active_artifacts();
+        prover::prove(artifacts, secret, commitment, &mut thread_rng(), &Cancel::default())
+            .map(|p| ProofResponse { proof: ProofJson::from_proof(&p), circuit_version: artifacts.vk_fingerprint().to_owned(), proof_hash: None })
+    })
+    .await;
+    drop(permit);
+    match res {
+        Ok(Ok(proof)) => cache.fill(commitment, Some(proof)),
+        Ok(Err(e)) => {
+            tracing::warn!(error = %e, "speculative proof failed");
+            cache.fill(commitment, None);
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "speculative prover task failed");
+            cache.fill(commitment, None);
+        }
+    }
+}
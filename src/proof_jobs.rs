@@ -0,0 +1,520 @@
+use actix_web::{delete, get, post, web, HttpResponse, ResponseError};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::AbortHandle;
+
+use crate::error::ApiError;
+use crate::handlers::{parse_proof_request, prove_parsed, ParsedProofRequest, ProofRequest, ProofResponse, ProveQuery};
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Asynchronous proof jobs
+//--------------------------------------------------------------------
+/// How long [`MemoryJobStore`] keeps a pending job that stopped changing,
+/// which only happens if its task was lost.
+const JOB_TTL: Duration = Duration::from_secs(600);
+/// How often [`run_job_gc`] sweeps expired jobs.
+const JOB_GC_INTERVAL: Duration = Duration::from_secs(60);
+/// Most jobs one `GET /admin/proof-jobs` lists.
+const MAX_LISTED_JOBS: usize = 1000;
+/// Times a [`FileJobStore`] job is started before a job that keeps
+/// interrupting the server is given up as `interrupted`.
+const MAX_JOB_ATTEMPTS: u32 = 3;
+/// Jobs [`MemoryJobStore`] holds at once; further submissions are refused
+/// until some expire.
+const MAX_JOBS: usize = 10_000;
+
+/// Where a `/proof-jobs` job stands, as `GET /proof-jobs/{id}` returns it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ProofJob {
+    /// Queued for a prover or being proved.
+    Pending,
+    /// The body `/generate-proof` would have answered with.
+    Done { result: ProofResponse },
+    /// The error `/generate-proof` would have answered with, without
+    /// witness diagnostics.
+    Failed { error: String, message: String },
+    /// Withdrawn by `DELETE /proof-jobs/{id}` before it finished.
+    Cancelled,
+}
+
+impl ProofJob {
+    /// The `status` tag this job serialises with.
+    pub fn status(&self) -> &'static str {
+        match self {
+            ProofJob::Pending => "pending",
+            ProofJob::Done { .. } => "done",
+            ProofJob::Failed { .. } => "failed",
+            ProofJob::Cancelled => "cancelled",
+        }
+    }
+
+    /// How long a job in this state is kept after its last change.
+    fn retention(&self, retention: &JobRetention) -> Duration {
+        match self {
+            ProofJob::Pending => JOB_TTL,
+            ProofJob::Done { .. } => retention.done,
+            ProofJob::Failed { .. } | ProofJob::Cancelled => retention.failed,
+        }
+    }
+}
+
+/// How long finished jobs are kept for collection, from the moment they
+/// finished.
+#[derive(Clone, Debug)]
+pub struct JobRetention {
+    /// Jobs with a proof.
+    pub done: Duration,
+    /// Failed and cancelled jobs.
+    pub failed: Duration,
+}
+
+impl Default for JobRetention {
+    fn default() -> Self {
+        Self { done: Duration::from_secs(600), failed: Duration::from_secs(600) }
+    }
+}
+
+/// `POST /proof-jobs` answer.
+#[derive(Debug, Serialize)]
+pub struct ProofJobAccepted {
+    pub job_id: String,
+}
+
+/// Holds jobs from submission until their result has had time to be
+/// fetched.  [`MemoryJobStore`] is the default; replicas behind a load
+/// balancer that does not pin clients can share an external one through
+/// [`AppState::with_job_store`].
+pub trait JobStore: Send + Sync {
+    /// Record a new job; `false` if the store cannot take more.
+    fn insert(&self, id: &str, job: ProofJob) -> bool;
+    /// Record a new pending job with the request it proves, so that it can
+    /// be proved again if this process stops first.  The default records
+    /// the job alone.
+    fn enqueue(&self, id: &str, request: &ProofRequest) -> bool {
+        let _ = request;
+        self.insert(id, ProofJob::Pending)
+    }
+    /// Replace the state of a job recorded by [`JobStore::insert`].
+    fn update(&self, id: &str, job: ProofJob);
+    /// `None` for unknown and expired jobs.
+    fn get(&self, id: &str) -> Option<ProofJob>;
+    /// Every live job with its id, for `GET /admin/proof-jobs`.  The
+    /// default lists none, for stores that cannot enumerate.
+    fn list(&self) -> Vec<(String, ProofJob)> {
+        Vec::new()
+    }
+    /// Drop expired jobs; how many went.  [`run_job_gc`] calls this
+    /// periodically.  The default drops none, for stores that expire
+    /// entries themselves.
+    fn remove_expired(&self) -> usize {
+        0
+    }
+    /// Jobs an earlier process left pending, with their request and how
+    /// many times they have been started, this time included.  Each is
+    /// counted as started again.  [`resume_proof_jobs`] calls this once at
+    /// startup; the default has none.
+    fn interrupted(&self) -> Vec<(String, ProofRequest, u32)> {
+        Vec::new()
+    }
+}
+
+/// Jobs in this process, each dropped when its [`JobRetention`] after its
+/// last change has passed.
+#[derive(Default)]
+pub struct MemoryJobStore {
+    jobs: Mutex<HashMap<String, (ProofJob, Instant)>>,
+    retention: JobRetention,
+}
+
+impl MemoryJobStore {
+    pub fn new(retention: JobRetention) -> Self {
+        Self { jobs: Default::default(), retention }
+    }
+}
+
+impl JobStore for MemoryJobStore {
+    fn insert(&self, id: &str, job: ProofJob) -> bool {
+        let mut jobs = self.jobs.lock().expect("proof jobs poisoned");
+        let now = Instant::now();
+        jobs.retain(|_, (_, expires)| *expires > now);
+        if jobs.len() >= MAX_JOBS {
+            return false;
+        }
+        let expires = now + job.retention(&self.retention);
+        jobs.insert(id.to_owned(), (job, expires));
+        true
+    }
+
+    fn update(&self, id: &str, job: ProofJob) {
+        let expires = Instant::now() + job.retention(&self.retention);
+        self.jobs.lock().expect("proof jobs poisoned").insert(id.to_owned(), (job, expires));
+    }
+
+    fn get(&self, id: &str) -> Option<ProofJob> {
+        let jobs = self.jobs.lock().expect("proof jobs poisoned");
+        jobs.get(id).filter(|(_, expires)| *expires > Instant::now()).map(|(job, _)| job.clone())
+    }
+
+    fn list(&self) -> Vec<(String, ProofJob)> {
+        let now = Instant::now();
+        let jobs = self.jobs.lock().expect("proof jobs poisoned");
+        jobs.iter().filter(|(_, (_, expires))| *expires > now).map(|(id, (job, _))| (id.clone(), job.clone())).collect()
+    }
+
+    fn remove_expired(&self) -> usize {
+        let now = Instant::now();
+        let mut jobs = self.jobs.lock().expect("proof jobs poisoned");
+        let before = jobs.len();
+        jobs.retain(|_, (_, expires)| *expires > now);
+        before - jobs.len()
+    }
+}
+
+/// Drop expired jobs every minute, so that results nobody collects do not
+/// pile up between submissions.  [`crate::run`] spawns this; embedding
+/// applications that serve `/proof-jobs` spawn it on their own runtime.
+pub async fn run_job_gc(state: web::Data<AppState>) {
+    let mut ticks = actix_web::rt::time::interval(JOB_GC_INTERVAL);
+    loop {
+        ticks.tick().await;
+        let removed = state.jobs.remove_expired();
+        if removed > 0 {
+            tracing::debug!(removed, "expired proof jobs dropped");
+        }
+    }
+}
+
+/// Where [`FileJobStore`] keeps its jobs.
+#[derive(Clone, Debug)]
+pub struct JobStoreConfig {
+    /// One `<job_id>.json` file per job; created if missing.
+    pub dir: PathBuf,
+}
+
+/// A job as [`FileJobStore`] writes it.
+#[derive(Serialize, Deserialize)]
+struct JobRecord {
+    job: ProofJob,
+    /// The request of a pending job.  It holds the secret, so it is
+    /// dropped as soon as the job ends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request: Option<ProofRequest>,
+    /// Processes that have started proving it.
+    #[serde(default)]
+    attempts: u32,
+    /// Unix seconds of the last change.
+    updated_at: u64,
+}
+
+impl JobRecord {
+    fn expired(&self, retention: &JobRetention, now: u64) -> bool {
+        self.updated_at.saturating_add(self.job.retention(retention).as_secs()) <= now
+    }
+}
+
+/// Jobs in a directory, so that accepted work survives a restart: pending
+/// jobs keep their request until they end and [`resume_proof_jobs`] proves
+/// them again.  Files are readable by this user only, since pending ones
+/// hold secrets.  Expiry is as in [`MemoryJobStore`].
+pub struct FileJobStore {
+    dir: PathBuf,
+    retention: JobRetention,
+    /// Serialises read-modify-write of the files within this process.
+    lock: Mutex<()>,
+}
+
+impl FileJobStore {
+    pub fn new(config: JobStoreConfig, retention: JobRetention) -> io::Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&config.dir, std::fs::Permissions::from_mode(0o700))?;
+        }
+        Ok(Self { dir: config.dir, retention, lock: Mutex::new(()) })
+    }
+
+    /// `None` for ids [`submit_proof_job`] cannot have made, which must
+    /// not reach the file system.
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        (id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())).then(|| self.dir.join(format!("{id}.json")))
+    }
+
+    fn read(&self, id: &str) -> Option<JobRecord> {
+        let bytes = std::fs::read(self.path(id)?).ok()?;
+        serde_json::from_slice(&bytes).inspect_err(|e| tracing::warn!(job_id = id, error = %e, "unreadable proof job")).ok()
+    }
+
+    fn write(&self, id: &str, record: &JobRecord) -> io::Result<()> {
+        let path = self.path(id).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a job id"))?;
+        // Write aside and rename, so a reader never sees half a file.
+        let tmp = self.dir.join(format!("{id}.json.tmp"));
+        let mut file = create_private(&tmp)?;
+        file.write_all(&serde_json::to_vec(record).expect("job record serialises"))?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &path)
+    }
+
+    /// Every job file's id and record.
+    fn records(&self) -> Vec<(String, JobRecord)> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else { return Vec::new() };
+        entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(".json").map(str::to_owned))
+            .filter_map(|id| self.read(&id).map(|record| (id, record)))
+            .collect()
+    }
+
+    fn put(&self, id: &str, record: &JobRecord) -> bool {
+        match self.write(id, record) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::error!(job_id = id, error = %e, "proof job not saved");
+                false
+            }
+        }
+    }
+
+    fn insert_record(&self, id: &str, job: ProofJob, request: Option<&ProofRequest>) -> bool {
+        let _guard = self.lock.lock().expect("job store poisoned");
+        let Ok(entries) = std::fs::read_dir(&self.dir) else { return false };
+        if entries.count() >= MAX_JOBS {
+            return false;
+        }
+        self.put(id, &JobRecord { job, request: request.cloned(), attempts: 1, updated_at: unix_now() })
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// A new file only this user can read.
+fn create_private(path: &std::path::Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+impl JobStore for FileJobStore {
+    fn insert(&self, id: &str, job: ProofJob) -> bool {
+        self.insert_record(id, job, None)
+    }
+
+    fn enqueue(&self, id: &str, request: &ProofRequest) -> bool {
+        self.insert_record(id, ProofJob::Pending, Some(request))
+    }
+
+    fn update(&self, id: &str, job: ProofJob) {
+        let _guard = self.lock.lock().expect("job store poisoned");
+        let previous = self.read(id);
+        let attempts = previous.as_ref().map_or(1, |r| r.attempts);
+        let request = previous.and_then(|r| r.request).filter(|_| matches!(job, ProofJob::Pending));
+        self.put(id, &JobRecord { job, request, attempts, updated_at: unix_now() });
+    }
+
+    fn get(&self, id: &str) -> Option<ProofJob> {
+        self.read(id).filter(|r| !r.expired(&self.retention, unix_now())).map(|r| r.job)
+    }
+
+    fn list(&self) -> Vec<(String, ProofJob)> {
+        let now = unix_now();
+        self.records().into_iter().filter(|(_, r)| !r.expired(&self.retention, now)).map(|(id, r)| (id, r.job)).collect()
+    }
+
+    fn remove_expired(&self) -> usize {
+        let _guard = self.lock.lock().expect("job store poisoned");
+        let now = unix_now();
+        self.records()
+            .into_iter()
+            .filter(|(_, r)| r.expired(&self.retention, now))
+            .filter(|(id, _)| self.path(id).is_some_and(|path| std::fs::remove_file(path).is_ok()))
+            .count()
+    }
+
+    fn interrupted(&self) -> Vec<(String, ProofRequest, u32)> {
+        let _guard = self.lock.lock().expect("job store poisoned");
+        let mut jobs = Vec::new();
+        for (id, record) in self.records() {
+            let JobRecord { job: ProofJob::Pending, request, attempts, .. } = record else { continue };
+            let (job, attempts) = match &request {
+                Some(_) => (ProofJob::Pending, attempts + 1),
+                // Recorded by `insert`, without a request to prove again.
+                None => {
+                    let message = "the server stopped before this job finished".to_owned();
+                    (ProofJob::Failed { error: "interrupted".into(), message }, attempts)
+                }
+            };
+            let record = JobRecord { job, request, attempts, updated_at: unix_now() };
+            if let (true, Some(request)) = (self.put(&id, &record), record.request) {
+                jobs.push((id, request, attempts));
+            }
+        }
+        jobs
+    }
+}
+
+/// Prove again the jobs an earlier process left pending in the job store,
+/// giving up on those started [`MAX_JOB_ATTEMPTS`] times already: a job
+/// that keeps taking the server down must not do so forever.
+/// [`crate::run`] calls this at startup; embedding applications that
+/// serve `/proof-jobs` call it once on their own runtime.
+pub fn resume_proof_jobs(state: &web::Data<AppState>) {
+    let (mut resumed, mut abandoned) = (0, 0);
+    for (id, request, attempts) in state.jobs.interrupted() {
+        if attempts > MAX_JOB_ATTEMPTS {
+            let message = format!("the server stopped while proving this job {MAX_JOB_ATTEMPTS} times");
+            state.jobs.update(&id, ProofJob::Failed { error: "interrupted".into(), message });
+            abandoned += 1;
+            continue;
+        }
+        match parse_proof_request(state, &request, false) {
+            Ok(parsed) => {
+                spawn_job(state, id, parsed);
+                resumed += 1;
+            }
+            // The configuration changed under it, e.g. its circuit is gone.
+            Err(e) => state.jobs.update(&id, ProofJob::Failed { error: e.code().to_owned(), message: e.to_string() }),
+        }
+    }
+    if resumed + abandoned > 0 {
+        tracing::info!(resumed, abandoned, "interrupted proof jobs picked up");
+    }
+}
+
+/// Jobs this node is proving, so that `DELETE /proof-jobs/{id}` can stop
+/// them.  The lock also orders a cancellation against the job's own final
+/// update, so whichever comes second sees the first.
+#[derive(Default)]
+pub(crate) struct RunningJobs(Mutex<HashMap<String, AbortHandle>>);
+
+/// Accept a `/generate-proof` body and prove it in the background.  Inputs
+/// are checked before answering, so a malformed request is still `400`;
+/// everything after that, rate limiting included, ends up in the job.
+#[post("/proof-jobs")]
+async fn submit_proof_job(state: web::Data<AppState>, body: web::Json<ProofRequest>, query: web::Query<ProveQuery>) -> HttpResponse {
+    let parsed = match parse_proof_request(&state, &body, query.debug) {
+        Ok(parsed) => parsed,
+        Err(e) => return e.error_response(),
+    };
+    let mut id = [0u8; 16];
+    thread_rng().fill_bytes(&mut id);
+    let job_id = hex::encode(id);
+    if !state.jobs.enqueue(&job_id, &body) {
+        return ApiError::Unavailable("cannot take more proof jobs").error_response();
+    }
+    spawn_job(&state, job_id.clone(), parsed);
+    HttpResponse::Accepted().json(ProofJobAccepted { job_id })
+}
+
+/// Prove job `id` on this node, where `DELETE /proof-jobs/{id}` can stop it.
+fn spawn_job(state: &web::Data<AppState>, id: String, parsed: ParsedProofRequest) {
+    let (task_state, task_id) = (state.clone(), id.clone());
+    let mut running = state.running_jobs.0.lock().expect("running jobs poisoned");
+    let task = actix_web::rt::spawn(async move {
+        let job = match prove_parsed(&task_state, parsed).await {
+            Ok(result) => ProofJob::Done { result },
+            Err(e) => ProofJob::Failed { error: e.code().to_owned(), message: e.to_string() },
+        };
+        let mut running = task_state.running_jobs.0.lock().expect("running jobs poisoned");
+        running.remove(&task_id);
+        // A node sharing the job store may have cancelled it meanwhile.
+        if !matches!(task_state.jobs.get(&task_id), Some(ProofJob::Cancelled)) {
+            task_state.jobs.update(&task_id, job);
+        }
+    });
+    running.insert(id, task.abort_handle());
+}
+
+#[get("/proof-jobs/{id}")]
+async fn proof_job(state: web::Data<AppState>, id: web::Path<String>) -> HttpResponse {
+    match state.jobs.get(&id) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => ApiError::NotFound("no such proof job, or it has expired").error_response(),
+    }
+}
+
+/// Cancel a job and answer with its final state.  A pending job becomes
+/// `cancelled`; if this node is proving it, the task is aborted, which
+/// stops a queued proof before it starts and asks a running one to give
+/// up.  A job that already finished is returned as it is.
+#[delete("/proof-jobs/{id}")]
+async fn cancel_proof_job(state: web::Data<AppState>, id: web::Path<String>) -> HttpResponse {
+    let mut running = state.running_jobs.0.lock().expect("running jobs poisoned");
+    match state.jobs.get(&id) {
+        Some(ProofJob::Pending) => {
+            if let Some(task) = running.remove(id.as_str()) {
+                task.abort();
+            }
+            state.jobs.update(&id, ProofJob::Cancelled);
+            tracing::info!(job_id = %id, "proof job cancelled");
+            HttpResponse::Ok().json(ProofJob::Cancelled)
+        }
+        Some(job) => HttpResponse::Ok().json(job),
+        None => ApiError::NotFound("no such proof job, or it has expired").error_response(),
+    }
+}
+
+//--------------------------------------------------------------------
+// /admin/proof-jobs
+//--------------------------------------------------------------------
+#[derive(Debug, Deserialize)]
+pub struct ProofJobsQuery {
+    /// Only jobs with this `status`.
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// A job as `GET /admin/proof-jobs` lists it, without its proof.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProofJobSummary {
+    pub job_id: String,
+    pub status: String,
+    /// The error code of a failed job.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProofJobsResponse {
+    pub jobs: Vec<ProofJobSummary>,
+    /// Set when `limit` cut the list short.
+    pub more: bool,
+}
+
+/// Live jobs ordered by id, for operators.  Behind the admin token because
+/// a job id is all it takes to collect or cancel a job.
+#[get("/proof-jobs")]
+pub(crate) async fn list_proof_jobs(state: web::Data<AppState>, query: web::Query<ProofJobsQuery>) -> HttpResponse {
+    let mut jobs: Vec<_> =
+        state.jobs.list().into_iter().filter(|(_, job)| query.status.as_deref().is_none_or(|s| s == job.status())).collect();
+    jobs.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let limit = query.limit.unwrap_or(MAX_LISTED_JOBS).min(MAX_LISTED_JOBS);
+    let more = jobs.len() > limit;
+    let jobs = jobs
+        .into_iter()
+        .take(limit)
+        .map(|(job_id, job)| {
+            let error = match &job {
+                ProofJob::Failed { error, .. } => Some(error.clone()),
+                _ => None,
+            };
+            ProofJobSummary { job_id, status: job.status().to_owned(), error }
+        })
+        .collect();
+    HttpResponse::Ok().json(ProofJobsResponse { jobs, more })
+}
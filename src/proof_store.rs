@@ -0,0 +1,255 @@
+use actix_web::{get, web, HttpResponse, ResponseError};
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::Proof;
+use ark_serialize::CanonicalSerialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::artifacts::CIRCUIT_ID;
+use crate::claims::ClaimBlock;
+use crate::config::CircuitKind;
+use crate::convert::normalize_public_input;
+use crate::derive::app_id;
+use crate::error::ApiError;
+use crate::handlers::{reverify, ProofJson, ProofResponse, VerifyRequest, VerifyResponse};
+use crate::snarkjs::public_signals;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Content-addressed proof store
+//--------------------------------------------------------------------
+/// Keeps every proof this server hands out, addressed by its hash, so a
+/// `proof_hash` recorded anywhere can be dereferenced at `/proofs/{hash}`.
+#[derive(Clone, Debug)]
+pub struct ProofStoreConfig {
+    /// One `<proof_hash>.json` file per proof; created if missing.
+    pub dir: PathBuf,
+}
+
+impl Default for ProofStoreConfig {
+    fn default() -> Self {
+        Self { dir: PathBuf::from("proofs") }
+    }
+}
+
+/// A stored proof as written to disk and served by `/proofs/{hash}`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredProof {
+    pub proof_hash: String,
+    pub proof: ProofJson,
+    /// Decimal public inputs in circuit order, as [`ProofStatement::kind`]
+    /// fixes it.
+    pub public_inputs: Vec<String>,
+    /// Fingerprint of the verifying key the proof was made for.
+    pub circuit_version: String,
+    #[serde(flatten)]
+    pub statement: ProofStatement,
+    /// Unix seconds when the proof was first stored.
+    pub created_at: u64,
+}
+
+impl StoredProof {
+    /// The commitment among [`StoredProof::public_inputs`]: second for a
+    /// nullifier circuit, first for every other.
+    fn commitment(&self) -> Option<&String> {
+        match self.statement.kind {
+            CircuitKind::Nullifier => self.public_inputs.get(1),
+            _ => self.public_inputs.first(),
+        }
+    }
+
+    /// The `/verify-proof` request the proof answers.
+    fn verify_request(&self) -> Option<VerifyRequest> {
+        let s = &self.statement;
+        Some(VerifyRequest {
+            commitment: self.commitment()?.clone(),
+            proof: self.proof.clone(),
+            circuit_version: None,
+            circuit_id: Some(s.circuit_id.clone()),
+            nullifier: s.nullifier.clone(),
+            application: s.application.clone(),
+            challenge: s.challenge.clone(),
+            claims: s.claims.clone(),
+            audience: s.audience.clone(),
+        })
+    }
+}
+
+/// What a stored proof was made for besides its key and commitment: the
+/// circuit, and the inputs scoping a proof for a nullifier, challenge,
+/// claims or audience circuit.  Files written before these were kept read
+/// as proofs for the bundled circuit.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProofStatement {
+    #[serde(default = "bundled_circuit")]
+    pub circuit_id: String,
+    #[serde(default)]
+    pub kind: CircuitKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nullifier: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub application: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claims: Option<ClaimBlock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+}
+
+fn bundled_circuit() -> String {
+    CIRCUIT_ID.to_owned()
+}
+
+impl ProofStatement {
+    /// A proof from [`CircuitKind::Secret`] circuit `circuit_id`.
+    pub(crate) fn secret(circuit_id: &str) -> Self {
+        Self { circuit_id: circuit_id.to_owned(), kind: CircuitKind::Secret, nullifier: None, application: None, challenge: None, claims: None, audience: None }
+    }
+
+    /// The public inputs in circuit order, as `/verify-proof` rebuilds them.
+    fn public_inputs(&self, commitment: Fr) -> io::Result<Vec<Fr>> {
+        let scalar = |s: &Option<String>| s.as_deref().map(normalize_public_input).transpose().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()));
+        Ok(match self.kind {
+            CircuitKind::Nullifier => {
+                let (Some(nullifier), Some(application)) = (scalar(&self.nullifier)?, &self.application) else {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "nullifier proof without nullifier and application"));
+                };
+                vec![nullifier, commitment, app_id(application)]
+            }
+            _ => [Some(commitment), scalar(&self.challenge)?, self.claims.as_ref().map(ClaimBlock::hash), self.audience.as_deref().map(app_id)]
+                .into_iter()
+                .flatten()
+                .collect(),
+        })
+    }
+}
+
+/// `/proofs/{hash}` answer.  `verification` is checked on every lookup
+/// against the keys the server accepts now; it is absent on prove-only nodes.
+#[derive(Debug, Serialize)]
+pub struct StoredProofResponse {
+    #[serde(flatten)]
+    pub stored: StoredProof,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<VerifyResponse>,
+}
+
+/// SHA-256 of the arkworks compressed encoding, as in [`crate::Receipt`].
+pub(crate) fn proof_hash(proof: &Proof<Bn254>) -> String {
+    let mut bytes = Vec::new();
+    proof.serialize_compressed(&mut bytes).expect("proof serialises into a Vec");
+    hex::encode(Sha256::digest(bytes))
+}
+
+pub(crate) struct ProofStore {
+    dir: PathBuf,
+}
+
+impl ProofStore {
+    pub(crate) fn new(config: ProofStoreConfig) -> io::Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        Ok(Self { dir: config.dir })
+    }
+
+    /// Store `proof` unless it is already there; returns its hash.
+    pub(crate) fn put(&self, proof: &Proof<Bn254>, commitment: Fr, circuit_version: &str, statement: &ProofStatement) -> io::Result<String> {
+        let hash = proof_hash(proof);
+        let path = self.path(&hash);
+        if path.exists() {
+            return Ok(hash);
+        }
+        let created_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let stored = StoredProof {
+            proof_hash: hash.clone(),
+            proof: ProofJson::from_proof(proof),
+            public_inputs: public_signals(&statement.public_inputs(commitment)?),
+            circuit_version: circuit_version.to_owned(),
+            statement: statement.clone(),
+            created_at,
+        };
+        // Write aside and rename, so a reader never sees half a file.
+        let tmp = self.dir.join(format!("{hash}.json.tmp"));
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(&stored).expect("stored proof serialises"))?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(hash)
+    }
+
+    pub(crate) fn get(&self, hash: &str) -> io::Result<Option<StoredProof>> {
+        match std::fs::read(self.path(hash)) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn path(&self, hash: &str) -> PathBuf {
+        self.dir.join(format!("{hash}.json"))
+    }
+}
+
+/// Store the proof in `response`, if the store is enabled, and set its
+/// `proof_hash`.  A failed write is logged and leaves `proof_hash` unset
+/// rather than withholding a proof that took seconds to make.
+pub(crate) async fn store_response(state: &web::Data<AppState>, mut response: ProofResponse, commitment: Fr, statement: ProofStatement) -> ProofResponse {
+    if state.proofs.is_none() {
+        return response;
+    }
+    let Some(proof) = response.proof.try_to_proof() else {
+        return response;
+    };
+    let worker_state = state.clone();
+    let circuit_version = response.circuit_version.clone();
+    let res = web::block(move || worker_state.proofs.as_ref().expect("checked above").put(&proof, commitment, &circuit_version, &statement)).await;
+    match res {
+        Ok(Ok(hash)) => response.proof_hash = Some(hash),
+        Ok(Err(e)) => tracing::error!(error = %e, "cannot store proof"),
+        Err(e) => tracing::error!(error = %e, "proof store task failed"),
+    }
+    response
+}
+
+//--------------------------------------------------------------------
+// /proofs/{hash}
+//--------------------------------------------------------------------
+#[get("/proofs/{hash}")]
+async fn get_proof(state: web::Data<AppState>, hash: web::Path<String>) -> HttpResponse {
+    if state.proofs.is_none() {
+        return ApiError::Unavailable("the proof store is not enabled on this server").error_response();
+    }
+    // Also keeps the path component from naming anything but a store file.
+    let hash = hash.into_inner().to_ascii_lowercase();
+    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return ApiError::InvalidInput { field: "hash", reason: "must be a hex SHA-256 digest" }.error_response();
+    }
+    let worker_state = state.clone();
+    let stored = match web::block(move || worker_state.proofs.as_ref().expect("checked above").get(&hash)).await {
+        Ok(Ok(Some(stored))) => stored,
+        Ok(Ok(None)) => return ApiError::NotFound("no proof with this hash").error_response(),
+        Ok(Err(e)) => {
+            tracing::error!(error = %e, "cannot read stored proof");
+            return ApiError::Internal.error_response();
+        }
+        Err(_) => return ApiError::Internal.error_response(),
+    };
+
+    let verification = if state.config.mode.verifies() {
+        let Some(request) = stored.verify_request() else {
+            tracing::error!(proof_hash = %stored.proof_hash, "stored proof lacks its commitment");
+            return ApiError::Internal.error_response();
+        };
+        // Not verify_request: looking a proof up must not spend its nullifier or challenge.
+        match reverify(&state, request).await {
+            Ok(v) => Some(v),
+            Err(e) => return e.error_response(),
+        }
+    } else {
+        None
+    };
+    HttpResponse::Ok().json(StoredProofResponse { stored, verification })
+}
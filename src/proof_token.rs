@@ -0,0 +1,122 @@
+use actix_web::{post, web, HttpResponse, ResponseError};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::ApiError;
+use crate::handlers::{verify_request, VerifyRequest};
+use crate::redact::Redacted;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// One-time proof tokens
+//--------------------------------------------------------------------
+/// How long a token may wait to be redeemed.
+const TOKEN_TTL: Duration = Duration::from_secs(30);
+/// Unredeemed tokens kept at once; further mints are refused until some
+/// are redeemed or expire.
+const MAX_TOKENS: usize = 100_000;
+
+/// A verified proof, exchanged for a token a latency-sensitive consumer
+/// such as a door controller checks with a map lookup instead of a pairing.
+#[derive(Serialize)]
+pub struct ProofTokenResponse {
+    pub token: String,
+    pub commitment: String,
+    pub circuit_version: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Deserialize)]
+pub struct RedeemRequest {
+    pub token: String,
+}
+
+/// What the redeemed token stood for.
+#[derive(Debug, Serialize)]
+pub struct RedeemResponse {
+    pub commitment: String,
+    pub circuit_version: String,
+}
+
+impl std::fmt::Debug for ProofTokenResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProofTokenResponse")
+            .field("token", &Redacted(&self.token))
+            .field("commitment", &self.commitment)
+            .field("circuit_version", &self.circuit_version)
+            .field("expires_in_secs", &self.expires_in_secs)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for RedeemRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedeemRequest").field("token", &Redacted(&self.token)).finish()
+    }
+}
+
+/// Unredeemed tokens, keyed by token.
+#[derive(Default)]
+pub(crate) struct ProofTokens(Mutex<HashMap<String, Issued>>);
+
+struct Issued {
+    commitment: String,
+    circuit_version: String,
+    expires: Instant,
+}
+
+impl ProofTokens {
+    fn mint(&self, commitment: String, circuit_version: String) -> Option<String> {
+        let mut tokens = self.0.lock().expect("proof tokens poisoned");
+        let now = Instant::now();
+        tokens.retain(|_, t| t.expires > now);
+        if tokens.len() >= MAX_TOKENS {
+            return None;
+        }
+        let mut token = [0u8; 32];
+        thread_rng().fill_bytes(&mut token);
+        let token = hex::encode(token);
+        tokens.insert(token.clone(), Issued { commitment, circuit_version, expires: now + TOKEN_TTL });
+        Some(token)
+    }
+
+    /// Tokens are single-use: the first redemption removes it.
+    fn redeem(&self, token: &str) -> Option<Issued> {
+        self.0.lock().expect("proof tokens poisoned").remove(token).filter(|t| t.expires > Instant::now())
+    }
+}
+
+/// Verify a proof as `/verify-proof` does and, if it holds, mint a token.
+/// An invalid proof gets the same `401` verdict as from `/verify-proof`.
+#[post("/proof-token")]
+async fn proof_token(state: web::Data<AppState>, body: web::Json<VerifyRequest>) -> HttpResponse {
+    let verdict = match verify_request(&state, &body).await {
+        Ok(verdict) => verdict,
+        Err(e) => return e.error_response(),
+    };
+    let Some(circuit_version) = verdict.circuit_version.clone().filter(|_| verdict.valid) else {
+        return HttpResponse::Unauthorized().json(verdict);
+    };
+    let Some(token) = state.proof_tokens.mint(body.commitment.clone(), circuit_version.clone()) else {
+        return ApiError::Unavailable("too many proof tokens awaiting redemption").error_response();
+    };
+    HttpResponse::Ok().json(ProofTokenResponse {
+        token,
+        commitment: body.into_inner().commitment,
+        circuit_version,
+        expires_in_secs: TOKEN_TTL.as_secs(),
+    })
+}
+
+/// Consume a token from `/proof-token`.  Unknown, expired and already
+/// redeemed tokens are all `401 invalid_token`.
+#[post("/redeem")]
+async fn redeem(state: web::Data<AppState>, body: web::Json<RedeemRequest>) -> HttpResponse {
+    match state.proof_tokens.redeem(body.token.trim()) {
+        Some(issued) => HttpResponse::Ok().json(RedeemResponse { commitment: issued.commitment, circuit_version: issued.circuit_version }),
+        None => ApiError::InvalidToken.error_response(),
+    }
+}
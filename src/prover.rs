@@ -0,0 +1,293 @@
+use ark_bn254::{Bn254, Fr};
+use ark_circom::circom::R1CS;
+use ark_circom::{CircomCircuit, CircomConfig, CircomReduction};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::{Groth16, Proof, ProvingKey};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+use ark_snark::SNARK;
+use num_bigint::{BigInt, Sign};
+use rand::{thread_rng, CryptoRng, RngCore};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::artifacts::{read_proving_key, Artifacts};
+use crate::cancel::Cancel;
+use crate::circuits;
+use crate::derive::commitment_for;
+use crate::error::ApiError;
+
+//--------------------------------------------------------------------
+// Proving and verification
+//--------------------------------------------------------------------
+// Blocking calls shared by the HTTP handlers and the offline tools.  Run
+// them on a blocking thread when called from async code.
+
+/// Prove knowledge of `secret` with `Poseidon(secret) = commitment`.
+/// Gives up with [`ProveError::Cancelled`] between phases once `cancel` is set.
+pub(crate) fn prove<R: RngCore + CryptoRng>(artifacts: &Artifacts, secret: Fr, commitment: Fr, rng: &mut R, cancel: &Cancel) -> Result<Proof<Bn254>, ProveError> {
+    prove_inputs(artifacts, &secret_inputs(secret, commitment), rng, cancel)
+}
+
+/// Input signals by name, each a single value or an array, as the circuit's
+/// witness calculator takes them.
+pub(crate) type Inputs = Vec<(&'static str, Vec<Fr>)>;
+
+/// [`prove`] for a circuit with other inputs than `secret` and `commitment`.
+pub(crate) fn prove_inputs<R: RngCore + CryptoRng>(artifacts: &Artifacts, inputs: &Inputs, rng: &mut R, cancel: &Cancel) -> Result<Proof<Bn254>, ProveError> {
+    checkpoint(cancel)?;
+    let mut cfg = artifacts.take_calculator().map_err(|e| ProveError::Artifacts(format!("cannot read circuit artefacts: {e}")))?;
+    let witness = calculate_witness_inputs(&mut cfg, inputs);
+    let r1cs = cfg.r1cs.clone();
+    // A calculator that failed may be left mid-run; the next proof loads a fresh one.
+    if witness.is_ok() {
+        artifacts.release_calculator(cfg);
+    }
+    prove_checked(r1cs, artifacts.proving_key(), witness?, rng, cancel)
+}
+
+/// [`prove`] for circuit files and a zkey at arbitrary paths, for callers
+/// outside the server such as the mobile bindings, which ship the files in
+/// the app bundle.  Reads the zkey on every call.
+pub fn prove_with_files(wasm: &Path, r1cs: &Path, zkey: &Path, secret: Fr, commitment: Fr) -> io::Result<Proof<Bn254>> {
+    prove_with_files_rng(wasm, r1cs, zkey, secret, commitment, &mut thread_rng())
+}
+
+/// [`prove_with_files`] with the caller's randomness.
+pub(crate) fn prove_with_files_rng<R: RngCore + CryptoRng>(wasm: &Path, r1cs: &Path, zkey: &Path, secret: Fr, commitment: Fr, rng: &mut R) -> io::Result<Proof<Bn254>> {
+    if commitment_for(&secret) != commitment {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "commitment is not Poseidon(secret)"));
+    }
+    // The witness calculator's file system wants a Tokio reactor, which
+    // callers outside the server do not have.
+    let runtime = match tokio::runtime::Handle::try_current() {
+        Ok(_) => None,
+        Err(_) => Some(tokio::runtime::Builder::new_current_thread().enable_all().build()?),
+    };
+    let _entered = runtime.as_ref().map(|rt| rt.enter());
+    let mut cfg = CircomConfig::<Fr>::new(wasm, r1cs).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let pk = read_proving_key(zkey)?;
+    let proof = calculate_witness(&mut cfg, secret, commitment).and_then(|witness| prove_checked(cfg.r1cs, &pk, witness, rng, &Cancel::default()));
+    proof.map_err(|e| match e {
+        ProveError::Artifacts(_) => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        _ => io::Error::new(io::ErrorKind::InvalidInput, e.to_string()),
+    })
+}
+
+/// Why no proof came out, by whose fault.
+#[derive(Debug)]
+pub enum ProveError {
+    /// A client-supplied witness is malformed.
+    Input(&'static str),
+    /// The inputs admit no witness.
+    Witness(WitnessFailure),
+    /// The circuit files and proving key cannot be read or do not belong
+    /// together: the operator's problem, not the client's.
+    Artifacts(String),
+    /// Abandoned between phases; see [`Cancel`].
+    Cancelled,
+}
+
+impl fmt::Display for ProveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProveError::Input(reason) => f.write_str(reason),
+            ProveError::Witness(failure) => failure.fmt(f),
+            ProveError::Artifacts(reason) => write!(f, "circuit artefacts are unusable: {reason}"),
+            ProveError::Cancelled => f.write_str("proving cancelled"),
+        }
+    }
+}
+
+impl ProveError {
+    /// The API error for this failure, logged at a level matching whose
+    /// fault it is.  `debug` is passed on to [`circuits::diagnose`].
+    pub(crate) fn into_api_error(self, r1cs: &Path, debug: bool) -> ApiError {
+        match self {
+            ProveError::Input(reason) => {
+                tracing::info!(reason, "proof rejected: malformed witness");
+                ApiError::InvalidInput { field: "witness", reason }
+            }
+            ProveError::Witness(failure) => {
+                tracing::info!(constraint = ?failure.constraint, "proof rejected: inputs admit no witness");
+                ApiError::WitnessFailed(Box::new(circuits::diagnose(r1cs, failure, debug)))
+            }
+            ProveError::Artifacts(reason) => {
+                tracing::error!(%reason, "proving failed: circuit artefacts are unusable");
+                ApiError::ArtifactMismatch
+            }
+            ProveError::Cancelled => {
+                tracing::info!("proving cancelled: client gone or deadline exceeded");
+                ApiError::DeadlineExceeded
+            }
+        }
+    }
+}
+
+/// Why no valid witness exists for the inputs.  Holds signal values: only
+/// `Display` is safe to log or return.
+#[derive(Debug)]
+pub struct WitnessFailure {
+    /// The calculator's own error, when it trapped instead of finishing.
+    pub calculator: Option<String>,
+    /// First constraint the computed witness violates.
+    pub constraint: Option<usize>,
+    /// Wires that constraint reads, without the constant-one wire.
+    pub wires: Vec<usize>,
+    /// The witness as computed, by wire; empty when the calculator trapped.
+    pub witness: Vec<Fr>,
+}
+
+impl fmt::Display for WitnessFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.constraint {
+            Some(c) => write!(f, "inputs violate constraint {c}"),
+            None => f.write_str("witness calculation failed"),
+        }
+    }
+}
+
+/// Check a computed witness against the circuit and the key, then prove.
+fn prove_checked<R: RngCore + CryptoRng>(mut r1cs: R1CS<Fr>, pk: &ProvingKey<Bn254>, witness: Vec<Fr>, rng: &mut R, cancel: &Cancel) -> Result<Proof<Bn254>, ProveError> {
+    r1cs.wire_mapping = None;
+    // A wasm from another build would otherwise show up as a violated
+    // constraint and be blamed on the inputs.
+    if witness.len() != r1cs.num_variables {
+        return Err(ProveError::Artifacts(format!("wasm computes {} wires, r1cs has {}", witness.len(), r1cs.num_variables)));
+    }
+    check_key(&r1cs, pk)?;
+    checkpoint(cancel)?;
+    if let Some(c) = first_unsatisfied(&r1cs, &witness) {
+        let (a, b, cc) = &r1cs.constraints[c];
+        let mut wires: Vec<usize> = a.iter().chain(b).chain(cc).map(|(w, _)| *w).filter(|w| *w != 0).collect();
+        wires.sort_unstable();
+        wires.dedup();
+        return Err(ProveError::Witness(WitnessFailure { calculator: None, constraint: Some(c), wires, witness }));
+    }
+    checkpoint(cancel)?;
+    prove_from_witness(r1cs, pk, witness, rng)
+}
+
+/// The witness phase of proving: run the circuit's wasm on `secret` and
+/// `commitment`.  The witness is not checked against the constraints.
+/// Public, like [`prove_from_witness`], so benchmarks can time the phases
+/// apart; needs a Tokio reactor, as [`prove_with_files`] explains.
+pub fn calculate_witness(cfg: &mut CircomConfig<Fr>, secret: Fr, commitment: Fr) -> Result<Vec<Fr>, ProveError> {
+    calculate_witness_inputs(cfg, &secret_inputs(secret, commitment))
+}
+
+/// [`calculate_witness`] on any named inputs.
+pub(crate) fn calculate_witness_inputs(cfg: &mut CircomConfig<Fr>, inputs: &Inputs) -> Result<Vec<Fr>, ProveError> {
+    // Not `CircomBuilder::build`: it panics on an unsatisfied witness in
+    // debug builds and passes it on to the prover in release ones.
+    let inputs = inputs.iter().map(|(name, values)| ((*name).to_owned(), values.iter().map(fr_to_bigint).collect::<Vec<_>>()));
+    cfg.wtns.calculate_witness_element::<Fr, _>(&mut cfg.store, inputs, cfg.sanity_check).map_err(|e| {
+        ProveError::Witness(WitnessFailure { calculator: Some(e.to_string()), constraint: None, wires: Vec::new(), witness: Vec::new() })
+    })
+}
+
+/// The Groth16 phase of proving over a wire-indexed witness, such as one
+/// from [`calculate_witness`].  No constraint or key check is made first.
+pub fn prove_from_witness<R: RngCore + CryptoRng>(mut r1cs: R1CS<Fr>, pk: &ProvingKey<Bn254>, witness: Vec<Fr>, rng: &mut R) -> Result<Proof<Bn254>, ProveError> {
+    r1cs.wire_mapping = None;
+    let circuit = CircomCircuit { r1cs, witness: Some(witness) };
+    Groth16::<Bn254, CircomReduction>::prove(pk, circuit, rng).map_err(|e| ProveError::Artifacts(e.to_string()))
+}
+
+fn secret_inputs(secret: Fr, commitment: Fr) -> Inputs {
+    vec![("secret", vec![secret]), ("commitment", vec![commitment])]
+}
+
+fn checkpoint(cancel: &Cancel) -> Result<(), ProveError> {
+    match cancel.is_cancelled() {
+        true => Err(ProveError::Cancelled),
+        false => Ok(()),
+    }
+}
+
+/// The proving key must be for this r1cs: same wire and public input counts.
+fn check_key(r1cs: &R1CS<Fr>, pk: &ProvingKey<Bn254>) -> Result<(), ProveError> {
+    if pk.a_query.len() != r1cs.num_variables {
+        return Err(ProveError::Artifacts(format!("r1cs has {} wires, proving key {}", r1cs.num_variables, pk.a_query.len())));
+    }
+    if pk.vk.gamma_abc_g1.len() != r1cs.num_inputs {
+        return Err(ProveError::Artifacts(format!("r1cs has {} public inputs, proving key {}", r1cs.num_inputs - 1, pk.vk.gamma_abc_g1.len() - 1)));
+    }
+    Ok(())
+}
+
+/// Index of the first constraint `<A,w> * <B,w> = <C,w>` the witness breaks.
+fn first_unsatisfied(r1cs: &R1CS<Fr>, witness: &[Fr]) -> Option<usize> {
+    let eval = |lc: &[(usize, Fr)]| lc.iter().map(|(w, k)| witness.get(*w).copied().unwrap_or_default() * k).sum::<Fr>();
+    r1cs.constraints.iter().position(|(a, b, c)| eval(a) * eval(b) != eval(c))
+}
+
+/// Check `proof` against `commitment` with the prepared verifying key.
+pub(crate) fn verify(artifacts: &Artifacts, commitment: Fr, proof: &Proof<Bn254>) -> bool {
+    verify_inputs(artifacts, &[commitment], proof)
+}
+
+/// [`verify`] for circuits with further public inputs, in the order the
+/// circuit declares them.
+pub(crate) fn verify_inputs(artifacts: &Artifacts, public: &[Fr], proof: &Proof<Bn254>) -> bool {
+    zk_auth_verify_core::verify(artifacts.prepared_vk(), public, proof).unwrap_or(false)
+}
+
+/// Prove from a full witness (e.g. a snarkjs `.wtns`) instead of computing
+/// one.  The witness is checked against the constraints first: the prover
+/// itself only asserts that in debug builds.
+pub(crate) fn prove_witness<R: RngCore + CryptoRng>(artifacts: &Artifacts, witness: Vec<Fr>, rng: &mut R, cancel: &Cancel) -> Result<Proof<Bn254>, ProveError> {
+    let r1cs = artifacts.witness_r1cs().clone();
+    if witness.len() != r1cs.num_variables {
+        return Err(ProveError::Input("witness has the wrong number of signals for this circuit"));
+    }
+    check_key(&r1cs, artifacts.proving_key())?;
+    let circuit = CircomCircuit { r1cs, witness: Some(witness) };
+    let cs = ConstraintSystem::<Fr>::new_ref();
+    if circuit.clone().generate_constraints(cs.clone()).is_err() || !cs.is_satisfied().unwrap_or(false) {
+        return Err(ProveError::Input("witness does not satisfy the circuit"));
+    }
+    checkpoint(cancel)?;
+    Groth16::<Bn254, CircomReduction>::prove(artifacts.proving_key(), circuit, rng).map_err(|e| ProveError::Artifacts(e.to_string()))
+}
+
+/// Parse an iden3 `.wtns` file: `wtns` magic, then a header section (field
+/// size and prime) and a section of little-endian field elements.
+pub(crate) fn read_wtns(bytes: &[u8]) -> Option<Vec<Fr>> {
+    let u32_at = |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let u64_at = |at: usize| Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?));
+    if bytes.get(..4)? != b"wtns" {
+        return None;
+    }
+    let sections = u32_at(8)?;
+    let (mut at, mut n8, mut count, mut values) = (12, 0usize, 0usize, None);
+    for _ in 0..sections {
+        let (kind, size) = (u32_at(at)?, u64_at(at + 4)? as usize);
+        let body = at + 12;
+        match kind {
+            1 => {
+                n8 = u32_at(body)? as usize;
+                let prime = bytes.get(body + 4..body + 4 + n8)?;
+                if prime != Fr::MODULUS.to_bytes_le().as_slice() {
+                    return None;
+                }
+                count = u32_at(body + 4 + n8)? as usize;
+            }
+            2 => values = Some(body..body.checked_add(size)?),
+            _ => {}
+        }
+        at = body.checked_add(size)?;
+    }
+    let values = bytes.get(values?)?;
+    if n8 == 0 || values.len() != n8 * count {
+        return None;
+    }
+    values.chunks(n8).map(|c| {
+        let n = BigInt::from_bytes_le(Sign::Plus, c);
+        (n < BigInt::from_bytes_le(Sign::Plus, &Fr::MODULUS.to_bytes_le())).then(|| Fr::from_le_bytes_mod_order(c))
+    }).collect()
+}
+
+fn fr_to_bigint(f: &Fr) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, &f.into_bigint().to_bytes_be())
+}
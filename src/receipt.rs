@@ -0,0 +1,135 @@
+use actix_web::{get, web, HttpRequest, Responder};
+use ed25519_dalek::{Signer, SigningKey};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::etag::json_with_etag;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Verification receipts
+//--------------------------------------------------------------------
+/// Signed evidence that a proof was checked, small enough to store in place
+/// of the proof itself.
+///
+/// `signature` is Ed25519 over [`Receipt::signed_message`]; fetch the public
+/// key from `GET /receipts/key` and match it by `key_id`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Receipt {
+    /// SHA-256 of the proof's arkworks compressed encoding, so the hash does
+    /// not depend on which JSON form the client sent.
+    pub proof_hash: String,
+    pub commitment: String,
+    pub vk_fingerprint: String,
+    /// Unix milliseconds when verification finished.
+    pub timestamp: u64,
+    pub latency_us: u64,
+    pub valid: bool,
+    pub key_id: String,
+    pub signature: String,
+}
+
+impl Receipt {
+    /// The exact bytes signed: newline-separated fields behind a version tag.
+    pub fn signed_message(&self) -> String {
+        format!(
+            "zk-auth-receipt/v1\n{}\n{}\n{}\n{}\n{}\n{}",
+            self.proof_hash, self.commitment, self.vk_fingerprint, self.timestamp, self.latency_us, self.valid
+        )
+    }
+}
+
+/// Holds the receipt signing key.
+pub(crate) struct ReceiptSigner {
+    key: SigningKey,
+    key_id: String,
+}
+
+impl ReceiptSigner {
+    /// Key from a file holding a 32-byte seed in hex, or a fresh key when
+    /// `path` is `None`.  A fresh key changes on every restart, so receipts
+    /// signed before then can only be checked against the logged key.
+    pub(crate) fn new(path: Option<&Path>) -> io::Result<Self> {
+        let key = ed25519_key(path, "receipt key")?;
+        Ok(Self { key_id: key_id(&key), key })
+    }
+
+    pub(crate) fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    pub(crate) fn public_key_hex(&self) -> String {
+        hex::encode(self.key.verifying_key().as_bytes())
+    }
+
+    pub(crate) fn sign(&self, proof_bytes: &[u8], commitment: &str, vk_fingerprint: &str, latency: Duration, valid: bool) -> Receipt {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+        let mut receipt = Receipt {
+            proof_hash: hex::encode(Sha256::digest(proof_bytes)),
+            commitment: commitment.to_owned(),
+            vk_fingerprint: vk_fingerprint.to_owned(),
+            timestamp,
+            latency_us: latency.as_micros() as u64,
+            valid,
+            key_id: self.key_id.clone(),
+            signature: String::new(),
+        };
+        receipt.signature = self.sign_message(receipt.signed_message().as_bytes());
+        receipt
+    }
+
+    /// Hex Ed25519 signature over `message`.  Callers prefix their own
+    /// version tag so a signature never passes for a receipt's.
+    pub(crate) fn sign_message(&self, message: &[u8]) -> String {
+        hex::encode(self.key.sign(message).to_bytes())
+    }
+}
+
+/// Ed25519 key from a file holding a 32-byte seed in hex, or a fresh one
+/// when `path` is `None`.  `what` names the key in errors.
+pub(crate) fn ed25519_key(path: Option<&Path>, what: &str) -> io::Result<SigningKey> {
+    let seed: [u8; 32] = match path {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)?;
+            hex::decode(text.trim())
+                .ok()
+                .and_then(|b| b.try_into().ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{what} must be 32 bytes of hex")))?
+        }
+        None => {
+            let mut seed = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut seed);
+            seed
+        }
+    };
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// First 8 bytes of SHA-256 of the public key, in hex.
+pub(crate) fn key_id(key: &SigningKey) -> String {
+    hex::encode(&Sha256::digest(key.verifying_key().as_bytes())[..8])
+}
+
+//--------------------------------------------------------------------
+// /receipts/key
+//--------------------------------------------------------------------
+#[derive(Debug, Serialize)]
+pub struct ReceiptKeyResponse {
+    pub algorithm: &'static str,
+    pub key_id: String,
+    pub public_key: String,
+}
+
+#[get("/receipts/key")]
+async fn receipt_key(state: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    let signer = &state.receipts;
+    json_with_etag(&req, &ReceiptKeyResponse {
+        algorithm: "ed25519",
+        key_id: signer.key_id.clone(),
+        public_key: signer.public_key_hex(),
+    })
+}
@@ -0,0 +1,276 @@
+use actix_web::body::{to_bytes, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING};
+use actix_web::web::Bytes;
+use actix_web::Error;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::future::{ready, Future, Ready};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use crate::redact::{redact_json, redact_query};
+
+//--------------------------------------------------------------------
+// Configuration
+//--------------------------------------------------------------------
+/// Opt-in traffic recording for [`replay`].  Exchanges are appended to
+/// `path` as NDJSON [`RecordedExchange`]s, sanitised like access-log
+/// bodies; `/admin` traffic is never recorded.
+#[derive(Clone, Debug)]
+pub struct RecordConfig {
+    pub path: PathBuf,
+    /// Fraction of requests recorded, `0.0..=1.0`.
+    pub sample_rate: f64,
+    /// Larger bodies are recorded without their content and not replayed.
+    pub max_body_bytes: usize,
+}
+
+impl Default for RecordConfig {
+    fn default() -> Self {
+        Self { path: PathBuf::from("traffic.ndjson"), sample_rate: 1.0, max_body_bytes: 64 * 1024 }
+    }
+}
+
+/// One request and the response it got.  Bodies are redacted JSON, or
+/// `None` when empty, not JSON or over the size cap.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedExchange {
+    pub method: String,
+    /// Path and query string as the client sent them, with sensitive
+    /// query parameters redacted.
+    pub uri: String,
+    pub status: u16,
+    pub request: Option<Value>,
+    pub response: Option<Value>,
+    /// The request can be sent again as recorded: its body, if any, was
+    /// captured whole and nothing in it or its query was redacted.
+    pub replayable: bool,
+}
+
+//--------------------------------------------------------------------
+// Middleware
+//--------------------------------------------------------------------
+/// Recording middleware.  [`Recorder::default`] records nothing, for use
+/// with `Condition`.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    sink: Option<Arc<Sink>>,
+}
+
+struct Sink {
+    config: RecordConfig,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl Recorder {
+    /// Appends to [`RecordConfig::path`], creating it if needed.
+    pub fn new(config: RecordConfig) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&config.path)?;
+        Ok(Self { sink: Some(Arc::new(Sink { config, file: Mutex::new(BufWriter::new(file)) })) })
+    }
+}
+
+impl Sink {
+    fn write(&self, exchange: &RecordedExchange) {
+        let mut line = serde_json::to_vec(exchange).expect("recorded exchange serialises");
+        line.push(b'\n');
+        let mut file = self.file.lock().expect("traffic recording poisoned");
+        // A lost record is not worth failing the request over.
+        if let Err(e) = file.write_all(&line).and_then(|_| file.flush()) {
+            tracing::warn!(error = %e, path = %self.config.path.display(), "cannot write traffic recording");
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Recorder
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = RecorderService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RecorderService { service: Rc::new(service), sink: self.sink.clone() }))
+    }
+}
+
+pub struct RecorderService<S> {
+    service: Rc<S>,
+    sink: Option<Arc<Sink>>,
+}
+
+impl<S, B> Service<ServiceRequest> for RecorderService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let sink = self.sink.clone().filter(|s| !req.path().contains("/admin") && rand::thread_rng().gen_bool(s.config.sample_rate.clamp(0.0, 1.0)));
+        Box::pin(async move {
+            let Some(sink) = sink else {
+                return Ok(service.call(req).await?.map_into_boxed_body());
+            };
+            let method = req.method().to_string();
+            let raw_uri = req.uri().path_and_query().map(|p| p.as_str().to_owned()).unwrap_or_default();
+            let uri = redact_query(&raw_uri);
+            // Only small JSON bodies are read: buffering an upload here would
+            // hit the extractor's size limit before the handler's own.
+            let length = req.headers().get(CONTENT_LENGTH).and_then(|v| v.to_str().ok()).and_then(|v| v.parse::<usize>().ok());
+            let has_body = length.is_some_and(|n| n > 0) || req.headers().contains_key(TRANSFER_ENCODING);
+            let (request, request_whole) = if !has_body {
+                (None, true)
+            } else if is_json(req.headers().get(CONTENT_TYPE)) && length.is_none_or(|n| n <= sink.config.max_body_bytes) {
+                let bytes = req.extract::<Bytes>().await?;
+                let sanitised = sanitise(&bytes, true, sink.config.max_body_bytes);
+                req.set_payload(Payload::from(bytes));
+                sanitised
+            } else {
+                (None, false)
+            };
+
+            let (http_req, http_res) = service.call(req).await?.map_into_boxed_body().into_parts();
+            let (http_res, body) = http_res.into_parts();
+            let bytes = to_bytes(body).await.unwrap_or_default();
+            let (response, _) = sanitise(&bytes, is_json(http_res.headers().get(CONTENT_TYPE)), sink.config.max_body_bytes);
+            let replayable = request_whole && uri == raw_uri;
+            sink.write(&RecordedExchange { method, uri, status: http_res.status().as_u16(), request, response, replayable });
+            Ok(ServiceResponse::new(http_req, http_res.set_body(BoxBody::new(bytes))))
+        })
+    }
+}
+
+fn is_json(content_type: Option<&HeaderValue>) -> bool {
+    content_type.and_then(|v| v.to_str().ok()).is_some_and(|v| v.starts_with("application/json"))
+}
+
+/// The redacted JSON body, and whether it was kept whole: empty, or JSON
+/// within the cap with nothing redacted.
+fn sanitise(bytes: &[u8], json: bool, max: usize) -> (Option<Value>, bool) {
+    if bytes.is_empty() {
+        return (None, true);
+    }
+    if !json || bytes.len() > max {
+        return (None, false);
+    }
+    let Ok(mut value) = serde_json::from_slice::<Value>(bytes) else {
+        return (None, false);
+    };
+    let original = value.clone();
+    redact_json(&mut value);
+    let whole = value == original;
+    (Some(value), whole)
+}
+
+//--------------------------------------------------------------------
+// Replay
+//--------------------------------------------------------------------
+/// Outcome of [`replay`].
+#[derive(Debug, Default, Serialize)]
+pub struct ReplayReport {
+    pub replayed: usize,
+    /// Recorded exchanges that could not be sent again as recorded.
+    pub skipped: usize,
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+impl ReplayReport {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// A replayed request whose answer differs from the recording.  `line` is
+/// 1-based in the recording.
+#[derive(Debug, Serialize)]
+pub struct ReplayMismatch {
+    pub line: usize,
+    pub uri: String,
+    pub reason: String,
+}
+
+/// Send every replayable exchange in `recording` to the server at
+/// `base_url` and compare the answers with the recorded ones.
+///
+/// Proofs, receipts and nonces differ from run to run, so responses are
+/// compared by shape: the status, the same object keys at every depth,
+/// and the same JSON types, with booleans (such as `valid`) compared by
+/// value.
+pub async fn replay(recording: &Path, base_url: &str) -> io::Result<ReplayReport> {
+    let client = reqwest::Client::new();
+    let base = base_url.trim_end_matches('/');
+    let mut report = ReplayReport::default();
+    for (index, line) in BufReader::new(File::open(recording)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let exchange: RecordedExchange = serde_json::from_str(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {e}", index + 1)))?;
+        if !exchange.replayable {
+            report.skipped += 1;
+            continue;
+        }
+        let method = exchange.method.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: bad method", index + 1)))?;
+        let mut request = client.request(method, format!("{base}{}", exchange.uri));
+        if let Some(body) = &exchange.request {
+            request = request.json(body);
+        }
+        report.replayed += 1;
+        let mismatch = |reason: String| ReplayMismatch { line: index + 1, uri: exchange.uri.clone(), reason };
+        let res = match request.send().await {
+            Ok(res) => res,
+            Err(e) => {
+                report.mismatches.push(mismatch(format!("request failed: {e}")));
+                continue;
+            }
+        };
+        let status = res.status().as_u16();
+        let json = res.headers().get(CONTENT_TYPE.as_str()).and_then(|v| v.to_str().ok()).is_some_and(|v| v.starts_with("application/json"));
+        let body = if json { res.json::<Value>().await.ok() } else { None };
+        if status != exchange.status {
+            report.mismatches.push(mismatch(format!("status {status}, recorded {}", exchange.status)));
+        } else if let (Some(recorded), Some(got)) = (&exchange.response, &body) {
+            if let Err(at) = same_shape(recorded, got, "$") {
+                report.mismatches.push(mismatch(format!("response differs at {at}")));
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// `Err` with the JSON path of the first difference.  A redacted value in
+/// the recording matches anything.
+fn same_shape(recorded: &Value, got: &Value, at: &str) -> Result<(), String> {
+    match (recorded, got) {
+        (Value::String(s), _) if s == crate::redact::REDACTED => Ok(()),
+        (Value::Object(a), Value::Object(b)) => {
+            if a.len() != b.len() || a.keys().any(|k| !b.contains_key(k)) {
+                return Err(at.to_owned());
+            }
+            a.iter().try_for_each(|(k, v)| same_shape(v, &b[k], &format!("{at}.{k}")))
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            a.iter().zip(b).enumerate().try_for_each(|(i, (x, y))| same_shape(x, y, &format!("{at}[{i}]")))
+        }
+        (Value::Bool(a), Value::Bool(b)) if a == b => Ok(()),
+        (Value::Null, Value::Null) | (Value::Number(_), Value::Number(_)) | (Value::String(_), Value::String(_)) => Ok(()),
+        _ => Err(at.to_owned()),
+    }
+}
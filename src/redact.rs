@@ -0,0 +1,67 @@
+use serde_json::Value;
+use std::fmt;
+
+//--------------------------------------------------------------------
+// Redaction policy
+//--------------------------------------------------------------------
+// Secrets, nonces, witness values and the identity attributes they are
+// derived from must never reach logs, panic messages or error bodies.
+// Types carrying them implement `Debug` by hand through `Redacted`, and any
+// JSON or URI captured for diagnostics goes through `redact_json` or
+// `redact_query` first.
+
+/// Placeholder written in place of a sensitive value.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// JSON keys and query parameters whose values are always replaced by
+/// [`REDACTED`].  `redirect_to` carries an OpenID Connect authorization code.
+pub const SENSITIVE_KEYS: &[&str] = &[
+    "secret", "secret_hex", "secrets_hex", "nonce", "witness", "email", "name", "age", "country", "dob", "phone", "biometric_hash", "token",
+    "access_token", "id_token", "client_secret", "code", "code_verifier", "redirect_to",
+];
+
+/// Wrapper whose `Debug` and `Display` never print the inner value.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Redacted<T>(pub T);
+
+impl<T> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Replace the value of every [`SENSITIVE_KEYS`] entry, at any depth.
+pub fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if SENSITIVE_KEYS.contains(&key.as_str()) {
+                    *v = Value::String(REDACTED.to_owned());
+                } else {
+                    redact_json(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+/// `uri` with the value of every [`SENSITIVE_KEYS`] query parameter
+/// replaced.  Other parameters are re-encoded as they decode.
+pub fn redact_query(uri: &str) -> String {
+    let Some((path, query)) = uri.split_once('?') else {
+        return uri.to_owned();
+    };
+    let mut redacted = url::form_urlencoded::Serializer::new(String::new());
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        redacted.append_pair(&key, if SENSITIVE_KEYS.contains(&key.as_ref()) { REDACTED } else { &value });
+    }
+    format!("{path}?{}", redacted.finish())
+}
@@ -0,0 +1,190 @@
+use actix_web::web;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::convert::normalize_public_input;
+use crate::revocation::RevocationsDelta;
+use crate::tree::RootsDelta;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Commitment-tree replication
+//--------------------------------------------------------------------
+/// Follow a primary's commitment tree so this node knows every registered
+/// commitment without a shared database.
+///
+/// The replica pulls the primary's `/roots/delta` feed, appends the leaves
+/// to its own tree and checks its root against the primary's after each
+/// one.  It mirrors the primary's `/revocations` the same way.  The primary
+/// needs no configuration.
+///
+/// Spent nullifiers and used challenges are not in either feed: a verifying
+/// replica must share the primary's [`Store`](crate::Store), or a proof
+/// spent on one node would verify again on the other.  [`crate::run`]
+/// refuses to start one that does not.
+#[derive(Clone, Debug)]
+pub struct ReplicationConfig {
+    /// Base URL of the primary, e.g. `http://primary:8080`.
+    pub primary: String,
+    /// Pause between polls once caught up.
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self { primary: "http://127.0.0.1:8080".into(), interval: Duration::from_secs(2), timeout: Duration::from_secs(10) }
+    }
+}
+
+/// Where a replica stands relative to its primary.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ReplicationStatus {
+    pub primary: String,
+    /// Leaves held locally.
+    pub leaves: u64,
+    /// Leaves the primary reported at the last complete sync.
+    pub primary_leaves: u64,
+    /// Revocations held locally.
+    pub revocations: u64,
+    /// Unix seconds of the last poll that reached the primary's head.
+    pub last_synced_at: Option<u64>,
+    /// A leaf did not reproduce the primary's root.  Replication stops
+    /// until the replica is restarted.
+    pub diverged: bool,
+}
+
+/// `/health` view of [`ReplicationStatus`].  `lag_secs` is the time since
+/// the replica last reached the primary's head, `None` before the first
+/// sync; it grows while the primary is unreachable.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReplicationLag {
+    pub leaves: u64,
+    pub lag_secs: Option<u64>,
+    pub diverged: bool,
+}
+
+pub(crate) struct Replicator {
+    config: ReplicationConfig,
+    client: reqwest::Client,
+    status: Mutex<ReplicationStatus>,
+}
+
+impl Replicator {
+    pub(crate) fn new(config: ReplicationConfig) -> Self {
+        let client = reqwest::Client::builder().timeout(config.timeout).build().expect("default TLS backend is available");
+        let status = Mutex::new(ReplicationStatus { primary: config.primary.clone(), ..Default::default() });
+        Self { config, client, status }
+    }
+
+    pub(crate) fn status(&self) -> ReplicationStatus {
+        self.status.lock().expect("replication status poisoned").clone()
+    }
+
+    /// How far behind the primary this replica may be.
+    pub(crate) fn lag(&self) -> ReplicationLag {
+        let status = self.status();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        ReplicationLag {
+            leaves: status.leaves,
+            lag_secs: status.last_synced_at.map(|at| now.saturating_sub(at)),
+            diverged: status.diverged,
+        }
+    }
+
+    async fn fetch<T: serde::de::DeserializeOwned>(&self, feed: &str, since_seq: u64) -> Result<T, String> {
+        let url = format!("{}/{feed}?since_seq={since_seq}", self.config.primary.trim_end_matches('/'));
+        let res = self.client.get(url).send().await.map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            return Err(format!("primary answered {}", res.status()));
+        }
+        res.json().await.map_err(|e| e.to_string())
+    }
+}
+
+/// Keep `state.tree` in step with the primary until it diverges.
+pub async fn run_replication(state: web::Data<AppState>) {
+    let Some(replicator) = &state.replication else { return };
+    let mut ticks = actix_web::rt::time::interval(replicator.config.interval);
+    loop {
+        ticks.tick().await;
+        // Drain the feed: one delta per request, `more` while behind.
+        loop {
+            let since_seq = state.tree.read().expect("commitment tree poisoned").len();
+            let delta: RootsDelta = match replicator.fetch("roots/delta", since_seq).await {
+                Ok(delta) => delta,
+                // Retried on the next tick.
+                Err(error) => {
+                    tracing::warn!(primary = %replicator.config.primary, %error, "replication poll failed");
+                    break;
+                }
+            };
+            let (seq, more) = (delta.seq, delta.more);
+            // Up to 10 000 leaves of Poseidon hashing under the write lock.
+            let worker_state = state.clone();
+            let applied = web::block(move || apply(&worker_state, &delta)).await.unwrap_or_else(|e| Err(e.to_string()));
+            if let Err(error) = applied {
+                tracing::error!(primary = %replicator.config.primary, since_seq, %error, "replica diverged from primary; replication stopped");
+                replicator.status.lock().expect("replication status poisoned").diverged = true;
+                return;
+            }
+            let mut status = replicator.status.lock().expect("replication status poisoned");
+            status.leaves = seq;
+            if !more {
+                status.primary_leaves = seq;
+                status.last_synced_at = Some(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+                break;
+            }
+        }
+        loop {
+            let since_seq = state.revocations.len();
+            let delta: RevocationsDelta = match replicator.fetch("revocations", since_seq).await {
+                Ok(delta) => delta,
+                Err(error) => {
+                    tracing::warn!(primary = %replicator.config.primary, %error, "revocation poll failed");
+                    break;
+                }
+            };
+            if let Err(error) = state.revocations.apply(delta.since_seq, &delta.revocations) {
+                tracing::error!(primary = %replicator.config.primary, since_seq, %error, "replica diverged from primary; replication stopped");
+                replicator.status.lock().expect("replication status poisoned").diverged = true;
+                return;
+            }
+            replicator.status.lock().expect("replication status poisoned").revocations = delta.seq;
+            if !delta.more {
+                break;
+            }
+        }
+    }
+}
+
+/// Append the delta's leaves and zero its revoked ones in the primary's
+/// order, checking the root after each step.
+fn apply(state: &AppState, delta: &RootsDelta) -> Result<(), String> {
+    let mut tree = state.tree.write().expect("commitment tree poisoned");
+    if delta.since_seq != tree.len() || delta.leaves.len() != delta.roots.len() {
+        return Err("delta does not continue the local tree".into());
+    }
+    let mut zeroed = delta.zeroed.iter().peekable();
+    for step in delta.leaves.iter().zip(&delta.roots).map(Some).chain([None]) {
+        while let Some(z) = zeroed.next_if(|z| z.at == tree.len()) {
+            let root = normalize_public_input(&z.root).map_err(|e| e.to_string())?;
+            // Already zeroed if the last delta listed it too.
+            if tree.zero_at(z.index) && tree.root() != root {
+                return Err(format!("root mismatch after zeroing leaf {}", z.index));
+            }
+        }
+        let Some((leaf, root)) = step else { break };
+        let leaf = normalize_public_input(leaf).map_err(|e| e.to_string())?;
+        let root = normalize_public_input(root).map_err(|e| e.to_string())?;
+        let index = tree.append(leaf).map_err(|e| e.to_string())?;
+        if tree.root() != root {
+            return Err(format!("root mismatch after leaf {index}"));
+        }
+    }
+    if zeroed.next().is_some() {
+        return Err("delta zeroes a leaf out of order".into());
+    }
+    Ok(())
+}
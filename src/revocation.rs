@@ -0,0 +1,176 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, ResponseError};
+use ark_bn254::Fr;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::convert::normalize_public_input;
+use crate::error::ApiError;
+use crate::etag::json_with_etag;
+use crate::snarkjs::public_signals;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Revoked commitments
+//--------------------------------------------------------------------
+/// Longest `reason` accepted by `/admin/revoke`.
+const MAX_REASON: usize = 256;
+/// Most revocations returned by one `/revocations` request.
+const MAX_DELTA: usize = 10_000;
+
+/// Commitments an operator has revoked, in the order they were revoked.
+/// Proofs for them are refused before the pairing, and their leaves in the
+/// commitment tree are zeroed so they fail group membership too.
+///
+/// Held in memory only, like the commitment tree: revocations are lost on
+/// restart unless a replica or relying party mirrors them.
+#[derive(Default)]
+pub(crate) struct Revocations(RwLock<Log>);
+
+#[derive(Default)]
+struct Log {
+    entries: Vec<RevocationEntry>,
+    /// Index of each revoked commitment in `entries`.
+    index: HashMap<Fr, usize>,
+}
+
+/// One revocation.  The position in the feed is its `seq`, counted from 1.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RevocationEntry {
+    /// Decimal, as `/register` returns it.
+    pub commitment: String,
+    /// Unix seconds.
+    pub revoked_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Revocations {
+    pub(crate) fn len(&self) -> u64 {
+        self.0.read().expect("revocations poisoned").entries.len() as u64
+    }
+
+    pub(crate) fn is_revoked(&self, commitment: Fr) -> bool {
+        self.0.read().expect("revocations poisoned").index.contains_key(&commitment)
+    }
+
+    /// Revoke `commitment`.  Revoking it again changes nothing and returns
+    /// the first entry and its `seq`.
+    fn revoke(&self, commitment: Fr, reason: Option<String>) -> (u64, RevocationEntry) {
+        let mut log = self.0.write().expect("revocations poisoned");
+        if let Some(&i) = log.index.get(&commitment) {
+            return (i as u64 + 1, log.entries[i].clone());
+        }
+        let entry = RevocationEntry {
+            commitment: public_signals(&[commitment]).remove(0),
+            revoked_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            reason,
+        };
+        let i = log.entries.len();
+        log.entries.push(entry.clone());
+        log.index.insert(commitment, i);
+        (i as u64 + 1, entry)
+    }
+
+    /// Entries after the first `since`, at most `limit`.
+    fn delta(&self, since: u64, limit: usize) -> Vec<RevocationEntry> {
+        let log = self.0.read().expect("revocations poisoned");
+        let start = (since as usize).min(log.entries.len());
+        log.entries[start..].iter().take(limit).cloned().collect()
+    }
+
+    /// Append entries mirrored from a primary's feed, which continue this
+    /// log after its first `since` entries.
+    pub(crate) fn apply(&self, since: u64, entries: &[RevocationEntry]) -> Result<(), String> {
+        let mut log = self.0.write().expect("revocations poisoned");
+        if since != log.entries.len() as u64 {
+            return Err("delta does not continue the local revocations".into());
+        }
+        for entry in entries {
+            let commitment = normalize_public_input(&entry.commitment).map_err(|e| e.to_string())?;
+            if log.index.contains_key(&commitment) {
+                return Err(format!("commitment {} revoked twice", entry.commitment));
+            }
+            let i = log.entries.len();
+            log.entries.push(entry.clone());
+            log.index.insert(commitment, i);
+        }
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------
+// /admin/revoke
+//--------------------------------------------------------------------
+#[derive(Debug, Deserialize)]
+pub struct RevokeRequest {
+    pub commitment: String,
+    /// Free text published in the feed, e.g. `"device lost"`.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// The revocation as the feed lists it, and its position there.
+#[derive(Debug, Serialize)]
+pub struct RevokeResponse {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub revocation: RevocationEntry,
+}
+
+#[post("/revoke")]
+async fn revoke(state: web::Data<AppState>, body: web::Json<RevokeRequest>) -> HttpResponse {
+    let Ok(commitment) = normalize_public_input(&body.commitment) else {
+        return ApiError::InvalidInput { field: "commitment", reason: "not a scalar field element" }.error_response();
+    };
+    // A replica mirrors the primary's revocations; its own would diverge.
+    if state.replication.is_some() {
+        return ApiError::InvalidInput { field: "commitment", reason: "revoke on the primary this node replicates" }.error_response();
+    }
+    if body.reason.as_ref().is_some_and(|r| r.len() > MAX_REASON) {
+        return ApiError::InvalidInput { field: "reason", reason: "longer than 256 bytes" }.error_response();
+    }
+    let (seq, revocation) = state.revocations.revoke(commitment, body.into_inner().reason);
+    // Anonymous membership proofs never name the commitment, so its leaf goes.
+    state.tree.write().expect("commitment tree poisoned").revoke(commitment);
+    tracing::info!(seq, commitment = %revocation.commitment, "commitment revoked");
+    HttpResponse::Ok().json(RevokeResponse { seq, revocation })
+}
+
+//--------------------------------------------------------------------
+// /revocations
+//--------------------------------------------------------------------
+#[derive(Debug, Deserialize)]
+pub struct RevocationsQuery {
+    /// Number of revocations the client already holds.
+    #[serde(default)]
+    pub since_seq: u64,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Revocations after `since_seq`, oldest first.  `seq` is the count the
+/// client holds once it has applied them; ask again from there while
+/// `more` is set.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevocationsDelta {
+    pub since_seq: u64,
+    pub seq: u64,
+    pub revocations: Vec<RevocationEntry>,
+    pub more: bool,
+}
+
+/// Lets relying parties that check proofs themselves refuse revoked
+/// commitments too.  A client polling from its head gets `304` until a
+/// commitment is revoked.
+#[get("/revocations")]
+async fn revocations(state: web::Data<AppState>, query: web::Query<RevocationsQuery>, req: HttpRequest) -> HttpResponse {
+    let total = state.revocations.len();
+    if query.since_seq > total {
+        return ApiError::InvalidInput { field: "since_seq", reason: "ahead of the revocations" }.error_response();
+    }
+    let revocations = state.revocations.delta(query.since_seq, query.limit.unwrap_or(MAX_DELTA).min(MAX_DELTA));
+    let seq = query.since_seq + revocations.len() as u64;
+    json_with_etag(&req, &RevocationsDelta { since_seq: query.since_seq, seq, revocations, more: seq < total })
+}
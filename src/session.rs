@@ -0,0 +1,197 @@
+use actix_web::{get, web, HttpRequest, HttpResponse, ResponseError};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL;
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use rand::{thread_rng, RngCore};
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::ApiError;
+use crate::etag::json_with_etag;
+use crate::handlers::VerifyResponse;
+use crate::receipt::{ed25519_key, key_id};
+use crate::redact::Redacted;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Session tokens
+//--------------------------------------------------------------------
+/// Hand out a JWT with every single-use proof `/verify-proof` accepts, so
+/// backends get a session without building their own.  Only proofs for a
+/// nullifier or challenge circuit qualify, and [`crate::AppState::new`]
+/// refuses sessions when neither is configured.
+///
+/// Tokens are signed with Ed25519 (`alg: EdDSA`).  Downstream services
+/// check them against the key at `GET /.well-known/jwks.json`.
+#[derive(Clone, Debug)]
+pub struct SessionConfig {
+    /// File holding the 32-byte hex seed of the signing key.  `None`
+    /// generates a key per process, so tokens stop validating on restart.
+    pub key: Option<PathBuf>,
+    /// Lifetime of a token, its `exp` minus its `iat`.
+    pub ttl: Duration,
+    /// The `iss` claim.
+    pub issuer: String,
+    /// The `aud` claim; omitted when `None`.
+    pub audience: Option<String>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self { key: None, ttl: Duration::from_secs(3600), issuer: "zk-auth".into(), audience: None }
+    }
+}
+
+/// The claims of a session token.
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionClaims {
+    pub iss: String,
+    /// The commitment the proof was for, decimal as `/register` returns it.
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    /// Unix seconds.
+    pub iat: u64,
+    pub exp: u64,
+    /// Random per token, so a consumer can deny-list one.
+    pub jti: String,
+    /// Fingerprint of the verifying key that accepted the proof.
+    pub circuit_version: String,
+}
+
+/// `session` in a `/verify-proof` answer, shaped like an OAuth 2.0 token
+/// response.
+#[derive(Serialize)]
+pub struct SessionToken {
+    pub access_token: String,
+    /// Always `"Bearer"`.
+    pub token_type: &'static str,
+    pub expires_in: u64,
+}
+
+impl std::fmt::Debug for SessionToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionToken")
+            .field("access_token", &Redacted(&self.access_token))
+            .field("token_type", &self.token_type)
+            .field("expires_in", &self.expires_in)
+            .finish()
+    }
+}
+
+pub(crate) struct SessionIssuer {
+    config: SessionConfig,
+    signer: JwtSigner,
+}
+
+impl SessionIssuer {
+    pub(crate) fn new(config: SessionConfig) -> io::Result<Self> {
+        Ok(Self { signer: JwtSigner::new(config.key.as_deref(), "session key")?, config })
+    }
+
+    pub(crate) fn mint(&self, commitment: &str, circuit_version: &str) -> SessionToken {
+        let iat = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mut jti = [0u8; 16];
+        thread_rng().fill_bytes(&mut jti);
+        let claims = SessionClaims {
+            iss: self.config.issuer.clone(),
+            sub: commitment.to_owned(),
+            aud: self.config.audience.clone(),
+            iat,
+            exp: iat + self.config.ttl.as_secs(),
+            jti: hex::encode(jti),
+            circuit_version: circuit_version.to_owned(),
+        };
+        SessionToken { access_token: self.sign(&claims), token_type: "Bearer", expires_in: self.config.ttl.as_secs() }
+    }
+
+    /// A compact JWS of `claims` under the session key.
+    pub(crate) fn sign<T: Serialize>(&self, claims: &T) -> String {
+        self.signer.sign(claims)
+    }
+
+    pub(crate) fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+}
+
+/// An Ed25519 key that signs JWTs and is published in the JWKS.
+pub(crate) struct JwtSigner {
+    key: SigningKey,
+    key_id: String,
+}
+
+impl JwtSigner {
+    /// Key from a hex seed file, or a fresh one; see [`ed25519_key`].
+    pub(crate) fn new(path: Option<&Path>, what: &str) -> io::Result<Self> {
+        let key = ed25519_key(path, what)?;
+        Ok(Self { key_id: key_id(&key), key })
+    }
+
+    /// A compact JWS of `claims`, with `alg: EdDSA` and this key's `kid`.
+    pub(crate) fn sign<T: Serialize>(&self, claims: &T) -> String {
+        let header = serde_json::json!({ "alg": "EdDSA", "typ": "JWT", "kid": self.key_id });
+        let header = BASE64URL.encode(header.to_string());
+        let claims = BASE64URL.encode(serde_json::to_vec(claims).expect("claims serialise to JSON"));
+        let signing_input = format!("{header}.{claims}");
+        let signature = BASE64URL.encode(self.key.sign(signing_input.as_bytes()).to_bytes());
+        format!("{signing_input}.{signature}")
+    }
+
+    pub(crate) fn jwk(&self) -> Jwk {
+        Jwk {
+            kty: "OKP",
+            crv: "Ed25519",
+            alg: "EdDSA",
+            key_use: "sig",
+            kid: self.key_id.clone(),
+            x: BASE64URL.encode(self.key.verifying_key().as_bytes()),
+        }
+    }
+}
+
+/// `verdict` with a session token added, when sessions are enabled and
+/// the proof is valid and spent a nullifier or a challenge.  A proof for
+/// the bundled circuit verifies again and again, so anyone who saw it once
+/// could mint sessions from it.
+pub(crate) fn with_session(state: &AppState, mut verdict: VerifyResponse) -> VerifyResponse {
+    let minted_for = verdict.circuit_version.as_deref().filter(|_| verdict.valid && verdict.single_use);
+    if let (Some(issuer), Some(circuit_version)) = (&state.sessions, minted_for) {
+        verdict.session = Some(issuer.mint(&verdict.receipt.commitment, circuit_version));
+    }
+    verdict
+}
+
+//--------------------------------------------------------------------
+// /.well-known/jwks.json
+//--------------------------------------------------------------------
+/// RFC 7517 key set with the session and credential signing keys.
+#[derive(Debug, Serialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+/// An RFC 8037 Ed25519 public key.
+#[derive(Debug, Serialize)]
+pub struct Jwk {
+    pub kty: &'static str,
+    pub crv: &'static str,
+    pub alg: &'static str,
+    #[serde(rename = "use")]
+    pub key_use: &'static str,
+    pub kid: String,
+    /// The public key, base64url without padding.
+    pub x: String,
+}
+
+#[get("/.well-known/jwks.json")]
+async fn jwks(state: web::Data<AppState>, req: HttpRequest) -> HttpResponse {
+    let sessions = state.sessions.as_ref().map(|s| &s.signer);
+    let keys: Vec<Jwk> = sessions.into_iter().chain(state.credentials.as_ref().map(|c| c.signer())).map(JwtSigner::jwk).collect();
+    if keys.is_empty() {
+        return ApiError::NotFound("neither session tokens nor credentials are enabled on this server").error_response();
+    }
+    json_with_etag(&req, &Jwks { keys })
+}
@@ -0,0 +1,133 @@
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::{Proof, VerifyingKey};
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+
+//--------------------------------------------------------------------
+// snarkjs JSON formats
+//--------------------------------------------------------------------
+// The `proof.json`, `public.json` and `verification_key.json` layouts that
+// `snarkjs groth16 verify` reads.  Points are projective with decimal
+// coordinates; G2 limbs are `[c0, c1]`, unlike the EVM order in ProofJson.
+
+#[derive(Debug,Serialize,Deserialize)]
+pub struct SnarkjsProof { pub pi_a:[String;3], pub pi_b:[[String;2];3], pub pi_c:[String;3], pub protocol:String, pub curve:String }
+
+#[derive(Debug,Serialize,Deserialize)]
+pub struct SnarkjsVerificationKey {
+    pub protocol:String,
+    pub curve:String,
+    #[serde(rename="nPublic")] pub n_public:usize,
+    pub vk_alpha_1:[String;3],
+    pub vk_beta_2:[[String;2];3],
+    pub vk_gamma_2:[[String;2];3],
+    pub vk_delta_2:[[String;2];3],
+    #[serde(rename="IC")] pub ic:Vec<[String;3]>,
+}
+
+impl SnarkjsProof {
+    pub fn from_proof(proof:&Proof<Bn254>)->Self{
+        SnarkjsProof{ pi_a:g1(&proof.a), pi_b:g2(&proof.b), pi_c:g1(&proof.c), protocol:"groth16".into(), curve:"bn128".into() }
+    }
+}
+
+impl SnarkjsVerificationKey {
+    pub fn from_vk(vk:&VerifyingKey<Bn254>)->Self{
+        SnarkjsVerificationKey{
+            protocol:"groth16".into(), curve:"bn128".into(), n_public:vk.gamma_abc_g1.len()-1,
+            vk_alpha_1:g1(&vk.alpha_g1), vk_beta_2:g2(&vk.beta_g2), vk_gamma_2:g2(&vk.gamma_g2), vk_delta_2:g2(&vk.delta_g2),
+            ic:vk.gamma_abc_g1.iter().map(g1).collect(),
+        }
+    }
+}
+
+/// `public.json`: the public signals as decimal strings, in circuit order.
+pub fn public_signals(inputs:&[Fr])->Vec<String>{ inputs.iter().map(dec).collect() }
+
+fn dec<F:PrimeField>(f:&F)->String{ BigUint::from_bytes_be(&f.into_bigint().to_bytes_be()).to_string() }
+fn fq2(f:&Fq2)->[String;2]{ [dec(&f.c0),dec(&f.c1)] }
+fn g1(p:&G1Affine)->[String;3]{ [dec(&p.x),dec(&p.y),"1".into()] }
+fn g2(p:&G2Affine)->[[String;2];3]{ [fq2(&p.x),fq2(&p.y),["1".into(),"0".into()]] }
+
+impl SnarkjsProof {
+    /// The ark proof, or `None` if a coordinate is malformed or a point is
+    /// not on the curve.  Only affine points (`z = 1`) are accepted, which
+    /// is all snarkjs emits.
+    pub fn to_proof(&self)->Option<Proof<Bn254>>{
+        Some(Proof{ a:g1_from(&self.pi_a)?, b:g2_from(&self.pi_b)?, c:g1_from(&self.pi_c)? })
+    }
+}
+
+pub(crate) fn fq_from_dec(s:&str)->Option<Fq>{
+    let n=BigUint::parse_bytes(s.as_bytes(),10)?;
+    (n<BigUint::from(Fq::MODULUS)).then(||Fq::from(n))
+}
+fn g1_from(p:&[String;3])->Option<G1Affine>{
+    if p[2]!="1" { return None; }
+    checked_g1(fq_from_dec(&p[0])?,fq_from_dec(&p[1])?)
+}
+fn g2_from(p:&[[String;2];3])->Option<G2Affine>{
+    if p[2][0]!="1" || p[2][1]!="0" { return None; }
+    let x=Fq2::new(fq_from_dec(&p[0][0])?,fq_from_dec(&p[0][1])?);
+    let y=Fq2::new(fq_from_dec(&p[1][0])?,fq_from_dec(&p[1][1])?);
+    checked_g2(x,y)
+}
+
+// Shared with the no_std verifier so both check points the same way.
+pub(crate) use zk_auth_verify_core::{checked_g1, checked_g2};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{identity, valid_proof};
+
+    fn proof() -> Proof<Bn254> {
+        valid_proof(&identity(11), 11).try_to_proof().expect("valid proof parses")
+    }
+
+    #[test]
+    fn round_trips_a_real_proof() {
+        let proof = proof();
+        let json = serde_json::to_string(&SnarkjsProof::from_proof(&proof)).unwrap();
+        let parsed: SnarkjsProof = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.to_proof(), Some(proof));
+        assert_eq!((parsed.protocol.as_str(), parsed.curve.as_str()), ("groth16", "bn128"));
+    }
+
+    #[test]
+    fn g2_limbs_are_c0_first() {
+        let proof = proof();
+        let snarkjs = SnarkjsProof::from_proof(&proof);
+        assert_eq!(snarkjs.pi_b[0], [dec(&proof.b.x.c0), dec(&proof.b.x.c1)]);
+        assert_eq!(snarkjs.pi_b[1], [dec(&proof.b.y.c0), dec(&proof.b.y.c1)]);
+        assert_eq!(snarkjs.pi_b[2], ["1".to_owned(), "0".to_owned()]);
+
+        let mut swapped = SnarkjsProof::from_proof(&proof);
+        swapped.pi_b[0].swap(0, 1);
+        assert_eq!(swapped.to_proof(), None);
+    }
+
+    #[test]
+    fn rejects_projective_and_out_of_range_coordinates() {
+        let proof = proof();
+        let mut projective = SnarkjsProof::from_proof(&proof);
+        projective.pi_a[2] = "2".into();
+        assert_eq!(projective.to_proof(), None);
+
+        let mut unreduced = SnarkjsProof::from_proof(&proof);
+        let x = BigUint::parse_bytes(unreduced.pi_c[0].as_bytes(), 10).unwrap() + BigUint::from(Fq::MODULUS);
+        unreduced.pi_c[0] = x.to_string();
+        assert_eq!(unreduced.to_proof(), None);
+        assert_eq!(fq_from_dec("0x01"), None);
+    }
+
+    #[test]
+    fn public_signals_are_decimal() {
+        assert_eq!(public_signals(&[Fr::from(0u64), Fr::from(1234u64), -Fr::from(1u64)]), [
+            "0",
+            "1234",
+            "21888242871839275222246405745257275088548364400416034343698204186575808495616",
+        ]);
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::RwLock;
+use std::time::{Instant, SystemTime};
+
+use crate::anchor::Anchorer;
+use crate::artifacts::{Artifacts, CIRCUIT_ID};
+use crate::attestation::Attester;
+use crate::commit_reveal::PendingCommits;
+use crate::config::CircuitKind;
+use crate::credential::CredentialIssuer;
+use crate::ipfs::IpfsFetcher;
+use crate::limits::{LimiterStats, Limits};
+use crate::oidc::AuthorizationCodes;
+use crate::prewarm::Prewarm;
+use crate::proof_jobs::{FileJobStore, JobStore, MemoryJobStore, RunningJobs};
+use crate::proof_store::ProofStore;
+use crate::proof_token::ProofTokens;
+use crate::receipt::ReceiptSigner;
+use crate::replication::Replicator;
+use crate::revocation::Revocations;
+use crate::session::SessionIssuer;
+#[cfg(feature = "redis")]
+use crate::store::RedisStore;
+use crate::store::{MemoryStore, Store};
+use crate::tree::CommitmentTree;
+use crate::vrf::VrfKey;
+use crate::webhook::Webhook;
+use crate::AppConfig;
+
+//--------------------------------------------------------------------
+// Shared handler state
+//--------------------------------------------------------------------
+/// State shared by every handler of one mounted scope.
+///
+/// Build it once and hand clones of the `web::Data` to each worker so the
+/// concurrency limits apply to the whole server rather than per worker.
+pub struct AppState {
+    pub(crate) config: AppConfig,
+    pub(crate) limits: Limits,
+    pub(crate) artifacts: Artifacts,
+    /// The key scheduled by [`AppConfig::rotation`], if any.
+    pub(crate) staged: Option<Artifacts>,
+    /// [`AppConfig::historical_vks`], in configured order.
+    pub(crate) historical: Vec<Artifacts>,
+    /// [`AppConfig::circuits`], by circuit id.
+    pub(crate) circuits: HashMap<String, Artifacts>,
+    pub(crate) receipts: ReceiptSigner,
+    /// Mints session tokens when [`AppConfig::session`] is set.
+    pub(crate) sessions: Option<SessionIssuer>,
+    /// Signs registration credentials when [`AppConfig::credentials`] is set.
+    pub(crate) credentials: Option<CredentialIssuer>,
+    /// Quote source when running inside a TEE.
+    pub(crate) attester: Option<Attester>,
+    pub(crate) ipfs: Option<IpfsFetcher>,
+    /// Commitments from `/register`, in registration order.
+    pub(crate) tree: RwLock<CommitmentTree>,
+    /// Commitments revoked through `/admin/revoke` or replicated.
+    pub(crate) revocations: Revocations,
+    pub(crate) anchor: Option<Anchorer>,
+    /// Pulls the primary's tree when [`AppConfig::replication`] is set.
+    pub(crate) replication: Option<Replicator>,
+    /// Sends registrations on when [`AppConfig::webhook`] is set.
+    pub(crate) webhook: Option<Webhook>,
+    /// Derives registration nonces when [`AppConfig::vrf_key`] is set.
+    pub(crate) vrf: Option<VrfKey>,
+    /// Commit–reveal registrations awaiting their reveal.
+    pub(crate) commits: PendingCommits,
+    /// Tokens minted by `/proof-token`, awaiting `/redeem`.
+    pub(crate) proof_tokens: ProofTokens,
+    /// Codes from `/authorize`, awaiting `/token`.
+    pub(crate) authorization_codes: AuthorizationCodes,
+    /// `/proof-jobs` jobs, in memory or in [`AppConfig::job_store`] unless
+    /// [`AppState::with_job_store`] replaced it.
+    pub(crate) jobs: Box<dyn JobStore>,
+    /// Proof job tasks on this node, for cancellation.
+    pub(crate) running_jobs: RunningJobs,
+    /// Spent nullifiers, issued challenges and, when shared, the prove
+    /// admission rate; see `AppConfig::redis` and [`AppState::with_store`].
+    pub(crate) store: Box<dyn Store>,
+    /// Proofs generated ahead of hinted logins.
+    pub(crate) prewarm: Option<Prewarm>,
+    /// Every proof handed out, by hash, when [`AppConfig::proof_store`] is set.
+    pub(crate) proofs: Option<ProofStore>,
+    /// [`AppConfig::mock_prover`], if this build honours it.
+    pub(crate) mock: bool,
+    #[cfg(feature = "chaos")]
+    pub(crate) chaos: crate::chaos::Chaos,
+    pub(crate) started: Instant,
+}
+
+impl AppState {
+    /// Check `config` and load its keys.  An inconsistent configuration or a
+    /// key that cannot be read is an error naming what is wrong.
+    pub fn new(config: AppConfig) -> io::Result<Self> {
+        let limits = Limits::new(&config.concurrency, config.prove_rate.clone());
+        let artifacts = Artifacts::new(&config);
+        let staged = config.rotation.as_ref().map(|r| Artifacts::staged(&config, &r.next_key));
+        let historical: Vec<_> = config.historical_vks.iter().map(|vk| Artifacts::historical(&config.circuit, vk)).collect();
+        if config.circuits.contains_key(CIRCUIT_ID) {
+            return Err(invalid(format!("circuit id `{CIRCUIT_ID}` is the bundled circuit's")));
+        }
+        if config.circuit.kind != CircuitKind::Secret {
+            return Err(invalid("the bundled circuit must be of kind `secret`"));
+        }
+        let circuits: HashMap<_, _> = config.circuits.iter().map(|(id, paths)| (id.clone(), Artifacts::circuit(paths))).collect();
+        if let Some(policy) = &config.artifact_signing {
+            let mut files: Vec<_> =
+                [&artifacts].into_iter().chain(&staged).chain(&historical).chain(circuits.values()).flat_map(|a| a.files()).collect();
+            files.sort();
+            files.dedup();
+            crate::cosign::verify_artifacts(policy, &files).map_err(context("artefact signature check failed"))?;
+        }
+        let receipts = ReceiptSigner::new(config.receipt_key.as_deref()).map_err(context("invalid receipt key"))?;
+        if config.oidc.is_some() && config.session.is_none() {
+            return Err(invalid("OpenID Connect signs with the session key; set AppConfig::session"));
+        }
+        if config.claim_policy.is_none() && config.circuits.values().any(|c| c.kind == CircuitKind::Claims) {
+            return Err(invalid("a claims circuit needs AppConfig::claim_policy"));
+        }
+        if config.audiences.is_empty() && config.circuits.values().any(|c| c.kind == CircuitKind::Audience) {
+            return Err(invalid("an audience circuit needs AppConfig::audiences"));
+        }
+        let single_use = config.circuits.values().any(|c| matches!(c.kind, CircuitKind::Nullifier | CircuitKind::Challenge));
+        if config.session.is_some() && !single_use {
+            return Err(invalid("session tokens need a nullifier or challenge circuit; other proofs can be replayed for further sessions"));
+        }
+        if config.oidc.is_some() && config.circuits.values().all(|c| c.kind != CircuitKind::Challenge) {
+            return Err(invalid("OpenID Connect logins prove with a challenge circuit; configure one"));
+        }
+        let sessions = config.session.clone().map(SessionIssuer::new).transpose().map_err(context("invalid session key"))?;
+        let credentials = config.credentials.clone().map(CredentialIssuer::new).transpose().map_err(context("invalid credential key"))?;
+        let ipfs = config.ipfs.clone().map(IpfsFetcher::new);
+        let vrf = config.vrf_key.as_deref().map(VrfKey::from_file).transpose().map_err(context("invalid VRF key"))?;
+        let anchor = config.anchor.clone().map(Anchorer::new).transpose().map_err(context("invalid anchoring config"))?;
+        if config.replica && config.replication.is_none() {
+            return Err(invalid("replica mode needs a primary to replicate from"));
+        }
+        let replication = config.replication.clone().map(Replicator::new);
+        let webhook = config.webhook.clone().map(Webhook::new);
+        let prewarm = config.prewarm.clone().map(Prewarm::new);
+        let proofs = config.proof_store.clone().map(ProofStore::new).transpose().map_err(context("cannot create proof store directory"))?;
+        let mock = crate::mock::enabled(config.mock_prover.as_ref());
+        let jobs: Box<dyn JobStore> = match config.job_store.clone() {
+            Some(dir) => Box::new(FileJobStore::new(dir, config.job_retention.clone()).map_err(context("cannot create job store directory"))?),
+            None => Box::new(MemoryJobStore::new(config.job_retention.clone())),
+        };
+        #[cfg(feature = "redis")]
+        let store: Box<dyn Store> = match config.redis.clone() {
+            Some(redis) => Box::new(RedisStore::new(redis).map_err(context("invalid Redis URL"))?),
+            None => Box::new(MemoryStore::default()),
+        };
+        #[cfg(not(feature = "redis"))]
+        let store: Box<dyn Store> = Box::new(MemoryStore::default());
+        Ok(Self {
+            config,
+            limits,
+            artifacts,
+            staged,
+            historical,
+            circuits,
+            receipts,
+            sessions,
+            credentials,
+            attester: Attester::detect(),
+            ipfs,
+            tree: RwLock::new(CommitmentTree::new()),
+            revocations: Default::default(),
+            anchor,
+            replication,
+            webhook,
+            vrf,
+            commits: Default::default(),
+            proof_tokens: Default::default(),
+            authorization_codes: Default::default(),
+            jobs,
+            running_jobs: Default::default(),
+            store,
+            prewarm,
+            proofs,
+            mock,
+            #[cfg(feature = "chaos")]
+            chaos: Default::default(),
+            started: Instant::now(),
+        })
+    }
+
+    /// Keep `/proof-jobs` jobs in `store` instead of this process's memory.
+    pub fn with_job_store(mut self, store: impl JobStore + 'static) -> Self {
+        self.jobs = Box::new(store);
+        self
+    }
+
+    /// Keep nullifiers, challenges and rate counters in `store` instead of
+    /// the one [`AppState::new`] chose.
+    pub fn with_store(mut self, store: impl Store + 'static) -> Self {
+        self.store = Box::new(store);
+        self
+    }
+
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// Capacity, occupancy and queue depth of each endpoint limiter.
+    pub fn concurrency_stats(&self) -> Vec<LimiterStats> {
+        self.limits.stats()
+    }
+
+    /// The key new proofs are generated with and clients are told about.
+    pub(crate) fn active_artifacts(&self) -> &Artifacts {
+        match (&self.config.rotation, &self.staged) {
+            (Some(r), Some(staged)) if SystemTime::now() >= r.cutover => staged,
+            _ => &self.artifacts,
+        }
+    }
+
+    /// The artefacts new proofs for `circuit_id` use; `None` when this node
+    /// does not serve that circuit.
+    pub(crate) fn circuit(&self, circuit_id: &str) -> Option<&Artifacts> {
+        if circuit_id == CIRCUIT_ID { Some(self.active_artifacts()) } else { self.circuits.get(circuit_id) }
+    }
+
+    /// [`AppState::verifying_artifacts`] for `circuit_id`; empty when this
+    /// node does not serve that circuit.
+    pub(crate) fn verifying_artifacts_for(&self, circuit_id: &str) -> Vec<&Artifacts> {
+        if circuit_id == CIRCUIT_ID { self.verifying_artifacts() } else { self.circuits.get(circuit_id).into_iter().collect() }
+    }
+
+    /// Keys that verify right now: the active one first, then any key in
+    /// its rotation window, then historical keys in configured order.
+    pub(crate) fn verifying_artifacts(&self) -> Vec<&Artifacts> {
+        let mut keys = self.rotation_artifacts();
+        keys.extend(&self.historical);
+        keys
+    }
+
+    fn rotation_artifacts(&self) -> Vec<&Artifacts> {
+        let (Some(r), Some(staged)) = (&self.config.rotation, &self.staged) else {
+            return vec![&self.artifacts];
+        };
+        let now = SystemTime::now();
+        if now + r.overlap < r.cutover {
+            vec![&self.artifacts]
+        } else if now < r.cutover {
+            vec![&self.artifacts, staged]
+        } else if now < r.cutover + r.overlap {
+            vec![staged, &self.artifacts]
+        } else {
+            vec![staged]
+        }
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, reason.into())
+}
+
+/// Prefix an error with what was being loaded, keeping its kind.
+fn context(what: &'static str) -> impl Fn(io::Error) -> io::Error {
+    move |e| io::Error::new(e.kind(), format!("{what}: {e}"))
+}
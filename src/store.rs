@@ -0,0 +1,284 @@
+use actix_web::web;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::ApiError;
+#[cfg(feature = "redis")]
+use crate::redact::Redacted;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Short-lived shared state
+//--------------------------------------------------------------------
+/// Expiring entries [`MemoryStore`] keeps at once; further ones are refused
+/// until some expire.  Bounds what a flood of `/challenge` calls can hold.
+const MAX_EXPIRING: usize = 100_000;
+
+/// Keys and counters behind spent nullifiers, login challenges and the
+/// prove admission rate.
+///
+/// [`MemoryStore`] is the default, which each process keeps to itself.
+/// Replicas behind a load balancer share a `RedisStore` (feature `redis`,
+/// `AppConfig::redis`) or another store passed to
+/// [`crate::AppState::with_store`], so that a nullifier spent or a
+/// challenge issued on one is seen by all.
+///
+/// Calls block until they return.  Request handlers make them through
+/// [`blocking`], which keeps a shared store's calls off the async workers.
+/// A store that cannot answer returns an error; the server then refuses the
+/// proof with `503` rather than risk accepting a replay.
+pub trait Store: Send + Sync {
+    /// Set `key`, expiring after `ttl` or never; `false` if it was already
+    /// set.  Must be atomic: of two concurrent calls, one returns `false`.
+    fn insert(&self, key: &str, ttl: Option<Duration>) -> io::Result<bool>;
+    /// Whether `key` is set and not expired.
+    fn contains(&self, key: &str) -> io::Result<bool>;
+    /// Unset `key`; `false` if it was not set.  Atomic like [`Store::insert`].
+    fn remove(&self, key: &str) -> io::Result<bool>;
+    /// Add one to the counter at `key` and return its new value.  A new
+    /// counter starts at zero and expires `ttl` later.
+    fn increment(&self, key: &str, ttl: Duration) -> io::Result<u64>;
+    /// Whether other processes see these keys.  Rate limits then count
+    /// fleet-wide; otherwise each process keeps its own.
+    fn is_shared(&self) -> bool;
+}
+
+/// Run `f`, which calls `state.store`, from a request handler.  A shared
+/// store's calls wait on the network, so they run on the blocking pool
+/// rather than hold up the async worker; a local store's run in place.
+pub(crate) async fn blocking<T, F>(state: &web::Data<AppState>, f: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce(&AppState) -> Result<T, ApiError> + Send + 'static,
+{
+    if !state.store.is_shared() {
+        return f(state);
+    }
+    let worker_state = state.clone();
+    web::block(move || f(&worker_state)).await.map_err(|_| ApiError::Internal)?
+}
+
+/// Entries in this process, kept until they expire or it exits.
+#[derive(Default)]
+pub struct MemoryStore(Mutex<Entries>);
+
+#[derive(Default)]
+struct Entries {
+    permanent: HashSet<String>,
+    /// Value and expiry; the value is `0` for keys set by [`Store::insert`].
+    expiring: HashMap<String, (u64, Instant)>,
+    /// `expiring`'s keys, soonest expiry first.  A key removed or set again
+    /// leaves its old entry behind; it is skipped when it comes up.
+    queue: BinaryHeap<Reverse<(Instant, String)>>,
+}
+
+impl Entries {
+    fn live(&self, key: &str) -> Option<u64> {
+        match self.expiring.get(key) {
+            Some((value, expires)) if *expires > Instant::now() => Some(*value),
+            Some(_) => None,
+            None => self.permanent.contains(key).then_some(0),
+        }
+    }
+
+    /// Drop expired entries and make room for a new one.
+    fn make_room(&mut self) -> io::Result<()> {
+        let now = Instant::now();
+        while let Some(Reverse((expires, _))) = self.queue.peek() {
+            if *expires > now {
+                break;
+            }
+            let Reverse((expires, key)) = self.queue.pop().expect("peeked");
+            if self.expiring.get(&key).is_some_and(|(_, e)| *e == expires) {
+                self.expiring.remove(&key);
+            }
+        }
+        if self.expiring.len() >= MAX_EXPIRING {
+            return Err(io::Error::other("memory store full"));
+        }
+        Ok(())
+    }
+
+    fn set_expiring(&mut self, key: &str, value: u64, expires: Instant) {
+        if self.expiring.insert(key.to_owned(), (value, expires)).map(|(_, e)| e) != Some(expires) {
+            self.queue.push(Reverse((expires, key.to_owned())));
+        }
+    }
+}
+
+impl Store for MemoryStore {
+    fn insert(&self, key: &str, ttl: Option<Duration>) -> io::Result<bool> {
+        let mut entries = self.0.lock().expect("store poisoned");
+        if entries.live(key).is_some() {
+            return Ok(false);
+        }
+        match ttl {
+            Some(ttl) => {
+                entries.make_room()?;
+                entries.set_expiring(key, 0, Instant::now() + ttl);
+            }
+            None => {
+                entries.expiring.remove(key);
+                entries.permanent.insert(key.to_owned());
+            }
+        }
+        Ok(true)
+    }
+
+    fn contains(&self, key: &str) -> io::Result<bool> {
+        Ok(self.0.lock().expect("store poisoned").live(key).is_some())
+    }
+
+    fn remove(&self, key: &str) -> io::Result<bool> {
+        let mut entries = self.0.lock().expect("store poisoned");
+        let live = entries.live(key).is_some();
+        entries.expiring.remove(key);
+        entries.permanent.remove(key);
+        Ok(live)
+    }
+
+    fn increment(&self, key: &str, ttl: Duration) -> io::Result<u64> {
+        let mut entries = self.0.lock().expect("store poisoned");
+        let value = match entries.live(key) {
+            Some(value) => value + 1,
+            None => {
+                entries.make_room()?;
+                1
+            }
+        };
+        let expires = entries.expiring.get(key).map(|(_, e)| *e).filter(|e| *e > Instant::now());
+        entries.set_expiring(key, value, expires.unwrap_or_else(|| Instant::now() + ttl));
+        Ok(value)
+    }
+
+    fn is_shared(&self) -> bool {
+        false
+    }
+}
+
+//--------------------------------------------------------------------
+// Redis
+//--------------------------------------------------------------------
+/// Where [`RedisStore`] keeps its keys.
+#[cfg(feature = "redis")]
+#[derive(Clone)]
+pub struct RedisConfig {
+    /// `redis://[:password@]host[:port][/db]`, or `rediss://` for TLS.
+    pub url: String,
+    /// Put before every key, so that several deployments can share a
+    /// database.
+    pub key_prefix: String,
+    /// Longest wait for a connection or a reply before the call fails.
+    pub timeout: Duration,
+}
+
+#[cfg(feature = "redis")]
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self { url: "redis://127.0.0.1/".into(), key_prefix: "zk-auth:".into(), timeout: Duration::from_secs(1) }
+    }
+}
+
+// The URL may carry a password.
+#[cfg(feature = "redis")]
+impl std::fmt::Debug for RedisConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisConfig")
+            .field("url", &Redacted(&self.url))
+            .field("key_prefix", &self.key_prefix)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// A [`Store`] in Redis, shared by every process that uses the same
+/// database and key prefix.
+///
+/// Connections are opened as needed and kept for reuse; one that fails a
+/// call is dropped.  Needs Redis 7 or later, for `PEXPIRE … NX`: a new
+/// connection to an older server fails, so every call does.
+#[cfg(feature = "redis")]
+pub struct RedisStore {
+    client: redis::Client,
+    config: RedisConfig,
+    idle: Mutex<Vec<redis::Connection>>,
+}
+
+#[cfg(feature = "redis")]
+impl RedisStore {
+    /// Checks the URL but does not connect; the first call does.
+    pub fn new(config: RedisConfig) -> io::Result<Self> {
+        let client = redis::Client::open(config.url.as_str()).map_err(io::Error::other)?;
+        Ok(Self { client, config, idle: Mutex::new(Vec::new()) })
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.config.key_prefix)
+    }
+
+    fn call<T>(&self, f: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>) -> io::Result<T> {
+        let idle = self.idle.lock().expect("redis connections poisoned").pop();
+        let mut conn = match idle {
+            Some(conn) => conn,
+            None => {
+                let mut conn = self.client.get_connection_with_timeout(self.config.timeout).map_err(io::Error::other)?;
+                conn.set_read_timeout(Some(self.config.timeout)).map_err(io::Error::other)?;
+                conn.set_write_timeout(Some(self.config.timeout)).map_err(io::Error::other)?;
+                check_version(&mut conn)?;
+                conn
+            }
+        };
+        let result = f(&mut conn).map_err(io::Error::other)?;
+        self.idle.lock().expect("redis connections poisoned").push(conn);
+        Ok(result)
+    }
+}
+
+/// Refuse a server older than Redis 7, which would reject `PEXPIRE … NX`
+/// only once a rate counter is first incremented.
+#[cfg(feature = "redis")]
+fn check_version(conn: &mut redis::Connection) -> io::Result<()> {
+    let info: String = redis::cmd("INFO").arg("server").query(conn).map_err(io::Error::other)?;
+    let version = info.lines().find_map(|l| l.strip_prefix("redis_version:")).unwrap_or("").trim();
+    match version.split('.').next().and_then(|major| major.parse::<u32>().ok()) {
+        Some(major) if major >= 7 => Ok(()),
+        _ => Err(io::Error::other(format!("Redis {version} is too old; 7 or later is needed"))),
+    }
+}
+
+#[cfg(feature = "redis")]
+impl Store for RedisStore {
+    fn insert(&self, key: &str, ttl: Option<Duration>) -> io::Result<bool> {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(key)).arg(1).arg("NX");
+        if let Some(ttl) = ttl {
+            cmd.arg("PX").arg(ttl.as_millis().max(1) as u64);
+        }
+        self.call(|conn| cmd.query::<Option<String>>(conn)).map(|set| set.is_some())
+    }
+
+    fn contains(&self, key: &str) -> io::Result<bool> {
+        self.call(|conn| redis::cmd("EXISTS").arg(self.key(key)).query::<u64>(conn)).map(|n| n > 0)
+    }
+
+    fn remove(&self, key: &str) -> io::Result<bool> {
+        self.call(|conn| redis::cmd("DEL").arg(self.key(key)).query::<u64>(conn)).map(|n| n > 0)
+    }
+
+    fn increment(&self, key: &str, ttl: Duration) -> io::Result<u64> {
+        let key = self.key(key);
+        let ttl = ttl.as_millis().max(1) as u64;
+        // `NX` so that later increments keep the first expiry.  Redis 7+.
+        let (value,): (u64,) = self.call(|conn| {
+            redis::pipe().atomic().cmd("INCR").arg(&key).cmd("PEXPIRE").arg(&key).arg(ttl).arg("NX").ignore().query(conn)
+        })?;
+        Ok(value)
+    }
+
+    fn is_shared(&self) -> bool {
+        true
+    }
+}
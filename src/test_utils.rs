@@ -0,0 +1,928 @@
+//! Deterministic fixtures for services that integrate with this crate.
+//!
+//! Everything here is a function of a `u64` seed, so a failing test
+//! reproduces from its seed alone.  Only [`valid_proof`] runs the prover, on
+//! the bundled circuit; the rest is plain data.  [`TestApp`] serves the
+//! endpoints in-process for handler-level tests, over a [`TestStore`] whose
+//! clock the test moves.
+
+use actix_web::dev::ServiceResponse;
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField, UniformRand};
+use futures_util::future::LocalBoxFuture;
+use num_bigint::BigUint;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::artifacts::CIRCUIT_PATH;
+use crate::config::AppConfig;
+use crate::derive::commitment_for;
+use crate::encoding::{derive_secret, derive_user_hash};
+use crate::handlers::{ProofJson, ProofRequest, RegisterRequest, VerifyRequest};
+use crate::mock::MockProver;
+use crate::proof_store::ProofStoreConfig;
+use crate::prover;
+use crate::state::AppState;
+use crate::store::{MemoryStore, Store};
+use crate::zk_auth_scope_with_state;
+
+//--------------------------------------------------------------------
+// Identities
+//--------------------------------------------------------------------
+/// A registration as `/register` would issue it, with the secret the
+/// client keeps.
+#[derive(Debug)]
+pub struct Identity {
+    pub attributes: RegisterRequest,
+    pub nonce: [u8; 16],
+    pub secret: Fr,
+    pub commitment: Fr,
+}
+
+impl Identity {
+    /// `secret_hex` as `/register` returns it.
+    pub fn secret_hex(&self) -> String {
+        format!("0x{}", hex::encode(fr_bytes(self.secret)))
+    }
+
+    /// `commitment` in the decimal form the API uses.
+    pub fn commitment_dec(&self) -> String {
+        fr_decimal(self.commitment)
+    }
+
+    /// A `/generate-proof` body for this identity.
+    pub fn proof_request(&self) -> ProofRequest {
+        ProofRequest {
+            secret_hex: self.secret_hex(),
+            commitment: self.commitment_dec(),
+            secrets_hex: Vec::new(),
+            #[cfg(feature = "deterministic")]
+            seed: None,
+            deadline_ms: None,
+            circuit_id: None,
+        }
+    }
+}
+
+/// The identity for `seed`: plausible attributes and a nonce, with the
+/// secret and commitment derived as `/register` derives them.
+pub fn identity(seed: u64) -> Identity {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let attributes = register_request(&mut rng);
+    let mut nonce = [0u8; 16];
+    rng.fill_bytes(&mut nonce);
+    let secret = derive_secret(&derive_user_hash(&attributes), &nonce);
+    Identity { attributes, nonce, secret, commitment: commitment_for(&secret) }
+}
+
+fn register_request(rng: &mut StdRng) -> RegisterRequest {
+    const COUNTRIES: [&str; 5] = ["US", "DE", "IN", "BR", "JP"];
+    let id: u32 = rng.gen();
+    RegisterRequest {
+        email: format!("user-{id:08x}@example.com"),
+        name: format!("Test User {id}"),
+        age: rng.gen_range(18..100),
+        country: COUNTRIES[rng.gen_range(0..COUNTRIES.len())].to_owned(),
+        dob: format!("{}{:02}{:02}", rng.gen_range(1925..2007), rng.gen_range(1..=12), rng.gen_range(1..=28)),
+        phone: None,
+        biometric_hash: None,
+    }
+}
+
+//--------------------------------------------------------------------
+// Proofs
+//--------------------------------------------------------------------
+/// A proof `/verify-proof` accepts for `identity`, made with proof
+/// randomness from `seed`.  Runs the prover on the bundled circuit (well
+/// under a second) and needs no server.
+pub fn valid_proof(identity: &Identity, seed: u64) -> ProofJson {
+    let (wasm, r1cs, zkey) = &*CIRCUIT_PATH;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let proof = prover::prove_with_files_rng(wasm, r1cs, zkey, identity.secret, identity.commitment, &mut rng)
+        .expect("bundled circuit proves its own identities");
+    ProofJson::from_proof(&proof)
+}
+
+/// `/verify-proof` bodies that must be rejected, each named for what is
+/// wrong with it, derived from a `valid` proof for `identity`.  Some are
+/// well-formed but fail the pairing (`valid: false`); others do not parse
+/// (`400`).
+pub fn invalid_proofs(identity: &Identity, valid: &ProofJson) -> Vec<(&'static str, VerifyRequest)> {
+    let request = |commitment: String, proof: ProofJson| VerifyRequest { commitment, proof, circuit_version: None, circuit_id: None, nullifier: None, application: None, challenge: None, claims: None, audience: None };
+    let copy = |p: &ProofJson| ProofJson { a: p.a.clone(), b: p.b.clone(), c: p.c.clone() };
+    let other = fr_decimal(identity.commitment + Fr::from(1u64));
+
+    let mut swapped = copy(valid);
+    std::mem::swap(&mut swapped.a, &mut swapped.c);
+    let mut off_curve = copy(valid);
+    off_curve.a[1] = format!("0x{}", hex::encode(fr_bytes(Fr::from(1u64))));
+    let mut bad_hex = copy(valid);
+    bad_hex.c[0] = "0xnot-hex".to_owned();
+    let mut b_limbs = copy(valid);
+    b_limbs.b[0].swap(0, 1);
+
+    vec![
+        ("wrong_commitment", request(other, copy(valid))),
+        ("a_and_c_swapped", request(identity.commitment_dec(), swapped)),
+        ("b_limbs_swapped", request(identity.commitment_dec(), b_limbs)),
+        ("a_off_curve", request(identity.commitment_dec(), off_curve)),
+        ("c_not_hex", request(identity.commitment_dec(), bad_hex)),
+    ]
+}
+
+//--------------------------------------------------------------------
+// In-process server
+//--------------------------------------------------------------------
+/// The endpoints served in-process, without a socket, over one shared
+/// [`AppState`].
+///
+/// [`TestApp::mock`] proves with [`MockProver`] and keeps proofs in a
+/// scratch directory removed on drop, so a test runs in milliseconds and
+/// leaves nothing behind.  The app is built on the first call and reused by
+/// the rest.  Call it from `#[actix_web::test]`.
+pub struct TestApp {
+    pub state: web::Data<AppState>,
+    app: OnceCell<AppService>,
+    scratch: Option<PathBuf>,
+}
+
+/// The initialised app, its concrete type erased.
+type AppService = Box<dyn Fn(test::TestRequest) -> LocalBoxFuture<'static, ServiceResponse>>;
+
+impl TestApp {
+    /// Serve `config` as it stands.  Panics if [`AppState::new`] refuses it.
+    pub fn new(config: AppConfig) -> Self {
+        Self { state: web::Data::new(AppState::new(config).expect("invalid test config")), app: OnceCell::new(), scratch: None }
+    }
+
+    /// Mock proofs and a scratch proof store.
+    pub fn mock() -> Self {
+        Self::mock_with(|_| {}, MemoryStore::default())
+    }
+
+    /// [`TestApp::mock`] with `configure` applied to the config and the
+    /// nullifiers, challenges and rate counters in `store`.
+    pub fn mock_with(configure: impl FnOnce(&mut AppConfig), store: impl Store + 'static) -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let scratch = std::env::temp_dir().join(format!("zk-auth-test-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+        let mut config = AppConfig {
+            mock_prover: Some(MockProver { allow_in_release: true }),
+            proof_store: Some(ProofStoreConfig { dir: scratch.clone() }),
+            ..AppConfig::default()
+        };
+        configure(&mut config);
+        Self { state: web::Data::new(AppState::new(config).expect("invalid test config").with_store(store)), app: OnceCell::new(), scratch: Some(scratch) }
+    }
+
+    /// Send `req` through the app.
+    pub async fn call(&self, req: test::TestRequest) -> ServiceResponse {
+        if self.app.get().is_none() {
+            let app = Rc::new(test::init_service(App::new().service(zk_auth_scope_with_state(self.state.clone()))).await);
+            let service: AppService = Box::new(move |req| {
+                let app = app.clone();
+                Box::pin(async move { test::call_service(&*app, req.to_request()).await })
+            });
+            let _ = self.app.set(service);
+        }
+        (self.app.get().expect("set above"))(req).await
+    }
+
+    /// POST `body` as JSON to `path`; the status and the JSON answer.
+    pub async fn post(&self, path: &str, body: &impl Serialize) -> (StatusCode, Value) {
+        let res = self.call(test::TestRequest::post().uri(path).set_json(body)).await;
+        (res.status(), test::read_body_json(res).await)
+    }
+
+    /// GET `path`; the status and the JSON answer.
+    pub async fn get(&self, path: &str) -> (StatusCode, Value) {
+        let res = self.call(test::TestRequest::get().uri(path)).await;
+        (res.status(), test::read_body_json(res).await)
+    }
+
+    /// Register `identity`'s attributes; the `/register` answer.
+    pub async fn register(&self, identity: &Identity) -> Value {
+        let a = &identity.attributes;
+        let body = json!({
+            "email": a.email, "name": a.name, "age": a.age, "country": a.country, "dob": a.dob,
+            "phone": a.phone, "biometric_hash": a.biometric_hash,
+        });
+        let (status, body) = self.post("/register", &body).await;
+        assert_eq!(status, StatusCode::OK, "/register refused {identity:?}: {body}");
+        body
+    }
+
+    /// `/generate-proof` for `identity`; the status and the answer.
+    pub async fn prove(&self, identity: &Identity) -> (StatusCode, Value) {
+        self.post("/generate-proof", &json!({"secret_hex": identity.secret_hex(), "commitment": identity.commitment_dec()})).await
+    }
+
+    /// `/verify-proof` with `req`; the status and the answer.
+    pub async fn verify(&self, req: &VerifyRequest) -> (StatusCode, Value) {
+        let body = json!({
+            "commitment": req.commitment, "proof": req.proof, "circuit_version": req.circuit_version, "circuit_id": req.circuit_id,
+            "nullifier": req.nullifier, "application": req.application, "challenge": req.challenge, "claims": req.claims,
+            "audience": req.audience,
+        });
+        self.post("/verify-proof", &body).await
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.scratch {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+//--------------------------------------------------------------------
+// Store
+//--------------------------------------------------------------------
+/// A [`Store`] on a clock the test moves by hand, which can also be made to
+/// fail like an unreachable Redis.  It reports itself shared, so requests
+/// take the path they take with Redis.  Clones share their entries: keep
+/// one to drive the store handed to [`TestApp::mock_with`].
+#[derive(Clone, Default)]
+pub struct TestStore(Arc<Mutex<TestEntries>>);
+
+#[derive(Default)]
+struct TestEntries {
+    now: Duration,
+    /// Value and expiry on the store's clock; the value is `0` for keys set
+    /// by [`Store::insert`].
+    entries: HashMap<String, (u64, Option<Duration>)>,
+    failing: bool,
+}
+
+impl TestEntries {
+    fn live(&self, key: &str) -> Option<u64> {
+        self.entries.get(key).filter(|(_, expires)| expires.is_none_or(|e| e > self.now)).map(|(value, _)| *value)
+    }
+}
+
+impl TestStore {
+    /// Move the clock on by `by`, expiring what is due.
+    pub fn advance(&self, by: Duration) {
+        self.0.lock().expect("test store poisoned").now += by;
+    }
+
+    /// Fail every call until set back to `false`.
+    pub fn set_failing(&self, failing: bool) {
+        self.0.lock().expect("test store poisoned").failing = failing;
+    }
+
+    fn entries(&self) -> io::Result<MutexGuard<'_, TestEntries>> {
+        let entries = self.0.lock().expect("test store poisoned");
+        if entries.failing {
+            return Err(io::Error::other("test store set to fail"));
+        }
+        Ok(entries)
+    }
+}
+
+impl Store for TestStore {
+    fn insert(&self, key: &str, ttl: Option<Duration>) -> io::Result<bool> {
+        let mut entries = self.entries()?;
+        if entries.live(key).is_some() {
+            return Ok(false);
+        }
+        let expires = ttl.map(|ttl| entries.now + ttl);
+        entries.entries.insert(key.to_owned(), (0, expires));
+        Ok(true)
+    }
+
+    fn contains(&self, key: &str) -> io::Result<bool> {
+        Ok(self.entries()?.live(key).is_some())
+    }
+
+    fn remove(&self, key: &str) -> io::Result<bool> {
+        let mut entries = self.entries()?;
+        let live = entries.live(key).is_some();
+        entries.entries.remove(key);
+        Ok(live)
+    }
+
+    fn increment(&self, key: &str, ttl: Duration) -> io::Result<u64> {
+        let mut entries = self.entries()?;
+        let now = entries.now;
+        let (value, expires) = match (entries.live(key), entries.entries.get(key)) {
+            (Some(value), Some((_, expires))) => (value + 1, *expires),
+            _ => (1, Some(now + ttl)),
+        };
+        entries.entries.insert(key.to_owned(), (value, expires));
+        Ok(value)
+    }
+
+    fn is_shared(&self) -> bool {
+        true
+    }
+}
+
+//--------------------------------------------------------------------
+// Malformed inputs
+//--------------------------------------------------------------------
+/// Strings every hex field (`secret_hex`, proof coordinates) must reject
+/// with `400` rather than panic on.
+pub const MALFORMED_HEX: &[&str] = &[
+    "0x0",
+    "0xzz",
+    "0x 12",
+    "0x12 ",
+    "0X1g",
+    "0x123",
+    "0x\u{0660}\u{0661}",
+    "0x0000000000000000000000000000000000000000000000000000000000000000ff",
+    "0x-1",
+    "-0x1",
+    "0x+1",
+    "0x1_0",
+];
+
+/// Strings every decimal field (`commitment`) must reject with `400`.
+pub const MALFORMED_DECIMAL: &[&str] = &["", "-1", "1e3", "0x10", "1.0", " 1", "1 ", "１２", "NaN"];
+
+/// A random field element for `seed`, in the decimal form the API uses.
+pub fn random_commitment(seed: u64) -> String {
+    fr_decimal(Fr::rand(&mut StdRng::seed_from_u64(seed)))
+}
+
+fn fr_bytes(f: Fr) -> [u8; 32] {
+    let b = f.into_bigint().to_bytes_be();
+    let mut out = [0u8; 32];
+    out[32 - b.len()..].copy_from_slice(&b);
+    out
+}
+
+fn fr_decimal(f: Fr) -> String {
+    BigUint::from_bytes_be(&f.into_bigint().to_bytes_be()).to_string()
+}
+
+//--------------------------------------------------------------------
+// proptest strategies
+//--------------------------------------------------------------------
+/// Strategies for the request DTOs.  Field values are arbitrary, not just
+/// valid ones: handlers must answer all of them without panicking.
+pub mod strategies {
+    use proptest::prelude::*;
+
+    use crate::handlers::{ProofJson, ProofRequest, RegisterRequest, VerifyRequest};
+
+    /// Mostly 0x-hex of up to 40 bytes, sometimes anything at all.
+    pub fn hex_string() -> impl Strategy<Value = String> {
+        prop_oneof![
+            4 => proptest::collection::vec(any::<u8>(), 0..40).prop_map(|b| format!("0x{}", hex::encode(b))),
+            1 => any::<String>(),
+        ]
+    }
+
+    /// Mostly decimal digits, sometimes anything at all.
+    pub fn decimal_string() -> impl Strategy<Value = String> {
+        prop_oneof![4 => "[0-9]{1,80}", 1 => any::<String>()]
+    }
+
+    pub fn register_request() -> impl Strategy<Value = RegisterRequest> {
+        (any::<String>(), any::<String>(), any::<u32>(), "[A-Z]{0,3}", "[0-9]{0,10}", proptest::option::of("\\+?[0-9 ()-]{0,20}"), proptest::option::of("(0x)?[0-9a-f]{64}"))
+            .prop_map(|(email, name, age, country, dob, phone, biometric_hash)| RegisterRequest { email, name, age, country, dob, phone, biometric_hash })
+    }
+
+    pub fn proof_request() -> impl Strategy<Value = ProofRequest> {
+        (hex_string(), decimal_string(), proptest::option::of(0u64..60_000)).prop_map(|(secret_hex, commitment, deadline_ms)| {
+            ProofRequest {
+                secret_hex,
+                commitment,
+                secrets_hex: Vec::new(),
+                #[cfg(feature = "deterministic")]
+                seed: None,
+                deadline_ms,
+                circuit_id: None,
+            }
+        })
+    }
+
+    pub fn proof_json() -> impl Strategy<Value = ProofJson> {
+        let pair = || proptest::array::uniform2(hex_string());
+        (pair(), proptest::array::uniform2(pair()), pair()).prop_map(|(a, b, c)| ProofJson { a, b, c })
+    }
+
+    pub fn verify_request() -> impl Strategy<Value = VerifyRequest> {
+        (decimal_string(), proof_json(), proptest::option::of("[0-9a-f]{64}"))
+            .prop_map(|(commitment, proof, circuit_version)| VerifyRequest { commitment, proof, circuit_version, circuit_id: None, nullifier: None, application: None, challenge: None, claims: None, audience: None })
+    }
+}
+
+//--------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::challenge::CHALLENGE_TTL;
+    use crate::config::{CircuitKind, CircuitPaths};
+    use crate::convert::normalize_public_input;
+    use crate::crypto::fq_to_hex;
+    use crate::proof_jobs::{resume_proof_jobs, FileJobStore, JobRetention, JobStore, JobStoreConfig};
+
+    /// A mock app that also serves a `kind` circuit as `id`, from the
+    /// bundled files: mock proofs do not look at the key.
+    fn app_with(id: &str, kind: CircuitKind, store: &TestStore) -> TestApp {
+        let configure = |c: &mut AppConfig| {
+            c.circuits.insert(id.to_owned(), CircuitPaths { kind, ..CircuitPaths::default() });
+        };
+        TestApp::mock_with(configure, store.clone())
+    }
+
+    /// Register the identity for `seed` and prove it; the `/verify-proof`
+    /// request for that proof.
+    async fn registered_proof(app: &TestApp, seed: u64) -> VerifyRequest {
+        let registered = app.register(&identity(seed)).await;
+        let body = json!({"secret_hex": registered["secret"], "commitment": registered["commitment"]});
+        let (status, proved) = app.post("/generate-proof", &body).await;
+        assert_eq!(status, StatusCode::OK, "/generate-proof refused: {proved}");
+        VerifyRequest {
+            commitment: registered["commitment"].as_str().expect("decimal commitment").to_owned(),
+            proof: serde_json::from_value(proved["proof"].clone()).expect("hex proof"),
+            circuit_version: None,
+            circuit_id: None,
+            nullifier: None,
+            application: None,
+            challenge: None,
+            claims: None,
+            audience: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn registered_identity_proves_and_verifies() {
+        let app = TestApp::mock();
+        let request = registered_proof(&app, 1).await;
+        let (status, verdict) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::OK, "{verdict}");
+        assert_eq!(verdict["valid"], true);
+
+        let other = VerifyRequest { commitment: random_commitment(2), ..request };
+        let (status, verdict) = app.verify(&other).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{verdict}");
+        assert_eq!(verdict["valid"], false);
+    }
+
+    #[actix_web::test]
+    async fn stored_proof_verifies_on_lookup() {
+        let app = TestApp::mock();
+        let (status, proved) = app.prove(&identity(3)).await;
+        assert_eq!(status, StatusCode::OK, "{proved}");
+        let hash = proved["proof_hash"].as_str().expect("proof store is enabled");
+        let (status, stored) = app.get(&format!("/proofs/{hash}")).await;
+        assert_eq!(status, StatusCode::OK, "{stored}");
+        assert_eq!(stored["kind"], "secret");
+        assert_eq!(stored["public_inputs"][0], identity(3).commitment_dec());
+        assert_eq!(stored["verification"]["valid"], true);
+    }
+
+    #[actix_web::test]
+    async fn nullifier_is_spent_once_per_application() {
+        let app = app_with("nullifier", CircuitKind::Nullifier, &TestStore::default());
+        let mut request = registered_proof(&app, 4).await;
+        request.circuit_id = Some("nullifier".into());
+        request.nullifier = Some(random_commitment(5));
+        request.application = Some("mail".into());
+
+        let (status, verdict) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::OK, "{verdict}");
+        let (status, replay) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{replay}");
+        assert_eq!(replay["error"], "already_used");
+
+        request.application = Some("wiki".into());
+        let (status, verdict) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::OK, "{verdict}");
+    }
+
+    #[actix_web::test]
+    async fn challenge_expires_and_is_used_once() {
+        let store = TestStore::default();
+        let app = app_with("challenge", CircuitKind::Challenge, &store);
+        let mut request = registered_proof(&app, 6).await;
+        request.circuit_id = Some("challenge".into());
+
+        let (status, issued) = app.get("/challenge").await;
+        assert_eq!(status, StatusCode::OK, "{issued}");
+        request.challenge = issued["challenge"].as_str().map(str::to_owned);
+        store.advance(CHALLENGE_TTL + Duration::from_secs(1));
+        let (status, expired) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{expired}");
+        assert_eq!(expired["error"], "invalid_challenge");
+
+        let (_, issued) = app.get("/challenge").await;
+        request.challenge = issued["challenge"].as_str().map(str::to_owned);
+        let (status, verdict) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::OK, "{verdict}");
+        let (status, replay) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{replay}");
+        assert_eq!(replay["error"], "invalid_challenge");
+    }
+
+    #[actix_web::test]
+    async fn unreachable_store_refuses_single_use_proofs() {
+        let store = TestStore::default();
+        let app = app_with("nullifier", CircuitKind::Nullifier, &store);
+        let mut request = registered_proof(&app, 7).await;
+        request.circuit_id = Some("nullifier".into());
+        request.nullifier = Some(random_commitment(8));
+        request.application = Some("mail".into());
+
+        store.set_failing(true);
+        let (status, refused) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "{refused}");
+        store.set_failing(false);
+        let (status, verdict) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::OK, "{verdict}");
+    }
+
+    #[actix_web::test]
+    async fn real_proof_verifies_under_the_bundled_key() {
+        let app = TestApp::new(AppConfig::default());
+        let identity = identity(10);
+        let proof = valid_proof(&identity, 10);
+        // Every coordinate is 32 bytes of hex, leading zeros kept.
+        for limb in proof.a.iter().chain(proof.b.iter().flatten()).chain(&proof.c) {
+            assert!(limb.len() == 66 && limb.starts_with("0x"), "{limb}");
+        }
+        // G2 coordinates in EVM order, `c1` first.
+        let parsed = proof.try_to_proof().expect("valid proof parses");
+        assert_eq!(proof.b[0], [fq_to_hex(&parsed.b.x.c1), fq_to_hex(&parsed.b.x.c0)]);
+        assert_eq!(proof.b[1], [fq_to_hex(&parsed.b.y.c1), fq_to_hex(&parsed.b.y.c0)]);
+
+        let request = VerifyRequest {
+            commitment: identity.commitment_dec(),
+            proof: proof.clone(),
+            circuit_version: None,
+            circuit_id: None,
+            nullifier: None,
+            application: None,
+            challenge: None,
+            claims: None,
+            audience: None,
+        };
+        let (status, verdict) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::OK, "{verdict}");
+        assert_eq!(verdict["valid"], true);
+        // `/calldata` turns the decimal commitment into the padded hex word.
+        let (status, calldata) = app.post("/calldata", &json!({"commitment": identity.commitment_dec(), "proof": proof})).await;
+        assert_eq!(status, StatusCode::OK, "{calldata}");
+        assert_eq!(calldata["input"][0], format!("0x{}", hex::encode(fr_bytes(identity.commitment))));
+        assert_eq!(calldata["b"], json!(proof.b));
+        for (name, invalid) in invalid_proofs(&identity, &proof) {
+            let (status, verdict) = app.verify(&invalid).await;
+            assert!(status == StatusCode::UNAUTHORIZED || status == StatusCode::BAD_REQUEST, "{name}: {status} {verdict}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn revoked_commitment_is_refused() {
+        let app = TestApp::mock_with(|c| c.admin_token = Some("test-token".into()), MemoryStore::default());
+        let request = registered_proof(&app, 9).await;
+        let (status, verdict) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::OK, "{verdict}");
+
+        let revoke = test::TestRequest::post()
+            .uri("/admin/revoke")
+            .insert_header(("Authorization", "Bearer test-token"))
+            .set_json(json!({"commitment": request.commitment, "reason": "device lost"}));
+        assert_eq!(app.call(revoke).await.status(), StatusCode::OK);
+        let (status, refused) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{refused}");
+        assert_eq!(refused["error"], "revoked");
+
+        let (status, feed) = app.get("/revocations").await;
+        assert_eq!(status, StatusCode::OK, "{feed}");
+        assert_eq!(feed["revocations"][0]["commitment"], request.commitment.as_str());
+    }
+
+    #[actix_web::test]
+    async fn registration_proof_comes_from_the_circuit_for_the_attributes() {
+        let configure = |c: &mut AppConfig| {
+            for (id, kind) in [("registration", CircuitKind::Registration), ("registration-phone", CircuitKind::RegistrationPhone)] {
+                c.circuits.insert(id.to_owned(), CircuitPaths { kind, ..CircuitPaths::default() });
+            }
+        };
+        let app = TestApp::mock_with(configure, MemoryStore::default());
+        let mut identity = identity(20);
+        let body = |a: &RegisterRequest| {
+            json!({"email": a.email, "name": a.name, "age": a.age, "country": a.country, "dob": a.dob, "phone": a.phone, "biometric_hash": a.biometric_hash})
+        };
+        assert!(app.register(&identity).await.get("registration_proof").is_none());
+
+        let (status, registered) = app.post("/register?proof=true", &body(&identity.attributes)).await;
+        assert_eq!(status, StatusCode::OK, "{registered}");
+        let user_hash = derive_user_hash(&identity.attributes);
+        let commitment = normalize_public_input(registered["commitment"].as_str().expect("decimal commitment")).expect("commitment");
+        let proof = &registered["registration_proof"];
+        assert_eq!(proof["circuit_id"], "registration");
+        assert_eq!(proof["user_hash"], fr_decimal(user_hash));
+        assert_eq!(proof["proof"], json!(ProofJson::from_proof(&crate::mock::prove_public(&[user_hash, commitment]))));
+
+        identity.attributes.phone = Some("+14155552671".into());
+        let (status, registered) = app.post("/register?proof=true", &body(&identity.attributes)).await;
+        assert_eq!(status, StatusCode::OK, "{registered}");
+        assert_eq!(registered["registration_proof"]["circuit_id"], "registration-phone");
+        assert_eq!(registered["registration_proof"]["user_hash"], fr_decimal(derive_user_hash(&identity.attributes)));
+
+        // No biometric circuit: refused, and nothing registered.
+        identity.attributes.biometric_hash = Some(hex::encode([7u8; 32]));
+        let leaves = app.state.tree.read().expect("tree").len();
+        let (status, refused) = app.post("/register?proof=true", &body(&identity.attributes)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{refused}");
+        assert_eq!(app.state.tree.read().expect("tree").len(), leaves);
+    }
+
+    #[actix_web::test]
+    async fn multi_secret_commitment_registers_proves_and_verifies() {
+        let app = app_with("multi", CircuitKind::Multi, &TestStore::default());
+        let (user, device) = (identity(40), identity(41));
+        let secrets_hex = [user.secret_hex(), device.secret_hex()];
+        let (status, registered) = app.post("/register/multi", &json!({"secrets_hex": secrets_hex})).await;
+        assert_eq!(status, StatusCode::OK, "{registered}");
+        let commitment = fr_decimal(crate::derive::commitment_for_secrets(&[user.secret, device.secret]).expect("two secrets"));
+        assert_eq!(registered["commitment"], commitment);
+
+        let body = json!({"circuit_id": "multi", "secrets_hex": secrets_hex, "commitment": commitment});
+        let (status, proved) = app.post("/generate-proof", &body).await;
+        assert_eq!(status, StatusCode::OK, "{proved}");
+        let request = VerifyRequest {
+            commitment: commitment.clone(),
+            proof: serde_json::from_value(proved["proof"].clone()).expect("hex proof"),
+            circuit_version: None,
+            circuit_id: Some("multi".into()),
+            nullifier: None,
+            application: None,
+            challenge: None,
+            claims: None,
+            audience: None,
+        };
+        let (status, verdict) = app.verify(&request).await;
+        assert_eq!(status, StatusCode::OK, "{verdict}");
+
+        // Every secret is needed, and only multi circuits take several.
+        let body = json!({"circuit_id": "multi", "secrets_hex": &secrets_hex[..1], "commitment": commitment});
+        let (status, refused) = app.post("/generate-proof", &body).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{refused}");
+        let (status, refused) = app.post("/generate-proof", &json!({"secrets_hex": secrets_hex, "commitment": commitment})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{refused}");
+        let (status, refused) = app.post("/register/multi", &json!({"secrets_hex": []})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{refused}");
+    }
+
+    #[actix_web::test]
+    async fn descent_proof_links_a_child_to_a_registered_master() {
+        let app = app_with("descent", CircuitKind::Descent, &TestStore::default());
+        let derive = |secret_hex: &str, path: &[&str]| json!({"secret_hex": secret_hex, "path": path});
+        let verify_body = |descent: &Value, application: &str| {
+            json!({
+                "circuit_id": "descent", "master_commitment": descent["master_commitment"], "application": application,
+                "child_commitment": descent["child_commitment"], "proof": descent["proof"],
+            })
+        };
+
+        let registered = app.register(&identity(50)).await;
+        let master = registered["secret"].as_str().expect("hex secret");
+        let (status, derived) = app.post("/derive?proof=true", &derive(master, &["mail"])).await;
+        assert_eq!(status, StatusCode::OK, "{derived}");
+        let descent = &derived["descent"];
+        assert_eq!(descent["master_commitment"], registered["commitment"]);
+        assert_eq!(descent["child_commitment"], derived["commitment"]);
+        let (status, verdict) = app.post("/verify-descent", &verify_body(descent, "mail")).await;
+        assert_eq!(status, StatusCode::OK, "{verdict}");
+        assert_eq!(verdict["valid"], true);
+        let (status, verdict) = app.post("/verify-descent", &verify_body(descent, "wiki")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{verdict}");
+
+        // The master must be registered, and one proof covers one level.
+        let (status, derived) = app.post("/derive?proof=true", &derive(&identity(51).secret_hex(), &["mail"])).await;
+        assert_eq!(status, StatusCode::OK, "{derived}");
+        let (status, refused) = app.post("/verify-descent", &verify_body(&derived["descent"], "mail")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{refused}");
+        assert_eq!(refused["error"], "unknown_commitment");
+        let (status, refused) = app.post("/derive?proof=true", &derive(master, &["mail", "work"])).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{refused}");
+    }
+
+    #[actix_web::test]
+    async fn cancelled_proof_job_stays_cancelled() {
+        let app = TestApp::mock();
+        let path = format!("/proof-jobs/{}", submit_job(&app, &identity(12)).await);
+
+        let res = app.call(test::TestRequest::delete().uri(&path)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let cancelled: Value = test::read_body_json(res).await;
+        assert_eq!(cancelled["status"], "cancelled");
+        // The aborted task must not overwrite the cancellation.
+        actix_web::rt::time::sleep(Duration::from_millis(200)).await;
+        let (status, job) = app.get(&path).await;
+        assert_eq!(status, StatusCode::OK, "{job}");
+        assert_eq!(job["status"], "cancelled");
+
+        let res = app.call(test::TestRequest::delete().uri("/proof-jobs/00")).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Submit a proof job for `identity`; its id.
+    async fn submit_job(app: &TestApp, identity: &Identity) -> String {
+        let (status, accepted) =
+            app.post("/proof-jobs", &json!({"secret_hex": identity.secret_hex(), "commitment": identity.commitment_dec()})).await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{accepted}");
+        accepted["job_id"].as_str().expect("job id").to_owned()
+    }
+
+    /// Poll job `id` until it is no longer pending; the last answer.
+    async fn settled_job(app: &TestApp, id: &str) -> (StatusCode, Value) {
+        for _ in 0..100 {
+            let (status, job) = app.get(&format!("/proof-jobs/{id}")).await;
+            if job["status"] != "pending" {
+                return (status, job);
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("proof job {id} still pending");
+    }
+
+    #[actix_web::test]
+    async fn finished_proof_jobs_are_listed_until_they_expire() {
+        let app = TestApp::mock_with(|c| c.admin_token = Some("admin".into()), MemoryStore::default());
+        let id = submit_job(&app, &identity(13)).await;
+        let (status, job) = settled_job(&app, &id).await;
+        assert_eq!(status, StatusCode::OK, "{job}");
+        assert_eq!(job["status"], "done");
+        let list = |filter: &'static str| test::TestRequest::get().uri(filter).insert_header(("authorization", "Bearer admin"));
+        let listed: Value = test::read_body_json(app.call(list("/admin/proof-jobs?status=done")).await).await;
+        assert_eq!(listed["jobs"][0]["job_id"], id.as_str());
+        let listed: Value = test::read_body_json(app.call(list("/admin/proof-jobs?status=failed")).await).await;
+        assert_eq!(listed["jobs"], json!([]));
+
+        let app = TestApp::mock_with(|c| c.job_retention.done = Duration::ZERO, MemoryStore::default());
+        let id = submit_job(&app, &identity(14)).await;
+        let (status, expired) = settled_job(&app, &id).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{expired}");
+        assert_eq!(app.state.jobs.remove_expired(), 1);
+    }
+
+    #[actix_web::test]
+    async fn interrupted_proof_jobs_resume_after_a_restart() {
+        let dir = std::env::temp_dir().join(format!("zk-auth-jobs-{}", std::process::id()));
+        let config = JobStoreConfig { dir: dir.clone() };
+        let store = FileJobStore::new(config.clone(), JobRetention::default()).expect("job store");
+        let identity = identity(15);
+        let request: ProofRequest =
+            serde_json::from_value(json!({"secret_hex": identity.secret_hex(), "commitment": identity.commitment_dec()})).expect("request");
+        let (resumed, stuck) = ("0".repeat(32), "1".repeat(32));
+        // Started, then picked up by two restarts that did not finish it either.
+        assert!(store.enqueue(&stuck, &request));
+        store.interrupted();
+        store.interrupted();
+        assert!(store.enqueue(&resumed, &request));
+
+        let app = TestApp::mock_with(|c| c.job_store = Some(config), MemoryStore::default());
+        resume_proof_jobs(&app.state);
+        let (status, job) = settled_job(&app, &resumed).await;
+        assert_eq!(status, StatusCode::OK, "{job}");
+        assert_eq!(job["status"], "done");
+        let (_, job) = app.get(&format!("/proof-jobs/{stuck}")).await;
+        assert_eq!(job["status"], "failed");
+        assert_eq!(job["error"], "interrupted");
+        // The secret goes with the job's end.
+        let saved = std::fs::read_to_string(dir.join(format!("{resumed}.json"))).expect("job file");
+        assert!(!saved.contains(&identity.secret_hex()), "{saved}");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.join(format!("{resumed}.json"))).expect("job file").permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(dir).expect("remove job store");
+    }
+
+    #[cfg(feature = "grpc")]
+    #[actix_web::test]
+    async fn grpc_stream_answers_every_request_in_order() {
+        use crate::grpc::proto::{verify_stream_response::Result as Answer, Proof, VerifyStreamRequest, VerifyStreamResponse};
+        use crate::grpc::VerifierService;
+        use tonic::codec::ProstCodec;
+        use tonic::transport::server::TcpIncoming;
+        use tonic::transport::{Endpoint, Server};
+
+        let app = TestApp::mock();
+        let request = registered_proof(&app, 16).await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("local address");
+        let incoming = TcpIncoming::from_listener(listener, true, None).expect("incoming");
+        actix_web::rt::spawn(Server::builder().add_service(VerifierService::new(app.state.clone())).serve_with_incoming(incoming));
+
+        let message = |id, r: &VerifyRequest| VerifyStreamRequest {
+            id,
+            commitment: r.commitment.clone(),
+            proof: Some(Proof { a: r.proof.a.to_vec(), b: r.proof.b.concat(), c: r.proof.c.to_vec() }),
+            ..Default::default()
+        };
+        let messages = vec![
+            message(7, &request),
+            VerifyStreamRequest { proof: None, ..message(8, &request) },
+            message(9, &VerifyRequest { commitment: random_commitment(17), ..request.clone() }),
+        ];
+        let channel = Endpoint::from_shared(format!("http://{addr}")).expect("endpoint").connect().await.expect("connect");
+        let mut client = tonic::client::Grpc::new(channel);
+        client.ready().await.expect("ready");
+        let path = tonic::codegen::http::uri::PathAndQuery::from_static("/zkauth.v1.Verifier/VerifyStream");
+        let codec = ProstCodec::<VerifyStreamRequest, VerifyStreamResponse>::default();
+        let mut answers = client.streaming(tonic::Request::new(futures_util::stream::iter(messages)), path, codec).await.expect("stream").into_inner();
+
+        let mut got = Vec::new();
+        while let Some(answer) = answers.message().await.expect("answer") {
+            got.push(answer);
+        }
+        assert_eq!(got.iter().map(|a| a.id).collect::<Vec<_>>(), [7, 8, 9]);
+        assert!(matches!(&got[0].result, Some(Answer::Verdict(v)) if v.valid), "{:?}", got[0]);
+        assert!(matches!(&got[1].result, Some(Answer::Error(e)) if e.error == "invalid_input"), "{:?}", got[1]);
+        assert!(matches!(&got[2].result, Some(Answer::Verdict(v)) if !v.valid), "{:?}", got[2]);
+    }
+}
+
+/// The [`strategies`] as properties: what holds for every value, and that
+/// handlers answer all of them.
+#[cfg(test)]
+mod properties {
+    use actix_web::test::TestRequest;
+    use proptest::prelude::*;
+    use proptest::test_runner::{Config, TestRunner};
+    use serde_json::{json, Value};
+
+    use super::{fr_bytes, strategies, TestApp};
+    use crate::convert::normalize_public_input;
+    use crate::encoding::derive_user_hash;
+    use crate::handlers::ProofJson;
+    use crate::snarkjs::public_signals;
+
+    proptest! {
+        #[test]
+        fn parsed_proofs_reencode_to_themselves(json in strategies::proof_json()) {
+            if let Some(proof) = json.try_to_proof() {
+                prop_assert_eq!(ProofJson::from_proof(&proof).try_to_proof(), Some(proof));
+            }
+        }
+
+        #[test]
+        fn public_inputs_round_trip_through_decimal_and_hex(input in strategies::decimal_string()) {
+            if let Ok(f) = normalize_public_input(&input) {
+                let decimal = public_signals(&[f]).remove(0);
+                prop_assert_eq!(normalize_public_input(&decimal).unwrap(), f);
+                prop_assert_eq!(normalize_public_input(&format!("0x{}", hex::encode(fr_bytes(f)))).unwrap(), f);
+            }
+        }
+
+        #[test]
+        fn user_hash_is_a_function_of_the_attributes(attributes in strategies::register_request()) {
+            prop_assert_eq!(derive_user_hash(&attributes), derive_user_hash(&attributes));
+        }
+    }
+
+    /// Arbitrary DTOs get an answer, never a 5xx.
+    #[test]
+    fn handlers_answer_arbitrary_requests() {
+        let system = actix_web::rt::System::new();
+        let app = TestApp::mock();
+        let mut runner = TestRunner::new(Config::with_cases(64));
+        let answer = |path: &str, body: Value| {
+            let res = system.block_on(app.call(TestRequest::post().uri(path).set_json(body)));
+            prop_assert!(!res.status().is_server_error(), "{path}: {}", res.status());
+            Ok(())
+        };
+        runner
+            .run(&strategies::verify_request(), |r| {
+                answer("/verify-proof", json!({"commitment": r.commitment, "proof": r.proof, "circuit_version": r.circuit_version}))
+            })
+            .unwrap();
+        runner
+            .run(&strategies::register_request(), |a| {
+                answer("/register", json!({
+                    "email": a.email, "name": a.name, "age": a.age, "country": a.country, "dob": a.dob,
+                    "phone": a.phone, "biometric_hash": a.biometric_hash,
+                }))
+            })
+            .unwrap();
+        runner
+            .run(&strategies::proof_request(), |p| {
+                answer("/generate-proof", json!({"secret_hex": p.secret_hex, "commitment": p.commitment, "deadline_ms": p.deadline_ms}))
+            })
+            .unwrap();
+    }
+}
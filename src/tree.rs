@@ -0,0 +1,343 @@
+use actix_web::{get, web, HttpRequest, HttpResponse, ResponseError};
+use ark_bn254::Fr;
+use ark_ff::Zero;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::crypto::poseidon_hash;
+use crate::error::ApiError;
+use crate::etag::json_with_etag;
+use crate::snarkjs::public_signals;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Commitment tree
+//--------------------------------------------------------------------
+/// Levels above the leaves; room for about a million registrations.
+pub const TREE_DEPTH: usize = 20;
+
+/// Binary Merkle tree of registered commitments, hashed with
+/// `Poseidon(left, right)` and padded with all-zero subtrees, as in
+/// circomlib's incremental trees.  Leaves are only appended, except that a
+/// revoked commitment's leaf is set to zero so it can no longer prove
+/// group membership.
+///
+/// Held in memory only: it is rebuilt empty on restart.
+pub(crate) struct CommitmentTree {
+    /// `levels[0]` are the leaves; `levels[TREE_DEPTH]` holds the root once
+    /// anything is appended.
+    levels: Vec<Vec<Fr>>,
+    /// Root of an all-zero subtree of each height.
+    zeros: Vec<Fr>,
+    /// The leaves as appended, before any was zeroed.
+    appended: Vec<Fr>,
+    /// `roots[i]` is the root right after leaf `i` was appended.
+    roots: Vec<Fr>,
+    /// Leaves zeroed so far, in order.
+    zeroed: Vec<ZeroedLeaf>,
+    /// The leaves again, for membership checks.
+    registered: HashSet<Fr>,
+    /// Roots since the last leaf was zeroed, for group membership proofs.
+    /// Older ones may still hold a revoked commitment.
+    live_roots: HashSet<Fr>,
+}
+
+/// A leaf set to zero, `at` the number of leaves the tree had then, and
+/// the root after.
+#[derive(Clone, Copy)]
+pub(crate) struct ZeroedLeaf {
+    pub(crate) at: u64,
+    pub(crate) index: u64,
+    pub(crate) root: Fr,
+}
+
+impl CommitmentTree {
+    pub(crate) fn new() -> Self {
+        let mut zeros = vec![Fr::zero()];
+        for level in 0..TREE_DEPTH {
+            zeros.push(poseidon_hash(&[zeros[level], zeros[level]]));
+        }
+        Self {
+            levels: vec![Vec::new(); TREE_DEPTH + 1],
+            zeros,
+            appended: Vec::new(),
+            roots: Vec::new(),
+            zeroed: Vec::new(),
+            registered: HashSet::new(),
+            live_roots: HashSet::new(),
+        }
+    }
+
+    /// Number of leaves appended so far.
+    pub(crate) fn len(&self) -> u64 {
+        self.levels[0].len() as u64
+    }
+
+    pub(crate) fn contains(&self, leaf: Fr) -> bool {
+        self.registered.contains(&leaf)
+    }
+
+    pub(crate) fn root(&self) -> Fr {
+        self.levels[TREE_DEPTH].first().copied().unwrap_or(self.zeros[TREE_DEPTH])
+    }
+
+    /// Whether a group membership proof may be made against `root`: the
+    /// tree has had it since its last zeroed leaf.
+    pub(crate) fn accepts_root(&self, root: Fr) -> bool {
+        self.live_roots.contains(&root)
+    }
+
+    /// Leaves from index `since` (at most `limit`) with the root after each,
+    /// and the leaves zeroed while the tree had from `since` up to that many
+    /// leaves.
+    pub(crate) fn delta(&self, since: u64, limit: usize) -> (&[Fr], &[Fr], &[ZeroedLeaf]) {
+        let start = (since as usize).min(self.appended.len());
+        let end = start.saturating_add(limit).min(self.appended.len());
+        let first = self.zeroed.partition_point(|z| z.at < start as u64);
+        let last = self.zeroed.partition_point(|z| z.at <= end as u64);
+        (&self.appended[start..end], &self.roots[start..end], &self.zeroed[first..last])
+    }
+
+    /// Append `leaf`, returning its index.  A full tree is left as it was:
+    /// no registration can be added until the server moves to a deeper one.
+    pub(crate) fn append(&mut self, leaf: Fr) -> Result<u64, ApiError> {
+        let index = self.levels[0].len();
+        if index >= 1 << TREE_DEPTH {
+            return Err(ApiError::Unavailable("commitment tree is full"));
+        }
+        let root = self.set_leaf(index, leaf);
+        self.appended.push(leaf);
+        self.roots.push(root);
+        self.live_roots.insert(root);
+        self.registered.insert(leaf);
+        Ok(index as u64)
+    }
+
+    /// Zero every leaf holding `leaf`, returning how many there were.  Only
+    /// roots from then on are accepted for membership proofs.
+    pub(crate) fn revoke(&mut self, leaf: Fr) -> usize {
+        let indices: Vec<_> = (0..self.levels[0].len()).filter(|&i| self.levels[0][i] == leaf).collect();
+        for &index in &indices {
+            self.zero(index);
+        }
+        indices.len()
+    }
+
+    /// Zero leaf `index` as the tree this one mirrors did; `false` if it
+    /// already was.
+    pub(crate) fn zero_at(&mut self, index: u64) -> bool {
+        match self.levels[0].get(index as usize) {
+            Some(leaf) if !leaf.is_zero() => {
+                self.zero(index as usize);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn zero(&mut self, index: usize) {
+        let root = self.set_leaf(index, Fr::zero());
+        self.zeroed.push(ZeroedLeaf { at: self.len(), index: index as u64, root });
+        self.live_roots.clear();
+        self.live_roots.insert(root);
+    }
+
+    /// Set leaf `index`, which is at most one past the last, and rehash its
+    /// path; returns the new root.
+    fn set_leaf(&mut self, index: usize, leaf: Fr) -> Fr {
+        let mut node = leaf;
+        let mut at = index;
+        for level in 0..TREE_DEPTH {
+            set(&mut self.levels[level], at, node);
+            let sibling = self.levels[level].get(at ^ 1).copied().unwrap_or(self.zeros[level]);
+            node = if at & 1 == 0 { poseidon_hash(&[node, sibling]) } else { poseidon_hash(&[sibling, node]) };
+            at >>= 1;
+        }
+        set(&mut self.levels[TREE_DEPTH], 0, node);
+        node
+    }
+}
+
+fn set(level: &mut Vec<Fr>, at: usize, node: Fr) {
+    if at == level.len() {
+        level.push(node);
+    } else {
+        level[at] = node;
+    }
+}
+
+//--------------------------------------------------------------------
+// /roots/delta
+//--------------------------------------------------------------------
+/// Most leaves returned by one delta request.
+const MAX_DELTA: usize = 10_000;
+
+#[derive(Debug, Deserialize)]
+pub struct DeltaQuery {
+    /// Number of leaves the client already holds.
+    #[serde(default)]
+    pub since_seq: u64,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Leaves appended after `since_seq`, in order, and `roots[i]`, the root
+/// right after `leaves[i]` was appended.  Values are decimal like
+/// commitments.  `seq` is the count of leaves the client holds once it has
+/// applied the delta; ask again from there while `more` is set.
+///
+/// `zeroed` lists the leaves of revoked commitments set to zero while the
+/// tree had from `since_seq` to `seq` leaves.  Zero each once the client
+/// holds `at` leaves, before appending the next.  One may come again in the
+/// next delta; zeroing a zero leaf changes nothing.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RootsDelta {
+    pub depth: usize,
+    pub since_seq: u64,
+    pub seq: u64,
+    pub leaves: Vec<String>,
+    pub roots: Vec<String>,
+    #[serde(default)]
+    pub zeroed: Vec<ZeroedEntry>,
+    pub more: bool,
+}
+
+/// A [`RootsDelta`] leaf set to zero on revocation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZeroedEntry {
+    /// Leaves the tree had when it was zeroed.
+    pub at: u64,
+    pub index: u64,
+    /// The root right after, decimal.
+    pub root: String,
+}
+
+/// Lets light clients mirror the tree and build Merkle witnesses offline.
+/// A client polling from its head gets `304` until a leaf is added.
+#[get("/roots/delta")]
+async fn roots_delta(state: web::Data<AppState>, query: web::Query<DeltaQuery>, req: HttpRequest) -> HttpResponse {
+    let tree = state.tree.read().expect("commitment tree poisoned");
+    if query.since_seq > tree.len() {
+        return ApiError::InvalidInput { field: "since_seq", reason: "ahead of the tree" }.error_response();
+    }
+    let (leaves, roots, zeroed) = tree.delta(query.since_seq, query.limit.unwrap_or(MAX_DELTA).min(MAX_DELTA));
+    let seq = query.since_seq + leaves.len() as u64;
+    let zeroed = zeroed.iter().map(|z| ZeroedEntry { at: z.at, index: z.index, root: public_signals(&[z.root]).remove(0) }).collect();
+    json_with_etag(&req, &RootsDelta {
+        depth: TREE_DEPTH,
+        since_seq: query.since_seq,
+        seq,
+        leaves: public_signals(leaves),
+        roots: public_signals(roots),
+        zeroed,
+        more: seq < tree.len(),
+    })
+}
+
+//--------------------------------------------------------------------
+// /root
+//--------------------------------------------------------------------
+/// The current root, decimal like commitments, and the leaf count it
+/// covers.
+#[derive(Debug, Serialize)]
+pub struct RootResponse {
+    pub depth: usize,
+    pub leaves: u64,
+    pub root: String,
+}
+
+/// What a group membership proof is made against.
+#[get("/root")]
+async fn current_root(state: web::Data<AppState>, req: HttpRequest) -> HttpResponse {
+    let tree = state.tree.read().expect("commitment tree poisoned");
+    json_with_etag(&req, &RootResponse { depth: TREE_DEPTH, leaves: tree.len(), root: public_signals(&[tree.root()]).remove(0) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The root of a depth-`TREE_DEPTH` tree holding `leaves`, hashed level
+    /// by level from scratch.
+    fn root_of(leaves: &[Fr]) -> Fr {
+        let mut zero = Fr::zero();
+        let mut level = leaves.to_vec();
+        for _ in 0..TREE_DEPTH {
+            level = level.chunks(2).map(|pair| poseidon_hash(&[pair[0], pair.get(1).copied().unwrap_or(zero)])).collect();
+            zero = poseidon_hash(&[zero, zero]);
+        }
+        level.first().copied().unwrap_or(zero)
+    }
+
+    fn leaves(n: u64) -> Vec<Fr> {
+        (1..=n).map(|i| Fr::from(i * 7919)).collect()
+    }
+
+    #[test]
+    fn roots_match_a_tree_hashed_from_scratch() {
+        let mut tree = CommitmentTree::new();
+        assert_eq!(tree.root(), root_of(&[]));
+        let leaves = leaves(5);
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert_eq!(tree.append(*leaf).unwrap(), i as u64);
+            assert_eq!(tree.root(), root_of(&leaves[..=i]));
+            assert!(tree.accepts_root(tree.root()));
+        }
+        assert!(tree.contains(leaves[2]));
+        assert!(!tree.contains(Fr::from(1u64)));
+    }
+
+    #[test]
+    fn revoking_zeroes_the_leaf_and_retires_older_roots() {
+        let mut tree = CommitmentTree::new();
+        let leaves = leaves(3);
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+        let before = tree.root();
+        assert_eq!(tree.revoke(leaves[1]), 1);
+        assert_eq!(tree.root(), root_of(&[leaves[0], Fr::zero(), leaves[2]]));
+        assert!(!tree.accepts_root(before));
+        assert!(tree.accepts_root(tree.root()));
+        // Zeroing again changes nothing.
+        assert!(!tree.zero_at(1));
+        assert_eq!(tree.revoke(leaves[1]), 0);
+        assert!(!tree.zero_at(7));
+    }
+
+    #[test]
+    fn delta_lists_leaves_roots_and_zeroed_leaves_in_range() {
+        let mut tree = CommitmentTree::new();
+        let leaves = leaves(4);
+        tree.append(leaves[0]).unwrap();
+        tree.append(leaves[1]).unwrap();
+        tree.revoke(leaves[0]);
+        tree.append(leaves[2]).unwrap();
+        tree.append(leaves[3]).unwrap();
+
+        let (appended, roots, zeroed) = tree.delta(1, 2);
+        assert_eq!(appended, &leaves[1..3]);
+        assert_eq!(roots[0], root_of(&leaves[..2]));
+        assert_eq!(roots[1], root_of(&[Fr::zero(), leaves[1], leaves[2]]));
+        assert_eq!(zeroed.len(), 1);
+        assert_eq!((zeroed[0].at, zeroed[0].index), (2, 0));
+        assert_eq!(zeroed[0].root, root_of(&[Fr::zero(), leaves[1]]));
+
+        let (appended, _, zeroed) = tree.delta(3, 10);
+        assert_eq!(appended, &leaves[3..]);
+        assert!(zeroed.is_empty());
+        assert!(tree.delta(9, 10).0.is_empty());
+    }
+
+    #[test]
+    fn full_tree_refuses_appends_and_stays_unchanged() {
+        let mut tree = CommitmentTree::new();
+        // Stand in for 2^20 registrations without hashing them.
+        tree.levels[0].resize(1 << TREE_DEPTH, Fr::zero());
+        let root = tree.root();
+        assert!(matches!(tree.append(Fr::from(1u64)), Err(ApiError::Unavailable(_))));
+        assert_eq!(tree.len(), 1 << TREE_DEPTH);
+        assert_eq!(tree.root(), root);
+        assert!(!tree.contains(Fr::from(1u64)));
+    }
+}
@@ -0,0 +1,60 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Serialize;
+
+use crate::artifacts::Fingerprints;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Build information
+//--------------------------------------------------------------------
+pub const GIT_HASH: &str = env!("ZK_AUTH_GIT_HASH");
+
+/// Cargo features compiled into this build.
+pub fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "playground") {
+        features.push("playground");
+    }
+    if cfg!(feature = "conformance") {
+        features.push("conformance");
+    }
+    if cfg!(feature = "chaos") {
+        features.push("chaos");
+    }
+    if cfg!(feature = "deterministic") {
+        features.push("deterministic");
+    }
+    if cfg!(feature = "witness-debug") {
+        features.push("witness-debug");
+    }
+    if cfg!(feature = "grpc") {
+        features.push("grpc");
+    }
+    features
+}
+
+#[derive(Serialize)]
+pub struct VersionResponse {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    pub features: Vec<&'static str>,
+    pub circuits: Vec<Fingerprints>,
+}
+
+//--------------------------------------------------------------------
+// /version
+//--------------------------------------------------------------------
+#[get("/version")]
+async fn version(state: web::Data<AppState>) -> impl Responder {
+    let worker_state = state.clone();
+    let circuit = web::block(move || worker_state.artifacts.fingerprints().clone()).await;
+    let circuits = circuit.map(|c| vec![c]).unwrap_or_default();
+    HttpResponse::Ok().json(VersionResponse {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        git_hash: GIT_HASH,
+        features: enabled_features(),
+        circuits,
+    })
+}
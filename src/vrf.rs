@@ -0,0 +1,264 @@
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::{clamp_integer, Scalar};
+use serde::Serialize;
+use sha2::{Digest, Sha512};
+use std::io;
+use std::path::Path;
+
+use crate::etag::json_with_etag;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// ECVRF-EDWARDS25519-SHA512-TAI (RFC 9381)
+//--------------------------------------------------------------------
+/// The only suite implemented.
+pub const VRF_SUITE: &str = "ECVRF-EDWARDS25519-SHA512-TAI";
+const SUITE: u8 = 0x03;
+
+/// A VRF secret key: an Ed25519 seed, expanded as RFC 8032 does.
+pub(crate) struct VrfKey {
+    x: Scalar,
+    /// Second half of SHA-512(seed), the nonce key.
+    prefix: [u8; 32],
+    public: [u8; 32],
+}
+
+impl VrfKey {
+    pub(crate) fn from_seed(seed: &[u8; 32]) -> Self {
+        let h = Sha512::digest(seed);
+        let x = Scalar::from_bytes_mod_order(clamp_integer(h[..32].try_into().expect("32 bytes")));
+        let public = EdwardsPoint::mul_base(&x).compress().to_bytes();
+        Self { x, prefix: h[32..].try_into().expect("32 bytes"), public }
+    }
+
+    /// Key from a file holding a 32-byte hex seed.
+    pub(crate) fn from_file(path: &Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let seed = hex::decode(text.trim())
+            .ok()
+            .and_then(|b| <[u8; 32]>::try_from(b).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "VRF key must be 32 bytes of hex"))?;
+        Ok(Self::from_seed(&seed))
+    }
+
+    pub(crate) fn public_key(&self) -> [u8; 32] {
+        self.public
+    }
+
+    /// `(pi, beta)`: the 80-byte proof and the 64-byte output for `alpha`.
+    pub(crate) fn prove(&self, alpha: &[u8]) -> ([u8; 80], [u8; 64]) {
+        let h = encode_to_curve(&self.public, alpha).expect("the public key is a valid point");
+        let gamma = self.x * h;
+        let k = Scalar::from_hash(Sha512::new().chain_update(self.prefix).chain_update(h.compress().as_bytes()));
+        let c = challenge(&self.public, &h, &gamma, &EdwardsPoint::mul_base(&k), &(k * h));
+        let s = k + c * self.x;
+
+        let mut pi = [0u8; 80];
+        pi[..32].copy_from_slice(gamma.compress().as_bytes());
+        pi[32..48].copy_from_slice(&c.as_bytes()[..16]);
+        pi[48..].copy_from_slice(s.as_bytes());
+        (pi, proof_to_hash(&gamma))
+    }
+}
+
+/// Check `pi` for `alpha` under `public_key` and return the VRF output.
+pub fn vrf_verify(public_key: &[u8; 32], alpha: &[u8], pi: &[u8; 80]) -> Option<[u8; 64]> {
+    let y = decompress(public_key).filter(|y| !y.is_small_order())?;
+    let gamma = decompress(pi[..32].try_into().ok()?)?;
+    let mut c_bytes = [0u8; 32];
+    c_bytes[..16].copy_from_slice(&pi[32..48]);
+    let c = Scalar::from_canonical_bytes(c_bytes).into_option()?;
+    let s = Scalar::from_canonical_bytes(pi[48..].try_into().ok()?).into_option()?;
+
+    let h = encode_to_curve(public_key, alpha)?;
+    let u = EdwardsPoint::mul_base(&s) - c * y;
+    let v = s * h - c * gamma;
+    (challenge(public_key, &h, &gamma, &u, &v) == c).then(|| proof_to_hash(&gamma))
+}
+
+/// Try-and-increment: the first SHA-512 output that decodes to a point,
+/// cleared of its cofactor.
+fn encode_to_curve(public_key: &[u8; 32], alpha: &[u8]) -> Option<EdwardsPoint> {
+    (0..=255u8).find_map(|ctr| {
+        let hash = Sha512::new()
+            .chain_update([SUITE, 0x01])
+            .chain_update(public_key)
+            .chain_update(alpha)
+            .chain_update([ctr, 0x00])
+            .finalize();
+        let point = decompress(hash[..32].try_into().expect("32 bytes"))?;
+        Some(point.mul_by_cofactor())
+    })
+}
+
+/// `string_to_point`: RFC 8032 decoding, which refuses a `y` of `p` or more
+/// and a negative zero `x`.  curve25519-dalek accepts both and reduces them,
+/// so a point only decodes if it encodes back to the same bytes.
+fn decompress(bytes: &[u8; 32]) -> Option<EdwardsPoint> {
+    CompressedEdwardsY(*bytes).decompress().filter(|point| point.compress().as_bytes() == bytes)
+}
+
+fn challenge(y: &[u8; 32], h: &EdwardsPoint, gamma: &EdwardsPoint, u: &EdwardsPoint, v: &EdwardsPoint) -> Scalar {
+    let mut hasher = Sha512::new().chain_update([SUITE, 0x02]).chain_update(y);
+    for point in [h, gamma, u, v] {
+        hasher.update(point.compress().as_bytes());
+    }
+    let hash = hasher.chain_update([0x00]).finalize();
+    let mut c = [0u8; 32];
+    c[..16].copy_from_slice(&hash[..16]);
+    Scalar::from_bytes_mod_order(c)
+}
+
+fn proof_to_hash(gamma: &EdwardsPoint) -> [u8; 64] {
+    Sha512::new()
+        .chain_update([SUITE, 0x03])
+        .chain_update(gamma.mul_by_cofactor().compress().as_bytes())
+        .chain_update([0x00])
+        .finalize()
+        .into()
+}
+
+//--------------------------------------------------------------------
+// Registration nonces
+//--------------------------------------------------------------------
+/// Domain tag prefixed to the user hash to form the VRF input.
+const NONCE_TAG: &[u8] = b"zk-auth-nonce/v1";
+
+/// Evidence that a registration nonce is the VRF output for the submitted
+/// attributes: `nonce` is the first 16 bytes of
+/// `vrf_verify(public_key, alpha, proof)`.
+#[derive(Clone, Debug, Serialize)]
+pub struct NonceProof {
+    pub suite: &'static str,
+    /// Hex `"zk-auth-nonce/v1" ‖ user_hash`, the hash big-endian.
+    pub alpha: String,
+    /// Hex 80-byte ECVRF proof.
+    pub proof: String,
+}
+
+impl VrfKey {
+    /// The nonce for `user_hash` and its proof.
+    pub(crate) fn nonce(&self, user_hash: &[u8; 32]) -> ([u8; 16], NonceProof) {
+        let alpha = [NONCE_TAG, user_hash].concat();
+        let (pi, beta) = self.prove(&alpha);
+        let nonce = beta[..16].try_into().expect("16 bytes");
+        (nonce, NonceProof { suite: VRF_SUITE, alpha: hex::encode(alpha), proof: hex::encode(pi) })
+    }
+}
+
+//--------------------------------------------------------------------
+// /vrf/key
+//--------------------------------------------------------------------
+#[derive(Debug, Serialize)]
+pub struct VrfKeyResponse {
+    pub suite: &'static str,
+    /// Hex Ed25519-encoded public key.
+    pub public_key: String,
+}
+
+/// The key registration nonce proofs verify against; 404 when nonces are
+/// random.
+#[get("/vrf/key")]
+async fn vrf_key(state: web::Data<AppState>, req: HttpRequest) -> impl Responder {
+    match &state.vrf {
+        Some(key) => json_with_etag(&req, &VrfKeyResponse { suite: VRF_SUITE, public_key: hex::encode(key.public_key()) }),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 9381 Appendix B.3, examples 16 to 18: `(SK, PK, alpha, pi, beta)`.
+    const VECTORS: [(&str, &str, &str, &str, &str); 3] = [
+        (
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "8657106690b5526245a92b003bb079ccd1a92130477671f6fc01ad16f26f723f26f8a57ccaed74ee1b190bed1f479d9727d2d0f9b005a6e456a35d4fb0daab1268a1b0db10836d9826a528ca76567805",
+            "90cf1df3b703cce59e2a35b925d411164068269d7b2d29f3301c03dd757876ff66b71dda49d2de59d03450451af026798e8f81cd2e333de5cdf4f3e140fdd8ae",
+        ),
+        (
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "f3141cd382dc42909d19ec5110469e4feae18300e94f304590abdced48aed5933bf0864a62558b3ed7f2fea45c92a465301b3bbf5e3e54ddf2d935be3b67926da3ef39226bbc355bdc9850112c8f4b02",
+            "eb4440665d3891d668e7e0fcaf587f1b4bd7fbfe99d0eb2211ccec90496310eb5e33821bc613efb94db5e5b54c70a848a0bef4553a41befc57663b56373a5031",
+        ),
+        (
+            "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            "af82",
+            "9bc0f79119cc5604bf02d23b4caede71393cedfbb191434dd016d30177ccbf8096bb474e53895c362d8628ee9f9ea3c0e52c7a5c691b6c18c9979866568add7a2d41b00b05081ed0f58ee5e31b3a970e",
+            "645427e5d00c62a23fb703732fa5d892940935942101e456ecca7bb217c61c452118fec1219202a0edcf038bb6373241578be7217ba85a2687f7a0310b2df19f",
+        ),
+    ];
+
+    fn bytes<const N: usize>(hex: &str) -> [u8; N] {
+        hex::decode(hex).expect("hex").try_into().expect("length")
+    }
+
+    #[test]
+    fn proves_the_rfc_vectors() {
+        for (sk, pk, alpha, pi, beta) in VECTORS {
+            let key = VrfKey::from_seed(&bytes(sk));
+            assert_eq!(hex::encode(key.public_key()), pk);
+            let (proof, output) = key.prove(&hex::decode(alpha).unwrap());
+            assert_eq!(hex::encode(proof), pi);
+            assert_eq!(hex::encode(output), beta);
+        }
+    }
+
+    #[test]
+    fn proof_to_hash_matches_the_rfc_vectors() {
+        for (_, _, _, pi, beta) in VECTORS {
+            let gamma = decompress(&bytes(&pi[..64])).expect("gamma decodes");
+            assert_eq!(hex::encode(proof_to_hash(&gamma)), beta);
+        }
+    }
+
+    #[test]
+    fn verifies_the_rfc_vectors() {
+        for (_, pk, alpha, pi, beta) in VECTORS {
+            let output = vrf_verify(&bytes(pk), &hex::decode(alpha).unwrap(), &bytes(pi));
+            assert_eq!(output.map(hex::encode).as_deref(), Some(beta));
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_proofs() {
+        let (_, pk, alpha, pi, _) = VECTORS[1];
+        let (pk, alpha, pi): ([u8; 32], _, [u8; 80]) = (bytes(pk), hex::decode(alpha).unwrap(), bytes(pi));
+        // A flipped bit in gamma, c or s, another input or another key.
+        for byte in [0, 40, 60] {
+            let mut tampered = pi;
+            tampered[byte] ^= 1;
+            assert_eq!(vrf_verify(&pk, &alpha, &tampered), None, "byte {byte}");
+        }
+        assert_eq!(vrf_verify(&pk, b"other input", &pi), None);
+        assert_eq!(vrf_verify(&bytes(VECTORS[0].1), &alpha, &pi), None);
+    }
+
+    #[test]
+    fn rejects_non_canonical_points() {
+        // y = p + 1 reduces to y = 1, the identity, which dalek would accept.
+        let mut y = [0xff; 32];
+        y[0] = 0xee;
+        y[31] = 0x7f;
+        assert!(CompressedEdwardsY(y).decompress().is_some());
+        assert!(decompress(&y).is_none());
+        // x = 0 with the sign bit set.
+        let mut negative_zero = [0; 32];
+        negative_zero[0] = 1;
+        negative_zero[31] = 0x80;
+        assert!(decompress(&negative_zero).is_none());
+        // A proof whose gamma is written non-canonically.
+        let (_, pk, alpha, pi, _) = VECTORS[0];
+        let mut pi: [u8; 80] = bytes(pi);
+        pi[..32].copy_from_slice(&y);
+        assert_eq!(vrf_verify(&bytes(pk), &hex::decode(alpha).unwrap(), &pi), None);
+    }
+}
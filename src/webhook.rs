@@ -0,0 +1,131 @@
+use actix_web::web;
+use ark_bn254::Fr;
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::snarkjs::public_signals;
+use crate::AppState;
+
+//--------------------------------------------------------------------
+// Registration webhooks
+//--------------------------------------------------------------------
+/// Header carrying the hex Ed25519 signature over
+/// `zk-auth-webhook/v1\n` followed by the request body.
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-zk-signature";
+/// Header naming the signing key, as `key_id` does for receipts.
+pub const WEBHOOK_KEY_ID_HEADER: &str = "x-zk-key-id";
+
+/// POST every new registration to a provisioning endpoint, such as a
+/// directory, a CRM or an on-chain allowlist updater.
+///
+/// Events are sent in registration order, one at a time.  A failed
+/// delivery is retried with growing pauses until it succeeds, so the
+/// endpoint sees each commitment at least once and must tolerate repeats.
+/// Requests are signed with the receipt key; check them against
+/// `GET /receipts/key`.
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Pause between checks for new registrations.
+    pub interval: Duration,
+    pub timeout: Duration,
+    /// Longest pause between retries of a failing delivery.
+    pub max_backoff: Duration,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://127.0.0.1:9000/zk-auth".into(),
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+/// The JSON body of a webhook request.
+#[derive(Clone, Debug, Serialize)]
+pub struct RegistrationEvent {
+    /// Always `"registration"`.
+    pub event: &'static str,
+    /// Decimal, as `/register` returns it.
+    pub commitment: String,
+    pub leaf_index: u64,
+    /// Tree root right after this commitment was added, decimal.
+    pub root: String,
+    /// Unix seconds when this attempt was sent; reject stale ones.
+    pub sent_at: u64,
+}
+
+pub(crate) struct Webhook {
+    config: WebhookConfig,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    pub(crate) fn new(config: WebhookConfig) -> Self {
+        let client = reqwest::Client::builder().timeout(config.timeout).build().expect("default TLS backend is available");
+        Self { config, client }
+    }
+
+    async fn send(&self, state: &AppState, event: &RegistrationEvent) -> Result<(), String> {
+        let body = serde_json::to_vec(event).expect("event serialises to JSON");
+        let signature = state.receipts.sign_message(&[b"zk-auth-webhook/v1\n".as_slice(), &body].concat());
+        let res = self
+            .client
+            .post(&self.config.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_SIGNATURE_HEADER, signature)
+            .header(WEBHOOK_KEY_ID_HEADER, state.receipts.key_id())
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            return Err(format!("endpoint answered {}", res.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Deliver each commitment added to `state.tree` to the webhook.
+/// [`crate::run`] spawns this; embedding applications that set
+/// [`crate::AppConfig::webhook`] spawn it on their own runtime.
+pub async fn run_webhooks(state: web::Data<AppState>) {
+    let Some(webhook) = &state.webhook else { return };
+    let mut ticks = actix_web::rt::time::interval(webhook.config.interval);
+    let mut delivered = 0;
+    loop {
+        ticks.tick().await;
+        while let Some((commitment, root)) = leaf(&state, delivered) {
+            let mut backoff = Duration::from_secs(1);
+            loop {
+                let sent_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+                let event = RegistrationEvent {
+                    event: "registration",
+                    commitment: public_signals(&[commitment]).remove(0),
+                    leaf_index: delivered,
+                    root: public_signals(&[root]).remove(0),
+                    sent_at,
+                };
+                match webhook.send(&state, &event).await {
+                    Ok(()) => break,
+                    Err(error) => {
+                        tracing::warn!(url = %webhook.config.url, leaf_index = delivered, %error, retry_in_secs = backoff.as_secs(), "webhook delivery failed");
+                        actix_web::rt::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(webhook.config.max_backoff);
+                    }
+                }
+            }
+            delivered += 1;
+        }
+    }
+}
+
+/// Leaf `index` and the root after it, once it exists.
+fn leaf(state: &AppState, index: u64) -> Option<(Fr, Fr)> {
+    let tree = state.tree.read().expect("commitment tree poisoned");
+    let (leaves, roots, _) = tree.delta(index, 1);
+    leaves.first().copied().zip(roots.first().copied())
+}
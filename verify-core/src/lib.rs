@@ -0,0 +1,171 @@
+//! Groth16/BN254 verification for zk-auth proofs without std, tokio or
+//! actix, for TEEs, embedded gateways and other constrained hosts.
+//!
+//! It covers the whole verify path of the server: proof parsing (EVM-order
+//! hex and compressed arkworks bytes), public-input parsing, and the
+//! pairing check.  Nothing here allocates except the arkworks internals,
+//! which need `alloc`.
+
+#![no_std]
+
+use ark_bn254::{Fq, Fq2, G1Affine, G1Projective, G2Affine};
+use ark_ec::pairing::Pairing;
+use ark_ff::{BigInt, Field, PrimeField, Zero};
+use ark_groth16::Groth16;
+use ark_serialize::CanonicalDeserialize;
+use core::fmt;
+
+pub use ark_bn254::{Bn254, Fr};
+pub use ark_groth16::{prepare_verifying_key, PreparedVerifyingKey, Proof, VerifyingKey};
+
+//--------------------------------------------------------------------
+// Errors
+//--------------------------------------------------------------------
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A public input is not a decimal or `0x` hex integer below the BN254
+    /// scalar modulus.
+    Scalar,
+    /// A coordinate is not at most 32 bytes of hex.
+    Hex,
+    /// A point is off the curve or outside the prime-order subgroup.
+    Point,
+    /// Compressed proof or key bytes do not decode.
+    Encoding,
+    /// The number of public inputs does not match the verifying key.
+    InputCount,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::Scalar => "public input is not a BN254 scalar",
+            Error::Hex => "coordinate is not at most 32 bytes of hex",
+            Error::Point => "point is not in the BN254 prime-order subgroup",
+            Error::Encoding => "bytes are not a compressed BN254 proof or key",
+            Error::InputCount => "wrong number of public inputs for the verifying key",
+        })
+    }
+}
+
+//--------------------------------------------------------------------
+// Public inputs
+//--------------------------------------------------------------------
+/// Parse a public input in decimal or `0x` hex, rejecting values at or
+/// above the scalar modulus instead of reducing them.
+pub fn parse_scalar(s: &str) -> Result<Fr, Error> {
+    let s = s.trim();
+    let (digits, radix) = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(h) => (h, 16),
+        None => (s, 10),
+    };
+    Fr::from_bigint(parse_u256(digits, radix).ok_or(Error::Scalar)?).ok_or(Error::Scalar)
+}
+
+/// Digits in `radix` as a 256-bit integer; `None` when empty, not digits or
+/// too large.
+fn parse_u256(digits: &str, radix: u32) -> Option<BigInt<4>> {
+    if digits.is_empty() {
+        return None;
+    }
+    let mut limbs = [0u64; 4];
+    for ch in digits.chars() {
+        let mut carry = ch.to_digit(radix)? as u128;
+        for limb in limbs.iter_mut() {
+            let v = (*limb as u128) * radix as u128 + carry;
+            *limb = v as u64;
+            carry = v >> 64;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    Some(BigInt(limbs))
+}
+
+//--------------------------------------------------------------------
+// Proofs
+//--------------------------------------------------------------------
+pub fn checked_g1(x: Fq, y: Fq) -> Option<G1Affine> {
+    let p = G1Affine::new_unchecked(x, y);
+    (p.is_on_curve() && p.is_in_correct_subgroup_assuming_on_curve()).then_some(p)
+}
+
+pub fn checked_g2(x: Fq2, y: Fq2) -> Option<G2Affine> {
+    let p = G2Affine::new_unchecked(x, y);
+    (p.is_on_curve() && p.is_in_correct_subgroup_assuming_on_curve()).then_some(p)
+}
+
+/// A proof in the server's JSON layout: hex affine coordinates, `0x`
+/// optional, with G2 limbs `[c1, c0]` (EVM order).  Coordinates are read
+/// big-endian and reduced mod q, as the server does.
+pub fn proof_from_evm_hex(a: [&str; 2], b: [[&str; 2]; 2], c: [&str; 2]) -> Result<Proof<Bn254>, Error> {
+    let g2 = |x: [&str; 2]| Ok::<_, Error>(Fq2::new(fq_from_hex(x[1])?, fq_from_hex(x[0])?));
+    Ok(Proof {
+        a: checked_g1(fq_from_hex(a[0])?, fq_from_hex(a[1])?).ok_or(Error::Point)?,
+        b: checked_g2(g2(b[0])?, g2(b[1])?).ok_or(Error::Point)?,
+        c: checked_g1(fq_from_hex(c[0])?, fq_from_hex(c[1])?).ok_or(Error::Point)?,
+    })
+}
+
+/// A compressed arkworks proof, as written by `zk-auth-api convert <from> ark`;
+/// points are checked.
+pub fn proof_from_compressed(bytes: &[u8]) -> Result<Proof<Bn254>, Error> {
+    Proof::deserialize_compressed(bytes).map_err(|_| Error::Encoding)
+}
+
+/// A verifying key as written by `export-vk` (compressed arkworks),
+/// prepared for [`verify`].
+pub fn prepared_key_from_compressed(bytes: &[u8]) -> Result<PreparedVerifyingKey<Bn254>, Error> {
+    VerifyingKey::deserialize_compressed(bytes).map(|vk| prepare_verifying_key(&vk)).map_err(|_| Error::Encoding)
+}
+
+fn fq_from_hex(s: &str) -> Result<Fq, Error> {
+    let h = s.strip_prefix("0x").unwrap_or(s).as_bytes();
+    if !h.len().is_multiple_of(2) || h.len() > 64 {
+        return Err(Error::Hex);
+    }
+    let mut bytes = [0u8; 32];
+    let out = &mut bytes[32 - h.len() / 2..];
+    for (byte, pair) in out.iter_mut().zip(h.chunks(2)) {
+        let nibble = |c: u8| (c as char).to_digit(16).ok_or(Error::Hex);
+        *byte = (nibble(pair[0])? << 4 | nibble(pair[1])?) as u8;
+    }
+    Ok(Fq::from_be_bytes_mod_order(&bytes))
+}
+
+//--------------------------------------------------------------------
+// Pairing check
+//--------------------------------------------------------------------
+/// Check `proof` against `public_inputs` under the prepared key.
+///
+/// The QAP reduction only matters to the prover, so this accepts proofs from
+/// snarkjs and from the server's circom prover alike.
+pub fn verify(pvk: &PreparedVerifyingKey<Bn254>, public_inputs: &[Fr], proof: &Proof<Bn254>) -> Result<bool, Error> {
+    if public_inputs.len() + 1 != pvk.vk.gamma_abc_g1.len() {
+        return Err(Error::InputCount);
+    }
+    Groth16::<Bn254>::verify_proof(pvk, proof, public_inputs).map_err(|_| Error::InputCount)
+}
+
+/// Check every `(public_inputs, proof)` pair under one key with a single
+/// multi-pairing, about a third of the cost of checking them one by one.
+///
+/// The Groth16 equations are combined with one `randomizers` entry per
+/// proof, which must be fresh, secret and uniformly random; 128 bits each
+/// bound the chance of a bad proof slipping through by 2^-128.  `Ok(false)`
+/// means at least one proof does not hold, without saying which.
+pub fn verify_batch(pvk: &PreparedVerifyingKey<Bn254>, batch: &[(&[Fr], &Proof<Bn254>)], randomizers: &[Fr]) -> Result<bool, Error> {
+    assert_eq!(batch.len(), randomizers.len(), "one randomizer per proof");
+    let (mut inputs, mut c, mut sum) = (G1Projective::zero(), G1Projective::zero(), Fr::zero());
+    for ((public_inputs, proof), r) in batch.iter().zip(randomizers) {
+        inputs += Groth16::<Bn254>::prepare_inputs(pvk, public_inputs).map_err(|_| Error::InputCount)? * r;
+        c += proof.c * r;
+        sum += r;
+    }
+    // prod e(r_i A_i, B_i) * e(sum r_i IC_i, -gamma) * e(sum r_i C_i, -delta) = e(alpha, beta)^(sum r_i)
+    let g1 = batch.iter().zip(randomizers).map(|((_, proof), r)| proof.a * r).chain([inputs, c]);
+    let g2 = batch.iter().map(|(_, proof)| <Bn254 as Pairing>::G2Prepared::from(proof.b)).chain([pvk.gamma_g2_neg_pc.clone(), pvk.delta_g2_neg_pc.clone()]);
+    let lhs = Bn254::multi_pairing(g1, g2);
+    Ok(lhs.0 == pvk.alpha_g1_beta_g2.pow(sum.into_bigint()))
+}
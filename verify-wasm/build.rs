@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+// Copies the verifying key named by ZK_EMBED_VK into the bundle, or leaves
+// it empty so `Verifier.embedded()` reports that none was embedded.
+fn main() {
+    let out = PathBuf::from(std::env::var_os("OUT_DIR").expect("cargo sets OUT_DIR")).join("vk.bin");
+    let vk = match std::env::var("ZK_EMBED_VK") {
+        Ok(path) => {
+            println!("cargo:rerun-if-changed={path}");
+            std::fs::read(&path).unwrap_or_else(|e| panic!("ZK_EMBED_VK={path}: {e}"))
+        }
+        Err(_) => Vec::new(),
+    };
+    std::fs::write(out, vk).expect("OUT_DIR is writable");
+    println!("cargo:rerun-if-env-changed=ZK_EMBED_VK");
+}
@@ -0,0 +1,81 @@
+//! Verify-only WebAssembly bundle: relying-party frontends check a proof in
+//! the browser before making any network call.
+//!
+//! ```js
+//! import init, { Verifier } from "./pkg/zk_auth_verify_wasm.js";
+//! await init();
+//! const vk = new Uint8Array(await (await fetch("/vk.bin")).arrayBuffer());
+//! const ok = new Verifier(vk).verify(commitment, response.proof);
+//! ```
+
+use js_sys::{Array, Reflect};
+use wasm_bindgen::prelude::*;
+use zk_auth_verify_core::{parse_scalar, prepared_key_from_compressed, proof_from_compressed, proof_from_evm_hex, verify, Bn254, PreparedVerifyingKey, Proof};
+
+/// Empty unless `ZK_EMBED_VK` was set at build time; see `build.rs`.
+const EMBEDDED_VK: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/vk.bin"));
+
+#[wasm_bindgen]
+pub struct Verifier {
+    pvk: PreparedVerifyingKey<Bn254>,
+}
+
+#[wasm_bindgen]
+impl Verifier {
+    /// Verifier for a key in `export-vk`'s compressed format.
+    #[wasm_bindgen(constructor)]
+    pub fn new(vk: &[u8]) -> Result<Verifier, JsError> {
+        Ok(Verifier { pvk: prepared_key_from_compressed(vk).map_err(err)? })
+    }
+
+    /// Verifier for the key baked in at build time from the `export-vk`
+    /// file named by `ZK_EMBED_VK`.
+    pub fn embedded() -> Result<Verifier, JsError> {
+        if EMBEDDED_VK.is_empty() {
+            return Err(JsError::new("built without ZK_EMBED_VK"));
+        }
+        Verifier::new(EMBEDDED_VK)
+    }
+
+    /// Check a proof object as returned by `/generate-proof` (`{a, b, c}`,
+    /// hex, EVM order) against a decimal or `0x` hex commitment.
+    pub fn verify(&self, commitment: &str, proof: &JsValue) -> Result<bool, JsError> {
+        let a = strings(proof, "a")?;
+        let b = Reflect::get(proof, &"b".into()).map_err(|_| missing("b"))?;
+        let (b0, b1) = (strings(&b, 0)?, strings(&b, 1)?);
+        let c = strings(proof, "c")?;
+        let proof = proof_from_evm_hex([&a[0], &a[1]], [[&b0[0], &b0[1]], [&b1[0], &b1[1]]], [&c[0], &c[1]]).map_err(err)?;
+        self.check(commitment, &proof)
+    }
+
+    /// Check a compressed arkworks proof (`zk-auth-api convert <from> ark`).
+    #[wasm_bindgen(js_name = verifyCompressed)]
+    pub fn verify_compressed(&self, commitment: &str, proof: &[u8]) -> Result<bool, JsError> {
+        self.check(commitment, &proof_from_compressed(proof).map_err(err)?)
+    }
+}
+
+impl Verifier {
+    fn check(&self, commitment: &str, proof: &Proof<Bn254>) -> Result<bool, JsError> {
+        verify(&self.pvk, &[parse_scalar(commitment).map_err(err)?], proof).map_err(err)
+    }
+}
+
+/// `value[key]` as a pair of strings.
+fn strings(value: &JsValue, key: impl Into<JsValue>) -> Result<[String; 2], JsError> {
+    let key = key.into();
+    let name = key.as_string().unwrap_or_else(|| "b[]".into());
+    let array: Array = Reflect::get(value, &key).ok().and_then(|v| v.dyn_into().ok()).ok_or_else(|| missing(&name))?;
+    match (array.get(0).as_string(), array.get(1).as_string()) {
+        (Some(x), Some(y)) if array.length() == 2 => Ok([x, y]),
+        _ => Err(missing(&name)),
+    }
+}
+
+fn missing(name: &str) -> JsError {
+    JsError::new(&format!("proof.{name} must be a pair of hex strings"))
+}
+
+fn err(e: zk_auth_verify_core::Error) -> JsError {
+    JsError::new(&e.to_string())
+}